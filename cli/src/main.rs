@@ -1,10 +1,13 @@
+extern crate env_logger;
 extern crate image;
 extern crate rustico_core;
 extern crate rustico_ui_common;
 
+use rustico_core::memory;
 use rustico_core::nes::NesState;
 use rustico_core::palettes::NTSC_PAL;
 use rustico_core::cartridge::mapper_from_file;
+use rustico_core::test_rom;
 
 use rustico_ui_common::application::RuntimeState as RusticoRuntimeState;
 use rustico_ui_common::events;
@@ -14,7 +17,6 @@ use rustico_ui_common::event_window::EventWindow;
 
 use std::env;
 use std::fs::File;
-use std::str;
 
 use std::io::Read;
 use std::io::Write;
@@ -214,44 +216,25 @@ fn save_screenshot(nes: &NesState, output_path: &str) {
 }
 
 fn save_blargg(nes: &mut NesState, output_filename: &str) {
-  if nes.mapper.has_sram() {
-    let sram = nes.mapper.get_sram();
-
-    let test_status = sram[0];
-    let magic_0 = sram[1];
-    let magic_1 = sram[2];
-    let magic_2 = sram[3];
-
-    if magic_0 == 0xDE && magic_1 == 0xB0 && magic_2 == 0x61 {
+  let output = match test_rom::read_status(nes) {
+    Some((test_status, test_text)) => {
       let test_status_string = match test_status {
-        0x80 => format!("Running"),
-        0x81 => format!("Needs RESET"),
+        test_rom::STATUS_RUNNING => format!("Running"),
+        test_rom::STATUS_NEEDS_RESET => format!("Needs RESET"),
         _ => format!("0x{:02X}", test_status),
       };
+      format!("Test Status: {}\n\n{}", test_status_string, test_text)
+    },
+    None => {
+      let magic: Vec<u8> = (0x6001 .. 0x6004).map(|address| memory::debug_read_byte(nes, address)).collect();
+      format!("Invalid blargg magic header, found 0x{:02X} 0x{:02X} 0x{:02X} instead.", magic[0], magic[1], magic[2])
+    }
+  };
 
-
-      // Starting at 0x6004, read all of NES memory up to the next null terminator (or 0x8000) as ASCII
-      let begin = 4;
-      // Locate the next null terminator
-      let mut end = 4;
-      while sram[end] != 0  && end < sram.len() {
-        end+=1;
-      }
-
-      let test_text = str::from_utf8(&sram[begin .. end]).unwrap();
-      let output = format!("Test Status: {}\n\n{}", test_status_string, test_text);
-
-      // Output!
-      let ref mut file = File::create(output_filename).unwrap();
-      let _ = file.write_all(output.as_ref());
-      println!("Saved blargg data to {}", output_filename);
-    } else {
-      let ref mut file = File::create(output_filename).unwrap();
-      let _ = file.write_all(format!("Invalid blargg magic header, found 0x{:02X} 0x{:02X} 0x{:02X} instead.", magic_0, magic_1, magic_2).as_ref());
-    }    
-  } else {
-    panic!("Cannot output blargg data, ROM has no SRAM!");
-  }
+  // Output!
+  let ref mut file = File::create(output_filename).unwrap();
+  let _ = file.write_all(output.as_ref());
+  println!("Saved blargg data to {}", output_filename);
 }
 
 fn command_file(state: &mut CliRuntimeState, command_path: &str) {
@@ -370,6 +353,9 @@ fn process_command_list(state: &mut CliRuntimeState, mut command_list: Vec<Strin
 }
 
 fn main() {
+  // RUST_LOG overrides this, same as the egui shell
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
 	let mut args: Vec<_> = env::args().collect();
   if args.len() < 2 {
     panic!("Usage: rustico-cli <commands>");
@@ -1,5 +1,7 @@
 use cycle_cpu::Registers;
 use memory::read_byte;
+use memory::log_cdl;
+use memory::CDL_OPERAND;
 use memory::write_byte;
 use nes::NesState;
 
@@ -18,6 +20,7 @@ pub struct AddressingMode {
 pub fn read_data1(nes: &mut NesState) {
   let pc = nes.registers.pc;
   nes.cpu.data1 = read_byte(nes, pc);
+  log_cdl(nes, pc, CDL_OPERAND);
   nes.registers.pc = nes.registers.pc.wrapping_add(1);
 }
 
@@ -36,6 +39,7 @@ pub fn read_address_low(nes: &mut NesState) {
 pub fn read_address_high(nes: &mut NesState) {
   let pc = nes.registers.pc;
   nes.cpu.data2 = read_byte(nes, pc);
+  log_cdl(nes, pc, CDL_OPERAND);
   nes.registers.pc = nes.registers.pc.wrapping_add(1);
   nes.cpu.temp_address = ((nes.cpu.data2 as u16) << 8) | (nes.cpu.data1 as u16);
 }
@@ -105,6 +109,7 @@ pub static ACCUMULATOR: AddressingMode = AddressingMode{
 pub fn immediate_read(nes: &mut NesState, opcode_func: ReadOpcode) {
   let pc = nes.registers.pc;
   let data = read_byte(nes, pc);
+  log_cdl(nes, pc, CDL_OPERAND);
   opcode_func(&mut nes.registers, data);
   nes.registers.pc = nes.registers.pc.wrapping_add(1);
   nes.cpu.tick = 0;
@@ -3,6 +3,7 @@ use super::audio_channel::AudioChannelState;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use save_load::*;
 
 pub struct DmcState {
     pub name: String,
@@ -32,6 +33,10 @@ pub struct DmcState {
     pub interrupt_flag: bool,
     pub rdy_line: bool,
     pub rdy_delay: u8,
+    // Set by the CPU on every cycle it spends halted by RDY
+    pub cpu_halted: bool,
+    // Set when a fetch completes over a halted read; the CPU repeats that read afterwards
+    pub interrupted_read: bool,
 }
 
 impl DmcState {
@@ -43,7 +48,7 @@ impl DmcState {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
 
             looping: false,
             period_initial: 428,
@@ -63,6 +68,8 @@ impl DmcState {
             interrupt_flag: false,
             rdy_line: false,
             rdy_delay: 0,
+            cpu_halted: false,
+            interrupted_read: false,
         }
     }
 
@@ -73,12 +80,17 @@ impl DmcState {
     }
 
     pub fn read_next_sample(&mut self, mapper: &mut dyn Mapper) {
-        match mapper.read_cpu(0x8000 | (self.current_address & 0x7FFF)) {
+        // Reads go through the mapper's live path, so a bank switched mid-sample takes effect
+        // on the very next byte
+        match mapper.read_cpu(self.current_address) {
             Some(byte) => self.sample_buffer = byte,
             None => self.sample_buffer = 0,
         }
-        self.current_address = self.current_address.wrapping_add(1);
+        // The address counter is 15 bits wide with the top bit forced on, so a sample
+        // running past $FFFF continues from $8000
+        self.current_address = self.current_address.wrapping_add(1) | 0x8000;
         self.bytes_remaining -= 1;
+        // A looping sample restarts from $4012 / $4013 and never raises an IRQ
         if self.bytes_remaining == 0 {
             if self.looping {
                 self.current_address = self.starting_address;
@@ -93,6 +105,7 @@ impl DmcState {
         self.sample_buffer_empty = false;
         self.rdy_line = false;
         self.rdy_delay = 0;
+        self.interrupted_read = self.cpu_halted;
     }
 
     pub fn begin_output_cycle(&mut self) {
@@ -127,28 +140,85 @@ impl DmcState {
         }
     }
 
-    pub fn clock(&mut self, mapper: &mut dyn Mapper) {
+    pub fn clock(&mut self) {
         if self.period_current == 0 {
             self.period_current = self.period_initial - 1;
             self.update_output_unit();
         } else {
             self.period_current -= 1;
         }
-        if self.sample_buffer_empty && self.bytes_remaining > 0 {
-            self.rdy_line = true;
-            self.rdy_delay += 1;
-            if self.rdy_delay > 2 {
-                self.read_next_sample(mapper);
-            }
-        } else {
+    }
+
+    // Runs once per CPU cycle. RDY is asserted as soon as the sample buffer empties,
+    // but the 6502 only honors it on a read cycle, so writes push the whole DMA back.
+    // Once halted, the DMA spends a halt cycle and a dummy cycle, then waits for a
+    // "get" cycle to perform the fetch: 3 or 4 cycles stolen in total. An OAM DMA
+    // has already halted the CPU, so in that case the fetch can begin immediately.
+    pub fn clock_dma(&mut self, mapper: &mut dyn Mapper, get_cycle: bool) {
+        if !(self.sample_buffer_empty && self.bytes_remaining > 0) {
             self.rdy_line = false;
             self.rdy_delay = 0;
+            self.cpu_halted = false;
+            return;
+        }
+        self.rdy_line = true;
+        if self.cpu_halted {
+            self.rdy_delay += 1;
+        }
+        if self.rdy_delay >= 3 && get_cycle {
+            self.read_next_sample(mapper);
         }
+        self.cpu_halted = false;
     }
 
     pub fn output(&self) -> i16 {
         return self.output_level as i16;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.looping);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_u8(buff, self.output_level);
+        save_u16(buff, self.starting_address);
+        save_u16(buff, self.sample_length);
+        save_u16(buff, self.current_address);
+        save_u8(buff, self.sample_buffer);
+        save_u8(buff, self.shift_register);
+        save_bool(buff, self.sample_buffer_empty);
+        save_u8(buff, self.bits_remaining);
+        save_u16(buff, self.bytes_remaining);
+        save_bool(buff, self.silence_flag);
+        save_bool(buff, self.interrupt_enabled);
+        save_bool(buff, self.interrupt_flag);
+        save_bool(buff, self.rdy_line);
+        save_u8(buff, self.rdy_delay);
+        save_bool(buff, self.cpu_halted);
+        save_bool(buff, self.interrupted_read);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.looping = buff.load_bool()?;
+        self.period_initial = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.output_level = buff.load_u8()?;
+        self.starting_address = buff.load_u16()?;
+        self.sample_length = buff.load_u16()?;
+        self.current_address = buff.load_u16()?;
+        self.sample_buffer = buff.load_u8()?;
+        self.shift_register = buff.load_u8()?;
+        self.sample_buffer_empty = buff.load_bool()?;
+        self.bits_remaining = buff.load_u8()?;
+        self.bytes_remaining = buff.load_u16()?;
+        self.silence_flag = buff.load_bool()?;
+        self.interrupt_enabled = buff.load_bool()?;
+        self.interrupt_flag = buff.load_bool()?;
+        self.rdy_line = buff.load_bool()?;
+        self.rdy_delay = buff.load_u8()?;
+        self.cpu_halted = buff.load_bool()?;
+        self.interrupted_read = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for DmcState {
@@ -201,7 +271,8 @@ impl AudioChannelState for DmcState {
 
     fn amplitude(&self) -> f32 {
         let buffer = self.output_buffer.buffer();
-        let mut index = (self.output_buffer.index() - 256) % buffer.len();
+        // Wraps back around the end of the buffer while fewer than 256 samples have been recorded
+        let mut index = (self.output_buffer.index() + buffer.len() - 256) % buffer.len();
         let mut max = buffer[index];
         let mut min = buffer[index];
         for _i in 0 .. 256 {
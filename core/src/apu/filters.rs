@@ -39,7 +39,7 @@ pub struct HighPassIIR {
 impl HighPassIIR {
     pub fn new(sample_rate: f32, cutoff_frequency: f32) -> HighPassIIR {
         let delta_t = 1.0 / sample_rate;
-        let time_constant = 1.0 / cutoff_frequency;
+        let time_constant = 1.0 / (2.0 * PI * cutoff_frequency);
         let alpha = time_constant / (time_constant + delta_t);
         return HighPassIIR {
             alpha: alpha,
@@ -1,3 +1,5 @@
+use save_load::*;
+
 pub struct LengthCounterState {
     pub length: u8,
     pub halt_flag: bool,
@@ -33,4 +35,17 @@ impl LengthCounterState{
             self.length = 0
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.length);
+        save_bool(buff, self.halt_flag);
+        save_bool(buff, self.channel_enabled);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.length = buff.load_u8()?;
+        self.halt_flag = buff.load_bool()?;
+        self.channel_enabled = buff.load_bool()?;
+        return Ok(());
+    }
 }
\ No newline at end of file
@@ -2,6 +2,8 @@ use mmc::mapper::Mapper;
 
 use std::fs::OpenOptions;
 use std::io::prelude::*;
+use region::Region;
+use save_load::*;
 
 mod audio_channel;
 mod dmc;
@@ -10,6 +12,8 @@ mod length_counter;
 mod noise;
 mod pulse;
 mod ring_buffer;
+mod sample_queue;
+mod snapshot;
 mod triangle;
 mod volume_envelope;
 
@@ -21,6 +25,9 @@ pub use self::dmc::DmcState;
 pub use self::noise::NoiseChannelState;
 pub use self::pulse::PulseChannelState;
 pub use self::ring_buffer::RingBuffer;
+pub use self::sample_queue::SampleQueue;
+pub use self::snapshot::ApuSnapshot;
+pub use self::snapshot::ChannelSnapshot;
 pub use self::triangle::TriangleChannelState;
 
 pub use self::filters::DspFilter;
@@ -32,6 +39,17 @@ pub enum FilterType {
     FamiCom,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChannelId {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+    // Index into the mapper's channel list
+    Expansion(usize),
+}
+
 pub struct ApuState {
     pub current_cycle: u64,
 
@@ -54,7 +72,12 @@ pub struct ApuState {
     pub edge_buffer: RingBuffer,
     pub output_buffer: Vec<i16>,
     pub buffer_full: bool,
+    // The same samples again, for consumers that pull through drain_samples instead
+    pub sample_queue: SampleQueue,
+    // Requested output latency; None sizes the buffers for one frame of audio instead
+    pub buffer_ms: Option<u32>,
     pub sample_rate: u64,
+    pub region: Region,
     pub cpu_clock_rate: u64,
     pub generated_samples: u64,
     pub next_sample_at: u64,
@@ -62,19 +85,36 @@ pub struct ApuState {
     // Lookup tables for emulating the mixer
     pub pulse_table: Vec<f32>,
     pub tnd_table: Vec<f32>,
+    // User mix levels for Pulse 1, Pulse 2, Triangle, Noise and DMC, applied to each channel's
+    // output level ahead of the mixer. The lookup tables only cover unity gain.
+    pub channel_gains: [f32; 5],
 
     // filter chain (todo: make this a tad more flexible)
-    // also todo: make sure these are recreated when changing sample rate
 
     pub filter_type: FilterType,
     pub filter_chain: FilterChain,
     pub filter_hq: bool,
 }
 
+fn pulse_mix(combined_pulse: f32) -> f32 {
+    if combined_pulse <= 0.0 {
+        return 0.0;
+    }
+    return 95.52 / (8128.0 / combined_pulse + 100.0);
+}
+
+fn tnd_mix(tri: f32, noise: f32, dmc: f32) -> f32 {
+    let weighted = (tri / 8227.0) + (noise / 12241.0) + (dmc / 22638.0);
+    if weighted <= 0.0 {
+        return 0.0;
+    }
+    return 159.79 / ((1.0 / weighted) + 100.0);
+}
+
 fn generate_pulse_table() -> Vec<f32> {
     let mut pulse_table = vec!(0f32; 31);
     for n in 1 .. 31 {
-        pulse_table[n] = 95.52 / (8128.0 / (n as f32) + 100.0);
+        pulse_table[n] = pulse_mix(n as f32);
     }
     return pulse_table;
 }
@@ -90,7 +130,7 @@ fn generate_tnd_table() -> Vec<f32> {
             for dmc in 0 .. 128 {
                 if tri + noise + dmc > 0 {
                     let i = full_tnd_index(tri, noise, dmc);
-                    tnd_table[i] = 159.79 / ((1.0 / ((tri as f32 / 8227.0) + (noise as f32 / 12241.0) + (dmc as f32 / 22638.0))) + 100.0);
+                    tnd_table[i] = tnd_mix(tri as f32, noise as f32, dmc as f32);
                 }
             }
         }
@@ -110,72 +150,66 @@ fn recommended_buffer_size(sample_rate: u64) -> usize {
     return buffer_size as usize;
 }
 
-fn construct_hq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType) -> FilterChain {
-    // https://wiki.nesdev.org/w/index.php?title=APU_Mixer
-
-    // First, no matter what the hardware specifies, we'll do a lightweight downsample to around 8x
-    // the target sample rate. This is to somewhat reduce the CPU cost of the rest of the chain
-    let mut chain = FilterChain::new();
-    let intermediate_samplerate = target_sample_rate * (2.0 + (std::f32::consts::PI / 32.0));
-    let intermediate_cutoff_frequency = target_sample_rate * 0.4;
-    // This IIR isn't especially sharp, but that's okay. We'll do a better filter later
-    // to deal with any aliasing this leaves behind
-    chain.add(Box::new(filters::LowPassIIR::new(clock_rate, intermediate_cutoff_frequency)), clock_rate);
+/// Samples needed to cover `buffer_ms` milliseconds at `sample_rate`, rounded up, and never
+/// less than one.
+pub fn buffer_size_for_latency(sample_rate: u64, buffer_ms: u32) -> usize {
+    let samples = (sample_rate * buffer_ms as u64 + 999) / 1000;
+    return std::cmp::max(samples, 1) as usize;
+}
 
+// https://wiki.nesdev.org/w/index.php?title=APU_Mixer
+// The RC filters on the console's audio output. These run last, at the output sample rate, so
+// their cutoffs stay put whatever rate the host asks for.
+fn add_hardware_filters(chain: &mut FilterChain, sample_rate: f32, filter_type: FilterType) {
     match filter_type {
         FilterType::Nes => {
             //The NES hardware follows the DACs with a surprisingly involved circuit that adds several low-pass and high-pass filters:
 
             // A first-order high-pass filter at 90 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(intermediate_samplerate, 90.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 90.0)), sample_rate);
             //  Another first-order high-pass filter at 440 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(intermediate_samplerate, 440.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 440.0)), sample_rate);
             // A first-order low-pass filter at 14 kHz
-            chain.add(Box::new(filters::LowPassIIR::new(intermediate_samplerate, 14000.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::LowPassIIR::new(sample_rate, 14000.0)), sample_rate);
         },
         FilterType::FamiCom => {
             // The Famicom hardware instead ONLY specifies a first-order high-pass filter at 37 Hz, 
             // followed by the unknown (and varying) properties of the RF modulator and demodulator. 
-            chain.add(Box::new(filters::HighPassIIR::new(intermediate_samplerate, 37.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 37.0)), sample_rate);
         }
     }
+}
+
+fn construct_hq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType) -> FilterChain {
+    // First, no matter what the hardware specifies, we'll do a lightweight downsample to around 8x
+    // the target sample rate. This is to somewhat reduce the CPU cost of the rest of the chain
+    let mut chain = FilterChain::new();
+    let intermediate_samplerate = target_sample_rate * (2.0 + (std::f32::consts::PI / 32.0));
+    let intermediate_cutoff_frequency = target_sample_rate * 0.4;
+    // This IIR isn't especially sharp, but that's okay. We'll do a better filter later
+    // to deal with any aliasing this leaves behind
+    chain.add(Box::new(filters::LowPassIIR::new(clock_rate, intermediate_cutoff_frequency)), clock_rate);
 
-    // Finally, perform a high-quality low pass, the result of which will be decimated to become the final output
+    // Next, perform a high-quality low pass, the result of which will be decimated to become the final output
     // TODO: 160 is huge! That was needed when going from 1.7 MHz -> 44.1 kHz; is it still needed when the source
     // is more like 88.2 kHz? Figure out if we can lower this, it's very expensive.
     let window_size = 160;
     let cutoff_frequency = target_sample_rate * 0.45;
     chain.add(Box::new(filters::LowPassFIR::new(intermediate_samplerate, cutoff_frequency, window_size)), intermediate_samplerate);
 
+    add_hardware_filters(&mut chain, target_sample_rate, filter_type);
+
     return chain;
 }
 
 fn construct_lq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType) -> FilterChain {
-    // https://wiki.nesdev.org/w/index.php?title=APU_Mixer
-
     // Quicker and more dirty. Will sound somewhat muffled.
     let mut chain = FilterChain::new();
     let cutoff_frequency = target_sample_rate * 0.40;
 
     chain.add(Box::new(filters::LowPassIIR::new(clock_rate, cutoff_frequency)), clock_rate);
 
-    match filter_type {
-        FilterType::Nes => {
-            //The NES hardware follows the DACs with a surprisingly involved circuit that adds several low-pass and high-pass filters:
-
-            // A first-order high-pass filter at 90 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(target_sample_rate, 90.0)), target_sample_rate);
-            //  Another first-order high-pass filter at 440 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(target_sample_rate, 440.0)), target_sample_rate);
-            // A first-order low-pass filter at 14 kHz
-            chain.add(Box::new(filters::LowPassIIR::new(target_sample_rate, 14000.0)), target_sample_rate);
-        },
-        FilterType::FamiCom => {
-            // The Famicom hardware instead ONLY specifies a first-order high-pass filter at 37 Hz, 
-            // followed by the unknown (and varying) properties of the RF modulator and demodulator. 
-            chain.add(Box::new(filters::HighPassIIR::new(target_sample_rate, 37.0)), target_sample_rate);
-        }
-    }
+    add_hardware_filters(&mut chain, target_sample_rate, filter_type);
 
     return chain;
 }
@@ -203,12 +237,16 @@ impl ApuState {
             edge_buffer: RingBuffer::new(output_buffer_size),
             output_buffer: vec!(0i16; output_buffer_size),
             buffer_full: false,
+            sample_queue: SampleQueue::new(output_buffer_size * 4),
+            buffer_ms: None,
             sample_rate: default_samplerate,
+            region: Region::Ntsc,
             cpu_clock_rate: 1_789_773,
             generated_samples: 0,
             next_sample_at: 0,
             pulse_table: generate_pulse_table(),
             tnd_table: generate_tnd_table(),
+            channel_gains: [1.0; 5],
 
             filter_type: FilterType::FamiCom,
             filter_chain: construct_hq_filter_chain(1789773.0, 44100.0, FilterType::FamiCom),
@@ -222,13 +260,43 @@ impl ApuState {
         self.buffer_full = false;
     }
 
-    pub fn set_sample_rate(&mut self, sample_rate: u64) {
+    // Should match the host audio device; anything else will play back at the wrong pitch
+    pub fn set_output_sample_rate(&mut self, hz: u32) {
+        let sample_rate = hz as u64;
         self.sample_rate = sample_rate;
+        // Sample timing is derived from the running sample count, so rebase that count onto
+        // the new rate. Otherwise the next sample lands far in the past or future, and we
+        // either flood the buffer or go quiet until the count catches up.
+        self.generated_samples = (self.current_cycle * sample_rate) / self.cpu_clock_rate;
+        self.next_sample_at = ((self.generated_samples + 1) * self.cpu_clock_rate) / self.sample_rate;
         self.update_filter();
-        let output_buffer_size = recommended_buffer_size(sample_rate);
+        let output_buffer_size = match self.buffer_ms {
+            Some(buffer_ms) => buffer_size_for_latency(sample_rate, buffer_ms),
+            None => recommended_buffer_size(sample_rate)
+        };
         self.set_buffer_size(output_buffer_size);
     }
 
+    /// Sizes the output buffers to hold `buffer_ms` of audio at the current sample rate. The
+    /// setting survives later sample rate and region changes.
+    pub fn set_buffer_latency(&mut self, buffer_ms: u32) {
+        self.buffer_ms = Some(buffer_ms);
+        self.set_buffer_size(buffer_size_for_latency(self.sample_rate, buffer_ms));
+    }
+
+    /// Switches the CPU clock rate, frame counter timing, and noise / DMC period tables.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.cpu_clock_rate = region.cpu_clock_rate();
+        self.pulse_1.cpu_clock_rate = self.cpu_clock_rate;
+        self.pulse_2.cpu_clock_rate = self.cpu_clock_rate;
+        self.triangle.cpu_clock_rate = self.cpu_clock_rate;
+        self.noise.period_table = region.noise_periods();
+        // Rebase sample timing onto the new clock, same as a sample rate change
+        let sample_rate = self.sample_rate as u32;
+        self.set_output_sample_rate(sample_rate);
+    }
+
     pub fn set_filter(&mut self, filter_type: FilterType, hq: bool) {
         self.filter_type = filter_type;
         self.filter_hq = hq;
@@ -253,6 +321,16 @@ impl ApuState {
         return channels;
     }
 
+    pub fn snapshot(&self) -> ApuSnapshot {
+        return ApuSnapshot {
+            pulse_1: ChannelSnapshot::from_channel(&self.pulse_1),
+            pulse_2: ChannelSnapshot::from_channel(&self.pulse_2),
+            triangle: ChannelSnapshot::from_channel(&self.triangle),
+            noise: ChannelSnapshot::from_channel(&self.noise),
+            dmc: ChannelSnapshot::from_channel(&self.dmc),
+        };
+    }
+
     pub fn channels_mut(&mut self) -> Vec<&mut dyn AudioChannelState> {
         let mut channels: Vec<&mut  dyn AudioChannelState> = Vec::new();
         channels.push(&mut self.dmc);
@@ -263,6 +341,9 @@ impl ApuState {
         return channels;
     }
 
+    /// $4015 status without side effects: bits 0-3 are set while the pulse, triangle and noise
+    /// length counters are nonzero, bit 4 while the DMC has bytes left to play, bit 6 is the
+    /// frame IRQ and bit 7 the DMC IRQ. Bit 5 is open bus, and is filled in by the caller.
     pub fn debug_read_register(&self, address: u16) -> u8 {
         match address {
             0x4015 => {
@@ -299,8 +380,9 @@ impl ApuState {
         let data = self.debug_read_register(address);
         match address {
             0x4015 => {
-                // Reading from this register resets frame_interrupt:
-                self.frame_interrupt = false;                
+                // Reading from this register resets frame_interrupt. The DMC's flag is left alone;
+                // only a write to $4015, or clearing the IRQ enable bit in $4010, acknowledges it.
+                self.frame_interrupt = false;
             },
             _ => {}
         }
@@ -417,8 +499,7 @@ impl ApuState {
                 self.noise.envelope.volume_register = data & 0b0000_1111;
             },
             0x400E => {
-                let noise_period = [
-                    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+                let noise_period = self.region.noise_periods();
 
                 let mode =        (data & 0b1000_0000) >> 7;
                 let period_index = data & 0b0000_1111;
@@ -435,8 +516,7 @@ impl ApuState {
 
             // DMC Channel
             0x4010 => {
-                let period_table = [
-                    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106,  84,  72,  54];
+                let period_table = self.region.dmc_periods();
                 self.dmc.looping = (data & 0b0100_0000) != 0;
                 self.dmc.interrupt_enabled = (data & 0b1000_0000) != 0;
                 if !self.dmc.interrupt_enabled {
@@ -488,6 +568,9 @@ impl ApuState {
             }
 
             // Frame Counter / Interrupts
+            // MI-- ----: M selects the 5-step sequence (which never raises the frame IRQ), and I
+            // inhibits the frame IRQ. The sequencer restarts 3 or 4 CPU cycles later, depending
+            // on which half of the APU cycle the write landed in.
             0x4017 => {
                 self.frame_sequencer_mode = (data & 0b1000_0000) >> 7;
                 self.disable_interrupt =    (data & 0b0100_0000) != 0;
@@ -521,52 +604,41 @@ impl ApuState {
             }
         }
 
+        let steps = self.region.frame_sequencer_steps();
         if self.frame_sequencer_mode == 0 {
             // 4-step sequence
-            match self.frame_sequencer {
-                7457 => self.clock_quarter_frame(),
-                14913 => {
-                    self.clock_quarter_frame();
-                    self.clock_half_frame();
-                },
-                22371 => self.clock_quarter_frame(),
-                29828 => {
-                    if !self.disable_interrupt {
-                        self.frame_interrupt = true;
-                    }
-                },
-                29829 => {
-                    if !self.disable_interrupt {
-                        self.frame_interrupt = true;
-                    }
-                    self.clock_quarter_frame();
-                    self.clock_half_frame();
-                },
-                29830 => {
-                    if !self.disable_interrupt {
-                        self.frame_interrupt = true;
-                    }
-                    self.frame_sequencer = 0;
-                },
-                _ => ()
+            let step = &steps.four_step;
+            if self.frame_sequencer == step[0] || self.frame_sequencer == step[2] {
+                self.clock_quarter_frame();
+            } else if self.frame_sequencer == step[1] {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            } else if self.frame_sequencer == step[3] {
+                if !self.disable_interrupt {
+                    self.frame_interrupt = true;
+                }
+            } else if self.frame_sequencer == step[4] {
+                if !self.disable_interrupt {
+                    self.frame_interrupt = true;
+                }
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            } else if self.frame_sequencer == step[5] {
+                if !self.disable_interrupt {
+                    self.frame_interrupt = true;
+                }
+                self.frame_sequencer = 0;
             }
         } else {
-            match self.frame_sequencer {
-                // "5-step" sequence (uneven timing)
-                7457 => self.clock_quarter_frame(),
-                14913 => {
-                    self.clock_quarter_frame();
-                    self.clock_half_frame();
-                },
-                22371 => self.clock_quarter_frame(),
-                37281 => {
-                    self.clock_quarter_frame();
-                    self.clock_half_frame();
-                },
-                37282 => {
-                  self.frame_sequencer = 0;  
-                },
-                _ => ()
+            // "5-step" sequence (uneven timing)
+            let step = &steps.five_step;
+            if self.frame_sequencer == step[0] || self.frame_sequencer == step[2] {
+                self.clock_quarter_frame();
+            } else if self.frame_sequencer == step[1] || self.frame_sequencer == step[3] {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            } else if self.frame_sequencer == step[4] {
+                self.frame_sequencer = 0;
             }
         }
         
@@ -604,8 +676,9 @@ impl ApuState {
         if (self.current_cycle & 0b1) == 0 {
             self.pulse_1.clock();
             self.pulse_2.clock();
-            self.dmc.clock(mapper);
+            self.dmc.clock();
         }
+        self.dmc.clock_dma(mapper, (self.current_cycle & 0b1) == 0);
         
         // Collect current samples from the various channels
         let pulse_1_sample = self.pulse_1.output();
@@ -616,19 +689,22 @@ impl ApuState {
 
         // Mix samples, using the LUT we generated earlier, based on documentation here:
         // https://wiki.nesdev.com/w/index.php/APU_Mixer
-        let mut combined_pulse = 0;
-        if !(self.pulse_1.debug_disable) {
-            combined_pulse += pulse_1_sample;
-        }
-        if !(self.pulse_2.debug_disable) {
-            combined_pulse += pulse_2_sample;
-        }
-        let pulse_output = self.pulse_table[combined_pulse as usize];
-        
+        let pulse_1_output = if self.pulse_1.debug_disable {0} else {pulse_1_sample};
+        let pulse_2_output = if self.pulse_2.debug_disable {0} else {pulse_2_sample};
         let tri_output = if self.triangle.debug_disable {0} else {triangle_sample};
         let noise_output = if self.noise.debug_disable {0} else {noise_sample};
         let dmc_output = if self.dmc.debug_disable {0} else {dmc_sample};
-        let tnd_output = self.tnd_table[full_tnd_index(tri_output as usize, noise_output as usize, dmc_output as usize)];
+
+        let (pulse_output, tnd_output) = if self.channel_gains.iter().all(|gain| *gain == 1.0) {
+            let combined_pulse = pulse_1_output + pulse_2_output;
+            (self.pulse_table[combined_pulse as usize],
+             self.tnd_table[full_tnd_index(tri_output as usize, noise_output as usize, dmc_output as usize)])
+        } else {
+            // Scaled levels fall between table entries, so evaluate the mixer formulas directly
+            let gains = self.channel_gains;
+            (pulse_mix(pulse_1_output as f32 * gains[0] + pulse_2_output as f32 * gains[1]),
+             tnd_mix(tri_output as f32 * gains[2], noise_output as f32 * gains[3], dmc_output as f32 * gains[4]))
+        };
 
         let current_2a03_sample = (pulse_output - 0.5) + (tnd_output - 0.5);
         let current_dac_sample = mapper.mix_expansion_audio(current_2a03_sample) as f32;
@@ -641,6 +717,7 @@ impl ApuState {
             let composite_sample = (self.filter_chain.output() * 32767.0) as i16;
 
             self.staging_buffer.push(composite_sample);
+            self.sample_queue.push(composite_sample as f32 / 32767.0);
             self.edge_buffer.push(true as i16);
 
             // Write debug buffers from these, regardless of enable / disable status
@@ -691,6 +768,23 @@ impl ApuState {
         let _ = file.write_all(&buffer);
     }
 
+    /// Sets how many samples drain_samples can fall behind by before the oldest are dropped.
+    /// Anything still queued is discarded.
+    pub fn set_sample_queue_length(&mut self, length: usize) {
+        self.sample_queue = SampleQueue::new(length);
+    }
+
+    /// Pulls the oldest queued samples (as -1.0 to 1.0) into `out`, returning how many were
+    /// available. Anything not filled is left untouched.
+    pub fn drain_samples(&mut self, out: &mut [f32]) -> usize {
+        return self.sample_queue.drain(out);
+    }
+
+    /// Samples lost because drain_samples wasn't called often enough, for diagnostics
+    pub fn dropped_samples(&self) -> u64 {
+        return self.sample_queue.dropped();
+    }
+
     pub fn consume_samples(&mut self) -> Vec<i16> {
         let mut output_buffer = vec!(0i16; 0);
         if self.buffer_full {
@@ -703,6 +797,7 @@ impl ApuState {
         return output_buffer;
     }
 
+    // Feeds the CPU's IRQ line (see cycle_cpu::irq_signal) alongside the mapper's
     pub fn irq_signal(&self) -> bool {
         return self.frame_interrupt || self.dmc.interrupt_flag;
     }
@@ -724,6 +819,96 @@ impl ApuState {
             channels[channel_index].unmute();
         }
     }
+
+    /// Finds a channel by the chip and name it reports through AudioChannelState, which is how
+    /// the UI's channel lists refer to them.
+    pub fn channel_id(&self, mapper: &dyn Mapper, chip: &str, name: &str) -> Option<ChannelId> {
+        let stock_channels: [(&dyn AudioChannelState, ChannelId); 5] = [
+            (&self.pulse_1, ChannelId::Pulse1),
+            (&self.pulse_2, ChannelId::Pulse2),
+            (&self.triangle, ChannelId::Triangle),
+            (&self.noise, ChannelId::Noise),
+            (&self.dmc, ChannelId::Dmc),
+        ];
+        for &(channel, id) in stock_channels.iter() {
+            if channel.chip() == chip && channel.name() == name {
+                return Some(id);
+            }
+        }
+        return mapper.channels().iter()
+            .position(|channel| channel.chip() == chip && channel.name() == name)
+            .map(|index| ChannelId::Expansion(index));
+    }
+
+    /// Muted channels keep running (length counters, sweep, DMC IRQs and so on all behave
+    /// normally) but are left out of the final mix.
+    pub fn set_channel_enabled(&mut self, mapper: &mut dyn Mapper, channel: ChannelId, enabled: bool) {
+        let target: Option<&mut dyn AudioChannelState> = match channel {
+            ChannelId::Pulse1 => Some(&mut self.pulse_1),
+            ChannelId::Pulse2 => Some(&mut self.pulse_2),
+            ChannelId::Triangle => Some(&mut self.triangle),
+            ChannelId::Noise => Some(&mut self.noise),
+            ChannelId::Dmc => Some(&mut self.dmc),
+            ChannelId::Expansion(index) => mapper.channels_mut().into_iter().nth(index),
+        };
+        match target {
+            Some(channel) => {
+                if enabled {channel.unmute()} else {channel.mute()}
+            },
+            None => {}
+        }
+    }
+
+    /// Scales one 2A03 channel ahead of the mixer; 1.0 is the console's own balance. Expansion
+    /// channels are mixed by the mapper, and aren't affected.
+    pub fn set_channel_volume(&mut self, channel: ChannelId, gain: f32) {
+        let index = match channel {
+            ChannelId::Pulse1 => 0,
+            ChannelId::Pulse2 => 1,
+            ChannelId::Triangle => 2,
+            ChannelId::Noise => 3,
+            ChannelId::Dmc => 4,
+            ChannelId::Expansion(_) => {return;}
+        };
+        self.channel_gains[index] = gain.max(0.0);
+    }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u64(buff, self.current_cycle);
+        save_u8(buff, self.frame_sequencer_mode);
+        save_u16(buff, self.frame_sequencer);
+        save_u8(buff, self.frame_reset_delay);
+        save_u32(buff, self.quarter_frame_counter);
+        save_u32(buff, self.half_frame_counter);
+        save_bool(buff, self.frame_interrupt);
+        save_bool(buff, self.disable_interrupt);
+        self.pulse_1.save_state(buff);
+        self.pulse_2.save_state(buff);
+        self.triangle.save_state(buff);
+        self.noise.save_state(buff);
+        self.dmc.save_state(buff);
+        save_u64(buff, self.generated_samples);
+        save_u64(buff, self.next_sample_at);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.current_cycle = buff.load_u64()?;
+        self.frame_sequencer_mode = buff.load_u8()?;
+        self.frame_sequencer = buff.load_u16()?;
+        self.frame_reset_delay = buff.load_u8()?;
+        self.quarter_frame_counter = buff.load_u32()?;
+        self.half_frame_counter = buff.load_u32()?;
+        self.frame_interrupt = buff.load_bool()?;
+        self.disable_interrupt = buff.load_bool()?;
+        self.pulse_1.load_state(buff)?;
+        self.pulse_2.load_state(buff)?;
+        self.triangle.load_state(buff)?;
+        self.noise.load_state(buff)?;
+        self.dmc.load_state(buff)?;
+        self.generated_samples = buff.load_u64()?;
+        self.next_sample_at = buff.load_u64()?;
+        return Ok(());
+    }
 }
 
 // The APU itself counts as a channel, loosely, mostly for debugging purposes. Its output is a
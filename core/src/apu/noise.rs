@@ -7,6 +7,8 @@ use super::audio_channel::Timbre;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use region::Region;
+use save_load::*;
 
 pub struct NoiseChannelState {
     pub name: String,
@@ -25,6 +27,8 @@ pub struct NoiseChannelState {
     pub mode: u8,
     pub period_initial: u16,
     pub period_current: u16,
+    // The console's period lookup, used to recover the register index for rate()
+    pub period_table: &'static [u16; 16],
 
     // Actually a 15-bit register
     pub shift_register: u16,
@@ -39,7 +43,7 @@ impl NoiseChannelState {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
             length: 0,
             length_halt_flag: false,
 
@@ -48,6 +52,7 @@ impl NoiseChannelState {
             mode: 0,
             period_initial: 0,
             period_current: 0,
+            period_table: Region::Ntsc.noise_periods(),
 
             // Actually a 15-bit register
             shift_register: 1,
@@ -81,6 +86,29 @@ impl NoiseChannelState {
             return 0;
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.length);
+        save_bool(buff, self.length_halt_flag);
+        self.envelope.save_state(buff);
+        self.length_counter.save_state(buff);
+        save_u8(buff, self.mode);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_u16(buff, self.shift_register);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.length = buff.load_u8()?;
+        self.length_halt_flag = buff.load_bool()?;
+        self.envelope.load_state(buff)?;
+        self.length_counter.load_state(buff)?;
+        self.mode = buff.load_u8()?;
+        self.period_initial = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.shift_register = buff.load_u16()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for NoiseChannelState {
@@ -134,24 +162,10 @@ impl AudioChannelState for NoiseChannelState {
     }
 
     fn rate(&self) -> PlaybackRate {
-        let lsfr_index = match self.period_initial {
-            4    => {0xF},
-            8    => {0xE},
-            16   => {0xD},
-            32   => {0xC},
-            64   => {0xB},
-            96   => {0xA},
-            128  => {0x9},
-            160  => {0x8},
-            202  => {0x7},
-            254  => {0x6},
-            380  => {0x5},
-            508  => {0x4},
-            762  => {0x3},
-            1016 => {0x2},
-            2034 => {0x1},
-            4068 => {0x0},
-            _ => {0x0} // also unreachable
+        // The table runs from the fastest period to the slowest, while the index runs the other way
+        let lsfr_index = match self.period_table.iter().position(|&period| period == self.period_initial) {
+            Some(position) => 0xF - position,
+            None => 0x0 // unreachable once the period register has been written
         };
         return PlaybackRate::LfsrRate {index: lsfr_index, max: 0xF};
     }
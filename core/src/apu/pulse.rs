@@ -7,6 +7,7 @@ use super::audio_channel::Timbre;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use save_load::*;
 
 pub struct PulseChannelState {
     pub name: String,
@@ -46,7 +47,7 @@ impl PulseChannelState {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0), // for visual flair, and also to remove DC offset
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0), // for visual flair, and also to remove DC offset
 
             envelope: VolumeEnvelopeState::new(),
             length_counter: LengthCounterState::new(),
@@ -136,6 +137,37 @@ impl PulseChannelState {
             self.sweep_divider -= 1;
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.envelope.save_state(buff);
+        self.length_counter.save_state(buff);
+        save_bool(buff, self.sweep_enabled);
+        save_u8(buff, self.sweep_period);
+        save_u8(buff, self.sweep_divider);
+        save_bool(buff, self.sweep_negate);
+        save_u8(buff, self.sweep_shift);
+        save_bool(buff, self.sweep_reload);
+        save_u8(buff, self.duty);
+        save_u8(buff, self.sequence_counter);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.envelope.load_state(buff)?;
+        self.length_counter.load_state(buff)?;
+        self.sweep_enabled = buff.load_bool()?;
+        self.sweep_period = buff.load_u8()?;
+        self.sweep_divider = buff.load_u8()?;
+        self.sweep_negate = buff.load_bool()?;
+        self.sweep_shift = buff.load_u8()?;
+        self.sweep_reload = buff.load_bool()?;
+        self.duty = buff.load_u8()?;
+        self.sequence_counter = buff.load_u8()?;
+        self.period_initial = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for PulseChannelState {
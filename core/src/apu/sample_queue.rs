@@ -0,0 +1,63 @@
+// A fixed capacity FIFO of mixed output samples, for audio callbacks that would rather pull
+// exactly as many samples as they need than collect whole buffers. Once full, each new sample
+// pushes out the oldest one, so a stalled consumer hears the most recent audio when it resumes.
+
+pub struct SampleQueue {
+    buffer: Vec<f32>,
+    read_index: usize,
+    length: usize,
+    dropped: u64,
+}
+
+impl SampleQueue {
+    pub fn new(capacity: usize) -> SampleQueue {
+        return SampleQueue {
+            buffer: vec!(0f32; capacity.max(1)),
+            read_index: 0,
+            length: 0,
+            dropped: 0,
+        };
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        let capacity = self.buffer.len();
+        if self.length == capacity {
+            self.read_index = (self.read_index + 1) % capacity;
+            self.length -= 1;
+            self.dropped += 1;
+        }
+        let write_index = (self.read_index + self.length) % capacity;
+        self.buffer[write_index] = sample;
+        self.length += 1;
+    }
+
+    /// Copies out up to `out.len()` of the oldest samples, returning how many were written
+    pub fn drain(&mut self, out: &mut [f32]) -> usize {
+        let capacity = self.buffer.len();
+        let count = out.len().min(self.length);
+        for sample in out[.. count].iter_mut() {
+            *sample = self.buffer[self.read_index];
+            self.read_index = (self.read_index + 1) % capacity;
+        }
+        self.length -= count;
+        return count;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.length;
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.buffer.len();
+    }
+
+    /// Samples discarded on overflow since the queue was created
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+
+    pub fn clear(&mut self) {
+        self.read_index = 0;
+        self.length = 0;
+    }
+}
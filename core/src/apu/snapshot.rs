@@ -0,0 +1,40 @@
+// A plain, copyable summary of what each 2A03 channel is doing right now. Visualizers can take
+// one of these per frame and keep it around without borrowing the running emulator.
+
+use super::audio_channel::AudioChannelState;
+use super::audio_channel::PlaybackRate;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelSnapshot {
+    pub playing: bool,
+    pub muted: bool,
+    // The pitch of the note, for tonal channels. Noise has no meaningful pitch, and is None.
+    pub frequency: Option<f32>,
+    // Same scale as AudioChannelState::amplitude, where 1.0 is a pulse channel at full volume
+    pub amplitude: f32,
+}
+
+impl ChannelSnapshot {
+    pub fn from_channel(channel: &dyn AudioChannelState) -> ChannelSnapshot {
+        let frequency = match channel.rate() {
+            PlaybackRate::FundamentalFrequency{frequency} => Some(frequency),
+            PlaybackRate::SampleRate{frequency} => Some(frequency),
+            PlaybackRate::LfsrRate{index: _, max: _} => None,
+        };
+        return ChannelSnapshot {
+            playing: channel.playing(),
+            muted: channel.muted(),
+            frequency: frequency,
+            amplitude: channel.amplitude(),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ApuSnapshot {
+    pub pulse_1: ChannelSnapshot,
+    pub pulse_2: ChannelSnapshot,
+    pub triangle: ChannelSnapshot,
+    pub noise: ChannelSnapshot,
+    pub dmc: ChannelSnapshot,
+}
@@ -6,6 +6,11 @@ use super::audio_channel::Timbre;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use save_load::*;
+
+// At or below this period the triangle runs faster than the mixer can follow, and the output
+// settles near the middle of the waveform instead of producing an audible tone
+const ULTRASONIC_PERIOD: u16 = 2;
 
 pub struct TriangleChannelState {
     pub name: String,
@@ -38,7 +43,7 @@ impl TriangleChannelState {
             debug_disable: false,
             output_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
             edge_buffer: RingBuffer::new(32768),
             length_counter: LengthCounterState::new(),
             control_flag: false,
@@ -93,7 +98,7 @@ impl TriangleChannelState {
     }
 
     pub fn output(&self) -> i16 {
-        if self.period_initial <= 2 {
+        if self.period_initial <= ULTRASONIC_PERIOD {
             // This frequency is so high that the hardware mixer can't keep up, and effectively
             // receives 7.5. We'll just return 7 here (close enough). Some games use this
             // to silence the channel, and returning 7 emulates the resulting clicks and pops.
@@ -104,6 +109,31 @@ impl TriangleChannelState {
             return triangle_sequence[self.sequence_counter as usize];
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.length_counter.save_state(buff);
+        save_bool(buff, self.control_flag);
+        save_bool(buff, self.linear_reload_flag);
+        save_u8(buff, self.linear_counter_initial);
+        save_u8(buff, self.linear_counter_current);
+        save_u8(buff, self.sequence_counter);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_u8(buff, self.length);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.length_counter.load_state(buff)?;
+        self.control_flag = buff.load_bool()?;
+        self.linear_reload_flag = buff.load_bool()?;
+        self.linear_counter_initial = buff.load_u8()?;
+        self.linear_counter_current = buff.load_u8()?;
+        self.sequence_counter = buff.load_u8()?;
+        self.period_initial = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.length = buff.load_u8()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for TriangleChannelState {
@@ -151,10 +181,16 @@ impl AudioChannelState for TriangleChannelState {
     }
 
     fn playing(&self) -> bool {
+        // Writing $400B only sets the reload flag; the linear counter itself isn't refilled until
+        // the next quarter frame. Treat a pending reload as playing, otherwise a retriggered note
+        // reads as silent for part of a frame and flickers in the debug views.
+        let linear_active = 
+            self.linear_counter_current != 0 ||
+            (self.linear_reload_flag && self.linear_counter_initial != 0);
         return 
             self.length_counter.length > 0 && 
-            self.linear_counter_current != 0 &&
-            self.period_initial > 2;
+            linear_active &&
+            self.period_initial > ULTRASONIC_PERIOD;
     }
 
     fn rate(&self) -> PlaybackRate {
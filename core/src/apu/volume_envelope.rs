@@ -1,3 +1,5 @@
+use save_load::*;
+
 pub struct VolumeEnvelopeState {
     // Volume Envelope
     pub volume_register: u8,
@@ -49,4 +51,23 @@ impl VolumeEnvelopeState {
             }
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.volume_register);
+        save_u8(buff, self.decay);
+        save_u8(buff, self.divider);
+        save_bool(buff, self.enabled);
+        save_bool(buff, self.looping);
+        save_bool(buff, self.start_flag);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.volume_register = buff.load_u8()?;
+        self.decay = buff.load_u8()?;
+        self.divider = buff.load_u8()?;
+        self.enabled = buff.load_bool()?;
+        self.looping = buff.load_bool()?;
+        self.start_flag = buff.load_bool()?;
+        return Ok(());
+    }
 }
\ No newline at end of file
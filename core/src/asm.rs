@@ -5,6 +5,10 @@
 // the line.
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use opcode_info;
 
 #[derive(Clone,Debug)]
 pub enum AddressingMode {
@@ -350,4 +354,481 @@ pub fn assemble(opcodes: Vec<Opcode>, starting_address: u16) -> Result<Vec<u8>,
         bytes.extend(opcode_bytes(opcode)?);
     }
     return Ok(bytes);
-}
\ No newline at end of file
+}
+
+// Disassembly, for debugger views. This goes the other direction, from raw bytes back
+// to readable text, and leans on opcode_info for the per-opcode tables.
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub operand: String,
+    pub unofficial: bool,
+}
+
+impl Instruction {
+    pub fn text(&self) -> String {
+        if self.operand.len() == 0 {
+            return self.mnemonic.clone();
+        }
+        return format!("{} {}", self.mnemonic, self.operand);
+    }
+
+    /// Where control goes if this instruction jumps or branches: the destination of a branch,
+    /// JMP or JSR. None for everything else, including JMP (indirect), whose destination
+    /// depends on memory.
+    pub fn target(&self) -> Option<u16> {
+        if self.mnemonic == ".byte" || self.bytes.len() == 0 {
+            return None;
+        }
+        let (opcode_name, addressing_mode) = opcode_info::opcode_name_and_mode(self.bytes[0]);
+        return match (opcode_name, addressing_mode) {
+            (_, "r") => Some(self.address.wrapping_add(2).wrapping_add(self.bytes[1] as i8 as u16)),
+            ("JMP", "a") | ("JSR", "a") => Some((self.bytes[2] as u16) << 8 | self.bytes[1] as u16),
+            _ => None
+        };
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    return escaped;
+}
+
+/// Disassembles like `disassemble`, but as a JSON array for external tools. Each instruction
+/// becomes one object, in address order, with these fields:
+///
+/// - `address`: number, the CPU address of the first byte
+/// - `bytes`: array of numbers, the raw instruction bytes
+/// - `mnemonic`: string, e.g. `"LDA"`, or `".byte"` for trailing bytes too short to decode
+/// - `operand`: string, formatted as in `Instruction::text`, empty if there is none
+/// - `target`: number or null, see `Instruction::target`
+/// - `unofficial`: boolean, true for undocumented opcodes
+///
+/// Numbers are plain decimal integers. Fields may be added in the future, but these won't be
+/// renamed or removed.
+pub fn disassemble_to_json(bytes: &[u8], base_addr: u16) -> String {
+    let mut objects: Vec<String> = Vec::new();
+    for instruction in disassemble(bytes, base_addr) {
+        let instruction_bytes = instruction.bytes.iter().map(|b| b.to_string()).collect::<Vec<String>>().join(", ");
+        let target = match instruction.target() {
+            Some(address) => address.to_string(),
+            None => String::from("null"),
+        };
+        objects.push(format!(
+            "{{\"address\": {}, \"bytes\": [{}], \"mnemonic\": {}, \"operand\": {}, \"target\": {}, \"unofficial\": {}}}",
+            instruction.address, instruction_bytes, json_string(&instruction.mnemonic),
+            json_string(&instruction.operand), target, instruction.unofficial));
+    }
+    return format!("[{}]", objects.join(", "));
+}
+
+pub fn format_operand(addressing_mode: &str, address: u16, data: &[u8]) -> String {
+    let byte = if data.len() > 0 {data[0]} else {0};
+    let word = if data.len() > 1 {(data[1] as u16) << 8 | byte as u16} else {byte as u16};
+    return match addressing_mode {
+        "#i" =>     format!("#${:02X}", byte),
+        "d" =>      format!("${:02X}", byte),
+        "d, x" =>   format!("${:02X}, X", byte),
+        "d, y" =>   format!("${:02X}, Y", byte),
+        "(d, x)" => format!("(${:02X}, X)", byte),
+        "(d), y" => format!("(${:02X}), Y", byte),
+        "a" =>      format!("${:04X}", word),
+        "a, x" =>   format!("${:04X}, X", word),
+        "a, y" =>   format!("${:04X}, Y", word),
+        "(a)" =>    format!("(${:04X})", word),
+        // Branches are relative to the address of the following instruction
+        "r" =>      format!("${:04X}", address.wrapping_add(2).wrapping_add(byte as i8 as u16)),
+        _ => String::from("")
+    };
+}
+
+pub fn disassemble(bytes: &[u8], base_addr: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = base_addr.wrapping_add(offset as u16);
+        let opcode = bytes[offset];
+        let (opcode_name, addressing_mode) = opcode_info::opcode_name_and_mode(opcode);
+        let length = 1 + opcode_info::addressing_bytes(addressing_mode) as usize;
+        if offset + length > bytes.len() {
+            // Not enough bytes left for the full instruction; show what remains as raw data
+            let remaining = &bytes[offset ..];
+            instructions.push(Instruction {
+                address: address,
+                bytes: remaining.to_vec(),
+                mnemonic: String::from(".byte"),
+                operand: remaining.iter().map(|b| format!("${:02X}", b)).collect::<Vec<String>>().join(", "),
+                unofficial: false,
+            });
+            break;
+        }
+
+        let mut operand = format_operand(addressing_mode, address, &bytes[offset + 1 .. offset + length]);
+        let accumulator_shift = match opcode {
+            0x0A | 0x2A | 0x4A | 0x6A => true,
+            _ => false
+        };
+        if accumulator_shift {
+            operand = String::from("A");
+        }
+        instructions.push(Instruction {
+            address: address,
+            bytes: bytes[offset .. offset + length].to_vec(),
+            mnemonic: String::from(opcode_name),
+            operand: operand,
+            unofficial: opcode_info::is_unofficial(opcode),
+        });
+        offset += length;
+    }
+    return instructions;
+}
+
+// Text assembly, for quickly writing test ROMs and patches. This is a simple two pass affair:
+// the first pass sizes every line and records label addresses, the second encodes each line
+// using the same opcode_info tables the disassembler reads from. Zero page addressing is only
+// picked when the operand's value is already known on the first pass; forward references are
+// assumed to need a full address.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error for AsmError {}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+enum OperandKind {
+    Implied,
+    Immediate,
+    IndexedIndirectX,
+    IndirectIndexedY,
+    Indirect,
+    Plain,
+    PlainX,
+    PlainY,
+}
+
+enum Statement {
+    Origin(u16),
+    // Element width in bytes, and one expression per element
+    Data(usize, Vec<String>),
+    Instruction(u8, &'static str, String),
+}
+
+impl Statement {
+    fn size(&self) -> u32 {
+        return match self {
+            Statement::Origin(_) => 0,
+            Statement::Data(width, values) => (width * values.len()) as u32,
+            Statement::Instruction(_, mode, _) => 1 + opcode_info::addressing_bytes(mode) as u32,
+        };
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    return match chars.next() {
+        Some(first) => (first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        None => false
+    };
+}
+
+fn evaluate_term(term: &str, labels: &HashMap<String, u16>, here: Option<u16>) -> Result<i32, String> {
+    if term == "*" {
+        return here.map(|address| address as i32).ok_or(String::from("* can't be used here"));
+    }
+    let parsed = if term.starts_with('$') {
+        i32::from_str_radix(&term[1..], 16).ok()
+    } else if term.starts_with('%') {
+        i32::from_str_radix(&term[1..], 2).ok()
+    } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+        term.parse::<i32>().ok()
+    } else if term.len() == 3 && term.starts_with('\'') && term.ends_with('\'') {
+        Some(term.as_bytes()[1] as i32)
+    } else if is_identifier(term) {
+        return match labels.get(term) {
+            Some(address) => Ok(*address as i32),
+            None => Err(format!("Label not found: {}", term))
+        };
+    } else {
+        None
+    };
+    return parsed.ok_or(format!("Can't parse value: {}", term));
+}
+
+// Supports numbers ($hex, %binary, decimal, 'c'), labels, * for the current address, + and -
+// between terms, and a leading < or > to take the low or high byte of the result.
+fn evaluate(expression: &str, labels: &HashMap<String, u16>, here: Option<u16>) -> Result<i32, String> {
+    let expression = expression.trim();
+    if expression.starts_with('<') {
+        return Ok(evaluate(&expression[1..], labels, here)? & 0xFF);
+    }
+    if expression.starts_with('>') {
+        return Ok((evaluate(&expression[1..], labels, here)? >> 8) & 0xFF);
+    }
+    let mut total: i32 = 0;
+    let mut sign: i32 = 1;
+    let mut term = String::new();
+    for c in expression.chars() {
+        if (c == '+' || c == '-') && !term.trim().is_empty() {
+            total += sign * evaluate_term(term.trim(), labels, here)?;
+            term.clear();
+            sign = if c == '-' {-1} else {1};
+        } else if c == '-' && term.trim().is_empty() {
+            sign = -sign;
+        } else {
+            term.push(c);
+        }
+    }
+    if term.trim().is_empty() {
+        return Err(format!("Missing value in expression: {}", expression));
+    }
+    total += sign * evaluate_term(term.trim(), labels, here)?;
+    return Ok(total);
+}
+
+fn parse_operand(operand: &str) -> Result<(OperandKind, String), String> {
+    let compact: String = operand.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = compact.to_ascii_uppercase();
+    let length = compact.len();
+    if compact.is_empty() || upper == "A" {
+        return Ok((OperandKind::Implied, String::new()));
+    }
+    if compact.starts_with('#') {
+        return Ok((OperandKind::Immediate, compact[1 ..].to_string()));
+    }
+    if compact.starts_with('(') {
+        if upper.ends_with(",X)") {
+            return Ok((OperandKind::IndexedIndirectX, compact[1 .. length - 3].to_string()));
+        }
+        if upper.ends_with("),Y") {
+            return Ok((OperandKind::IndirectIndexedY, compact[1 .. length - 3].to_string()));
+        }
+        if upper.ends_with(')') {
+            return Ok((OperandKind::Indirect, compact[1 .. length - 1].to_string()));
+        }
+        return Err(format!("Unbalanced parentheses: {}", operand));
+    }
+    if upper.ends_with(",X") {
+        return Ok((OperandKind::PlainX, compact[.. length - 2].to_string()));
+    }
+    if upper.ends_with(",Y") {
+        return Ok((OperandKind::PlainY, compact[.. length - 2].to_string()));
+    }
+    return Ok((OperandKind::Plain, compact));
+}
+
+fn find_opcode(mnemonic: &str, addressing_mode: &str) -> Option<u8> {
+    for opcode in 0 ..= 0xFFu8 {
+        let (opcode_name, opcode_mode) = opcode_info::opcode_name_and_mode(opcode);
+        if opcode_name == mnemonic && opcode_mode == addressing_mode && !opcode_info::is_unofficial(opcode) {
+            return Some(opcode);
+        }
+    }
+    return None;
+}
+
+fn choose_opcode(mnemonic: &str, kind: &OperandKind, value: &str, labels: &HashMap<String, u16>, here: u16) -> Result<(u8, &'static str), String> {
+    let zero_page = match evaluate(value, labels, Some(here)).ok() {
+        Some(literal) => literal >= 0 && literal <= 0xFF,
+        None => false
+    };
+    let candidates: &[&'static str] = match kind {
+        OperandKind::Implied => &[""],
+        OperandKind::Immediate => &["#i"],
+        OperandKind::IndexedIndirectX => &["(d, x)"],
+        OperandKind::IndirectIndexedY => &["(d), y"],
+        OperandKind::Indirect => &["(a)"],
+        OperandKind::Plain if zero_page => &["r", "d", "a"],
+        OperandKind::Plain => &["r", "a", "d"],
+        OperandKind::PlainX if zero_page => &["d, x", "a, x"],
+        OperandKind::PlainX => &["a, x", "d, x"],
+        OperandKind::PlainY if zero_page => &["d, y", "a, y"],
+        OperandKind::PlainY => &["a, y", "d, y"],
+    };
+    for addressing_mode in candidates {
+        if let Some(opcode) = find_opcode(mnemonic, addressing_mode) {
+            return Ok((opcode, addressing_mode));
+        }
+    }
+    let known_mnemonic = (0 ..= 0xFFu8).any(|opcode|
+        opcode_info::opcode_name_and_mode(opcode).0 == mnemonic && !opcode_info::is_unofficial(opcode));
+    if !known_mnemonic {
+        return Err(format!("Unknown instruction: {}", mnemonic));
+    }
+    return Err(format!("{} doesn't support this addressing mode", mnemonic));
+}
+
+fn define_label(labels: &mut HashMap<String, u16>, name: &str, value: i32, line: usize) -> Result<(), AsmError> {
+    if labels.contains_key(name) {
+        return Err(AsmError{line: line, message: format!("Label defined twice: {}", name)});
+    }
+    if value < 0 || value > 0xFFFF {
+        return Err(AsmError{line: line, message: format!("Value of {} is out of range: {}", name, value)});
+    }
+    labels.insert(name.to_string(), value as u16);
+    return Ok(());
+}
+
+fn encode_value(value: i32, width: usize) -> Result<Vec<u8>, String> {
+    return match width {
+        1 if value >= -128 && value <= 0xFF => Ok(vec![value as u8]),
+        2 if value >= 0 && value <= 0xFFFF => Ok(vec![low(value as u16), high(value as u16)]),
+        _ => Err(format!("Value {} doesn't fit in {} byte(s)", value, width))
+    };
+}
+
+fn encode_instruction(opcode: u8, addressing_mode: &str, operand: &str, labels: &HashMap<String, u16>, here: u16) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![opcode];
+    match addressing_mode {
+        "" => {},
+        "r" => {
+            let target = evaluate(operand, labels, Some(here))?;
+            // Branches are relative to the address of the following instruction
+            let offset = target - (here as i32 + 2);
+            if offset > 127 || offset < -128 {
+                return Err(format!("Branch to {} is out of range ({})", operand, offset));
+            }
+            bytes.push(offset as i8 as u8);
+        },
+        _ => {
+            let value = evaluate(operand, labels, Some(here))?;
+            bytes.extend(encode_value(value, opcode_info::addressing_bytes(addressing_mode) as usize)?);
+        }
+    }
+    return Ok(bytes);
+}
+
+/// Assembles 6502 source text into bytes, starting at the first origin. Supports the official
+/// instruction set, `label:` and `NAME = value` definitions, `.org` / `*=` to set the origin,
+/// and `.byte` / `.word` for data. Comments start with `;`. Moving the origin forward pads the
+/// output with zeroes.
+pub fn assemble_source(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut statements: Vec<(usize, Statement)> = Vec::new();
+    let mut address: u32 = 0;
+
+    // Pass 1: parse every line, and work out where each label lands
+    for (index, raw_line) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |message: String| AsmError{line: line, message: message};
+        let mut code = match raw_line.find(';') {
+            Some(comment) => &raw_line[.. comment],
+            None => raw_line
+        }.trim();
+
+        while let Some(colon) = code.find(':') {
+            let name = code[.. colon].trim();
+            if !is_identifier(name) {
+                return Err(error(format!("Invalid label name: {}", name)));
+            }
+            define_label(&mut labels, name, address as i32, line)?;
+            code = code[colon + 1 ..].trim();
+        }
+        if code.is_empty() {
+            continue;
+        }
+
+        let (word, operand) = if code.starts_with("*=") {
+            (".org", &code[2 ..])
+        } else {
+            match code.find(char::is_whitespace) {
+                Some(space) => (&code[.. space], code[space ..].trim()),
+                None => (code, "")
+            }
+        };
+
+        if operand.starts_with('=') {
+            if !is_identifier(word) {
+                return Err(error(format!("Invalid constant name: {}", word)));
+            }
+            let value = evaluate(&operand[1 ..], &labels, Some(address as u16)).map_err(error)?;
+            define_label(&mut labels, word, value, line)?;
+            continue;
+        }
+
+        let statement = match word.to_ascii_lowercase().as_str() {
+            ".org" => {
+                let origin = evaluate(operand, &labels, Some(address as u16)).map_err(error)?;
+                if origin < 0 || origin > 0xFFFF {
+                    return Err(error(format!("Origin out of range: {}", origin)));
+                }
+                address = origin as u32;
+                Statement::Origin(origin as u16)
+            },
+            ".byte" | ".db" => Statement::Data(1, operand.split(',').map(|value| value.trim().to_string()).collect()),
+            ".word" | ".dw" => Statement::Data(2, operand.split(',').map(|value| value.trim().to_string()).collect()),
+            directive if directive.starts_with('.') => {
+                return Err(error(format!("Unknown directive: {}", word)));
+            },
+            _ => {
+                let mnemonic = word.to_ascii_uppercase();
+                let (kind, value) = parse_operand(operand).map_err(error)?;
+                let (opcode, addressing_mode) = choose_opcode(&mnemonic, &kind, &value, &labels, address as u16).map_err(error)?;
+                Statement::Instruction(opcode, addressing_mode, value)
+            }
+        };
+        address += statement.size();
+        if address > 0x10000 {
+            return Err(error(String::from("Code runs past the end of the address space")));
+        }
+        statements.push((line, statement));
+    }
+
+    // Pass 2: every label is known now, so encode for real
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut start: Option<u16> = None;
+    let mut address: u16 = 0;
+    for (line, statement) in &statements {
+        let error = |message: String| AsmError{line: *line, message: message};
+        let encoded = match statement {
+            Statement::Origin(origin) => {
+                if let Some(start_address) = start {
+                    let end = start_address as u32 + bytes.len() as u32;
+                    if (*origin as u32) < end {
+                        return Err(error(format!("Origin ${:04X} would overwrite earlier output", origin)));
+                    }
+                }
+                address = *origin;
+                continue;
+            },
+            Statement::Data(width, values) => {
+                let mut data = Vec::new();
+                for value in values {
+                    let evaluated = evaluate(value, &labels, Some(address)).map_err(error)?;
+                    data.extend(encode_value(evaluated, *width).map_err(error)?);
+                }
+                data
+            },
+            Statement::Instruction(opcode, addressing_mode, operand) => {
+                encode_instruction(*opcode, addressing_mode, operand, &labels, address).map_err(error)?
+            },
+        };
+        let start_address = *start.get_or_insert(address);
+        let offset = (address - start_address) as usize;
+        if offset > bytes.len() {
+            bytes.resize(offset, 0);
+        }
+        bytes.extend(&encoded);
+        address = address.wrapping_add(encoded.len() as u16);
+    }
+    return Ok(bytes);
+}
@@ -0,0 +1,78 @@
+// CPU breakpoints for the debugger. Execute breakpoints fire when the CPU is about to run
+// the instruction at an address; read and write breakpoints fire on any CPU bus access
+// within a range. A hit doesn't interrupt the instruction in progress. Instead it's
+// recorded here, and the run loops in NesState stop at the next instruction boundary.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BreakpointKind {
+    Execute,
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Breakpoint {
+    pub kind: BreakpointKind,
+    // Inclusive on both ends, so a single address has start == end
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Breakpoint {
+    pub fn matches(&self, kind: BreakpointKind, address: u16) -> bool {
+        return self.kind == kind && address >= self.start && address <= self.end;
+    }
+}
+
+pub struct Breakpoints {
+    pub breakpoints: Vec<Breakpoint>,
+    // The address and kind of the first breakpoint hit during the most recent step
+    pub hit: Option<(u16, BreakpointKind)>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        return Breakpoints {
+            breakpoints: Vec::new(),
+            hit: None,
+        };
+    }
+
+    pub fn add(&mut self, kind: BreakpointKind, start: u16, end: u16) {
+        let breakpoint = Breakpoint{kind: kind, start: start, end: end};
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn add_execute(&mut self, address: u16) {
+        self.add(BreakpointKind::Execute, address, address);
+    }
+
+    pub fn remove(&mut self, kind: BreakpointKind, start: u16, end: u16) {
+        let breakpoint = Breakpoint{kind: kind, start: start, end: end};
+        self.breakpoints.retain(|existing| *existing != breakpoint);
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.hit = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.breakpoints.is_empty();
+    }
+
+    pub fn check(&mut self, kind: BreakpointKind, address: u16) {
+        if self.hit.is_some() {
+            return;
+        }
+        if self.breakpoints.iter().any(|breakpoint| breakpoint.matches(kind, address)) {
+            self.hit = Some((address, kind));
+        }
+    }
+
+    pub fn take_hit(&mut self) -> Option<(u16, BreakpointKind)> {
+        return self.hit.take();
+    }
+}
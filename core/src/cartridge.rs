@@ -20,14 +20,64 @@ use mmc::vrc6::Vrc6;
 use mmc::vrc7::Vrc7;
 
 use ines::INesCartridge;
+use ines::INesError;
 use nsf::NsfFile;
 use fds::FdsFile;
+use region::Region;
 
+use std::error::Error;
+use std::fmt;
 use std::io::Read;
 
-fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, String> {
+#[derive(Clone, Debug, PartialEq)]
+pub enum CartridgeError {
+    // Couldn't read the file at all
+    ReadError(String),
+    // Not an iNES, NSF or FDS image
+    BadMagic,
+    BadHeader(String),
+    TruncatedRom,
+    UnsupportedMapper(u16),
+    // The header parsed fine, but the mapper couldn't work with what it describes
+    MapperError(String),
+}
+
+impl Error for CartridgeError {}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::ReadError(reason) => {write!(f, "Failed to read any data at all: {}", reason)},
+            CartridgeError::BadMagic => {write!(f, "Not a recognized ROM image (no iNES, NSF or FDS signature)")},
+            CartridgeError::BadHeader(reason) => {write!(f, "Invalid iNES header: {}", reason)},
+            CartridgeError::TruncatedRom => {write!(f, "ROM data is truncated; the file is shorter than its header says")},
+            CartridgeError::UnsupportedMapper(number) => {write!(f, "Unsupported iNES mapper: {}", number)},
+            CartridgeError::MapperError(reason) => {write!(f, "{}", reason)},
+        }
+    }
+}
+
+impl From<INesError> for CartridgeError {
+    fn from(error: INesError) -> Self {
+        return match error {
+            INesError::InvalidHeader => CartridgeError::BadMagic,
+            INesError::Truncated => CartridgeError::TruncatedRom,
+            INesError::Unimplemented => CartridgeError::BadHeader(error.to_string()),
+            INesError::ReadError{reason} => CartridgeError::BadHeader(reason),
+        };
+    }
+}
+
+impl From<String> for CartridgeError {
+    fn from(reason: String) -> Self {
+        return CartridgeError::MapperError(reason);
+    }
+}
+
+fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, CartridgeError> {
     let mapper_number = ines.header.mapper_number();
 
+    // New entries here belong in mmc::supported_mappers as well
     let mapper: Box<dyn Mapper> = match mapper_number {
         0 => Box::new(Nrom::from_ines(ines)?),
         1 => Box::new(Mmc1::from_ines(ines)?),
@@ -48,28 +98,31 @@ fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, String> {
         85 => Box::new(Vrc7::from_ines(ines)?),
         682 => Box::new(Rainbow::from_ines(ines)?),
         _ => {
-            return Err(format!("Unsupported iNES mapper: {}", ines.header.mapper_number()));
+            return Err(CartridgeError::UnsupportedMapper(mapper_number));
         }
     };
 
-    println!("Successfully loaded mapper: {}", mapper_number);
+    info!("Successfully loaded mapper: {}", mapper_number);
 
     return Ok(mapper);
 }
 
-pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>, String> {
+pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>, CartridgeError> {
     let mut entire_file = Vec::new();
     match file_reader.read_to_end(&mut entire_file) {
         Ok(_) => {/* proceed normally */},
         Err(e) => {
-            return Err(format!("Failed to read any data at all, giving up.{}\n", e));
+            return Err(CartridgeError::ReadError(e.to_string()));
         }
     }
 
     let mut errors = String::new();
     match INesCartridge::from_reader(&mut entire_file.as_slice()) {
         Ok(ines) => {return mapper_from_ines(ines);},
-        Err(e) => {errors += format!("ines: {}\n", e).as_str()}
+        // With a valid signature this is certainly an iNES file, just a broken one, so
+        // there's no sense trying the other formats
+        Err(e @ INesError::InvalidHeader) => {errors += format!("ines: {}\n", e).as_str()},
+        Err(e) => {return Err(CartridgeError::from(e));}
     }
 
     match NsfFile::from_reader(&mut entire_file.as_slice()) {
@@ -82,10 +135,123 @@ pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>,
         Err(e) => {errors += format!("fds: {}\n", e).as_str()}
     }
 
-    return Err(format!("Unable to open file as any known type, giving up.\n{}", errors));
+    info!("Unable to open file as any known type.\n{}", errors);
+    return Err(CartridgeError::BadMagic);
+}
+
+/// Loads an iNES image specifically, refusing NSF and FDS images as BadMagic.
+pub fn mapper_from_ines_file(file_data: &[u8]) -> Result<Box<dyn Mapper>, CartridgeError> {
+    let ines = INesCartridge::from_reader(&mut &file_data[..])?;
+    return mapper_from_ines(ines);
 }
 
-pub fn mapper_from_file(file_data: &[u8]) -> Result<Box<dyn Mapper>, String> {
+pub fn mapper_from_file(file_data: &[u8]) -> Result<Box<dyn Mapper>, CartridgeError> {
     let mut file_reader = file_data;
     return mapper_from_reader(&mut file_reader);
-}
\ No newline at end of file
+}
+
+/// The region an image's header asks for. Formats without one (and headerless guesses) are NTSC.
+pub fn region_from_file(file_data: &[u8]) -> Region {
+    match INesCartridge::from_reader(&mut &file_data[..]) {
+        Ok(ines) => {return ines.header.region();},
+        Err(_) => {return Region::Ntsc;}
+    }
+}
+
+/// Checksums identifying a game by its ROM contents alone. For iNES files only the PRG and CHR
+/// data are hashed, so dumps that differ only in header bytes (or a missing trainer) still match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RomHashes {
+    pub crc32: u32,
+    pub sha1: [u8; 20],
+}
+
+impl RomHashes {
+    pub fn from_data(data: &[u8]) -> RomHashes {
+        return RomHashes {
+            crc32: crc32(data),
+            sha1: sha1(data),
+        };
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        return self.sha1.iter().map(|byte| format!("{:02x}", byte)).collect();
+    }
+}
+
+/// Hashes the PRG+CHR of an iNES file. Anything else (NSF, FDS) has no header worth
+/// skipping, so the whole file is hashed instead.
+pub fn rom_hash(file_data: &[u8]) -> RomHashes {
+    match INesCartridge::from_reader(&mut &file_data[..]) {
+        Ok(ines) => {
+            let mut rom_data = ines.prg.clone();
+            rom_data.extend_from_slice(&ines.chr);
+            return RomHashes::from_data(&rom_data);
+        },
+        Err(_) => {
+            return RomHashes::from_data(file_data);
+        }
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0 .. 8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    return !crc;
+}
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Pad to a multiple of 64 bytes: a single 1 bit, zeroes, then the bit length as a big-endian u64
+    let mut message = data.to_vec();
+    let bit_length = (data.len() as u64).wrapping_mul(8);
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_length.to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0 .. 16 {
+            w[i] = u32::from_be_bytes([chunk[i*4], chunk[i*4+1], chunk[i*4+2], chunk[i*4+3]]);
+        }
+        for i in 16 .. 80 {
+            w[i] = (w[i-3] ^ w[i-8] ^ w[i-14] ^ w[i-16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for i in 0 .. 80 {
+            let (f, k) = match i {
+                0 ..= 19 => ((b & c) | (!b & d), 0x5A827999),
+                20 ..= 39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40 ..= 59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[i]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for i in 0 .. 5 {
+        digest[i*4 .. i*4+4].copy_from_slice(&h[i].to_be_bytes());
+    }
+    return digest;
+}
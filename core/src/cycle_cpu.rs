@@ -5,10 +5,13 @@
 
 use addressing;
 use memory::read_byte;
+use memory::log_cdl;
+use memory::CDL_EXECUTED;
 use memory::write_byte;
 use nes::NesState;
 use opcodes;
 use unofficial_opcodes;
+use save_load::*;
 
 #[derive(Copy, Clone)]
 pub struct Flags {
@@ -23,6 +26,53 @@ pub struct Flags {
     pub last_nmi: bool,
 }
 
+// The flags a program can see, for debuggers. last_nmi is internal and deliberately absent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpuFlag {
+    Carry,
+    Zero,
+    InterruptDisable,
+    Decimal,
+    Overflow,
+    Negative,
+}
+
+impl Flags {
+    pub fn get(&self, flag: CpuFlag) -> bool {
+        return match flag {
+            CpuFlag::Carry => self.carry,
+            CpuFlag::Zero => self.zero,
+            CpuFlag::InterruptDisable => self.interrupts_disabled,
+            CpuFlag::Decimal => self.decimal,
+            CpuFlag::Overflow => self.overflow,
+            CpuFlag::Negative => self.negative,
+        };
+    }
+
+    pub fn set(&mut self, flag: CpuFlag, value: bool) {
+        match flag {
+            CpuFlag::Carry => self.carry = value,
+            CpuFlag::Zero => self.zero = value,
+            CpuFlag::InterruptDisable => self.interrupts_disabled = value,
+            CpuFlag::Decimal => self.decimal = value,
+            CpuFlag::Overflow => self.overflow = value,
+            CpuFlag::Negative => self.negative = value,
+        }
+    }
+}
+
+// A plain copy of the programmer-visible registers, with P packed into a byte the way PHP
+// would push it (minus the B flag), for debuggers to display and edit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub s: u8,
+    pub pc: u16,
+}
+
 #[derive(Copy, Clone)]
 pub struct Registers {
     pub a: u8,
@@ -73,6 +123,37 @@ impl Registers {
         self.flags.overflow = data & (1 << 6) != 0;
         self.flags.negative = data & (1 << 7) != 0;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.a);
+        save_u8(buff, self.x);
+        save_u8(buff, self.y);
+        save_u16(buff, self.pc);
+        save_u8(buff, self.s);
+        save_bool(buff, self.flags.carry);
+        save_bool(buff, self.flags.zero);
+        save_bool(buff, self.flags.decimal);
+        save_bool(buff, self.flags.interrupts_disabled);
+        save_bool(buff, self.flags.overflow);
+        save_bool(buff, self.flags.negative);
+        save_bool(buff, self.flags.last_nmi);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.a = buff.load_u8()?;
+        self.x = buff.load_u8()?;
+        self.y = buff.load_u8()?;
+        self.pc = buff.load_u16()?;
+        self.s = buff.load_u8()?;
+        self.flags.carry = buff.load_bool()?;
+        self.flags.zero = buff.load_bool()?;
+        self.flags.decimal = buff.load_bool()?;
+        self.flags.interrupts_disabled = buff.load_bool()?;
+        self.flags.overflow = buff.load_bool()?;
+        self.flags.negative = buff.load_bool()?;
+        self.flags.last_nmi = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 pub struct CpuState {
@@ -114,10 +195,49 @@ impl CpuState {
       old_nmi_requested: false,
     }
   }
+
+  pub fn save_state(&self, buff: &mut Vec<u8>) {
+    save_u8(buff, self.tick);
+    save_u8(buff, self.opcode);
+    save_u8(buff, self.data1);
+    save_u8(buff, self.data2);
+    save_u16(buff, self.temp_address);
+    save_bool(buff, self.service_routine_active);
+    save_bool(buff, self.nmi_requested);
+    save_bool(buff, self.irq_requested);
+    save_bool(buff, self.last_nmi);
+    save_bool(buff, self.upcoming_write);
+    save_bool(buff, self.oam_dma_active);
+    save_u16(buff, self.oam_dma_cycle);
+    save_u16(buff, self.oam_dma_address);
+    save_bool(buff, self.old_nmi_requested);
+  }
+
+  pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+    self.tick = buff.load_u8()?;
+    self.opcode = buff.load_u8()?;
+    self.data1 = buff.load_u8()?;
+    self.data2 = buff.load_u8()?;
+    self.temp_address = buff.load_u16()?;
+    self.service_routine_active = buff.load_bool()?;
+    self.nmi_requested = buff.load_bool()?;
+    self.irq_requested = buff.load_bool()?;
+    self.last_nmi = buff.load_bool()?;
+    self.upcoming_write = buff.load_bool()?;
+    self.oam_dma_active = buff.load_bool()?;
+    self.oam_dma_cycle = buff.load_u16()?;
+    self.oam_dma_address = buff.load_u16()?;
+    self.old_nmi_requested = buff.load_bool()?;
+    return Ok(());
+  }
 }
 
 
 
+// The PPU's NMI output is the AND of the vblank flag and PPUCTRL bit 7, and the CPU reacts to
+// its rising edge (see poll_for_interrupts). So enabling NMI partway through vblank fires one
+// immediately, and clearing and setting bit 7 again while vblank is still set fires another.
+// The ppu_vbl_nmi test ROMs check for both.
 pub fn nmi_signal(nes: &NesState) -> bool {
     return ((nes.ppu.control & 0x80) & (nes.ppu.status & 0x80)) != 0;
 }
@@ -126,7 +246,7 @@ pub fn irq_signal(nes: &NesState) -> bool {
   if nes.registers.flags.interrupts_disabled {
     return false;
   } else {
-    return nes.apu.irq_signal() || nes.mapper.irq_flag();
+    return nes.apu.irq_signal() || nes.mapper.irq_pending();
   }
 }
 
@@ -149,8 +269,8 @@ pub fn interrupt_requested(nes: &NesState) -> bool {
 pub fn halt_cpu(nes: &mut NesState) {
   // HALT the CPU. It died, jim.
   if nes.cpu.tick < 10 {
-    println!("STP opcode encountered: {}", nes.cpu.opcode);
-    println!("Proceeding to lock up CPU. Goodbye, cruel world!");
+    warn!("STP opcode encountered: {}", nes.cpu.opcode);
+    warn!("Proceeding to lock up CPU. Goodbye, cruel world!");
   }
   nes.cpu.tick = 10;
 }
@@ -302,7 +422,7 @@ pub fn control_block(nes: &mut NesState) {
 
     _ => {
       // Unimplemented, fall back on old behavior
-      println!("Undefined (0x00) opcode: {:02X}", nes.cpu.opcode);
+      warn!("Undefined (0x00) opcode: {:02X}", nes.cpu.opcode);
       nes.cpu.tick = 0;
     }
   };
@@ -326,9 +446,12 @@ pub fn unofficial_block(nes: &mut NesState, addressing_mode_index: u8, opcode_in
     0xCB => {(addressing::IMMEDIATE.read)(nes, unofficial_opcodes::axs)},
     0xEB => {(addressing::IMMEDIATE.read)(nes, opcodes::sbc)},
     _ => {
-      // The remaining opcodes all use the same addressing mode as the ALU block, and the wame
+      // The remaining opcodes all use the same addressing mode as the ALU block, and the same
       // read / write / modify type as the corresponding RMW block. Opcodes are mostly a combination
       // of the two, with a few exceptions.
+      // SLO, RLA, SRE, RRA, DCP and ISC go through the same modify paths as the official RMW
+      // opcodes, so they pick up the dummy write of the unmodified value and the extra cycles
+      // of the indexed modes (always 7 for a,x / a,y and 8 for (d),y, page cross or not).
 
       let addressing_mode = match addressing_mode_index {
         // Zero Page Mode
@@ -360,19 +483,38 @@ pub fn unofficial_block(nes: &mut NesState, addressing_mode_index: u8, opcode_in
   }
 }
 
+// OAM DMA runs in stages: stage 0 is the halt cycle, then odd stages read a byte ("get") and
+// even stages write it to OAM ("put"), 256 of each. Reads can only happen on an APU get cycle,
+// so a DMA that comes out of its halt cycle on a put cycle spends one more cycle aligning:
+// 513 cycles in total, or 514 when misaligned. A DMC fetch steals the bus on a put stage, and
+// costs the OAM DMA a realignment cycle afterwards.
 pub fn advance_oam_dma(nes: &mut NesState) {
-  if nes.cpu.oam_dma_cycle & 0b1 == 0 && nes.cpu.oam_dma_cycle <= 511 {
-    let address = nes.cpu.oam_dma_address;
-    let oam_byte = read_byte(nes, address);
-    write_byte(nes, 0x2004, oam_byte);
-    nes.cpu.oam_dma_address += 1;
-  }
-  
-  if nes.cpu.oam_dma_cycle & 0b1 == 0 || nes.apu.dmc.rdy_line == false {
+  let get_cycle = (nes.apu.current_cycle & 0b1) == 0;
+  let stage = nes.cpu.oam_dma_cycle;
+
+  if stage == 0 {
+    // Halt cycle
+    nes.cpu.oam_dma_cycle += 1;
+  } else if stage & 0b1 == 1 {
+    if get_cycle {
+      let address = nes.cpu.oam_dma_address;
+      let oam_byte = read_byte(nes, address);
+      write_byte(nes, 0x2004, oam_byte);
+      nes.cpu.oam_dma_address += 1;
+      nes.cpu.oam_dma_cycle += 1;
+    }
+    // Otherwise this is an alignment cycle, and the read waits for the next one
+  } else if nes.apu.dmc.rdy_line == false {
     nes.cpu.oam_dma_cycle += 1;
-  }  
+  }
 
-  if nes.cpu.oam_dma_cycle > 513 {
+  if nes.apu.dmc.rdy_line {
+    // The CPU is already halted, so the DMC's own halt and dummy cycles overlap the OAM DMA
+    nes.apu.dmc.rdy_delay = nes.apu.dmc.rdy_delay.max(2);
+    nes.apu.dmc.cpu_halted = true;
+  }
+
+  if nes.cpu.oam_dma_cycle > 512 {
     nes.cpu.oam_dma_active = false;
   }
 }
@@ -386,6 +528,7 @@ pub fn run_one_clock(nes: &mut NesState) {
   if nes.cpu.upcoming_write == false && nes.apu.dmc.rdy_line == true {
     // The DMC DMA is active during an upcoming READ cycle. PAUSE until the rdy_line
     // is no longer being asserted by the APU.
+    nes.apu.dmc.cpu_halted = true;
     return;
   }
 
@@ -408,9 +551,16 @@ pub fn run_one_clock(nes: &mut NesState) {
 
   // Universal behavior for every opcode
   if nes.cpu.tick == 1 {
+    if nes.trace_sink.is_some() {
+      nes.trace_instruction();
+    }
     // Fetch opcode from memory
     let pc = nes.registers.pc;
+    if !nes.watchpoints.is_empty() {
+      nes.watchpoints.instruction_address = pc;
+    }
     nes.cpu.opcode = read_byte(nes, pc);
+    log_cdl(nes, pc, CDL_EXECUTED);
     nes.registers.pc = nes.registers.pc.wrapping_add(1);
     return; // all done
   }
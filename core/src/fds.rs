@@ -78,6 +78,10 @@ impl FdsFile {
 
         let mut disk_sides: Vec<Vec<u8>> = Vec::new();
 
+        if fds_data.len() < 16 {
+            return Err(FdsError::InvalidHeader);
+        }
+
         // First try the 16-byte header originating in fwNES
         let header = FdsHeader::from(&fds_data[0..16]);
         if header.magic_header_valid() {
@@ -99,8 +103,7 @@ impl FdsFile {
         // Second, see if the first 15 bytes correspond to the start of info block 1. If they do, this is
         // likely a raw dump. Assume disk sides as a multiple of 65500 bytes and complain if we have anything else
         let verification_string = "\x01*NINTENDO-HVC*";
-        let candidate_string = std::str::from_utf8(&fds_data[0..15]).expect("invalid utf8-sequence");
-        if candidate_string.eq(verification_string) {
+        if &fds_data[0..15] == verification_string.as_bytes() {
             for i in 0 .. fds_data.len() / 65500 {
                 let start = i * 65500;
                 let end = (i+1) * 65500;
@@ -118,4 +121,18 @@ impl FdsFile {
 
         return Err(FdsError::InvalidHeader);
     }
+
+    // Always writes the fwNES header, whichever layout the disk was originally read from
+    pub fn to_bytes(disk_sides: &[Vec<u8>]) -> Vec<u8> {
+        let mut fds_data: Vec<u8> = vec![0u8; 16];
+        fds_data[FDS_MAGIC_F] = b'F';
+        fds_data[FDS_MAGIC_D] = b'D';
+        fds_data[FDS_MAGIC_S] = b'S';
+        fds_data[FDS_MAGIC_EOF] = MSDOS_EOF;
+        fds_data[FDS_DISK_SIDES] = disk_sides.len() as u8;
+        for side in disk_sides {
+            fds_data.extend_from_slice(side);
+        }
+        return fds_data;
+    }
 }
@@ -0,0 +1,92 @@
+// Game Genie codes. The Game Genie sits between the cartridge and the console and
+// substitutes its own value when the CPU reads a patched ROM address. Six letter
+// codes always substitute; eight letter codes only do so when the cartridge would
+// have returned the compare value, which lets them target a single bank.
+// Reference: https://wiki.nesdev.com/w/index.php/Game_Genie
+
+use std::error::Error;
+use std::fmt;
+
+const LETTERS: &str = "APZLGITYEOXUKSVN";
+
+#[derive(Debug, PartialEq)]
+pub enum CheatError {
+    InvalidLength(usize),
+    InvalidCharacter(char),
+    NotFound(String),
+}
+
+impl Error for CheatError {}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::InvalidLength(length) => {write!(f, "Game Genie codes are 6 or 8 letters long, got {}", length)},
+            CheatError::InvalidCharacter(letter) => {write!(f, "'{}' is not a Game Genie letter", letter)},
+            CheatError::NotFound(code) => {write!(f, "No active cheat matches {}", code)},
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameGenieCode {
+    pub code: String,
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl GameGenieCode {
+    pub fn decode(code: &str) -> Result<GameGenieCode, CheatError> {
+        let code = code.trim().to_uppercase();
+        let mut n: Vec<u16> = Vec::new();
+        for letter in code.chars() {
+            match LETTERS.find(letter) {
+                Some(index) => {n.push(index as u16);},
+                None => {return Err(CheatError::InvalidCharacter(letter));}
+            }
+        }
+        if n.len() != 6 && n.len() != 8 {
+            return Err(CheatError::InvalidLength(n.len()));
+        }
+
+        let address = 0x8000 |
+            ((n[3] & 7) << 12) |
+            ((n[5] & 7) << 8) | ((n[4] & 8) << 8) |
+            ((n[2] & 7) << 4) | ((n[1] & 8) << 4) |
+            (n[4] & 7) | (n[3] & 8);
+        // Bit 3 of the value comes from the sixth letter of a short code, or the eighth of a long one
+        let value_bit_3 = if n.len() == 8 {n[7] & 8} else {n[5] & 8};
+        let value = (((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | value_bit_3) as u8;
+        let compare = if n.len() == 8 {
+            Some((((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8)) as u8)
+        } else {
+            None
+        };
+
+        return Ok(GameGenieCode {
+            code: code,
+            address: address,
+            value: value,
+            compare: compare,
+        });
+    }
+
+    pub fn apply(&self, address: u16, cartridge_byte: u8) -> u8 {
+        if address != self.address {
+            return cartridge_byte;
+        }
+        match self.compare {
+            Some(compare) if compare != cartridge_byte => {return cartridge_byte},
+            _ => {return self.value}
+        }
+    }
+}
+
+pub fn apply_cheats(cheats: &[GameGenieCode], address: u16, cartridge_byte: u8) -> u8 {
+    let mut byte = cartridge_byte;
+    for cheat in cheats {
+        byte = cheat.apply(address, byte);
+    }
+    return byte;
+}
@@ -10,10 +10,13 @@ use std::fmt;
 use mmc::mapper::Mirroring;
 use memoryblock::MemoryBlock;
 use memoryblock::MemoryType;
+use region::Region;
 
 #[derive(Debug)]
 pub enum INesError {
     InvalidHeader,
+    // The file ended before all of the PRG / CHR data the header promises
+    Truncated,
     Unimplemented,
     ReadError{reason: String}
 }
@@ -24,6 +27,7 @@ impl fmt::Display for INesError  {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             INesError::InvalidHeader => {write!(f, "Invalid iNES Header")},
+            INesError::Truncated => {write!(f, "File is shorter than its header says it should be")},
             INesError::Unimplemented => {write!(f, "Unimplemented (Lazy programmers!!1)")},
             INesError::ReadError{reason} => {write!(f, "Error reading cartridge: {}", reason)}
         }
@@ -32,6 +36,9 @@ impl fmt::Display for INesError  {
 
 impl From<std::io::Error> for INesError {
     fn from(error: std::io::Error) -> Self {
+        if error.kind() == std::io::ErrorKind::UnexpectedEof {
+            return INesError::Truncated;
+        }
         return INesError::ReadError{reason: error.to_string()};
     }
 }
@@ -53,14 +60,14 @@ const INES_FLAGS_7: usize = 7;
 
 // here the constants diverge depending on type
 const INES1_PRG_RAM_SIZE: usize = 8;
-//const INES1_TV_SYSTEM: usize = 9;
+const INES1_TV_SYSTEM: usize = 9;
 //const INES1_FLAGS_10: usize = 10;
 
 const INES2_MAPPER_SUB_MSB: usize = 8;
 const INES2_PRG_CHR_MSB: usize = 9;
 const INES2_PRG_RAM: usize = 10;
 const INES2_CHR_RAM: usize = 11;
-//const INES2_CPU_PPU_TIMING: usize = 12;
+const INES2_CPU_PPU_TIMING: usize = 12;
 //const INES2_SYSTEM_TYPE: usize = 13;
 //const INES2_MISC_ROM_COUNT: usize = 14;
 //const INES2_DEFAULT_EXPANSION: usize = 15;
@@ -89,15 +96,30 @@ impl INesHeader {
     }
 
     pub fn version(&self) -> u8 {
+        if !self.magic_header_valid() {
+            return 0;
+        }
         // A file is a NES 2.0 ROM image file if it begins with "NES<EOF>" (same as iNES) and, 
         // additionally, the byte at offset 7 has bit 2 clear and bit 3 set:
-        if (self.raw_bytes[INES_FLAGS_7] & 0x0C) == 0x08 {
+        if (self.raw_bytes[INES_FLAGS_7] & 0x0C) == 0x08 && self._ines2_sizes_valid() {
             return 2;
         }
-        if self.magic_header_valid() {
-            return 1;
-        }
-        return 0;
+        return 1;
+    }
+
+    pub fn is_nes2(&self) -> bool {
+        return self.version() == 2;
+    }
+
+    fn _ines2_sizes_valid(&self) -> bool {
+        // Some ancient dumps happen to carry garbage in byte 7 that looks like the NES 2.0
+        // signature. If the sizes that result from treating the header as NES 2.0 are
+        // nonsense, fall back to iNES 1.0 rules instead of rejecting the file outright.
+        let prg_msb = self.raw_bytes[INES2_PRG_CHR_MSB] & 0b0000_1111;
+        let chr_msb = (self.raw_bytes[INES2_PRG_CHR_MSB] & 0b1111_0000) >> 4;
+        let prg_valid = prg_msb != 0xF || exponent_multiplier_size(self.raw_bytes[INES_PRG_ROM_LSB]).is_some();
+        let chr_valid = chr_msb != 0xF || exponent_multiplier_size(self.raw_bytes[INES_CHR_ROM_LSB]).is_some();
+        return prg_valid && chr_valid;
     }
 
     fn _prg_size_ines1(&self) -> usize {
@@ -109,29 +131,14 @@ impl INesHeader {
         let lsb = self.raw_bytes[INES_PRG_ROM_LSB];
         let msb = self.raw_bytes[INES2_PRG_CHR_MSB] & 0b0000_1111;
         if msb == 0xF {
-            // exponent-multiplier mode
-            //  ++++----------- Header byte 9 D0..D3
-            //  |||| ++++-++++- Header byte 4
-            //  D~BA98 7654 3210
-            //  --------------
-            //  1111 EEEE EEMM
-            //  |||| ||++- Multiplier, actual value is MM*2+1 (1,3,5,7)
-            //  ++++-++--- Exponent (2^E), 0-63
-
-            let multiplier = ((lsb & 0b0000_0011) * 2 + 1) as usize;
-            let exponent = ((lsb & 0b1111_1100) >> 2) as u32;
-            let base: usize = 2;
-            let size = base.pow(exponent) * multiplier;
-            println!("base: {}, multiplier: {}, exponent: {}, size: {}", base, multiplier, exponent, size);
-            return size;
+            return exponent_multiplier_size(lsb).unwrap_or(0);
         } else {
             // simple mode
-            println!("used simple mode!");
             return (((msb as usize) << 8) + (lsb as usize)) * 16 * 1024;
         }
     }
 
-    pub fn prg_size(&self) -> usize {
+    pub fn prg_rom_size(&self) -> usize {
         return match self.version() {
             1 => self._prg_size_ines1(),
             2 => self._prg_size_ines2(),
@@ -139,32 +146,24 @@ impl INesHeader {
         }
     }
 
+    pub fn prg_size(&self) -> usize {
+        return self.prg_rom_size();
+    }
+
     fn _chr_rom_size_ines1(&self) -> usize {
         let chr_size = self.raw_bytes[INES_CHR_ROM_LSB] as usize * 8 * 1024;
         return chr_size;
     }
 
     fn _chr_rom_size_ines2(&self) -> usize {
-        // https://wiki.nesdev.com/w/index.php/NES_2.0#PRG-ROM_Area
+        // https://wiki.nesdev.com/w/index.php/NES_2.0#CHR-ROM_Area
         let lsb = self.raw_bytes[INES_CHR_ROM_LSB];
         let msb = (self.raw_bytes[INES2_PRG_CHR_MSB] & 0b1111_0000) >> 4;
         if msb == 0xF {
-            // exponent-multiplier mode
-            //  ++++----------- Header byte 9 D0..D3
-            //  |||| ++++-++++- Header byte 4
-            //  D~BA98 7654 3210
-            //  --------------
-            //  1111 EEEE EEMM
-            //  |||| ||++- Multiplier, actual value is MM*2+1 (1,3,5,7)
-            //  ++++-++--- Exponent (2^E), 0-63
-
-            let multiplier = ((lsb & 0b0000_0011) * 2 + 1) as usize;
-            let exponent = ((lsb & 0b1111_1100) >> 2) as u32;
-            let base: usize = 2;
-            return base.pow(exponent) * multiplier;
+            return exponent_multiplier_size(lsb).unwrap_or(0);
         } else {
             // simple mode
-            return ((msb as usize) << 8) + (lsb as usize) * 8 * 1024;
+            return (((msb as usize) << 8) + (lsb as usize)) * 8 * 1024;
         }
     }
 
@@ -224,13 +223,12 @@ impl INesHeader {
         return Mirroring::Horizontal;
     }
 
-    pub fn has_sram(&self) -> bool {
+    pub fn has_battery(&self) -> bool {
         return self.raw_bytes[INES_FLAGS_6] & 0b0000_0010 != 0;
     }
 
     fn _prg_ram_size_ines1(&self) -> usize  {
-        let has_sram = self.raw_bytes[INES_FLAGS_6] & 0b0000_0010 != 0;
-        if has_sram {
+        if self.has_battery() {
             return 0;
         }
         if self.ines1_extended_attributes_valid() && self.raw_bytes[INES1_PRG_RAM_SIZE] != 0 {
@@ -257,8 +255,7 @@ impl INesHeader {
     }
 
     fn _prg_sram_size_ines1(&self) -> usize  {
-        let has_sram = self.raw_bytes[INES_FLAGS_6] & 0b0000_0010 != 0;
-        if !has_sram {
+        if !self.has_battery() {
             return 0;
         }
         if self.ines1_extended_attributes_valid() && self.raw_bytes[INES1_PRG_RAM_SIZE] != 0 {
@@ -323,6 +320,57 @@ impl INesHeader {
         }
     }
 
+    pub fn prg_ram_shift(&self) -> u8 {
+        // Raw NES 2.0 byte 10 nybbles, for loaders that want the encoded value rather than
+        // the resolved size. Always zero for iNES 1.0.
+        if self.version() != 2 {
+            return 0;
+        }
+        return self.raw_bytes[INES2_PRG_RAM] & 0b0000_1111;
+    }
+
+    pub fn prg_sram_shift(&self) -> u8 {
+        if self.version() != 2 {
+            return 0;
+        }
+        return (self.raw_bytes[INES2_PRG_RAM] & 0b1111_0000) >> 4;
+    }
+
+    pub fn chr_ram_shift(&self) -> u8 {
+        if self.version() != 2 {
+            return 0;
+        }
+        return self.raw_bytes[INES2_CHR_RAM] & 0b0000_1111;
+    }
+
+    pub fn chr_sram_shift(&self) -> u8 {
+        if self.version() != 2 {
+            return 0;
+        }
+        return (self.raw_bytes[INES2_CHR_RAM] & 0b1111_0000) >> 4;
+    }
+
+    /// The console timing this image asks for. Multi-region NES 2.0 images, and iNES 1.0
+    /// images whose unused bytes are filled with garbage, fall back to NTSC.
+    pub fn region(&self) -> Region {
+        match self.version() {
+            1 => {
+                if self.ines1_extended_attributes_valid() && (self.raw_bytes[INES1_TV_SYSTEM] & 0b1) != 0 {
+                    return Region::Pal;
+                }
+                return Region::Ntsc;
+            },
+            2 => {
+                return match self.raw_bytes[INES2_CPU_PPU_TIMING] & 0b11 {
+                    1 => Region::Pal,
+                    3 => Region::Dendy,
+                    _ => Region::Ntsc,
+                };
+            },
+            _ => Region::Ntsc
+        }
+    }
+
     pub fn submapper_number(&self) -> u8 {
         match self.version() {
             1 => 0,
@@ -332,6 +380,34 @@ impl INesHeader {
     }
 }
 
+fn exponent_multiplier_size(lsb: u8) -> Option<usize> {
+    // exponent-multiplier mode
+    //  ++++----------- Header byte 9 D0..D3
+    //  |||| ++++-++++- Header byte 4
+    //  D~BA98 7654 3210
+    //  --------------
+    //  1111 EEEE EEMM
+    //  |||| ||++- Multiplier, actual value is MM*2+1 (1,3,5,7)
+    //  ++++-++--- Exponent (2^E), 0-63
+    let multiplier = ((lsb & 0b0000_0011) * 2 + 1) as usize;
+    let exponent = ((lsb & 0b1111_1100) >> 2) as u32;
+    let base: usize = 2;
+    // Large exponents can't possibly describe a real cartridge, and would overflow
+    return base.checked_pow(exponent)?.checked_mul(multiplier);
+}
+
+// Reads exactly `size` bytes without allocating them up front. An exponent-multiplier header
+// can claim an absurd size, and a short file should fail as Truncated rather than after the
+// whole declared size has been reserved.
+fn read_block(file_reader: &mut dyn Read, size: usize) -> Result<Vec<u8>, INesError> {
+    let mut block: Vec<u8> = Vec::new();
+    file_reader.take(size as u64).read_to_end(&mut block)?;
+    if block.len() < size {
+        return Err(INesError::Truncated);
+    }
+    return Ok(block);
+}
+
 #[derive(Clone)]
 pub struct INesCartridge {
     // Internal strategy is to store each major chunk of the file as
@@ -347,38 +423,38 @@ pub struct INesCartridge {
 impl INesCartridge {
     pub fn from_reader(file_reader: &mut dyn Read) -> Result<INesCartridge, INesError> {
         let mut header_bytes = [0u8; 16];
-        file_reader.read_exact(&mut header_bytes)?;
+        match file_reader.read_exact(&mut header_bytes) {
+            // Too short to even hold a header, so this isn't an iNES file at all
+            Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {return Err(INesError::InvalidHeader);},
+            result => result?
+        }
 
         let header = INesHeader::from(&header_bytes);
         if !header.magic_header_valid() {
             return Err(INesError::InvalidHeader);
         }
-        println!("iNes version: {}", header.version());
+        debug!("iNes version: {}", header.version());
 
         let trainer_size = if header.has_trainer() {512} else {0};
         let mut trainer: Vec<u8> = Vec::new();
         trainer.resize(trainer_size, 0);
         file_reader.read_exact(&mut trainer)?;
-        println!("trainer size: {}", trainer.len());
+        debug!("trainer size: {}", trainer.len());
 
-        let mut prg: Vec<u8> = Vec::new();
-        prg.resize(header.prg_size(), 0);
-        file_reader.read_exact(&mut prg)?;
+        let prg = read_block(file_reader, header.prg_rom_size())?;
         if prg.len() == 0 {
             return Err(INesError::ReadError{reason: format!("PRG ROM size is {}. This file is invalid, or at the very least quite unusual. Aborting.", prg.len())});
         }
-        println!("prg rom size: {}", prg.len());
+        debug!("prg rom size: {}", prg.len());
 
-        let mut chr: Vec<u8> = Vec::new();
-        chr.resize(header.chr_rom_size(), 0);
-        file_reader.read_exact(&mut chr)?;
-        println!("chr rom size: {}", chr.len());
+        let chr = read_block(file_reader, header.chr_rom_size())?;
+        debug!("chr rom size: {}", chr.len());
 
         // If there is any remaining data at this point, it becomes misc_rom and,
         // currently, has no other special handling
         let mut misc: Vec<u8> = Vec::new();
         file_reader.read_to_end(&mut misc)?;
-        println!("misc_size: {}", misc.len());
+        debug!("misc_size: {}", misc.len());
 
         return Ok(INesCartridge {
             header: header,
@@ -405,11 +481,19 @@ impl INesCartridge {
             prg_sram.resize(self.header.prg_sram_size(), 0);
             blocks.push(MemoryBlock::new(&prg_sram, MemoryType::NvRam));
         }
+        if blocks.len() == 0 && self.trainer.len() > 0 {
+            // The trainer needs somewhere to live, so give the cart the usual 8k of PRG RAM
+            blocks.push(MemoryBlock::new(&vec![0u8; 8 * 1024], MemoryType::Ram));
+        }
         if blocks.len() == 0 {
             // Always include at least one entry in this list; in this case, a
             // single empty block.
             blocks.push(MemoryBlock::new(&Vec::new(), MemoryType::Rom));
         }
+        // Trainers are loaded at $7000-$71FF, which is $1000 into the first PRG RAM block
+        for (i, byte) in self.trainer.iter().enumerate() {
+            blocks[0].bounded_write(0x1000 + i, *byte);
+        }
         return blocks;
     }
 
@@ -429,9 +513,12 @@ impl INesCartridge {
             blocks.push(MemoryBlock::new(&chr_sram, MemoryType::NvRam));
         }
         if blocks.len() == 0 {
-            // Always include at least one entry in this list; in this case, a
-            // single empty block.
-            blocks.push(MemoryBlock::new(&Vec::new(), MemoryType::Rom));
+            // A NES 2.0 header that declares no CHR memory at all. Every real board has
+            // something on the PPU bus, and a cart without CHR ROM is almost certainly using
+            // the usual 8 KiB of CHR RAM, so assume that rather than leave the pattern tables
+            // unwritable.
+            let chr_ram = vec![0u8; 8 * 1024];
+            blocks.push(MemoryBlock::new(&chr_ram, MemoryType::Ram));
         }
         return blocks;
     }
@@ -1,18 +1,36 @@
+// Diagnostics go through the log crate rather than println!. Calls keep the default target,
+// which is the module path (rustico_core::mmc::fds, rustico_core::nes, and so on), so a
+// frontend can raise or silence a whole subsystem with a prefix filter such as
+// RUST_LOG=rustico_core::mmc=debug. Mapper print_debug_status() dumps are explicit requests
+// for output and stay on stdout.
+#[macro_use]
+extern crate log;
+
 pub mod addressing;
 pub mod apu;
 pub mod asm;
+pub mod breakpoints;
 pub mod cartridge;
 pub mod cycle_cpu;
 pub mod fds;
+pub mod game_genie;
 pub mod tracked_events;
 pub mod ines;
 pub mod memory;
+pub mod memory_search;
 pub mod memoryblock;
 pub mod mmc;
+pub mod movie;
 pub mod nes;
 pub mod nsf;
 pub mod opcodes;
 pub mod opcode_info;
 pub mod palettes;
 pub mod ppu;
-pub mod unofficial_opcodes;
\ No newline at end of file
+pub mod region;
+pub mod rewind;
+pub mod save_load;
+pub mod test_rom;
+pub mod unofficial_opcodes;
+pub mod watchpoints;
+pub mod zapper;
\ No newline at end of file
@@ -1,11 +1,58 @@
+use breakpoints::BreakpointKind;
+use game_genie::apply_cheats;
 use nes::NesState;
+use save_load::*;
+use watchpoints::WatchEntry;
+
+/// Initial contents of internal RAM. Real consoles power on with a mostly random
+/// pattern that varies between units, and a few games read it before clearing it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RamInit {
+    AllZero,
+    AllOnes,
+    Pattern(u8),
+    // Pseudo-random, but the same seed always produces the same contents
+    Seeded(u64),
+}
+
+impl RamInit {
+    pub fn fill(&self, ram: &mut [u8]) {
+        match *self {
+            RamInit::AllZero => {for byte in ram.iter_mut() {*byte = 0x00;}},
+            RamInit::AllOnes => {for byte in ram.iter_mut() {*byte = 0xFF;}},
+            RamInit::Pattern(value) => {for byte in ram.iter_mut() {*byte = value;}},
+            RamInit::Seeded(seed) => {
+                // splitmix64, which is well behaved even for small or zero seeds
+                let mut state = seed;
+                for chunk in ram.chunks_mut(8) {
+                    state = state.wrapping_add(0x9E3779B97F4A7C15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+                    z = z ^ (z >> 31);
+                    for (byte, random_byte) in chunk.iter_mut().zip(z.to_le_bytes().iter()) {
+                        *byte = *random_byte;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Code/data logger flags, ORed into one byte per byte of PRG ROM
+pub const CDL_EXECUTED: u8 = 0x01;
+pub const CDL_OPERAND: u8 = 0x02;
+pub const CDL_DATA: u8 = 0x04;
 
 pub struct CpuMemory {
     pub iram_raw: Vec<u8>,
 
     pub recent_reads: Vec<u16>,
     pub recent_writes: Vec<u16>,
-    pub open_bus: u8
+    // The last byte driven on the data bus by either a read or a write. Reads that
+    // nothing responds to, and any undriven bits of partially decoded registers,
+    // see this value instead.
+    pub last_bus_value: u8,
 }
 
 impl CpuMemory {
@@ -14,9 +61,20 @@ impl CpuMemory {
             iram_raw: vec!(0u8; 0x800),
             recent_reads: Vec::new(),
             recent_writes: Vec::new(),
-            open_bus: 0,
+            last_bus_value: 0,
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec(buff, &self.iram_raw);
+        save_u8(buff, self.last_bus_value);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        buff.load_into(&mut self.iram_raw)?;
+        self.last_bus_value = buff.load_u8()?;
+        return Ok(());
+    }
 }
 
 pub fn debug_read_byte(nes: &NesState, address: u16) -> u8 {
@@ -34,17 +92,52 @@ pub fn debug_read_byte(nes: &NesState, address: u16) -> u8 {
             }
         },
         0x4015 => {
-            return nes.apu.debug_read_register(address);
+            return nes.apu.debug_read_register(address) | (nes.memory.last_bus_value & 0x20);
         },
         _ => {}
     }
 
-    let mapped_byte = nes.mapper.debug_read_cpu(address).unwrap_or(nes.memory.open_bus);
+    let mut mapped_byte = nes.mapper.debug_read_cpu(address).unwrap_or(nes.memory.last_bus_value);
+    if address >= 0x8000 {
+        mapped_byte = apply_cheats(&nes.cheats, address, mapped_byte);
+    }
     return _read_byte(nes, address, mapped_byte);
 }
 
 pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
-    let mapped_byte = nes.mapper.read_cpu(address).unwrap_or(nes.memory.open_bus);
+    if !nes.breakpoints.is_empty() {
+        nes.breakpoints.check(BreakpointKind::Read, address);
+    }
+    // Reads at PC are opcode and operand fetches (logged by the CPU, which knows which is which)
+    // or dummy reads of the next instruction, which shouldn't count as data
+    if nes.cdl.is_some() && address != nes.registers.pc {
+        log_cdl(nes, address, CDL_DATA);
+    }
+    let byte = _live_read_byte(nes, address);
+    nes.memory.last_bus_value = byte;
+    return byte;
+}
+
+pub fn log_cdl(nes: &mut NesState, address: u16, flag: u8) {
+    if let Some(ref mut cdl) = nes.cdl {
+        if let Some(offset) = nes.mapper.prg_rom_offset(address) {
+            if offset < cdl.len() {
+                cdl[offset] |= flag;
+            }
+        }
+    }
+}
+
+fn _live_read_byte(nes: &mut NesState, address: u16) -> u8 {
+    // If a DMC fetch just halted the CPU on this read, the address was already read
+    // at least once while halted. Only matters for registers with read side effects.
+    let dmc_interrupted_read = nes.apu.dmc.interrupted_read;
+    nes.apu.dmc.interrupted_read = false;
+
+    let mut mapped_byte = nes.mapper.read_cpu(address).unwrap_or(nes.memory.last_bus_value);
+    if address >= 0x8000 {
+        mapped_byte = apply_cheats(&nes.cheats, address, mapped_byte);
+    }
 
     // This is a live read, handle any side effects
     match address {
@@ -53,9 +146,9 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
             match ppu_reg {
                 // PPUSTATUS
                 2 => {
-                    nes.ppu.write_toggle = false;
-                    nes.ppu.latch = (nes.ppu.status & 0xE0) + (nes.ppu.latch & 0x1F);
-                    nes.ppu.status = nes.ppu.status & 0x7F; // Clear VBlank bit
+                    if nes.ppu.read_status() {
+                        nes.cpu.nmi_requested = false;
+                    }
                     nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, nes.ppu.latch);
                     return nes.ppu.latch;
                 },
@@ -69,7 +162,7 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
                     let ppu_addr = nes.ppu.current_vram_address;
                     nes.ppu.latch = nes.ppu.read_latched_byte(&mut *nes.mapper, ppu_addr);
                     if nes.ppu.rendering_enabled() && 
-                    (nes.ppu.current_scanline == 261 ||
+                    (nes.ppu.current_scanline == nes.ppu.region.prerender_scanline() ||
                      nes.ppu.current_scanline <= 239) {
                         // Glitchy increment, a fine y and a coarse x 
                         nes.ppu.increment_coarse_x();
@@ -93,29 +186,50 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
             }
         },
         0x4015 => {
-            let apu_byte = nes.apu.read_register(address);
+            // Bit 5 isn't driven by the APU
+            let apu_byte = nes.apu.read_register(address) | (nes.memory.last_bus_value & 0x20);
             nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, apu_byte);
             return apu_byte;
         },
         0x4016 => {
+            if nes.p1_zapper.connected {
+                let result = (nes.memory.last_bus_value & 0xE0) | nes.p1_zapper.read(&nes.ppu, &nes.palette);
+                nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, result);
+                return result;
+            }
             if nes.input_latch {
                 // strobe register is high, so copy input data to latch (probably bad if this
                 // actually occurs here, but it matches what real hardware would do)
-                nes.p1_data = nes.p1_input;
+                nes.p1_data = nes.controller_input(0);
+            }
+            if dmc_interrupted_read && !nes.input_latch {
+                // The halted read already clocked the controller once; that bit is lost. While
+                // strobe is high the shift register keeps reloading, so there's nothing to lose.
+                nes.p1_data = (nes.p1_data >> 1) | 0x80;
             }
-            let result = 0x40 | (nes.p1_data & 0x1);
+            // Only the low bits are driven; the rest float at whatever was last on the bus,
+            // which is usually $40 from the high byte of the operand
+            let result = (nes.memory.last_bus_value & 0xE0) | (nes.p1_data & 0x1);
             // Standard Controllers set extra bits to 1, which affects controller detection routines
             nes.p1_data = (nes.p1_data >> 1) | 0x80; 
             nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, result);
             return result;
         },
         0x4017 => {
+            if nes.p2_zapper.connected {
+                let result = (nes.memory.last_bus_value & 0xE0) | nes.p2_zapper.read(&nes.ppu, &nes.palette);
+                nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, result);
+                return result;
+            }
             if nes.input_latch {
                 // strobe register is high, so copy input data to latch (probably bad if this
                 // actually occurs here, but it matches what real hardware would do)
-                nes.p2_data = nes.p2_input;
+                nes.p2_data = nes.controller_input(1);
             }
-            let result = 0x40 | (nes.p2_data & 0x1);
+            if dmc_interrupted_read && !nes.input_latch {
+                nes.p2_data = (nes.p2_data >> 1) | 0x80;
+            }
+            let result = (nes.memory.last_bus_value & 0xE0) | (nes.p2_data & 0x1);
             // Standard Controllers set extra bits to 1, which affects controller detection routines
             nes.p2_data = (nes.p2_data >> 1) | 0x80; 
             nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, result);
@@ -125,7 +239,6 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
     }
 
     let byte = _read_byte(nes, address, mapped_byte);
-    nes.memory.open_bus = byte;
     nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, byte);
     return byte;
 }
@@ -162,18 +275,24 @@ fn _read_byte(nes: &NesState, address: u16, mapped_byte: u8) -> u8 {
             return mapped_byte;
         },
         0x4016 => {
-            let result = 0x40 | (nes.p1_data & 0x1);
+            if nes.p1_zapper.connected {
+                return (nes.memory.last_bus_value & 0xE0) | nes.p1_zapper.read(&nes.ppu, &nes.palette);
+            }
+            let result = (nes.memory.last_bus_value & 0xE0) | (nes.p1_data & 0x1);
             return result;
         },
         0x4017 => {
-            let result = 0x40 | (nes.p2_data & 0x1);
+            if nes.p2_zapper.connected {
+                return (nes.memory.last_bus_value & 0xE0) | nes.p2_zapper.read(&nes.ppu, &nes.palette);
+            }
+            let result = (nes.memory.last_bus_value & 0xE0) | (nes.p2_data & 0x1);
             return result;
         },
         0x4020 ..= 0xFFFF => {
             return mapped_byte;
         },
         _ => {
-            return nes.memory.open_bus;
+            return nes.memory.last_bus_value;
         }
     }
 }
@@ -182,6 +301,22 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
     // Track every byte written, unconditionally
     // (filtering is done inside the tracker)
     nes.event_tracker.snoop_cpu_write(nes.registers.pc, address, data);
+    nes.memory.last_bus_value = data;
+    if !nes.breakpoints.is_empty() {
+        nes.breakpoints.check(BreakpointKind::Write, address);
+    }
+    if !nes.watchpoints.is_empty() && nes.watchpoints.is_watched(address) {
+        let entry = WatchEntry {
+            frame: nes.ppu.current_frame,
+            scanline: nes.ppu.current_scanline,
+            dot: nes.ppu.current_scanline_cycle,
+            pc: nes.watchpoints.instruction_address,
+            address: address,
+            old_value: debug_read_byte(nes, address),
+            new_value: data,
+        };
+        nes.watchpoints.record(entry);
+    }
 
     // The mapper *always* sees the write. Even to RAM, and even to internal registers.
     // Most mappers ignore writes to addresses below 0x6000. Some (notably MMC5) do not.
@@ -262,7 +397,7 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
                 7 => {
                     let ppu_addr = nes.ppu.current_vram_address;
                     if nes.ppu.rendering_enabled() && 
-                    (nes.ppu.current_scanline == 261 ||
+                    (nes.ppu.current_scanline == nes.ppu.region.prerender_scanline() ||
                     nes.ppu.current_scanline <= 239) {
                         // Glitchy increment, a fine y and a coarse x 
                         nes.ppu.increment_coarse_x();
@@ -305,11 +440,14 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
             nes.apu.write_register(address, data);
         },
         0x4016 => {
-            // Input latch
+            // Input latch. The controllers reload their shift registers for as long as strobe
+            // is held high, so the buttons that get shifted out are the ones held when it
+            // falls, not when it rose.
+            let was_latched = nes.input_latch;
             nes.input_latch = data & 0x1 != 0;
-            if nes.input_latch {
-                nes.p1_data = nes.p1_input;
-                nes.p2_data = nes.p2_input;
+            if nes.input_latch || was_latched {
+                nes.p1_data = nes.controller_input(0);
+                nes.p2_data = nes.controller_input(1);
             }
         },
         0x4017 => {
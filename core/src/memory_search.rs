@@ -0,0 +1,96 @@
+// RAM searching for cheat hunting. Take a snapshot, let the game run a little, then narrow the
+// candidate addresses down by how their values changed. Repeat until only a few remain; those
+// are good targets for a Game Genie code or a memory viewer watch.
+
+use memory;
+use nes::NesState;
+
+const WORK_RAM_SIZE: u16 = 0x800;
+const PRG_RAM_START: u16 = 0x6000;
+const PRG_RAM_SIZE: u16 = 0x2000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchFilter {
+    Equals(u8),
+    Increased,
+    Decreased,
+    Unchanged,
+    Changed,
+}
+
+impl SearchFilter {
+    pub fn matches(&self, previous: u8, current: u8) -> bool {
+        return match *self {
+            SearchFilter::Equals(value) => current == value,
+            SearchFilter::Increased => current > previous,
+            SearchFilter::Decreased => current < previous,
+            SearchFilter::Unchanged => current == previous,
+            SearchFilter::Changed => current != previous,
+        };
+    }
+}
+
+pub struct MemorySearch {
+    pub include_prg_ram: bool,
+    candidates: Vec<u16>,
+    // Values as of the last snapshot, indexed by snapshot_index
+    previous: Vec<u8>,
+}
+
+fn snapshot_index(address: u16) -> Option<usize> {
+    return match address {
+        0 ..= 0x7FF => Some(address as usize),
+        0x6000 ..= 0x7FFF => Some((WORK_RAM_SIZE + (address - PRG_RAM_START)) as usize),
+        _ => None
+    };
+}
+
+impl MemorySearch {
+    /// Starts a new search with every address as a candidate. PRG RAM ($6000-$7FFF) is only
+    /// searched if asked for, since many cartridges don't have any.
+    pub fn new(nes: &NesState, include_prg_ram: bool) -> MemorySearch {
+        let mut candidates: Vec<u16> = (0 .. WORK_RAM_SIZE).collect();
+        if include_prg_ram {
+            candidates.extend(PRG_RAM_START .. PRG_RAM_START + PRG_RAM_SIZE);
+        }
+        let mut search = MemorySearch {
+            include_prg_ram: include_prg_ram,
+            candidates: candidates,
+            previous: Vec::new(),
+        };
+        search.previous = search.snapshot(nes);
+        return search;
+    }
+
+    fn snapshot(&self, nes: &NesState) -> Vec<u8> {
+        let mut values = nes.memory.iram_raw.clone();
+        if self.include_prg_ram {
+            values.extend((PRG_RAM_START .. PRG_RAM_START + PRG_RAM_SIZE).map(|address| memory::debug_read_byte(nes, address)));
+        }
+        return values;
+    }
+
+    /// Compares the console's current RAM against the last snapshot, keeping only the candidates
+    /// that pass the filter. The current values then become the new baseline.
+    pub fn filter(&mut self, nes: &NesState, filter: SearchFilter) -> &[u16] {
+        let current = self.snapshot(nes);
+        let previous = &self.previous;
+        self.candidates.retain(|address| {
+            return match snapshot_index(*address) {
+                Some(index) => filter.matches(previous[index], current[index]),
+                None => false
+            };
+        });
+        self.previous = current;
+        return &self.candidates;
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        return &self.candidates;
+    }
+
+    /// The value at a candidate address as of the last snapshot
+    pub fn last_value(&self, address: u16) -> Option<u8> {
+        return snapshot_index(address).and_then(|index| self.previous.get(index).cloned());
+    }
+}
@@ -1,3 +1,5 @@
+use save_load::*;
+
 /// Represents one contiguous block of memory, typically residing on a single
 /// physical chip. Implementations have varying behavior, but provide one
 /// consistent guarantee: all memory access will return some value, possibly
@@ -67,6 +69,15 @@ impl MemoryBlock {
         self.bytes[address % len] = data;
     }
 
+    /// The index into this block that banked_read would use, or None if the block is empty
+    pub fn banked_address(&self, bank_size: usize, bank_index: usize, offset: usize) -> Option<usize> {
+        if self.bytes.len() == 0 {
+            return None;
+        }
+        let effective_address = (bank_size * bank_index) + (offset % bank_size);
+        return Some(effective_address % self.len());
+    }
+
     pub fn banked_read(&self, bank_size: usize, bank_index: usize, offset: usize) -> Option<u8> {
         let effective_address = (bank_size * bank_index) + (offset % bank_size);
         return self.wrapping_read(effective_address);
@@ -84,5 +95,19 @@ impl MemoryBlock {
     pub fn as_mut_vec(&mut self) -> &mut Vec<u8> {
         return &mut self.bytes;
     }
-}
 
+    /// Writable blocks are included in savestates. ROM is skipped, since it
+    /// comes from the cartridge and never changes.
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        if !self.readonly {
+            save_vec(buff, &self.bytes);
+        }
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        if !self.readonly {
+            buff.load_into(&mut self.bytes)?;
+        }
+        return Ok(());
+    }
+}
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Action53 {
     prg_rom: MemoryBlock,
@@ -168,4 +169,31 @@ impl Mapper for Action53 {
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec(buff, &self.vram);
+        save_u8(buff, self.register_select);
+        save_u8(buff, self.mirroring_mode);
+        save_usize(buff, self.chr_ram_a13_a14);
+        save_usize(buff, self.prg_inner_bank);
+        save_usize(buff, self.prg_outer_bank);
+        save_u8(buff, self.prg_mode);
+        save_usize(buff, self.prg_outer_bank_size);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        buff.load_into(&mut self.vram)?;
+        self.register_select = buff.load_u8()?;
+        self.mirroring_mode = buff.load_u8()?;
+        self.chr_ram_a13_a14 = buff.load_usize()?;
+        self.prg_inner_bank = buff.load_usize()?;
+        self.prg_outer_bank = buff.load_usize()?;
+        self.prg_mode = buff.load_u8()?;
+        self.prg_outer_bank_size = buff.load_usize()?;
+        return Ok(());
+    }
 }
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct AxRom {
     pub prg_rom: MemoryBlock,
@@ -13,6 +14,9 @@ pub struct AxRom {
     pub mirroring: Mirroring,
     pub prg_bank: usize,
     pub vram: Vec<u8>,
+    // AMROM and AOROM boards don't disable the ROM during writes, so the value the mapper sees
+    // is the written byte ANDed with the ROM byte at that address. ANROM avoids this.
+    pub bus_conflicts: bool,
 }
 
 impl AxRom {
@@ -26,6 +30,9 @@ impl AxRom {
             mirroring: Mirroring::OneScreenUpper,
             prg_bank: 0x07,
             vram: vec![0u8; 0x1000],
+            // NES 2.0 submapper 2 is the variant with bus conflicts. Submapper 0 (and iNES 1.0)
+            // leaves it unspecified; games for those boards avoid conflicts anyway, so don't model them.
+            bus_conflicts: ines.header.submapper_number() == 2,
         });
     }
 }
@@ -41,6 +48,17 @@ impl Mapper for AxRom {
         println!("====================");
     }
 
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x8000, self.prg_bank, (address - 0x8000) as usize),
+            _ => None
+        }
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x8000 ..= 0xFFFF => {self.prg_rom.banked_read(0x8000, self.prg_bank, (address - 0x8000) as usize)},
@@ -51,6 +69,11 @@ impl Mapper for AxRom {
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x8000 ..= 0xFFFF => {
+                let mut data = data;
+                if self.bus_conflicts {
+                    data = data & self.debug_read_cpu(address).unwrap_or(0xFF);
+                }
+                // Bits 0-2 pick the 32k PRG bank, bit 4 picks which of the two nametables to show
                 self.prg_bank = (data & 0x07) as usize;
                 if data & 0x10 == 0 {
                     self.mirroring = Mirroring::OneScreenLower;
@@ -85,4 +108,19 @@ impl Mapper for AxRom {
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.prg_bank = buff.load_usize()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
@@ -8,6 +8,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct BnRom {
     pub prg_rom: MemoryBlock,
@@ -43,6 +44,17 @@ impl Mapper for BnRom {
         println!("====================");
     }
 
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x8000, self.prg_bank, (address - 0x8000) as usize),
+            _ => None
+        }
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x8000 ..= 0xFFFF => {self.prg_rom.banked_read(0x8000, self.prg_bank, (address - 0x8000) as usize)},
@@ -63,6 +75,7 @@ impl Mapper for BnRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
                 Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
                 _ => None
             },
             _ => None
@@ -75,9 +88,25 @@ impl Mapper for BnRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
                 _ => {}
             },
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.prg_bank = buff.load_usize()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct CnRom {
     pub prg_rom: MemoryBlock,
@@ -13,6 +14,8 @@ pub struct CnRom {
     pub mirroring: Mirroring,
     pub chr_bank: usize,
     pub vram: Vec<u8>,
+    // When set, the value the mapper sees is the written byte ANDed with the ROM byte at that address
+    pub bus_conflicts: bool,
 }
 
 impl CnRom {
@@ -26,6 +29,8 @@ impl CnRom {
             mirroring: ines.header.mirroring(),
             chr_bank: 0x00,
             vram: vec![0u8; 0x1000],
+            // NES 2.0 submapper 2 has bus conflicts, 1 doesn't, and 0 doesn't say
+            bus_conflicts: ines.header.submapper_number() == 2,
         });
     }
 }
@@ -41,6 +46,17 @@ impl Mapper for CnRom {
         return self.mirroring;
     }
 
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x8000, 0, (address - 0x8000) as usize),
+            _ => None
+        }
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x8000 ..= 0xFFFF => {self.prg_rom.wrapping_read((address - 0x8000) as usize)},
@@ -51,6 +67,10 @@ impl Mapper for CnRom {
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x8000 ..= 0xFFFF => {
+                let mut data = data;
+                if self.bus_conflicts {
+                    data = data & self.debug_read_cpu(address).unwrap_or(0xFF);
+                }
                 self.chr_bank = data as usize;
             }
             _ => {}
@@ -63,6 +83,7 @@ impl Mapper for CnRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
                 Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
                 _ => None
             },
             _ => None
@@ -75,9 +96,41 @@ impl Mapper for CnRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
                 _ => {}
             },
             _ => {}
         }
     }
+
+    fn prg_bank_at(&self, address: u16) -> Option<usize> {
+        match address {
+            // PRG is fixed; a 16k ROM is simply mirrored into both halves
+            0x8000 ..= 0xFFFF => Some(0),
+            _ => None
+        }
+    }
+
+    fn chr_bank_at(&self, address: u16) -> Option<usize> {
+        match address {
+            // banked_read wraps oversized bank numbers, so report the bank that actually answers
+            0x0000 ..= 0x1FFF => Some(self.chr_bank % (self.chr.len() / 0x2000).max(1)),
+            _ => None
+        }
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.chr_bank);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.chr_bank = buff.load_usize()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
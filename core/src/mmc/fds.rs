@@ -5,6 +5,7 @@ use fds::FdsFile;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 use apu::AudioChannelState;
 use apu::PlaybackRate;
@@ -39,8 +40,11 @@ pub struct FdsMapper {
     expansion_port_buffer: u8,
 
     disk_images: Vec<Vec<u8>>,
+    dirty_sides: Vec<bool>,
     current_side: usize,
-    desired_side: usize,
+    disk_inserted: bool,
+    // A side waiting to go in once the drive has noticed the previous one is gone
+    pending_side: Option<usize>,
     disk_change_cooldown: u32,
 
     head_position: usize,
@@ -67,7 +71,6 @@ pub struct FdsMapper {
 
 impl FdsMapper {
     pub fn from_fds(fds: FdsFile) -> Result<FdsMapper, String> {
-        // FOR NOW, use just the first disk and ignore the rest
         let mut expanded_disks = Vec::new();
         for i in 0 .. fds.disk_sides.len() {
             expanded_disks.push(expand_disk_image(&fds.disk_sides[i]));
@@ -92,9 +95,11 @@ impl FdsMapper {
             read_buffer: 0,
             expansion_port_buffer: 0,
 
+            dirty_sides: vec![false; expanded_disks.len()],
             disk_images: expanded_disks,
             current_side: 0,
-            desired_side: 0,
+            disk_inserted: true,
+            pending_side: None,
             disk_change_cooldown: 0,
 
             head_position: 0,
@@ -134,23 +139,39 @@ impl FdsMapper {
         }
     }
 
-    fn update_disk_sides(&mut self) {
-        if self.desired_side != self.current_side {
-            self.disk_change_cooldown = 1000000; // CPU cycles before the disk becomes available again
-            println!("Ejected disk #{}", self.current_side);
-            self.current_side = self.desired_side;
+    fn eject(&mut self) {
+        if self.disk_inserted {
+            info!("Ejected disk side {}", self.current_side);
         }
+        self.disk_inserted = false;
+        self.disk_ready_flag = false;
+        self.byte_transfer_flag = false;
+    }
+
+    fn insert(&mut self, side: usize) {
+        info!("Inserted disk side {}", side);
+        self.current_side = side;
+        self.disk_inserted = true;
+        // A freshly inserted disk starts with the head parked at the beginning
+        self.head_position = 0;
+        self.rewinding = false;
+    }
+
+    fn update_disk_sides(&mut self) {
         if self.disk_change_cooldown > 0 {
             self.disk_change_cooldown -= 1;
             if self.disk_change_cooldown == 0 {
-                println!("Inserted disk {}", self.current_side);
+                match self.pending_side.take() {
+                    Some(side) => {self.insert(side);},
+                    None => {}
+                }
             }
         }
     }
 
     fn update_disk_motor(&mut self) {
-        if self.disk_change_cooldown > 0 {
-            // Presumably the motor at least pauses when a disk is ejected
+        if !self.disk_inserted {
+            // No disk, so no data, but the motor at least pauses
             return;
         }
 
@@ -245,6 +266,7 @@ impl FdsMapper {
             } else {
                 self.disk_images[self.current_side][self.head_position] = self.write_buffer;
             }
+            self.dirty_sides[self.current_side] = true;
             self.byte_transfer_flag = true;
             if self.disk_irq_enabled {
                 self.disk_irq_pending = true;
@@ -259,20 +281,20 @@ impl FdsMapper {
         if address == self.debug_old_cpuread + 1 {
             // The first address in the pair should match the 
             match self.debug_old_cpuread {
-                0xE1F8 => println!("=== BIOS: LoadFiles ==="),
-                0xE237 => println!("=== BIOS: AppendFile ==="),
-                0xE239 => println!("=== BIOS: WriteFile ==="),
-                0xE2B7 => println!("=== BIOS: CheckFileCount ==="),
-                0xE2BB => println!("=== BIOS: AdjustFileCount ==="),
-                0xE301 => println!("=== BIOS: SetFileCount1 ==="),
-                0xE305 => println!("=== BIOS: SetFileCount ==="),
-                0xE32A => println!("=== BIOS: GetDiskInfo ==="),
-
-                0xE445 => println!("=== BIOS: CheckDiskHeader ==="),
-                0xE484 => println!("=== BIOS: GetNumFiles ==="),
-                0xE492 => println!("=== BIOS: SetNumFiles ==="),
-                0xE4A0 => println!("=== BIOS: FileMatchTest ==="),
-                0xE4DA => println!("=== BIOS: SkipFiles ==="),
+                0xE1F8 => debug!("=== BIOS: LoadFiles ==="),
+                0xE237 => debug!("=== BIOS: AppendFile ==="),
+                0xE239 => debug!("=== BIOS: WriteFile ==="),
+                0xE2B7 => debug!("=== BIOS: CheckFileCount ==="),
+                0xE2BB => debug!("=== BIOS: AdjustFileCount ==="),
+                0xE301 => debug!("=== BIOS: SetFileCount1 ==="),
+                0xE305 => debug!("=== BIOS: SetFileCount ==="),
+                0xE32A => debug!("=== BIOS: GetDiskInfo ==="),
+
+                0xE445 => debug!("=== BIOS: CheckDiskHeader ==="),
+                0xE484 => debug!("=== BIOS: GetNumFiles ==="),
+                0xE492 => debug!("=== BIOS: SetNumFiles ==="),
+                0xE4A0 => debug!("=== BIOS: FileMatchTest ==="),
+                0xE4DA => debug!("=== BIOS: SkipFiles ==="),
                 _ => {}
             }
         }
@@ -291,7 +313,7 @@ impl Mapper for FdsMapper {
         return self.mirroring;
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         self.clock_timer_irq();
         self.update_disk_sides();
         self.update_disk_motor();
@@ -310,7 +332,7 @@ impl Mapper for FdsMapper {
             nes_sample;
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.timer_pending || self.disk_irq_pending;
     }
 
@@ -350,18 +372,17 @@ impl Mapper for FdsMapper {
                 Some(self.read_buffer)
             },
             0x4032 => {
-                // We always have a disk in the drive, so for now leave this at 0
                 let mut data = 0b0000_0000;
                 // Disk inserted (1 == ejected)
-                if self.disk_change_cooldown > 0 {
+                if !self.disk_inserted {
                     data |= 0b0000_0001;
                 }
                 // Transfer ready flag (0 == ready)
-                if (self.disk_change_cooldown > 0) || (!self.disk_ready_flag) {
+                if !self.disk_inserted || !self.disk_ready_flag {
                     data |= 0b0000_0010;
                 }
                 // Writable (1 == read-only or ejected) (all emulated disks are r/w)
-                if self.disk_change_cooldown > 0 {
+                if !self.disk_inserted {
                     data |= 0b0000_0100;
                 }
                 // should we set bit 6 here? I think it's technically open bus
@@ -480,23 +501,66 @@ impl Mapper for FdsMapper {
             self.bios_rom = bios_rom.clone();
             self.bios_loaded = true;
         } else {
-            println!("FDS bios provided is less than 8k in length! Bad dump?")
+            warn!("FDS bios provided is less than 8k in length! Bad dump?")
         }
     }
 
     fn switch_disk(&mut self, side: usize) {
-        if side <= self.disk_images.len()  {
-            self.desired_side = side;
+        self.fds_insert_disk(side);
+    }
+
+    fn fds_insert_disk(&mut self, side: usize) {
+        if side >= self.disk_images.len() {
+            warn!("No disk with side {} present, refusing to switch.", side);
+            return;
+        }
+        if self.disk_inserted && self.current_side == side {
+            return;
+        }
+        if self.disk_inserted {
+            // Swapping sides in one step; games only notice if the drive reports empty for a
+            // while first, so hold the new side out for about half a second
+            self.eject();
+            self.pending_side = Some(side);
+            self.disk_change_cooldown = 1000000;
         } else {
-            println!("No disk with side {} present, refusing to switch.", side);
+            self.pending_side = None;
+            self.disk_change_cooldown = 0;
+            self.insert(side);
         }
     }
 
+    fn fds_eject_disk(&mut self) {
+        self.eject();
+        self.pending_side = None;
+        self.disk_change_cooldown = 0;
+    }
+
+    fn fds_inserted_disk(&self) -> Option<usize> {
+        return if self.disk_inserted {Some(self.current_side)} else {None};
+    }
+
+    fn fds_side_count(&self) -> usize {
+        return self.disk_images.len();
+    }
+
+    fn fds_write_back(&self) -> Option<Vec<u8>> {
+        if !self.dirty_sides.iter().any(|dirty| *dirty) {
+            return None;
+        }
+        let compact_sides: Vec<Vec<u8>> = self.disk_images.iter().map(|side| compact_disk_image(side)).collect();
+        return Some(FdsFile::to_bytes(&compact_sides));
+    }
+
     fn has_sram(&self) -> bool {
         // There is no header flag to tell us otherwise, so we assume all disks are writeable and therefore saveable
         return true;
     }
 
+    fn has_persistent_ram(&self) -> bool {
+        return true;
+    }
+
     fn get_sram(&self) -> Vec<u8> {
         let mut combined_disk_images = Vec::new();
         for i in 0 .. self.disk_images.len() {
@@ -507,7 +571,7 @@ impl Mapper for FdsMapper {
 
     fn load_sram(&mut self, raw_data: Vec<u8>) {
         if raw_data.len() != self.disk_images.len() * 81920 {
-            println!("Wrong .sav file size for currently loaded FDS image! Refusing to load.");
+            error!("Wrong .sav file size for currently loaded FDS image! Refusing to load.");
             return;
         }
 
@@ -517,6 +581,10 @@ impl Mapper for FdsMapper {
             let end = start + 81920;
             let mut disk = Vec::new();
             disk.extend_from_slice(&raw_data[start .. end]);
+            // Progress saved in a previous session still needs to end up in the write-back
+            if disk != self.disk_images[i] {
+                self.dirty_sides[i] = true;
+            }
             expanded_disk_images.push(disk);
         }
 
@@ -538,6 +606,93 @@ impl Mapper for FdsMapper {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.audio.record_current_output();
     }
+
+    // The disk images themselves are left out: like battery RAM they persist through
+    // fds_write_back, and rewinding the drive shouldn't also rewind the player's save data.
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec(buff, &self.prg_ram);
+        save_vec(buff, &self.chr);
+        save_vec(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+        save_u16(buff, self.timer_reload_value);
+        save_u16(buff, self.timer_current_value);
+        save_bool(buff, self.timer_enabled);
+        save_bool(buff, self.timer_repeat);
+        save_bool(buff, self.timer_pending);
+        save_bool(buff, self.enable_disk_registers);
+        save_u8(buff, self.write_buffer);
+        save_u8(buff, self.read_buffer);
+        save_u8(buff, self.expansion_port_buffer);
+        save_usize(buff, self.current_side);
+        save_bool(buff, self.disk_inserted);
+        save_bool(buff, self.pending_side.is_some());
+        save_usize(buff, self.pending_side.unwrap_or(0));
+        save_u32(buff, self.disk_change_cooldown);
+        save_usize(buff, self.head_position);
+        save_bool(buff, self.rewinding);
+        save_bool(buff, self.motor_on);
+        save_bool(buff, self.disk_irq_enabled);
+        save_bool(buff, self.disk_irq_pending);
+        save_bool(buff, self.byte_transfer_flag);
+        save_bool(buff, self.write_mode);
+        save_u16(buff, self.motor_delay_counter as u16);
+        save_bool(buff, self.disk_ready_flag);
+        save_bool(buff, self.transfer_reset_flag);
+        save_bool(buff, self.transfer_active_flag);
+        save_u16(buff, self.checksum);
+        save_bool(buff, self.crc_control);
+        save_u8(buff, self.old_4025);
+        self.audio.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        buff.load_into(&mut self.prg_ram)?;
+        buff.load_into(&mut self.chr)?;
+        buff.load_into(&mut self.vram)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.timer_reload_value = buff.load_u16()?;
+        self.timer_current_value = buff.load_u16()?;
+        self.timer_enabled = buff.load_bool()?;
+        self.timer_repeat = buff.load_bool()?;
+        self.timer_pending = buff.load_bool()?;
+        self.enable_disk_registers = buff.load_bool()?;
+        self.write_buffer = buff.load_u8()?;
+        self.read_buffer = buff.load_u8()?;
+        self.expansion_port_buffer = buff.load_u8()?;
+        let current_side = buff.load_usize()?;
+        if current_side >= self.disk_images.len() {
+            return Err(StateError::InvalidValue("current_side"));
+        }
+        self.current_side = current_side;
+        self.disk_inserted = buff.load_bool()?;
+        let has_pending_side = buff.load_bool()?;
+        let pending_side = buff.load_usize()?;
+        if has_pending_side && pending_side >= self.disk_images.len() {
+            return Err(StateError::InvalidValue("pending_side"));
+        }
+        self.pending_side = if has_pending_side {Some(pending_side)} else {None};
+        self.disk_change_cooldown = buff.load_u32()?;
+        let head_position = buff.load_usize()?;
+        if head_position >= self.disk_images[self.current_side].len() {
+            return Err(StateError::InvalidValue("head_position"));
+        }
+        self.head_position = head_position;
+        self.rewinding = buff.load_bool()?;
+        self.motor_on = buff.load_bool()?;
+        self.disk_irq_enabled = buff.load_bool()?;
+        self.disk_irq_pending = buff.load_bool()?;
+        self.byte_transfer_flag = buff.load_bool()?;
+        self.write_mode = buff.load_bool()?;
+        self.motor_delay_counter = buff.load_u16()? as i16;
+        self.disk_ready_flag = buff.load_bool()?;
+        self.transfer_reset_flag = buff.load_bool()?;
+        self.transfer_active_flag = buff.load_bool()?;
+        self.checksum = buff.load_u16()?;
+        self.crc_control = buff.load_bool()?;
+        self.old_4025 = buff.load_u8()?;
+        self.audio.load_state(buff)?;
+        return Ok(());
+    }
 }
 
 pub fn expand_disk_image(compact_disk_image: &Vec<u8>) -> Vec<u8> {
@@ -592,6 +747,48 @@ pub fn expand_disk_image(compact_disk_image: &Vec<u8>) -> Vec<u8> {
     return expanded_image;
 }
 
+// The reverse of expand_disk_image: walks the blocks on an expanded disk, dropping the gaps,
+// start marks and checksums, and returns the 65500 byte side used by .fds files.
+pub fn compact_disk_image(expanded_disk_image: &[u8]) -> Vec<u8> {
+    const BLOCK_1_SIZE: usize = 0x38;
+    const BLOCK_2_SIZE: usize = 0x02;
+    const FILE_HEADER_SIZE: usize = 0x10;
+    const FILE_SIZE_OFFSET: usize = 0x0D;
+    const CHECKSUM_SIZE: usize = 2;
+
+    let mut compact_image: Vec<u8> = Vec::new();
+    let mut file_size = 0;
+    let mut pos = 0;
+    loop {
+        while pos < expanded_disk_image.len() && expanded_disk_image[pos] == 0 {
+            pos += 1;
+        }
+        if pos + 1 >= expanded_disk_image.len() || expanded_disk_image[pos] != 0x80 {
+            break;
+        }
+        pos += 1;
+        let block_size = match expanded_disk_image[pos] {
+            1 => BLOCK_1_SIZE,
+            2 => BLOCK_2_SIZE,
+            3 => FILE_HEADER_SIZE,
+            4 => file_size + 1,
+            _ => break
+        };
+        if pos + block_size > expanded_disk_image.len() {
+            break;
+        }
+        let block = &expanded_disk_image[pos .. pos + block_size];
+        if block[0] == 3 {
+            file_size = (block[FILE_SIZE_OFFSET] as usize) | ((block[FILE_SIZE_OFFSET + 1] as usize) << 8);
+        }
+        compact_image.extend_from_slice(block);
+        pos += block_size + CHECKSUM_SIZE;
+    }
+
+    compact_image.resize(65500, 0);
+    return compact_image;
+}
+
 // credit Persune and plgDavid for reverse engineering the hardware behavior
 const FDS_DAC_LUT: [f32; 64] = [
                      0.0,
@@ -765,7 +962,7 @@ impl FdsAudio {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
         }
     }
 
@@ -929,6 +1126,74 @@ impl FdsAudio {
         }
     }
 
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.enable_sound_registers);
+        save_vec(buff, &self.wavetable_ram);
+        save_u8(buff, self.volume_envelope_output);
+        save_u8(buff, self.volume_envelope_value);
+        save_bool(buff, self.volume_envelope_positive);
+        save_bool(buff, self.volume_envelope_disabled);
+        save_usize(buff, self.volume_envelope_counter_current);
+        save_usize(buff, self.volume_envelope_counter_initial);
+        save_usize(buff, self.frequency);
+        save_bool(buff, self.frequency_envelope_disable);
+        save_bool(buff, self.frequency_halt);
+        save_usize(buff, self.frequency_accumulator);
+        save_u8(buff, self.mod_envelope_output);
+        save_u8(buff, self.mod_envelope_value);
+        save_bool(buff, self.mod_envelope_positive);
+        save_bool(buff, self.mod_envelope_disabled);
+        save_usize(buff, self.mod_accumulator);
+        save_usize(buff, self.mod_envelope_counter_current);
+        save_usize(buff, self.mod_envelope_counter_initial);
+        save_u8(buff, self.mod_counter as u8);
+        save_usize(buff, self.mod_frequency);
+        save_bool(buff, self.mod_always_carry);
+        save_bool(buff, self.mod_table_halt);
+        save_vec(buff, &self.mod_table);
+        save_u8(buff, self.master_volume);
+        save_bool(buff, self.wave_write_enabled);
+        save_u8(buff, self.master_envelope_speed);
+        save_usize(buff, self.mod_position);
+        save_usize(buff, self.wave_position);
+        save_u32(buff, self.current_output.to_bits());
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.enable_sound_registers = buff.load_bool()?;
+        buff.load_into(&mut self.wavetable_ram)?;
+        self.volume_envelope_output = buff.load_u8()?;
+        self.volume_envelope_value = buff.load_u8()?;
+        self.volume_envelope_positive = buff.load_bool()?;
+        self.volume_envelope_disabled = buff.load_bool()?;
+        self.volume_envelope_counter_current = buff.load_usize()?;
+        self.volume_envelope_counter_initial = buff.load_usize()?;
+        self.frequency = buff.load_usize()?;
+        self.frequency_envelope_disable = buff.load_bool()?;
+        self.frequency_halt = buff.load_bool()?;
+        self.frequency_accumulator = buff.load_usize()?;
+        self.mod_envelope_output = buff.load_u8()?;
+        self.mod_envelope_value = buff.load_u8()?;
+        self.mod_envelope_positive = buff.load_bool()?;
+        self.mod_envelope_disabled = buff.load_bool()?;
+        self.mod_accumulator = buff.load_usize()?;
+        self.mod_envelope_counter_current = buff.load_usize()?;
+        self.mod_envelope_counter_initial = buff.load_usize()?;
+        self.mod_counter = buff.load_u8()? as i8;
+        self.mod_frequency = buff.load_usize()?;
+        self.mod_always_carry = buff.load_bool()?;
+        self.mod_table_halt = buff.load_bool()?;
+        buff.load_into(&mut self.mod_table)?;
+        self.master_volume = buff.load_u8()?;
+        self.wave_write_enabled = buff.load_bool()?;
+        self.master_envelope_speed = buff.load_u8()?;
+        // Both positions index fixed size tables, so keep a corrupt state from panicking later
+        self.mod_position = buff.load_usize()? & 0x3F;
+        self.wave_position = buff.load_usize()? & 0x3F;
+        self.current_output = f32::from_bits(buff.load_u32()?);
+        return Ok(());
+    }
+
     pub fn output(&self) -> f32 {
         if self.debug_disable {
             return 0.0;
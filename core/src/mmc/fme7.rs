@@ -1,5 +1,7 @@
-// Sunsoft FME-7, 5A, and 5B (notably lacking expansion audio for now)
+// Sunsoft FME-7, 5A, and 5B. The 5B adds a YM2149F-style PSG, addressed through
+// $C000 (register select) and $E000 (data); the NSF player reuses the chip below.
 // Reference implementation: https://wiki.nesdev.com/w/index.php/Sunsoft_FME-7
+// Audio: https://wiki.nesdev.com/w/index.php/Sunsoft_5B_audio
 
 use ines::INesCartridge;
 use memoryblock::MemoryBlock;
@@ -14,6 +16,7 @@ use apu::Timbre;
 use apu::RingBuffer;
 use apu::filters;
 use apu::filters::DspFilter;
+use save_load::*;
 
 pub struct Fme7 {
     pub prg_rom: MemoryBlock,
@@ -72,9 +75,29 @@ impl Fme7 {
 
 impl Mapper for Fme7 {
     fn mirroring(&self) -> Mirroring {
-        return Mirroring::Horizontal;
+        return self.mirroring;
     }
-    
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn has_persistent_ram(&self) -> bool {
+        return !self.prg_ram.is_volatile();
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        *self.prg_ram.as_mut_vec() = sram_data;
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        return self.prg_ram_selected && self.prg_ram_enabled;
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x6000 ..= 0x7FFF => {
@@ -96,7 +119,7 @@ impl Mapper for Fme7 {
         }
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         self.clock_irq();
         self.expansion_audio_chip.clock();
     }
@@ -185,6 +208,15 @@ impl Mapper for Fme7 {
 
     fn write_ppu(&mut self, address: u16, data: u8) {
         match address {
+            // Ignored unless the board has CHR RAM
+            0x0000 ..= 0x03FF => self.chr_rom.banked_write(0x400, self.chr_banks[0], (address - 0x0000) as usize, data),
+            0x0400 ..= 0x07FF => self.chr_rom.banked_write(0x400, self.chr_banks[1], (address - 0x0400) as usize, data),
+            0x0800 ..= 0x0BFF => self.chr_rom.banked_write(0x400, self.chr_banks[2], (address - 0x0800) as usize, data),
+            0x0C00 ..= 0x0FFF => self.chr_rom.banked_write(0x400, self.chr_banks[3], (address - 0x0C00) as usize, data),
+            0x1000 ..= 0x13FF => self.chr_rom.banked_write(0x400, self.chr_banks[4], (address - 0x1000) as usize, data),
+            0x1400 ..= 0x17FF => self.chr_rom.banked_write(0x400, self.chr_banks[5], (address - 0x1400) as usize, data),
+            0x1800 ..= 0x1BFF => self.chr_rom.banked_write(0x400, self.chr_banks[6], (address - 0x1800) as usize, data),
+            0x1C00 ..= 0x1FFF => self.chr_rom.banked_write(0x400, self.chr_banks[7], (address - 0x1C00) as usize, data),
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
@@ -196,7 +228,7 @@ impl Mapper for Fme7 {
         }
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.irq_enabled && self.irq_pending;
     }
 
@@ -223,6 +255,51 @@ impl Mapper for Fme7 {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.expansion_audio_chip.record_output();
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr_rom.save_state(buff);
+        save_u8(buff, self.command);
+        for bank in &self.chr_banks {
+            save_usize(buff, *bank);
+        }
+        for bank in &self.prg_banks {
+            save_usize(buff, *bank);
+        }
+        save_bool(buff, self.prg_ram_enabled);
+        save_bool(buff, self.prg_ram_selected);
+        save_vec(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_counter_enabled);
+        save_u16(buff, self.irq_counter);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.audio_command_select);
+        self.expansion_audio_chip.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr_rom.load_state(buff)?;
+        self.command = buff.load_u8()?;
+        for bank in self.chr_banks.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        for bank in self.prg_banks.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        self.prg_ram_enabled = buff.load_bool()?;
+        self.prg_ram_selected = buff.load_bool()?;
+        buff.load_into(&mut self.vram)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.irq_enabled = buff.load_bool()?;
+        self.irq_counter_enabled = buff.load_bool()?;
+        self.irq_counter = buff.load_u16()?;
+        self.irq_pending = buff.load_bool()?;
+        self.audio_command_select = buff.load_u8()?;
+        self.expansion_audio_chip.load_state(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct ToneGenerator {
@@ -256,6 +333,21 @@ impl ToneGenerator {
     pub fn output(&self) -> u8 {
         return self.output;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u16(buff, self.period_compare);
+        save_u16(buff, self.period_current);
+        save_u8(buff, self.output);
+        save_bool(buff, self.last_edge);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.period_compare = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.output = buff.load_u8()?;
+        self.last_edge = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 pub struct NoiseGenerator {
@@ -293,6 +385,19 @@ impl NoiseGenerator {
     pub fn output(&self) -> u8 {
         return (self.shift_register & 0b1) as u8;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u16(buff, self.period_compare);
+        save_u16(buff, self.period_current);
+        save_u32(buff, self.shift_register);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.period_compare = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.shift_register = buff.load_u32()?;
+        return Ok(());
+    }
 }
 
 pub struct EnvelopeGenerator {
@@ -398,6 +503,31 @@ impl EnvelopeGenerator {
     pub fn output(&self) -> usize {
         return self.current_value as usize;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u16(buff, self.period_compare);
+        save_u16(buff, self.period_current);
+        save_bool(buff, self.continue_flag);
+        save_bool(buff, self.attack_flag);
+        save_bool(buff, self.alternate_flag);
+        save_bool(buff, self.hold_flag);
+        save_u8(buff, self.current_value as u8);
+        save_bool(buff, self.increasing);
+        save_bool(buff, self.holding);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.period_compare = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.continue_flag = buff.load_bool()?;
+        self.attack_flag = buff.load_bool()?;
+        self.alternate_flag = buff.load_bool()?;
+        self.hold_flag = buff.load_bool()?;
+        self.current_value = buff.load_u8()? as i8;
+        self.increasing = buff.load_bool()?;
+        self.holding = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 pub struct YmChannel {
@@ -422,7 +552,7 @@ impl YmChannel {
             name: String::from(channel_name),
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
             muted: false,
             tone: ToneGenerator::new(),
             tone_enabled: false,
@@ -439,6 +569,25 @@ impl YmChannel {
         self.edge_buffer.push(self.tone.last_edge as i16);
         self.tone.last_edge = false;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.tone.save_state(buff);
+        save_bool(buff, self.tone_enabled);
+        save_bool(buff, self.noise_enabled);
+        save_bool(buff, self.envelope_enabled);
+        save_u8(buff, self.static_volume);
+        save_usize(buff, self.effective_volume);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.tone.load_state(buff)?;
+        self.tone_enabled = buff.load_bool()?;
+        self.noise_enabled = buff.load_bool()?;
+        self.envelope_enabled = buff.load_bool()?;
+        self.static_volume = buff.load_u8()?;
+        self.effective_volume = buff.load_usize()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for YmChannel {
@@ -674,4 +823,23 @@ impl YM2149F {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.channel_a.save_state(buff);
+        self.channel_b.save_state(buff);
+        self.channel_c.save_state(buff);
+        self.noise.save_state(buff);
+        self.envelope.save_state(buff);
+        save_u8(buff, self.clock_divider_counter);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.channel_a.load_state(buff)?;
+        self.channel_b.load_state(buff)?;
+        self.channel_c.load_state(buff)?;
+        self.noise.load_state(buff)?;
+        self.envelope.load_state(buff)?;
+        self.clock_divider_counter = buff.load_u8()?;
+        return Ok(());
+    }
 }
\ No newline at end of file
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct GxRom {
     pub prg_rom: MemoryBlock,
@@ -14,6 +15,8 @@ pub struct GxRom {
     pub prg_bank: usize,
     pub chr_bank: usize,
     pub vram: Vec<u8>,
+    // When set, the value the mapper sees is the written byte ANDed with the ROM byte at that address
+    pub bus_conflicts: bool,
 }
 
 impl GxRom {
@@ -28,6 +31,8 @@ impl GxRom {
             prg_bank: 0x00,
             chr_bank: 0x00,
             vram: vec![0u8; 0x1000],
+            // Every GxROM board lets the ROM drive the bus during writes
+            bus_conflicts: true,
         });
     }
 }
@@ -43,6 +48,26 @@ impl Mapper for GxRom {
         return self.mirroring;
     }
 
+    // GxROM has no IRQ hardware at all; spelled out here as the minimal example of the hooks
+    fn irq_pending(&self) -> bool {
+        return false;
+    }
+
+    fn on_cpu_cycle(&mut self) {}
+
+    fn on_ppu_scanline(&mut self) {}
+
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x8000, self.prg_bank, (address - 0x8000) as usize),
+            _ => None
+        }
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x8000 ..= 0xFFFF => {self.prg_rom.banked_read(0x8000, self.prg_bank, (address - 0x8000) as usize)},
@@ -53,6 +78,10 @@ impl Mapper for GxRom {
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x8000 ..= 0xFFFF => {
+                let mut data = data;
+                if self.bus_conflicts {
+                    data = data & self.debug_read_cpu(address).unwrap_or(0xFF);
+                }
                 self.prg_bank = ((data & 0b0011_0000) >> 4) as usize;
                 self.chr_bank =  (data & 0b0000_0011) as usize;
             }
@@ -66,6 +95,7 @@ impl Mapper for GxRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
                 Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
                 _ => None
             },
             _ => None
@@ -78,9 +108,42 @@ impl Mapper for GxRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
                 _ => {}
             },
             _ => {}
         }
     }
+
+    fn prg_bank_at(&self, address: u16) -> Option<usize> {
+        match address {
+            // banked_read wraps oversized bank numbers, so report the bank that actually answers
+            0x8000 ..= 0xFFFF => Some(self.prg_bank % (self.prg_rom.len() / 0x8000).max(1)),
+            _ => None
+        }
+    }
+
+    fn chr_bank_at(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF => Some(self.chr_bank % (self.chr.len() / 0x2000).max(1)),
+            _ => None
+        }
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_usize(buff, self.chr_bank);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.prg_bank = buff.load_usize()?;
+        self.chr_bank = buff.load_usize()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
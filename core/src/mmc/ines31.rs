@@ -7,6 +7,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct INes31 {
     pub prg_rom: MemoryBlock,
@@ -76,6 +77,7 @@ impl Mapper for INes31 {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
                 Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
                 _ => None
             },
             _ => None
@@ -88,9 +90,29 @@ impl Mapper for INes31 {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
                 _ => {}
             },
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_vec(buff, &self.vram);
+        for bank in &self.prg_banks {
+            save_usize(buff, *bank);
+        }
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        buff.load_into(&mut self.vram)?;
+        for bank in self.prg_banks.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        return Ok(());
+    }
 }
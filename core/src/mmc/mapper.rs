@@ -1,4 +1,5 @@
 use apu::AudioChannelState;
+use save_load::*;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Mirroring {
@@ -30,18 +31,49 @@ pub trait Mapper: Send {
     fn print_debug_status(&self) {}
     fn mirroring(&self) -> Mirroring;
     fn has_sram(&self) -> bool {return false;}
+    // Whether the SRAM survives power off, usually thanks to a battery. Frontends should
+    // only write save files when this is true.
+    fn has_persistent_ram(&self) -> bool {return false;}
     fn get_sram(&self) -> Vec<u8> {return vec![0u8; 0];}
     fn load_sram(&mut self, _: Vec<u8>) {}
-    fn irq_flag(&self) -> bool {return false;}
-    fn clock_cpu(&mut self) {}
+    // IRQ clocking hooks. Cycle counters (VRC, Sunsoft, FDS) count in on_cpu_cycle, which runs
+    // once per CPU cycle after the APU. on_ppu_scanline runs each time the PPU finishes a
+    // scanline, rendering or not; mappers that watch A12 (MMC3) should keep doing so in
+    // access_ppu instead. The CPU polls irq_pending at the start of every cycle.
+    fn irq_pending(&self) -> bool {return false;}
+    fn on_cpu_cycle(&mut self) {}
+    fn on_ppu_scanline(&mut self) {}
+    // Expansion audio: ApuState hands over its stock 2A03 mix once per CPU cycle, and the
+    // mapper adds its own chip on top, scaled to that chip's documented level relative to
+    // the 2A03. Carts without expansion audio pass the stock mix through untouched.
     fn mix_expansion_audio(&self, nes_sample: f32) -> f32 {return nes_sample;}
     fn channels(&self) ->  Vec<& dyn AudioChannelState> {return Vec::new();}
     fn channels_mut(&mut self) ->  Vec<&mut dyn AudioChannelState> {return Vec::new();}
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {}
     fn nsf_set_track(&mut self, _track_index: u8) {}
+    fn nsf_current_track(&self) -> u8 {return 0;}
+    fn nsf_track_count(&self) -> u8 {return 0;}
     fn nsf_manual_mode(&mut self) {}
     fn audio_multiplexing(&mut self, _emulate: bool) {}
     fn needs_bios(&self) -> bool {return false;}
     fn load_bios(&mut self, _: Vec<u8>) {}
     fn switch_disk(&mut self, _: usize) {}
+    fn fds_insert_disk(&mut self, _side: usize) {}
+    fn fds_eject_disk(&mut self) {}
+    fn fds_inserted_disk(&self) -> Option<usize> {return None;}
+    fn fds_side_count(&self) -> usize {return 0;}
+    // The disk as an archival .fds file, but only once the game has written to it
+    fn fds_write_back(&self) -> Option<Vec<u8>> {return None;}
+    // The physical bank currently mapped at a CPU or PPU address, for debuggers. None for fixed
+    // memory, open bus, or mappers that don't report their banking.
+    fn prg_bank_at(&self, _address: u16) -> Option<usize> {return None;}
+    fn chr_bank_at(&self, _address: u16) -> Option<usize> {return None;}
+    // Where a CPU address lands within PRG ROM, for the code/data logger. None if the address
+    // isn't PRG ROM, or the mapper doesn't report it.
+    fn prg_rom_size(&self) -> usize {return 0;}
+    // Whether $6000-$7FFF currently reaches PRG RAM. Mappers without an enable bit always say yes.
+    fn prg_ram_enabled(&self) -> bool {return true;}
+    fn prg_rom_offset(&self, _address: u16) -> Option<usize> {return None;}
+    fn save_state(&self, _buff: &mut Vec<u8>) {}
+    fn load_state(&mut self, _buff: &mut StateReader) -> Result<(), StateError> {return Ok(());}
 }
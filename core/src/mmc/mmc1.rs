@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Mmc1 {
     pub prg_rom: MemoryBlock,
@@ -26,7 +27,11 @@ pub struct Mmc1 {
     pub control: u8,
 
     pub mirroring: Mirroring,
+    // The serial port ignores a write on the cycle right after another write, which is what
+    // makes the two back-to-back writes of a read-modify-write instruction count only once.
+    // last_write covers the previous CPU cycle, write_this_cycle the one in progress.
     pub last_write: bool,
+    pub write_this_cycle: bool,
 }
 
 impl Mmc1 {
@@ -54,6 +59,7 @@ impl Mmc1 {
             control: 0x0C,
             mirroring: Mirroring::Vertical,
             last_write: false,
+            write_this_cycle: false,
         })
     }
 }
@@ -76,17 +82,22 @@ impl Mapper for Mmc1 {
         return self.mirroring;
     }
 
-    fn read_cpu(&mut self, address: u16) -> Option<u8> {
-        self.last_write = false;
-        return self.debug_read_cpu(address);
+    fn on_cpu_cycle(&mut self) {
+        // Runs after this cycle's bus access, so savestates always land with write_this_cycle clear
+        self.last_write = self.write_this_cycle;
+        self.write_this_cycle = false;
     }
 
-    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+    fn prg_ram_enabled(&self) -> bool {
+        return self.prg_ram_enabled;
+    }
+
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
         match address {
-            // PRG RAM
-            0x6000 ..= 0x7FFF => {
-                self.prg_ram.banked_read(0x2000, self.prg_ram_bank, address as usize)
-            },
             // PRG ROM - First 16k Page
             0x8000 ..= 0xBFFF => {
                 let prg_rom_len = self.prg_rom.len();
@@ -96,15 +107,15 @@ impl Mapper for Mmc1 {
                         0 | 1 => {
                             // 32kb PRG mode, use prg_bank ignoring bit 0
                             let lower_half_bank = self.prg_bank & 0xFFFE;
-                            return self.prg_rom.banked_read(0x4000, lower_half_bank, (address - 0x8000) as usize)
+                            return self.prg_rom.banked_address(0x4000, lower_half_bank, (address - 0x8000) as usize)
                         },
                         2 => {
                             // Fixed first bank, read that out here
-                            return self.prg_rom.banked_read(0x4000, 0, (address - 0x8000) as usize)
+                            return self.prg_rom.banked_address(0x4000, 0, (address - 0x8000) as usize)
                         },
                         3 => {
                             // Fixed last bank, read out the bank-switched first bank
-                            return self.prg_rom.banked_read(0x4000, self.prg_bank, (address - 0x8000) as usize)
+                            return self.prg_rom.banked_address(0x4000, self.prg_bank, (address - 0x8000) as usize)
                         },
                         _ => return None, // Never called
                     }
@@ -121,15 +132,15 @@ impl Mapper for Mmc1 {
                         0 | 1 => {
                             // 32kb PRG mode, use prg_bank and force-set bit 1
                             let upper_half_bank = self.prg_bank | 0x0001;
-                            return self.prg_rom.banked_read(0x4000, upper_half_bank, (address - 0x8000) as usize)
+                            return self.prg_rom.banked_address(0x4000, upper_half_bank, (address - 0x8000) as usize)
                         },
                         2 => {
                             // Fixed first bank, read out the bank-switched second bank
-                            return self.prg_rom.banked_read(0x4000, self.prg_bank, (address - 0x8000) as usize)
+                            return self.prg_rom.banked_address(0x4000, self.prg_bank, (address - 0x8000) as usize)
                         },
                         3 => {
                             // Fixed last bank, read out the bank-switched *last* bank
-                            return self.prg_rom.banked_read(0x4000, 0xFF, (address - 0x8000) as usize)
+                            return self.prg_rom.banked_address(0x4000, 0xFF, (address - 0x8000) as usize)
                         },
                         _ => return None, // Never called
                     }
@@ -141,6 +152,22 @@ impl Mapper for Mmc1 {
         }
     }
 
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            // PRG RAM
+            0x6000 ..= 0x7FFF => {
+                if self.prg_ram_enabled {
+                    self.prg_ram.banked_read(0x2000, self.prg_ram_bank, address as usize)
+                } else {
+                    None
+                }
+            },
+            // PRG ROM
+            0x8000 ..= 0xFFFF => self.prg_rom_offset(address).and_then(|offset| self.prg_rom.bounded_read(offset)),
+            _ => return None
+        }
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             // PRG RAM
@@ -151,16 +178,18 @@ impl Mapper for Mmc1 {
             },
             // Control Registers
             0x8000 ..= 0xFFFF => {
-                if self.last_write {
-                    // Ignore this write! MMC1 ignores successive writes, and will clear this flag
-                    // on the next read cycle.
+                let consecutive = self.last_write;
+                self.write_this_cycle = true;
+                if consecutive {
+                    // Ignore this write! MMC1 only latches a write if the previous CPU cycle
+                    // wasn't also a write.
                     return;
                 }
-                self.last_write = true;
-                
+
                 if data & 0x80 != 0 {
                     // Shift / Control Reset!
                     self.shift_counter = 0;
+                    self.shift_data = 0;
                     // Upon reset, this sets the PRG ROM mode to 3, which fixes the last bank
                     // to the upper PRG Page. This is the startup state of MMC1 variants.
                     // https://wiki.nesdev.com/w/index.php/MMC1#Load_register_.28.248000-.24FFFF.29
@@ -182,7 +211,7 @@ impl Mapper for Mmc1 {
                                     1 => self.mirroring = Mirroring::OneScreenUpper,
                                     2 => self.mirroring = Mirroring::Vertical,
                                     3 => self.mirroring = Mirroring::Horizontal,
-                                    _ => println!("Bad mirroring mode!! {}", nametable_mode),
+                                    _ => warn!("Bad mirroring mode!! {}", nametable_mode),
                                 }
                             },
                             0xA000 ..= 0xBF00 => {
@@ -285,6 +314,10 @@ impl Mapper for Mmc1 {
         return true;
     }
 
+    fn has_persistent_ram(&self) -> bool {
+        return !self.prg_ram.is_volatile();
+    }
+
     fn get_sram(&self) -> Vec<u8> {
         return self.prg_ram.as_vec().clone();
     }
@@ -292,4 +325,37 @@ impl Mapper for Mmc1 {
     fn load_sram(&mut self, sram_data: Vec<u8>) {
         *self.prg_ram.as_mut_vec() = sram_data;
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec(buff, &self.vram);
+        save_u8(buff, self.shift_counter);
+        save_u8(buff, self.shift_data);
+        save_usize(buff, self.chr_bank_0);
+        save_usize(buff, self.chr_bank_1);
+        save_usize(buff, self.prg_bank);
+        save_bool(buff, self.prg_ram_enabled);
+        save_usize(buff, self.prg_ram_bank);
+        save_u8(buff, self.control);
+        save_mirroring(buff, self.mirroring);
+        save_bool(buff, self.last_write);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        buff.load_into(&mut self.vram)?;
+        self.shift_counter = buff.load_u8()?;
+        self.shift_data = buff.load_u8()?;
+        self.chr_bank_0 = buff.load_usize()?;
+        self.chr_bank_1 = buff.load_usize()?;
+        self.prg_bank = buff.load_usize()?;
+        self.prg_ram_enabled = buff.load_bool()?;
+        self.prg_ram_bank = buff.load_usize()?;
+        self.control = buff.load_u8()?;
+        self.mirroring = buff.load_mirroring()?;
+        self.last_write = buff.load_bool()?;
+        return Ok(());
+    }
 }
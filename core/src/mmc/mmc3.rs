@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Mmc3 {
     pub prg_rom: MemoryBlock,
@@ -33,6 +34,10 @@ pub struct Mmc3 {
     pub irq_reload_requested: bool,
     pub irq_enabled: bool,
     pub irq_flag: bool,
+    // Older MMC3A (and some MMC3B) chips don't raise an IRQ when the counter reloads to 0 on
+    // its own, only when it decrements to 0 or was reloaded by a $C001 write. NES 2.0 marks
+    // these with submapper 4; everything else gets the common MMC3C behavior.
+    pub irq_alternate_behavior: bool,
 
     pub last_a12: u8,
     pub filtered_a12: u8,
@@ -42,6 +47,13 @@ pub struct Mmc3 {
     pub last_chr_read: u16,
 
     pub mirroring: Mirroring,
+
+    // $A001. Only honored for NES 2.0 headers that rule out MMC6, which shares mapper 004 and
+    // uses these bits differently; plenty of iNES 1.0 dumps of either chip rely on RAM that
+    // is simply always on.
+    pub prg_ram_protect_supported: bool,
+    pub prg_ram_enabled: bool,
+    pub prg_ram_write_protected: bool,
 }
 
 impl Mmc3 {
@@ -77,6 +89,7 @@ impl Mmc3 {
             irq_reload_requested: false,
             irq_enabled: false,
             irq_flag: false,
+            irq_alternate_behavior: ines.header.is_nes2() && ines.header.submapper_number() == 4,
 
             last_a12: 0,
             filtered_a12: 0,
@@ -84,9 +97,17 @@ impl Mmc3 {
             low_a12_counter: 0,
 
             mirroring: ines.header.mirroring(),
+
+            prg_ram_protect_supported: ines.header.is_nes2() && ines.header.submapper_number() != 1,
+            prg_ram_enabled: true,
+            prg_ram_write_protected: false,
         })
     }
 
+    // The IRQ counter is clocked by rising edges on PPU A12, but only after A12 has been
+    // low for about 3 M2 cycles. With sprites at $1000 and the background at $0000 that
+    // works out to once per scanline, at dot 260; swapping the tables moves the edge to
+    // the background prefetch at dot 324. 8x16 sprites can clock it several times per line.
     fn snoop_ppu_a12(&mut self, address: u16) {
         self.last_chr_read = address;
         let current_a12 = ((address & 0b0001_0000_0000_0000) >> 12) as u8;
@@ -117,13 +138,17 @@ impl Mmc3 {
     }
 
     fn clock_irq_counter(&mut self) {
+        let old_counter = self.irq_counter;
+        let forced_reload = self.irq_reload_requested;
         if self.irq_counter == 0 || self.irq_reload_requested {
             self.irq_counter = self.irq_reload;
             self.irq_reload_requested = false;
         } else {
             self.irq_counter -= 1;                        
         }
-        if self.irq_counter == 0 && self.irq_enabled {
+        // With a reload value of 0, MMC3C fires on every clock while MMC3A fires only once
+        let counter_triggers = !self.irq_alternate_behavior || old_counter != 0 || forced_reload;
+        if self.irq_counter == 0 && self.irq_enabled && counter_triggers {
             self.irq_flag = true;                        
         }
     }
@@ -178,36 +203,35 @@ impl Mapper for Mmc3 {
         return self.mirroring;
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.irq_flag;
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         self.snoop_cpu_m2();
     }
 
-    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
         match address {
-            // PRG RAM
-            0x6000 ..= 0x7FFF => {
-                self.prg_ram.wrapping_read(address as usize - 0x6000)
-            },
-            // PRG ROM
             0x8000 ..= 0xFFFF => {
                 if self.switch_prg_banks {
                     match address {
-                        0x8000 ..= 0x9FFF => self.prg_rom.banked_read(0x2000, 0xFE,            address as usize -  0x8000),
-                        0xA000 ..= 0xBFFF => self.prg_rom.banked_read(0x2000, self.prg_bank_7, address as usize -  0xA000),
-                        0xC000 ..= 0xDFFF => self.prg_rom.banked_read(0x2000, self.prg_bank_6, address as usize -  0xC000),
-                        0xE000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, 0xFF,            address as usize -  0xE000),
+                        0x8000 ..= 0x9FFF => self.prg_rom.banked_address(0x2000, 0xFE,            address as usize -  0x8000),
+                        0xA000 ..= 0xBFFF => self.prg_rom.banked_address(0x2000, self.prg_bank_7, address as usize -  0xA000),
+                        0xC000 ..= 0xDFFF => self.prg_rom.banked_address(0x2000, self.prg_bank_6, address as usize -  0xC000),
+                        0xE000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, 0xFF,            address as usize -  0xE000),
                         _ => None,
                     }
                 } else {
                     match address {
-                        0x8000 ..= 0x9FFF => self.prg_rom.banked_read(0x2000, self.prg_bank_6, address as usize -  0x8000),
-                        0xA000 ..= 0xBFFF => self.prg_rom.banked_read(0x2000, self.prg_bank_7, address as usize -  0xA000),
-                        0xC000 ..= 0xDFFF => self.prg_rom.banked_read(0x2000, 0xFE,            address as usize -  0xC000),
-                        0xE000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, 0xFF,            address as usize -  0xE000),
+                        0x8000 ..= 0x9FFF => self.prg_rom.banked_address(0x2000, self.prg_bank_6, address as usize -  0x8000),
+                        0xA000 ..= 0xBFFF => self.prg_rom.banked_address(0x2000, self.prg_bank_7, address as usize -  0xA000),
+                        0xC000 ..= 0xDFFF => self.prg_rom.banked_address(0x2000, 0xFE,            address as usize -  0xC000),
+                        0xE000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, 0xFF,            address as usize -  0xE000),
                         _ => None,
                     }
                 }
@@ -216,13 +240,33 @@ impl Mapper for Mmc3 {
         }
     }
 
+    fn prg_ram_enabled(&self) -> bool {
+        return !self.prg_ram_protect_supported || self.prg_ram_enabled;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            // PRG RAM
+            0x6000 ..= 0x7FFF => {
+                if self.prg_ram_enabled() {
+                    self.prg_ram.wrapping_read(address as usize - 0x6000)
+                } else {
+                    None
+                }
+            },
+            // PRG ROM
+            0x8000 ..= 0xFFFF => self.prg_rom_offset(address).and_then(|offset| self.prg_rom.bounded_read(offset)),
+            _ => None
+        }
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             // PRG RAM
             0x6000 ..= 0x7FFF => {
-                // Note: Intentionally omitting PRG RAM protection feature, since this
-                // retains compatability with assumptions about iNES mapper 004
-                self.prg_ram.wrapping_write(address as usize - 0x6000, data)
+                if self.prg_ram_enabled() && !(self.prg_ram_protect_supported && self.prg_ram_write_protected) {
+                    self.prg_ram.wrapping_write(address as usize - 0x6000, data)
+                }
             },
             // Registers
             0x8000 ..= 0xFFFF => {
@@ -273,7 +317,8 @@ impl Mapper for Mmc3 {
                         },
                         0xA000 ..= 0xBFFF => {
                             // PRG RAM Protect
-                            // Intentionally not emulated, for compatability with iNES mapper 004
+                            self.prg_ram_enabled =         (data & 0b1000_0000) != 0;
+                            self.prg_ram_write_protected = (data & 0b0100_0000) != 0;
                         },
                         0xC000 ..= 0xDFFF => {
                             self.irq_reload_requested = true;
@@ -307,20 +352,7 @@ impl Mapper for Mmc3 {
         match address {
             // CHR RAM (if enabled)
             0x0000 ..= 0x1FFF => {
-                self.last_chr_read = address;
-                let current_a12 = ((address & 0b0001_0000_0000_0000) >> 12) as u8;
-                if current_a12 == 1 && self.last_a12 == 0 {
-                    if self.irq_counter == 0 || self.irq_reload_requested {
-                        self.irq_counter = self.irq_reload;
-                        self.irq_reload_requested = false;
-                    } else {
-                        self.irq_counter -= 1;                        
-                    }
-                    if self.irq_counter == 0 && self.irq_enabled {
-                        self.irq_flag = true;                        
-                    }
-                }
-                self.last_a12 = current_a12;
+                // A12 was already snooped above, which clocks the IRQ counter if needed
                 if self.switch_chr_banks {
                     match address {
                         0x0000 ..= 0x03FF => self.chr.banked_write(0x400, self.chr1_bank_2, address as usize -  0x000, data),
@@ -357,6 +389,10 @@ impl Mapper for Mmc3 {
         return true;
     }
 
+    fn has_persistent_ram(&self) -> bool {
+        return !self.prg_ram.is_volatile();
+    }
+
     fn get_sram(&self) -> Vec<u8> {
         return self.prg_ram.as_vec().clone();
     }
@@ -364,4 +400,61 @@ impl Mapper for Mmc3 {
     fn load_sram(&mut self, sram_data: Vec<u8>) {
         *self.prg_ram.as_mut_vec() = sram_data;
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec(buff, &self.vram);
+        save_usize(buff, self.chr2_bank_0);
+        save_usize(buff, self.chr2_bank_1);
+        save_usize(buff, self.chr1_bank_2);
+        save_usize(buff, self.chr1_bank_3);
+        save_usize(buff, self.chr1_bank_4);
+        save_usize(buff, self.chr1_bank_5);
+        save_usize(buff, self.prg_bank_6);
+        save_usize(buff, self.prg_bank_7);
+        save_bool(buff, self.switch_chr_banks);
+        save_bool(buff, self.switch_prg_banks);
+        save_u8(buff, self.bank_select);
+        save_u8(buff, self.irq_counter);
+        save_u8(buff, self.irq_reload);
+        save_bool(buff, self.irq_reload_requested);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_flag);
+        save_u8(buff, self.last_a12);
+        save_u8(buff, self.filtered_a12);
+        save_u8(buff, self.low_a12_counter);
+        save_mirroring(buff, self.mirroring);
+        save_bool(buff, self.prg_ram_enabled);
+        save_bool(buff, self.prg_ram_write_protected);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        buff.load_into(&mut self.vram)?;
+        self.chr2_bank_0 = buff.load_usize()?;
+        self.chr2_bank_1 = buff.load_usize()?;
+        self.chr1_bank_2 = buff.load_usize()?;
+        self.chr1_bank_3 = buff.load_usize()?;
+        self.chr1_bank_4 = buff.load_usize()?;
+        self.chr1_bank_5 = buff.load_usize()?;
+        self.prg_bank_6 = buff.load_usize()?;
+        self.prg_bank_7 = buff.load_usize()?;
+        self.switch_chr_banks = buff.load_bool()?;
+        self.switch_prg_banks = buff.load_bool()?;
+        self.bank_select = buff.load_u8()?;
+        self.irq_counter = buff.load_u8()?;
+        self.irq_reload = buff.load_u8()?;
+        self.irq_reload_requested = buff.load_bool()?;
+        self.irq_enabled = buff.load_bool()?;
+        self.irq_flag = buff.load_bool()?;
+        self.last_a12 = buff.load_u8()?;
+        self.filtered_a12 = buff.load_u8()?;
+        self.low_a12_counter = buff.load_u8()?;
+        self.mirroring = buff.load_mirroring()?;
+        self.prg_ram_enabled = buff.load_bool()?;
+        self.prg_ram_write_protected = buff.load_bool()?;
+        return Ok(());
+    }
 }
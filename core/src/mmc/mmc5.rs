@@ -11,6 +11,7 @@ use apu::AudioChannelState;
 use apu::RingBuffer;
 use apu::filters;
 use apu::filters::DspFilter;
+use save_load::*;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum PpuMode {
@@ -40,9 +41,24 @@ impl Mmc5PcmChannel {
             muted: false,
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.level);
+        save_bool(buff, self.read_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_pending);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.level = buff.load_u8()?;
+        self.read_mode = buff.load_bool()?;
+        self.irq_enable = buff.load_bool()?;
+        self.irq_pending = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Mmc5PcmChannel {
@@ -154,6 +170,11 @@ pub struct Mmc5 {
     pub ppu_fetches_this_scanline: u16,
     pub multiplicand_a: u8,
     pub multiplicand_b: u8,
+    pub vertical_split_enabled: bool,
+    pub vertical_split_right_side: bool,
+    pub vertical_split_threshold: u8,
+    pub vertical_split_scroll: u8,
+    pub vertical_split_chr_bank: usize,
     pub pulse_1: PulseChannelState,
     pub pulse_2: PulseChannelState,
     pub audio_sequencer_counter: u16,
@@ -213,6 +234,11 @@ impl Mmc5 {
             ppu_fetches_this_scanline: 0,
             multiplicand_a: 0xFF,
             multiplicand_b: 0xFF,
+            vertical_split_enabled: false,
+            vertical_split_right_side: false,
+            vertical_split_threshold: 0,
+            vertical_split_scroll: 0,
+            vertical_split_chr_bank: 0,
             pulse_1: pulse1,
             pulse_2: pulse2,
             audio_sequencer_counter: 0,
@@ -476,6 +502,65 @@ impl Mmc5 {
         return combined_attribute as u8;
     }
 
+    // Works out which background tile the PPU is fetching, from how far into the scanline
+    // we are. Detection lands on the attribute fetch of tile 2, since tiles 0 and 1 were
+    // prefetched at the end of the previous line. Returns (tile column, scanline).
+    pub fn background_fetch_position(&self) -> Option<(usize, usize)> {
+        if self.ppu_read_mode != PpuMode::Backgrounds {
+            return None;
+        }
+        let fetch = self.ppu_fetches_this_scanline as usize;
+        let line = self.current_scanline as usize;
+        return match fetch {
+            0 ..= 126 => {
+                let nametable_fetch = (fetch % 4) == 3;
+                let tile = (fetch / 4) + 2 + (nametable_fetch as usize);
+                Some((tile & 0x1F, line))
+            },
+            159 ..= 166 => Some(((fetch - 159) / 4, line + 1)),
+            167 ..= 169 => Some((2, line + 1)),
+            _ => None
+        };
+    }
+
+    // Vertical split mode replaces the background on one side of a tile threshold with a
+    // second, independently scrolled playfield whose nametable lives in ExRAM.
+    pub fn vertical_split_active(&self) -> bool {
+        return self.vertical_split_enabled && self.extended_ram_mode <= 1;
+    }
+
+    pub fn split_region_position(&self) -> Option<(usize, usize)> {
+        if !self.vertical_split_active() {
+            return None;
+        }
+        let (tile, line) = self.background_fetch_position()?;
+        let threshold = self.vertical_split_threshold as usize;
+        let inside_split = if self.vertical_split_right_side {tile >= threshold} else {tile < threshold};
+        if !inside_split {
+            return None;
+        }
+        let split_y = (self.vertical_split_scroll as usize + line) % 240;
+        return Some((tile, split_y));
+    }
+
+    pub fn read_split_nametable(&self, address: u16, tile: usize, split_y: usize) -> u8 {
+        let coarse_y = split_y / 8;
+        if (address & 0x3FF) >= 0x3C0 {
+            let attribute_index = 0x3C0 + (coarse_y / 4) * 8 + (tile / 4);
+            let shift = ((coarse_y & 0b10) << 1) | (tile & 0b10);
+            let palette_index = (self.extram[attribute_index] >> shift) & 0b11;
+            // As with extended attributes, duplicate to every quadrant so the PPU's choice doesn't matter
+            return palette_index << 6 | palette_index << 4 | palette_index << 2 | palette_index;
+        }
+        return self.extram[coarse_y * 32 + tile];
+    }
+
+    pub fn read_split_chr(&self, address: u16, split_y: usize) -> u8 {
+        // The tile index came from our split nametable, but the fine Y scroll must also be ours
+        let split_address = (address as usize & 0x0FF8) | (split_y & 0b111);
+        return self.chr.banked_read(4096, self.vertical_split_chr_bank, split_address).unwrap_or(0);
+    }
+
     fn read_pcm_sample(&mut self, address: u16) {
         if self.pcm_channel.read_mode {
             match address {
@@ -626,6 +711,13 @@ impl Mmc5 {
     }
 
     fn _read_ppu(&self, address: u16) -> Option<u8> {
+        if let Some((tile, split_y)) = self.split_region_position() {
+            match address {
+                0x0000 ..= 0x1FFF => {return Some(self.read_split_chr(address, split_y));},
+                0x2000 ..= 0x3FFF => {return Some(self.read_split_nametable(address, tile, split_y));},
+                _ => {}
+            }
+        }
         match address {
             0x0000 ..= 0x1FFF => {
                 if self.is_extended_pattern() {
@@ -662,11 +754,12 @@ impl Mapper for Mmc5 {
         println!("CHR Banks: A:{}, B:{}, C:{}, D:{}, E:{}, F:{}, G:{}, H:{}", self.chr_banks[0], self.chr_banks[1], self.chr_banks[2], self.chr_banks[3], self.chr_banks[4], self.chr_banks[5], self.chr_banks[6], self.chr_banks[7]);
         println!("CHR Ext:   AA:{}, BB:{}, CC:{}, DD:{}", self.chr_ext_banks[0], self.chr_ext_banks[1], self.chr_ext_banks[2], self.chr_ext_banks[3]);
         println!("Nametables: Q1:{}, Q2:{}, Q3:{}, Q4:{}", self.nametable_mapping & 0b0000_0011, (self.nametable_mapping & 0b0000_1100) >> 2, (self.nametable_mapping & 0b0011_0000) >> 4, (self.nametable_mapping & 0b1100_0000) >> 6);
+        println!("Vertical Split: E:{} Right:{} Threshold:{} Scroll:{} CHR Bank:{}", self.vertical_split_enabled, self.vertical_split_right_side, self.vertical_split_threshold, self.vertical_split_scroll, self.vertical_split_chr_bank);
         println!("Monitors: PPUCTRL: 0x{:02X}, PPUMASK: 0x{:02X}", self.ppuctrl_monitor, self.ppumask_monitor);
         println!("====================");
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.irq_enabled && self.irq_pending;
     }
 
@@ -817,6 +910,13 @@ impl Mapper for Mmc5 {
                 self.chr_last_write_ext = true;
             },
             0x5130 => {self.chr_bank_high_bits = ((data & 0b0000_0011) as usize) << 8;},
+            0x5200 => {
+                self.vertical_split_enabled =    (data & 0b1000_0000) != 0;
+                self.vertical_split_right_side = (data & 0b0100_0000) != 0;
+                self.vertical_split_threshold =   data & 0b0001_1111;
+            },
+            0x5201 => {self.vertical_split_scroll = data;},
+            0x5202 => {self.vertical_split_chr_bank = data as usize;},
             0x5203 => {self.irq_scanline_compare = data},
             0x5204 => {self.irq_enabled = (data & 0b1000_0000) != 0;},
             0x5205 => {self.multiplicand_a = data;},
@@ -842,7 +942,7 @@ impl Mapper for Mmc5 {
         }
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         self.audio_sequencer_counter += 1;
         if (self.audio_sequencer_counter & 0b1) == 0 {
             self.pulse_1.clock();
@@ -891,5 +991,128 @@ impl Mapper for Mmc5 {
         self.pulse_2.record_current_output();
         self.pcm_channel.record_current_output();
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.ppuctrl_monitor);
+        save_u8(buff, self.ppumask_monitor);
+        save_u8(buff, self.prg_mode);
+        save_u8(buff, self.chr_mode);
+        save_u8(buff, self.prg_ram_magic_low);
+        save_u8(buff, self.prg_ram_magic_high);
+        save_u8(buff, self.extended_ram_mode);
+        save_vec(buff, &self.vram);
+        save_vec(buff, &self.extram);
+        save_u8(buff, self.nametable_mapping);
+        save_u8(buff, self.fill_tile);
+        save_u8(buff, self.fill_attr);
+        save_bool(buff, self.prg_bank_a_isram);
+        save_bool(buff, self.prg_bank_b_isram);
+        save_bool(buff, self.prg_bank_c_isram);
+        save_u8(buff, self.prg_bank_a);
+        save_u8(buff, self.prg_bank_b);
+        save_u8(buff, self.prg_bank_c);
+        save_u8(buff, self.prg_bank_d);
+        save_u8(buff, self.prg_ram_bank);
+        for bank in &self.chr_banks {
+            save_usize(buff, *bank);
+        }
+        for bank in &self.chr_ext_banks {
+            save_usize(buff, *bank);
+        }
+        save_bool(buff, self.chr_last_write_ext);
+        let ppu_mode = match self.ppu_read_mode {
+            PpuMode::Backgrounds => 0,
+            PpuMode::Sprites => 1,
+            PpuMode::PpuData => 2,
+        };
+        save_u8(buff, ppu_mode);
+        save_usize(buff, self.chr_bank_high_bits);
+        save_u8(buff, self.irq_scanline_compare);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_pending);
+        save_bool(buff, self.in_frame);
+        save_u8(buff, self.current_scanline);
+        save_u16(buff, self.last_ppu_fetch);
+        save_u16(buff, self.last_bg_tile_fetch);
+        save_u8(buff, self.consecutive_nametable_count);
+        save_u8(buff, self.cpu_cycles_since_last_ppu_read);
+        save_u16(buff, self.ppu_fetches_this_scanline);
+        save_u8(buff, self.multiplicand_a);
+        save_u8(buff, self.multiplicand_b);
+        save_bool(buff, self.vertical_split_enabled);
+        save_bool(buff, self.vertical_split_right_side);
+        save_u8(buff, self.vertical_split_threshold);
+        save_u8(buff, self.vertical_split_scroll);
+        save_usize(buff, self.vertical_split_chr_bank);
+        self.pulse_1.save_state(buff);
+        self.pulse_2.save_state(buff);
+        save_u16(buff, self.audio_sequencer_counter);
+        self.pcm_channel.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.ppuctrl_monitor = buff.load_u8()?;
+        self.ppumask_monitor = buff.load_u8()?;
+        self.prg_mode = buff.load_u8()?;
+        self.chr_mode = buff.load_u8()?;
+        self.prg_ram_magic_low = buff.load_u8()?;
+        self.prg_ram_magic_high = buff.load_u8()?;
+        self.extended_ram_mode = buff.load_u8()?;
+        buff.load_into(&mut self.vram)?;
+        buff.load_into(&mut self.extram)?;
+        self.nametable_mapping = buff.load_u8()?;
+        self.fill_tile = buff.load_u8()?;
+        self.fill_attr = buff.load_u8()?;
+        self.prg_bank_a_isram = buff.load_bool()?;
+        self.prg_bank_b_isram = buff.load_bool()?;
+        self.prg_bank_c_isram = buff.load_bool()?;
+        self.prg_bank_a = buff.load_u8()?;
+        self.prg_bank_b = buff.load_u8()?;
+        self.prg_bank_c = buff.load_u8()?;
+        self.prg_bank_d = buff.load_u8()?;
+        self.prg_ram_bank = buff.load_u8()?;
+        for bank in self.chr_banks.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        for bank in self.chr_ext_banks.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        self.chr_last_write_ext = buff.load_bool()?;
+        self.ppu_read_mode = match buff.load_u8()? {
+            0 => PpuMode::Backgrounds,
+            1 => PpuMode::Sprites,
+            2 => PpuMode::PpuData,
+            _ => return Err(StateError::InvalidValue("ppu_read_mode")),
+        };
+        self.chr_bank_high_bits = buff.load_usize()?;
+        self.irq_scanline_compare = buff.load_u8()?;
+        self.irq_enabled = buff.load_bool()?;
+        self.irq_pending = buff.load_bool()?;
+        self.in_frame = buff.load_bool()?;
+        self.current_scanline = buff.load_u8()?;
+        self.last_ppu_fetch = buff.load_u16()?;
+        self.last_bg_tile_fetch = buff.load_u16()?;
+        self.consecutive_nametable_count = buff.load_u8()?;
+        self.cpu_cycles_since_last_ppu_read = buff.load_u8()?;
+        self.ppu_fetches_this_scanline = buff.load_u16()?;
+        self.multiplicand_a = buff.load_u8()?;
+        self.multiplicand_b = buff.load_u8()?;
+        self.vertical_split_enabled = buff.load_bool()?;
+        self.vertical_split_right_side = buff.load_bool()?;
+        self.vertical_split_threshold = buff.load_u8()?;
+        self.vertical_split_scroll = buff.load_u8()?;
+        self.vertical_split_chr_bank = buff.load_usize()?;
+        self.pulse_1.load_state(buff)?;
+        self.pulse_2.load_state(buff)?;
+        self.audio_sequencer_counter = buff.load_u16()?;
+        self.pcm_channel.load_state(buff)?;
+        return Ok(());
+    }
 }
 
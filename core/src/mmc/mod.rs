@@ -21,3 +21,16 @@ pub mod rainbow;
 pub mod uxrom;
 pub mod vrc6;
 pub mod vrc7;
+
+// iNES mapper numbers that cartridge::mapper_from_ines knows how to build. Keep the two in step.
+const SUPPORTED_MAPPERS: [u16; 18] = [0, 1, 2, 3, 4, 5, 7, 9, 19, 24, 26, 28, 31, 34, 66, 69, 85, 682];
+
+pub fn supported_mappers() -> &'static [u16] {
+    return &SUPPORTED_MAPPERS;
+}
+
+/// Whether an iNES image with this mapper and submapper can be loaded. Every submapper of a
+/// supported mapper is currently accepted, though some only run with that mapper's defaults.
+pub fn is_mapper_supported(number: u16, _submapper: u8) -> bool {
+    return SUPPORTED_MAPPERS.contains(&number);
+}
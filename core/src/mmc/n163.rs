@@ -17,6 +17,7 @@ use apu::filters::DspFilter;
 
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
+use save_load::*;
 
 pub struct Namco163AudioChannel {
     pub debug_disable: bool,
@@ -68,7 +69,7 @@ impl Namco163AudioChannel {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
         }
     }
 
@@ -587,17 +588,23 @@ impl Mapper for Namco163 {
         }
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         if self.irq_enabled && self.irq_counter < 0x7FFF {
             self.irq_counter += 1;
             if self.irq_counter == 0x7FFF {
                 self.irq_pending = true;
             }
         }
-        self.expansion_audio_chip.clock();
+        // $E000 bit 6 halts the sound hardware entirely; channels hold their phase until it resumes
+        if self.sound_enabled {
+            self.expansion_audio_chip.clock();
+        }
     }
 
     fn mix_expansion_audio(&self, nes_sample: f32) -> f32 {
+        if !self.sound_enabled {
+            return nes_sample;
+        }
         // APU pulse numbers from https://wiki.nesdev.com/w/index.php?title=APU_Mixer
         let nes_pulse_full_volume = 95.88 / ((8128.0 / 15.0) + 100.0);
         let n163_square_full_volume = 15.0 * 15.0; // loudest sample * loudest volume
@@ -643,7 +650,7 @@ impl Mapper for Namco163 {
         return channels;
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.irq_pending;
     }
 
@@ -651,6 +658,10 @@ impl Mapper for Namco163 {
         return true;
     }
 
+    fn has_persistent_ram(&self) -> bool {
+        return !self.prg_ram.is_volatile();
+    }
+
     fn get_sram(&self) -> Vec<u8> {
         return self.prg_ram.as_vec().clone();
     }
@@ -662,4 +673,47 @@ impl Mapper for Namco163 {
     fn audio_multiplexing(&mut self, emulate: bool) {
         self.expansion_audio_chip.emulate_multiplexing = emulate;
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        self.vram.save_state(buff);
+        // Channel registers and wavetables live in internal RAM; the rest of the
+        // audio state is re-derived as each channel is clocked.
+        save_vec(buff, &self.expansion_audio_chip.internal_ram);
+        save_u8(buff, self.expansion_audio_chip.channel_delay_counter);
+        save_usize(buff, self.expansion_audio_chip.current_channel);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_pending);
+        save_u16(buff, self.irq_counter);
+        save_vec(buff, &self.chr_banks);
+        save_vec(buff, &self.nt_banks);
+        save_vec(buff, &self.prg_banks);
+        save_u8(buff, self.internal_ram_addr);
+        save_bool(buff, self.internal_ram_auto_increment);
+        save_bool(buff, self.sound_enabled);
+        save_bool(buff, self.nt_ram_at_0000);
+        save_bool(buff, self.nt_ram_at_1000);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.vram.load_state(buff)?;
+        buff.load_into(&mut self.expansion_audio_chip.internal_ram)?;
+        self.expansion_audio_chip.channel_delay_counter = buff.load_u8()?;
+        self.expansion_audio_chip.current_channel = buff.load_usize()?;
+        self.irq_enabled = buff.load_bool()?;
+        self.irq_pending = buff.load_bool()?;
+        self.irq_counter = buff.load_u16()?;
+        buff.load_into(&mut self.chr_banks)?;
+        buff.load_into(&mut self.nt_banks)?;
+        buff.load_into(&mut self.prg_banks)?;
+        self.internal_ram_addr = buff.load_u8()?;
+        self.internal_ram_auto_increment = buff.load_bool()?;
+        self.sound_enabled = buff.load_bool()?;
+        self.nt_ram_at_0000 = buff.load_bool()?;
+        self.nt_ram_at_1000 = buff.load_bool()?;
+        return Ok(());
+    }
 }
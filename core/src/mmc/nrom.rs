@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Nrom {
     prg_rom: MemoryBlock,
@@ -22,7 +23,7 @@ impl Nrom {
         let prg_ram_block = ines.prg_ram_block()?;
         let chr_block = ines.chr_block()?;
 
-        println!("NROM Mirroring Mode: {}", mirroring_mode_name(ines.header.mirroring()));
+        debug!("NROM Mirroring Mode: {}", mirroring_mode_name(ines.header.mirroring()));
 
         return Ok(Nrom {
             prg_rom: prg_rom_block.clone(),
@@ -45,6 +46,17 @@ impl Mapper for Nrom {
         return self.mirroring;
     }
     
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x8000, 0, (address - 0x8000) as usize),
+            _ => None
+        }
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x6000 ..= 0x7FFF => {self.prg_ram.wrapping_read((address - 0x6000) as usize)},
@@ -88,4 +100,19 @@ impl Mapper for Nrom {
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
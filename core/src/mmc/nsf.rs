@@ -1126,7 +1126,18 @@ impl NsfMapper {
 
 impl Mapper for NsfMapper {
     fn nsf_set_track(&mut self, track_index: u8) {
-        self.current_track = track_index;
+        // Tracks are numbered from 1. The player routine notices the change on its next
+        // frame and re-runs INIT for the new song.
+        self.current_track = track_index.max(1).min(self.header.total_songs().max(1));
+        self.current_cycles = 0;
+    }
+
+    fn nsf_current_track(&self) -> u8 {
+        return self.current_track;
+    }
+
+    fn nsf_track_count(&self) -> u8 {
+        return self.header.total_songs();
     }
 
     fn nsf_manual_mode(&mut self) {
@@ -1137,7 +1148,7 @@ impl Mapper for NsfMapper {
         return self.mirroring;
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         self.playback_accumulator += 1.0;
         if self.playback_accumulator > self.playback_period {
             self.playback_counter = self.playback_counter.wrapping_add(1);
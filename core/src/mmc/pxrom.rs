@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct PxRom {
     pub prg_rom: MemoryBlock,
@@ -137,4 +138,33 @@ impl Mapper for PxRom {
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.chr_0_latch);
+        save_usize(buff, self.chr_0_fd_bank);
+        save_usize(buff, self.chr_0_fe_bank);
+        save_u8(buff, self.chr_1_latch);
+        save_usize(buff, self.chr_1_fd_bank);
+        save_usize(buff, self.chr_1_fe_bank);
+        save_usize(buff, self.prg_bank);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.chr_0_latch = buff.load_u8()?;
+        self.chr_0_fd_bank = buff.load_usize()?;
+        self.chr_0_fe_bank = buff.load_usize()?;
+        self.chr_1_latch = buff.load_u8()?;
+        self.chr_1_fd_bank = buff.load_usize()?;
+        self.chr_1_fe_bank = buff.load_usize()?;
+        self.prg_bank = buff.load_usize()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
@@ -14,6 +14,7 @@ use memoryblock::MemoryBlock;
 use memoryblock::MemoryType;
 
 use mmc::mapper::*;
+use save_load::*;
 
 use apu::AudioChannelState;
 use mmc::vrc6::Vrc6PulseChannel;
@@ -205,7 +206,7 @@ impl Rainbow {
             chr_ram.resize(ines.header.chr_ram_size(), 0);
             MemoryBlock::new(&chr_ram, MemoryType::Ram)
         } else if ines.header.chr_sram_size() > 0 {
-            println!("Rainbow: Unsupported non-volatile CHR RAM! Loading anyway, will treat like volatile CHR RAM instead. Game saving may not work!");
+            warn!("Rainbow: Unsupported non-volatile CHR RAM! Loading anyway, will treat like volatile CHR RAM instead. Game saving may not work!");
             let mut chr_sram: Vec<u8> = Vec::new();
             chr_sram.resize(ines.header.chr_sram_size(), 0);
             MemoryBlock::new(&chr_sram, MemoryType::Ram)
@@ -816,14 +817,14 @@ impl Mapper for Rainbow {
         return self.mirroring;
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         self.vrc6_pulse1.clock();
         self.vrc6_pulse2.clock();
         self.vrc6_sawtooth.clock();
         self.clock_irq();
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return (self.cpu_irq_pending) || (self.scanline_irq_enabled && self.scanline_irq_pending);
     }
 
@@ -1115,7 +1116,7 @@ impl Mapper for Rainbow {
                     0b01 => self.chr_chip = ChrChipSelect::ChrRam,
                     _    => self.chr_chip = ChrChipSelect::FpgaRam
                 };
-                println!("CHR mode: {:?}, chip: {:?}, splitwindow: {}, ext.sprites: {}", 
+                debug!("CHR mode: {:?}, chip: {:?}, splitwindow: {}, ext.sprites: {}", 
                     self.chr_mode, self.chr_chip, self.window_split, self.extended_sprites);
             },
 
@@ -1310,6 +1311,271 @@ impl Mapper for Rainbow {
         self.vrc6_pulse2.record_current_output();
         self.vrc6_sawtooth.record_current_output();
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_rom.save_state(buff);
+        self.prg_ram.save_state(buff);
+        self.chr_rom.save_state(buff);
+        self.chr_ram.save_state(buff);
+        self.ciram.save_state(buff);
+        self.fpga_ram.save_state(buff);
+
+        let prg_rom_mode = match self.prg_rom_mode {
+            PrgRomBankingMode::Mode0Bank1x32k => 0,
+            PrgRomBankingMode::Mode1Bank2x16k => 1,
+            PrgRomBankingMode::Mode2Bank1x16k2x8k => 2,
+            PrgRomBankingMode::Mode3Bank4x8k => 3,
+            PrgRomBankingMode::Mode4Bank8x4k => 4,
+        };
+        save_u8(buff, prg_rom_mode);
+        let prg_ram_mode = match self.prg_ram_mode {
+            PrgRamBankingMode::Mode0Bank1x8k => 0,
+            PrgRamBankingMode::Mode1Bank2x4k => 1,
+        };
+        save_u8(buff, prg_ram_mode);
+        let chr_mode = match self.chr_mode {
+            ChrBankingMode::Mode0Bank1x8k => 0,
+            ChrBankingMode::Mode1Bank2x4k => 1,
+            ChrBankingMode::Mode2Bank4x2k => 2,
+            ChrBankingMode::Mode3Bank8x1k => 3,
+            ChrBankingMode::Mode4Bank16x512b => 4,
+        };
+        save_u8(buff, chr_mode);
+        let chr_chip = match self.chr_chip {
+            ChrChipSelect::ChrRom => 0,
+            ChrChipSelect::ChrRam => 1,
+            ChrChipSelect::FpgaRam => 2,
+        };
+        save_u8(buff, chr_chip);
+
+        save_usize(buff, self.prg_bank_at_8000);
+        save_usize(buff, self.prg_bank_at_9000);
+        save_usize(buff, self.prg_bank_at_a000);
+        save_usize(buff, self.prg_bank_at_b000);
+        save_usize(buff, self.prg_bank_at_c000);
+        save_usize(buff, self.prg_bank_at_d000);
+        save_usize(buff, self.prg_bank_at_e000);
+        save_usize(buff, self.prg_bank_at_f000);
+        save_bool(buff, self.prg_ram_at_8000);
+        save_bool(buff, self.prg_ram_at_9000);
+        save_bool(buff, self.prg_ram_at_a000);
+        save_bool(buff, self.prg_ram_at_b000);
+        save_bool(buff, self.prg_ram_at_c000);
+        save_bool(buff, self.prg_ram_at_d000);
+        save_bool(buff, self.prg_ram_at_e000);
+        save_bool(buff, self.prg_ram_at_f000);
+        save_usize(buff, self.prg_bank_at_6000);
+        save_usize(buff, self.prg_bank_at_7000);
+        save_bool(buff, self.prg_ram_at_6000);
+        save_bool(buff, self.prg_ram_at_7000);
+        save_bool(buff, self.fpga_ram_at_6000);
+        save_bool(buff, self.fpga_ram_at_7000);
+        save_usize(buff, self.fpga_bank_at_5000);
+        for bank in &self.chr_banks {
+            save_usize(buff, *bank);
+        }
+        save_usize(buff, self.chr_bank_high_bits);
+        save_bool(buff, self.window_split);
+        save_bool(buff, self.extended_sprites);
+        save_mirroring(buff, self.mirroring);
+
+        self.vrc6_pulse1.save_state(buff);
+        self.vrc6_pulse2.save_state(buff);
+        self.vrc6_sawtooth.save_state(buff);
+        save_bool(buff, self.vrc6_exp6);
+        save_bool(buff, self.vrc6_exp9);
+        save_bool(buff, self.vrc6_zpcm);
+
+        save_u16(buff, self.cpu_irq_counter);
+        save_u16(buff, self.cpu_irq_latch);
+        save_bool(buff, self.cpu_irq_enable);
+        save_bool(buff, self.cpu_irq_auto_repeat);
+        save_bool(buff, self.cpu_irq_pending);
+
+        save_usize(buff, self.nametable_bank_at_2000);
+        save_usize(buff, self.nametable_bank_at_2400);
+        save_usize(buff, self.nametable_bank_at_2800);
+        save_usize(buff, self.nametable_bank_at_2c00);
+        save_nametable_chip(buff, self.nametable_chip_at_2000);
+        save_nametable_chip(buff, self.nametable_chip_at_2400);
+        save_nametable_chip(buff, self.nametable_chip_at_2800);
+        save_nametable_chip(buff, self.nametable_chip_at_2c00);
+        save_bool(buff, self.extended_attributes_2000);
+        save_bool(buff, self.extended_attributes_2400);
+        save_bool(buff, self.extended_attributes_2800);
+        save_bool(buff, self.extended_attributes_2c00);
+        save_bool(buff, self.extended_backgrounds_2000);
+        save_bool(buff, self.extended_backgrounds_2400);
+        save_bool(buff, self.extended_backgrounds_2800);
+        save_bool(buff, self.extended_backgrounds_2c00);
+        save_usize(buff, self.exram_bank_2000);
+        save_usize(buff, self.exram_bank_2400);
+        save_usize(buff, self.exram_bank_2800);
+        save_usize(buff, self.exram_bank_2c00);
+
+        save_bool(buff, self.scanline_irq_pending);
+        save_bool(buff, self.scanline_irq_enabled);
+        save_u8(buff, self.scanline_irq_compare);
+        save_u8(buff, self.scanline_irq_offset);
+        save_u8(buff, self.scanline_jitter_counter);
+        let ppu_mode = match self.ppu_read_mode {
+            PpuMode::Backgrounds => 0,
+            PpuMode::Sprites => 1,
+            PpuMode::PpuData => 2,
+        };
+        save_u8(buff, ppu_mode);
+        save_bool(buff, self.in_frame);
+        save_bool(buff, self.in_hblank);
+        save_u8(buff, self.current_scanline);
+        save_u8(buff, self.consecutive_nametable_count);
+        save_u8(buff, self.cpu_cycles_since_last_ppu_read);
+        save_u8(buff, self.ppu_fetches_this_scanline);
+        save_u16(buff, self.last_ppu_fetch);
+        save_u16(buff, self.last_bg_tile_fetch);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_rom.load_state(buff)?;
+        self.prg_ram.load_state(buff)?;
+        self.chr_rom.load_state(buff)?;
+        self.chr_ram.load_state(buff)?;
+        self.ciram.load_state(buff)?;
+        self.fpga_ram.load_state(buff)?;
+
+        self.prg_rom_mode = match buff.load_u8()? {
+            0 => PrgRomBankingMode::Mode0Bank1x32k,
+            1 => PrgRomBankingMode::Mode1Bank2x16k,
+            2 => PrgRomBankingMode::Mode2Bank1x16k2x8k,
+            3 => PrgRomBankingMode::Mode3Bank4x8k,
+            4 => PrgRomBankingMode::Mode4Bank8x4k,
+            _ => return Err(StateError::InvalidValue("prg_rom_mode")),
+        };
+        self.prg_ram_mode = match buff.load_u8()? {
+            0 => PrgRamBankingMode::Mode0Bank1x8k,
+            1 => PrgRamBankingMode::Mode1Bank2x4k,
+            _ => return Err(StateError::InvalidValue("prg_ram_mode")),
+        };
+        self.chr_mode = match buff.load_u8()? {
+            0 => ChrBankingMode::Mode0Bank1x8k,
+            1 => ChrBankingMode::Mode1Bank2x4k,
+            2 => ChrBankingMode::Mode2Bank4x2k,
+            3 => ChrBankingMode::Mode3Bank8x1k,
+            4 => ChrBankingMode::Mode4Bank16x512b,
+            _ => return Err(StateError::InvalidValue("chr_mode")),
+        };
+        self.chr_chip = match buff.load_u8()? {
+            0 => ChrChipSelect::ChrRom,
+            1 => ChrChipSelect::ChrRam,
+            2 => ChrChipSelect::FpgaRam,
+            _ => return Err(StateError::InvalidValue("chr_chip")),
+        };
+
+        self.prg_bank_at_8000 = buff.load_usize()?;
+        self.prg_bank_at_9000 = buff.load_usize()?;
+        self.prg_bank_at_a000 = buff.load_usize()?;
+        self.prg_bank_at_b000 = buff.load_usize()?;
+        self.prg_bank_at_c000 = buff.load_usize()?;
+        self.prg_bank_at_d000 = buff.load_usize()?;
+        self.prg_bank_at_e000 = buff.load_usize()?;
+        self.prg_bank_at_f000 = buff.load_usize()?;
+        self.prg_ram_at_8000 = buff.load_bool()?;
+        self.prg_ram_at_9000 = buff.load_bool()?;
+        self.prg_ram_at_a000 = buff.load_bool()?;
+        self.prg_ram_at_b000 = buff.load_bool()?;
+        self.prg_ram_at_c000 = buff.load_bool()?;
+        self.prg_ram_at_d000 = buff.load_bool()?;
+        self.prg_ram_at_e000 = buff.load_bool()?;
+        self.prg_ram_at_f000 = buff.load_bool()?;
+        self.prg_bank_at_6000 = buff.load_usize()?;
+        self.prg_bank_at_7000 = buff.load_usize()?;
+        self.prg_ram_at_6000 = buff.load_bool()?;
+        self.prg_ram_at_7000 = buff.load_bool()?;
+        self.fpga_ram_at_6000 = buff.load_bool()?;
+        self.fpga_ram_at_7000 = buff.load_bool()?;
+        self.fpga_bank_at_5000 = buff.load_usize()?;
+        for bank in self.chr_banks.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        self.chr_bank_high_bits = buff.load_usize()?;
+        self.window_split = buff.load_bool()?;
+        self.extended_sprites = buff.load_bool()?;
+        self.mirroring = buff.load_mirroring()?;
+
+        self.vrc6_pulse1.load_state(buff)?;
+        self.vrc6_pulse2.load_state(buff)?;
+        self.vrc6_sawtooth.load_state(buff)?;
+        self.vrc6_exp6 = buff.load_bool()?;
+        self.vrc6_exp9 = buff.load_bool()?;
+        self.vrc6_zpcm = buff.load_bool()?;
+
+        self.cpu_irq_counter = buff.load_u16()?;
+        self.cpu_irq_latch = buff.load_u16()?;
+        self.cpu_irq_enable = buff.load_bool()?;
+        self.cpu_irq_auto_repeat = buff.load_bool()?;
+        self.cpu_irq_pending = buff.load_bool()?;
+
+        self.nametable_bank_at_2000 = buff.load_usize()?;
+        self.nametable_bank_at_2400 = buff.load_usize()?;
+        self.nametable_bank_at_2800 = buff.load_usize()?;
+        self.nametable_bank_at_2c00 = buff.load_usize()?;
+        self.nametable_chip_at_2000 = load_nametable_chip(buff)?;
+        self.nametable_chip_at_2400 = load_nametable_chip(buff)?;
+        self.nametable_chip_at_2800 = load_nametable_chip(buff)?;
+        self.nametable_chip_at_2c00 = load_nametable_chip(buff)?;
+        self.extended_attributes_2000 = buff.load_bool()?;
+        self.extended_attributes_2400 = buff.load_bool()?;
+        self.extended_attributes_2800 = buff.load_bool()?;
+        self.extended_attributes_2c00 = buff.load_bool()?;
+        self.extended_backgrounds_2000 = buff.load_bool()?;
+        self.extended_backgrounds_2400 = buff.load_bool()?;
+        self.extended_backgrounds_2800 = buff.load_bool()?;
+        self.extended_backgrounds_2c00 = buff.load_bool()?;
+        self.exram_bank_2000 = buff.load_usize()?;
+        self.exram_bank_2400 = buff.load_usize()?;
+        self.exram_bank_2800 = buff.load_usize()?;
+        self.exram_bank_2c00 = buff.load_usize()?;
+
+        self.scanline_irq_pending = buff.load_bool()?;
+        self.scanline_irq_enabled = buff.load_bool()?;
+        self.scanline_irq_compare = buff.load_u8()?;
+        self.scanline_irq_offset = buff.load_u8()?;
+        self.scanline_jitter_counter = buff.load_u8()?;
+        self.ppu_read_mode = match buff.load_u8()? {
+            0 => PpuMode::Backgrounds,
+            1 => PpuMode::Sprites,
+            2 => PpuMode::PpuData,
+            _ => return Err(StateError::InvalidValue("ppu_read_mode")),
+        };
+        self.in_frame = buff.load_bool()?;
+        self.in_hblank = buff.load_bool()?;
+        self.current_scanline = buff.load_u8()?;
+        self.consecutive_nametable_count = buff.load_u8()?;
+        self.cpu_cycles_since_last_ppu_read = buff.load_u8()?;
+        self.ppu_fetches_this_scanline = buff.load_u8()?;
+        self.last_ppu_fetch = buff.load_u16()?;
+        self.last_bg_tile_fetch = buff.load_u16()?;
+        return Ok(());
+    }
+}
+
+fn save_nametable_chip(buff: &mut Vec<u8>, chip: NametableChipSelect) {
+    let value = match chip {
+        NametableChipSelect::CiRam => 0,
+        NametableChipSelect::ChrRam => 1,
+        NametableChipSelect::FpgaRam => 2,
+        NametableChipSelect::ChrRom => 3,
+    };
+    save_u8(buff, value);
+}
+
+fn load_nametable_chip(buff: &mut StateReader) -> Result<NametableChipSelect, StateError> {
+    return match buff.load_u8()? {
+        0 => Ok(NametableChipSelect::CiRam),
+        1 => Ok(NametableChipSelect::ChrRam),
+        2 => Ok(NametableChipSelect::FpgaRam),
+        3 => Ok(NametableChipSelect::ChrRom),
+        _ => Err(StateError::InvalidValue("nametable_chip")),
+    };
 }
 
 // Provided courtesy of Broke Studio. The raster font contained within is assumed
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct UxRom {
     pub prg_rom: MemoryBlock,
@@ -13,6 +14,8 @@ pub struct UxRom {
     pub mirroring: Mirroring,
     pub prg_bank: usize,
     pub vram: Vec<u8>,
+    // When set, the value the mapper sees is the written byte ANDed with the ROM byte at that address
+    pub bus_conflicts: bool,
 }
 
 impl UxRom {
@@ -26,6 +29,8 @@ impl UxRom {
             mirroring: ines.header.mirroring(),
             prg_bank: 0x00,
             vram: vec![0u8; 0x1000],
+            // NES 2.0 submapper 2 has bus conflicts, 1 doesn't, and 0 doesn't say
+            bus_conflicts: ines.header.submapper_number() == 2,
         })
     }
 }
@@ -42,6 +47,18 @@ impl Mapper for UxRom {
         return self.mirroring;
     }
 
+    fn prg_rom_size(&self) -> usize {
+        return self.prg_rom.len();
+    }
+
+    fn prg_rom_offset(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_address(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_address(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
@@ -53,6 +70,10 @@ impl Mapper for UxRom {
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x8000 ..= 0xFFFF => {
+                let mut data = data;
+                if self.bus_conflicts {
+                    data = data & self.debug_read_cpu(address).unwrap_or(0xFF);
+                }
                 self.prg_bank = data as usize;
             }
             _ => {}
@@ -65,6 +86,7 @@ impl Mapper for UxRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
                 Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
                 _ => None
             },
             _ => None
@@ -77,9 +99,25 @@ impl Mapper for UxRom {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
                 _ => {}
             },
             _ => {}
         }
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_vec(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        self.prg_bank = buff.load_usize()?;
+        buff.load_into(&mut self.vram)?;
+        return Ok(());
+    }
 }
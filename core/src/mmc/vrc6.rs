@@ -14,6 +14,7 @@ use apu::Timbre;
 use apu::RingBuffer;
 use apu::filters;
 use apu::filters::DspFilter;
+use save_load::*;
 
 pub struct Vrc6PulseChannel {
     pub name: String,
@@ -50,7 +51,7 @@ impl Vrc6PulseChannel {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
         };
     }
 
@@ -63,14 +64,19 @@ impl Vrc6PulseChannel {
         }
     }
 
-    pub fn _reload_period_counter(&mut self) {
+    // $9003 can shift the period right to speed up every channel at once; this is the
+    // period the divider actually reloads with.
+    pub fn effective_period(&self) -> u16 {
         if self.scale_256 {
-            self.period_current = self.period_initial >> 8;
+            return self.period_initial >> 8;
         } else if self.scale_16 {
-            self.period_current = self.period_initial >> 4;
-        } else {
-            self.period_current = self.period_initial;
+            return self.period_initial >> 4;
         }
+        return self.period_initial;
+    }
+
+    pub fn _reload_period_counter(&mut self) {
+        self.period_current = self.effective_period();
     }
 
     pub fn clock(&mut self) {
@@ -123,6 +129,31 @@ impl Vrc6PulseChannel {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.enabled);
+        save_u8(buff, self.duty_compare);
+        save_u8(buff, self.duty_counter);
+        save_u8(buff, self.volume);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_bool(buff, self.halt);
+        save_bool(buff, self.scale_256);
+        save_bool(buff, self.scale_16);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = buff.load_bool()?;
+        self.duty_compare = buff.load_u8()?;
+        self.duty_counter = buff.load_u8()?;
+        self.volume = buff.load_u8()?;
+        self.period_initial = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.halt = buff.load_bool()?;
+        self.scale_256 = buff.load_bool()?;
+        self.scale_16 = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Vrc6PulseChannel {
@@ -178,7 +209,7 @@ impl AudioChannelState for Vrc6PulseChannel {
     }
 
     fn rate(&self) -> PlaybackRate {
-        let frequency = 1_789_773.0 / (16.0 * (self.period_initial as f32 + 1.0));
+        let frequency = 1_789_773.0 / (16.0 * (self.effective_period() as f32 + 1.0));
         return PlaybackRate::FundamentalFrequency {frequency: frequency};
     }
 
@@ -224,7 +255,7 @@ impl Vrc6SawtoothChannel {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
         };
     }
 
@@ -242,14 +273,17 @@ impl Vrc6SawtoothChannel {
         }
     }
 
-    pub fn _reload_period_counter(&mut self) {
+    pub fn effective_period(&self) -> u16 {
         if self.scale_256 {
-            self.period_current = self.period_initial >> 8;
+            return self.period_initial >> 8;
         } else if self.scale_16 {
-            self.period_current = self.period_initial >> 4;
-        } else {
-            self.period_current = self.period_initial;
+            return self.period_initial >> 4;
         }
+        return self.period_initial;
+    }
+
+    pub fn _reload_period_counter(&mut self) {
+        self.period_current = self.effective_period();
     }
 
     pub fn clock(&mut self) {
@@ -293,6 +327,31 @@ impl Vrc6SawtoothChannel {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.enabled);
+        save_u8(buff, self.accumulator_rate);
+        save_u8(buff, self.accumulator_step);
+        save_u8(buff, self.accumulator);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_bool(buff, self.halt);
+        save_bool(buff, self.scale_256);
+        save_bool(buff, self.scale_16);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = buff.load_bool()?;
+        self.accumulator_rate = buff.load_u8()?;
+        self.accumulator_step = buff.load_u8()?;
+        self.accumulator = buff.load_u8()?;
+        self.period_initial = buff.load_u16()?;
+        self.period_current = buff.load_u16()?;
+        self.halt = buff.load_bool()?;
+        self.scale_256 = buff.load_bool()?;
+        self.scale_16 = buff.load_bool()?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Vrc6SawtoothChannel {
@@ -347,7 +406,7 @@ impl AudioChannelState for Vrc6SawtoothChannel {
     }
 
     fn rate(&self) -> PlaybackRate {
-        let frequency = 1_789_773.0 / (14.0 * (self.period_initial as f32 + 1.0));
+        let frequency = 1_789_773.0 / (14.0 * (self.effective_period() as f32 + 1.0));
         return PlaybackRate::FundamentalFrequency {frequency: frequency};
     }
 
@@ -566,7 +625,7 @@ impl Vrc6 {
 
     fn _mirroring_mode_0_write(&mut self, address: u16, data: u8) {
         if self.nametable_chrrom {
-            println!("Attempt to write to CHR ROM nametables!");
+            warn!("Attempt to write to CHR ROM nametables!");
         } else {
             match self.mirroring_mode {
                 0 => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
@@ -606,7 +665,7 @@ impl Vrc6 {
 
     fn _mirroring_mode_1_write(&mut self, address: u16, data: u8) {
         if self.nametable_chrrom {
-            println!("Attempt to write to CHR ROM nametables!");
+            warn!("Attempt to write to CHR ROM nametables!");
         } else {
             let mirrored_address = address & 0x2FFF;
             let masked_address = (mirrored_address & 0b0011_1111_1111) as usize;
@@ -788,7 +847,7 @@ impl Vrc6 {
 
     fn _mirroring_mode_3_write(&mut self, address: u16, data: u8) {
         if self.nametable_chrrom {
-            println!("Attempt to write to CHR ROM nametables!");
+            warn!("Attempt to write to CHR ROM nametables!");
         } else {
             match self.mirroring_mode {
                 0 => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
@@ -888,7 +947,7 @@ impl Vrc6 {
 
     fn _a10_nametable_write(&mut self, address: u16, data: u8) {
         if self.nametable_chrrom {
-            println!("Attempt to write to CHR ROM nametables!");
+            warn!("Attempt to write to CHR ROM nametables!");
             return;
         }
         let a10_rules_address = self._a10_nametable_address(address);
@@ -918,7 +977,7 @@ impl Mapper for Vrc6 {
         return self.mirroring;
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         if self.irq_enable {
             if self.irq_scanline_mode {
                 self._clock_irq_prescaler();
@@ -946,13 +1005,17 @@ impl Mapper for Vrc6 {
             nes_sample;
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.irq_pending;
     }
 
+    fn prg_ram_enabled(&self) -> bool {
+        return self.prg_ram_enable;
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
-            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x6000 ..= 0x7FFF => if self.prg_ram_enable {self.prg_ram.wrapping_read(address as usize - 0x6000)} else {None},
             0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank_16, address as usize -  0x8000),
             0xC000 ..= 0xDFFF => self.prg_rom.banked_read(0x2000, self.prg_bank_8, address as usize -  0xC000),
             0xE000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, 0xFF, address as usize -  0xE000),
@@ -1115,4 +1178,61 @@ impl Mapper for Vrc6 {
         self.pulse2.record_current_output();
         self.sawtooth.record_current_output();
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec(buff, &self.vram);
+        save_bool(buff, self.prg_ram_enable);
+        save_usize(buff, self.prg_bank_16);
+        save_usize(buff, self.prg_bank_8);
+        for bank in &self.r {
+            save_usize(buff, *bank);
+        }
+        save_u8(buff, self.ppu_banking_mode);
+        save_u8(buff, self.mirroring_mode);
+        save_bool(buff, self.nametable_chrrom);
+        save_bool(buff, self.chr_a10_rules);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.b003_shadow);
+        save_u16(buff, self.irq_scanline_prescaler as u16);
+        save_u8(buff, self.irq_latch);
+        save_bool(buff, self.irq_scanline_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_enable_after_acknowledgement);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.irq_counter);
+        self.pulse1.save_state(buff);
+        self.pulse2.save_state(buff);
+        self.sawtooth.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        buff.load_into(&mut self.vram)?;
+        self.prg_ram_enable = buff.load_bool()?;
+        self.prg_bank_16 = buff.load_usize()?;
+        self.prg_bank_8 = buff.load_usize()?;
+        for bank in self.r.iter_mut() {
+            *bank = buff.load_usize()?;
+        }
+        self.ppu_banking_mode = buff.load_u8()?;
+        self.mirroring_mode = buff.load_u8()?;
+        self.nametable_chrrom = buff.load_bool()?;
+        self.chr_a10_rules = buff.load_bool()?;
+        self.mirroring = buff.load_mirroring()?;
+        self.b003_shadow = buff.load_u8()?;
+        self.irq_scanline_prescaler = buff.load_u16()? as i16;
+        self.irq_latch = buff.load_u8()?;
+        self.irq_scanline_mode = buff.load_bool()?;
+        self.irq_enable = buff.load_bool()?;
+        self.irq_enable_after_acknowledgement = buff.load_bool()?;
+        self.irq_pending = buff.load_bool()?;
+        self.irq_counter = buff.load_u8()?;
+        self.pulse1.load_state(buff)?;
+        self.pulse2.load_state(buff)?;
+        self.sawtooth.load_state(buff)?;
+        return Ok(());
+    }
 }
@@ -14,6 +14,7 @@ use apu::Timbre;
 use apu::RingBuffer;
 use apu::filters;
 use apu::filters::DspFilter;
+use save_load::*;
 
 pub struct Vrc7 {
     pub prg_rom: MemoryBlock,
@@ -98,7 +99,7 @@ impl Mapper for Vrc7 {
         println!("====================");
     }
 
-    fn clock_cpu(&mut self) {
+    fn on_cpu_cycle(&mut self) {
         if self.irq_enable {
             if self.irq_scanline_mode {
                 self._clock_irq_prescaler();
@@ -129,7 +130,7 @@ impl Mapper for Vrc7 {
         return mixed_vrc7_audio + nes_sample;
     }
 
-    fn irq_flag(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         return self.irq_pending;
     }
 
@@ -262,6 +263,10 @@ impl Mapper for Vrc7 {
         return true;
     }
 
+    fn has_persistent_ram(&self) -> bool {
+        return !self.prg_ram.is_volatile();
+    }
+
     fn get_sram(&self) -> Vec<u8> {
         return self.prg_ram.as_vec().clone();
     }
@@ -295,6 +300,43 @@ impl Mapper for Vrc7 {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.audio.record_output();
     }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_vec(buff, &self.vram);
+        save_vec(buff, &self.chr_banks);
+        save_vec(buff, &self.prg_banks);
+        save_u16(buff, self.irq_scanline_prescaler as u16);
+        save_u8(buff, self.irq_latch);
+        save_bool(buff, self.irq_scanline_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_enable_after_acknowledgement);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.irq_counter);
+        save_u8(buff, self.audio_register);
+        // Note: the OPLL's operator and envelope state is not captured. Voices
+        // pick up again the next time the game writes to their registers.
+    }
+
+    fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.mirroring = buff.load_mirroring()?;
+        buff.load_into(&mut self.vram)?;
+        buff.load_into(&mut self.chr_banks)?;
+        buff.load_into(&mut self.prg_banks)?;
+        self.irq_scanline_prescaler = buff.load_u16()? as i16;
+        self.irq_latch = buff.load_u8()?;
+        self.irq_scanline_mode = buff.load_bool()?;
+        self.irq_enable = buff.load_bool()?;
+        self.irq_enable_after_acknowledgement = buff.load_bool()?;
+        self.irq_pending = buff.load_bool()?;
+        self.irq_counter = buff.load_u8()?;
+        self.audio_register = buff.load_u8()?;
+        return Ok(());
+    }
 }
 
 // TODO: explore and see if we can't somehow make these constant while keeping them
@@ -556,7 +598,7 @@ impl Vrc7AudioChannel {
             output_buffer: RingBuffer::new(32768),
             edge_buffer: RingBuffer::new(32768),
             last_edge: false,
-            debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
+            debug_filter: filters::HighPassIIR::new(44100.0, 48.0),
             debug_disable: false,
 
             am_pos: 0,
@@ -0,0 +1,71 @@
+// Input movies: a savestate to start from, plus the state of both controllers for
+// every frame afterwards. While a movie is recording or playing, controller reads
+// see the input latched at the start of each frame rather than the live value, so
+// input that changes mid-frame can't make playback diverge.
+
+use save_load::*;
+
+pub const MOVIE_MAGIC: [u8; 4] = *b"RNMV";
+pub const MOVIE_VERSION: u8 = 1;
+
+#[derive(Clone, PartialEq)]
+pub enum MovieMode {
+    Idle,
+    Recording,
+    // Index of the next frame of input to apply
+    Playing(usize),
+}
+
+#[derive(Clone)]
+pub struct Movie {
+    pub initial_state: Vec<u8>,
+    pub frames: Vec<[u8; 2]>,
+}
+
+impl Movie {
+    pub fn new(initial_state: Vec<u8>) -> Movie {
+        return Movie {
+            initial_state: initial_state,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buff: Vec<u8> = Vec::new();
+        buff.extend_from_slice(&MOVIE_MAGIC);
+        save_u8(&mut buff, MOVIE_VERSION);
+        save_vec(&mut buff, &self.initial_state);
+        save_usize(&mut buff, self.frames.len());
+        for frame in self.frames.iter() {
+            save_u8(&mut buff, frame[0]);
+            save_u8(&mut buff, frame[1]);
+        }
+        return buff;
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, StateError> {
+        let mut buff = StateReader::new(data);
+        for expected in MOVIE_MAGIC.iter() {
+            if buff.load_u8()? != *expected {
+                return Err(StateError::InvalidValue("movie header"));
+            }
+        }
+        let version = buff.load_u8()?;
+        if version != MOVIE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let mut movie = Movie::new(buff.load_vec()?);
+        let frame_count = buff.load_usize()?;
+        let input_length = match frame_count.checked_mul(2) {
+            Some(length) => length,
+            None => {return Err(StateError::InvalidValue("movie frame count"));}
+        };
+        if input_length != buff.remaining() {
+            return Err(StateError::SizeMismatch{expected: input_length, found: buff.remaining()});
+        }
+        for _ in 0 .. frame_count {
+            movie.frames.push([buff.load_u8()?, buff.load_u8()?]);
+        }
+        return Ok(movie);
+    }
+}
@@ -1,13 +1,43 @@
 use apu::ApuState;
+use apu::ApuSnapshot;
+use asm;
+use breakpoints::{BreakpointKind, Breakpoints};
 use cartridge;
 use cycle_cpu;
 use cycle_cpu::CpuState;
 use cycle_cpu::Registers;
+use cycle_cpu::{CpuFlag, CpuRegisters};
+use game_genie::{CheatError, GameGenieCode};
 use memory;
 use memory::CpuMemory;
+use memory::RamInit;
+use palettes::Palette;
 use ppu::PpuState;
+use region::Region;
+use save_load::*;
 use mmc::mapper::Mapper;
+use movie::{Movie, MovieMode};
 use tracked_events::EventTracker;
+use watchpoints::{WatchEntry, Watchpoints};
+use zapper::ZapperState;
+
+use std::collections::VecDeque;
+use std::io::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+// Upper bound for step_over and step_out, roughly 30 frames, so a subroutine that never
+// returns can't hang the debugger
+const STEP_INSTRUCTION_LIMIT: u32 = 500_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    Completed,
+    // The deadline passed first; holds the number of frames that did finish
+    TimedOut(u32),
+    // A breakpoint stopped emulation partway through; holds the frames finished before it
+    BreakpointHit(u32),
+}
 
 pub struct NesState {
     pub apu: ApuState,
@@ -16,14 +46,36 @@ pub struct NesState {
     pub ppu: PpuState,
     pub registers: Registers,
     pub master_clock: u64,
+    // Master clocks the PPU is behind the CPU; only ever nonzero on PAL, where dots don't divide evenly
+    pub ppu_clock_debt: u8,
+    // Master clocks into the current CPU cycle. Always 0 unless step_master_cycle stopped partway.
+    pub master_cycle_phase: u8,
+    // Sprite zero hit as of the start of the current CPU cycle, for the event tracker
+    pub sprite_zero_hit_before: bool,
+    pub region: Region,
     pub p1_input: u8,
     pub p1_data: u8,
     pub p2_input: u8,
     pub p2_data: u8,
     pub input_latch: bool,
+    pub p1_zapper: ZapperState,
+    pub p2_zapper: ZapperState,
     pub mapper: Box<dyn Mapper>,
     pub last_frame: u32,
     pub event_tracker: EventTracker,
+    pub rgba_framebuffer: Vec<u8>,
+    pub cheats: Vec<GameGenieCode>,
+    pub movie: Option<Movie>,
+    pub movie_mode: MovieMode,
+    pub movie_input: [u8; 2],
+    pub trace_sink: Option<Box<dyn Write + Send>>,
+    pub breakpoints: Breakpoints,
+    pub palette: Palette,
+    // How power_cycle fills internal RAM
+    pub ram_init: RamInit,
+    // Code/data log, one byte of memory::CDL_* flags per byte of PRG ROM. None when disabled.
+    pub cdl: Option<Vec<u8>>,
+    pub watchpoints: Watchpoints,
 }
 
 impl NesState {
@@ -35,17 +87,43 @@ impl NesState {
             ppu: PpuState::new(),
             registers: Registers::new(),
             master_clock: 0,
+            ppu_clock_debt: 0,
+            master_cycle_phase: 0,
+            sprite_zero_hit_before: false,
+            region: Region::Ntsc,
             p1_input: 0,
             p1_data: 0,
             p2_input: 0,
             p2_data: 0,
             input_latch: false,
+            p1_zapper: ZapperState::new(),
+            p2_zapper: ZapperState::new(),
             mapper: m,
             last_frame: 0,
             event_tracker: EventTracker::new(),
+            rgba_framebuffer: vec!(0u8; 256 * 240 * 4),
+            cheats: Vec::new(),
+            movie: None,
+            movie_mode: MovieMode::Idle,
+            movie_input: [0, 0],
+            trace_sink: None,
+            breakpoints: Breakpoints::new(),
+            palette: Palette::ntsc(),
+            ram_init: RamInit::AllZero,
+            cdl: None,
+            watchpoints: Watchpoints::new(),
         }
     }
 
+    /// Like `new`, but with internal RAM filled according to `init` rather than zeroed,
+    /// for reproducing a particular power-on state.
+    pub fn new_with_ram_init(m: Box<dyn Mapper>, init: RamInit) -> NesState {
+        let mut nes = NesState::new(m);
+        nes.ram_init = init;
+        init.fill(&mut nes.memory.iram_raw);
+        return nes;
+    }
+
     #[deprecated(since="0.2.0", note="please use `::new(mapper)` instead")]
     pub fn from_rom(cart_data: &[u8]) -> Result<NesState, String> {
         let maybe_mapper = cartridge::mapper_from_file(cart_data);
@@ -56,7 +134,7 @@ impl NesState {
                 return Ok(nes);
             },
             Err(why) => {
-                return Err(why);
+                return Err(why.to_string());
             }
         }
     }
@@ -87,6 +165,89 @@ impl NesState {
         }
     }
 
+    /// Selects console timing. Best done before power_on, as a switch mid-frame leaves the
+    /// PPU and APU partway through sequences of the old length.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.region = region;
+        self.apu.set_region(region);
+        self.ppu_clock_debt = 0;
+        self.master_cycle_phase = 0;
+    }
+
+    /// Starts or stops the code/data logger. Enabling it starts a fresh log; only mappers that
+    /// report their PRG ROM layout are covered, and the log is empty for the rest.
+    pub fn enable_cdl(&mut self, enabled: bool) {
+        if enabled {
+            self.cdl = Some(vec![0u8; self.mapper.prg_rom_size()]);
+        } else {
+            self.cdl = None;
+        }
+    }
+
+    pub fn cdl_buffer(&self) -> &[u8] {
+        return match self.cdl {
+            Some(ref cdl) => cdl,
+            None => &[],
+        };
+    }
+
+    /// A copy of each 2A03 channel's current pitch, volume and playing state, for visualizers
+    /// that don't want to hold on to the whole console.
+    pub fn apu_snapshot(&self) -> ApuSnapshot {
+        return self.apu.snapshot();
+    }
+
+    /// Logs every CPU write to `address` from now on; see watch_log.
+    pub fn add_watch(&mut self, address: u16) {
+        self.watchpoints.add(address);
+    }
+
+    pub fn remove_watch(&mut self, address: u16) {
+        self.watchpoints.remove(address);
+    }
+
+    /// Writes to watched addresses, oldest first. Only the most recent `watchpoints.log_length`
+    /// writes are kept.
+    pub fn watch_log(&self) -> &VecDeque<WatchEntry> {
+        return &self.watchpoints.log;
+    }
+
+    /// Turns the console off and on again: internal RAM is refilled per `ram_init`, and the CPU,
+    /// PPU and APU start over from scratch. The cartridge is left alone, so battery backed RAM
+    /// survives, as do bank registers (real boards power up with those in an arbitrary state).
+    pub fn power_cycle(&mut self) {
+        self.ram_init.fill(&mut self.memory.iram_raw);
+        self.memory.last_bus_value = 0;
+        self.cpu = CpuState::new();
+        self.registers = Registers::new();
+
+        let disable_sprite_limit = self.ppu.disable_sprite_limit;
+        let palette_backdrop_quirk = self.ppu.palette_backdrop_quirk;
+        self.ppu = PpuState::new();
+        self.ppu.disable_sprite_limit = disable_sprite_limit;
+        self.ppu.palette_backdrop_quirk = palette_backdrop_quirk;
+
+        let sample_rate = self.apu.sample_rate;
+        let filter_type = self.apu.filter_type;
+        let filter_hq = self.apu.filter_hq;
+        let channel_gains = self.apu.channel_gains;
+        let sample_queue_length = self.apu.sample_queue.capacity();
+        let buffer_ms = self.apu.buffer_ms;
+        self.apu = ApuState::new();
+        self.apu.buffer_ms = buffer_ms;
+        self.apu.set_filter(filter_type, filter_hq);
+        self.apu.set_output_sample_rate(sample_rate as u32);
+        self.apu.channel_gains = channel_gains;
+        self.apu.set_sample_queue_length(sample_queue_length);
+
+        self.master_clock = 0;
+        self.set_region(self.region);
+        self.power_on();
+    }
+
+    /// Presses the reset button. Only the CPU notices: it jumps through the reset vector with
+    /// RAM, the PPU and the cartridge all left as they were.
     pub fn reset(&mut self) {
         self.registers.s = self.registers.s.wrapping_sub(3);
         self.registers.flags.interrupts_disabled = true;
@@ -99,20 +260,77 @@ impl NesState {
         self.registers.pc = pc_low as u16 + ((pc_high as u16) << 8);
     }
 
+    /// Runs one CPU cycle, along with the PPU dots and APU work that go with it. If
+    /// step_master_cycle left the console partway through a CPU cycle, this just finishes it.
     pub fn cycle(&mut self) {
+        if self.master_cycle_phase != 0 {
+            while self.master_cycle_phase != 0 {
+                self.step_master_cycle();
+            }
+            return;
+        }
+        // Same sequence as calling step_master_cycle once per master clock, done in one go
+        let cpu_cycle_length = self.region.master_clocks_per_cpu_cycle();
+        let ppu_dot_length = self.region.master_clocks_per_ppu_dot();
+        self.begin_cpu_cycle();
+        self.master_clock = self.master_clock + cpu_cycle_length as u64;
+        // Three PPU clocks per every 1 CPU clock (3.2 on PAL, so some cycles get a fourth)
+        self.ppu_clock_debt += cpu_cycle_length;
+        while self.ppu_clock_debt >= ppu_dot_length {
+            self.ppu.clock(&mut *self.mapper);
+            self.ppu_clock_debt -= ppu_dot_length;
+        }
+        self.end_cpu_cycle();
+    }
+
+    /// Advances by a single master clock. The CPU does its work on the first master clock of
+    /// each of its cycles, the PPU clocks a dot every time master_clocks_per_ppu_dot have gone
+    /// by, and the APU and mapper clock on the last master clock of the CPU cycle. On NTSC that
+    /// is one CPU cycle followed by exactly three dots, which is what cycle() does all at once.
+    pub fn step_master_cycle(&mut self) {
+        let cpu_cycle_length = self.region.master_clocks_per_cpu_cycle();
+        let ppu_dot_length = self.region.master_clocks_per_ppu_dot();
+        if self.master_cycle_phase == 0 {
+            self.begin_cpu_cycle();
+        }
+        self.master_clock = self.master_clock + 1;
+        self.ppu_clock_debt += 1;
+        if self.ppu_clock_debt >= ppu_dot_length {
+            self.ppu.clock(&mut *self.mapper);
+            self.ppu_clock_debt -= ppu_dot_length;
+        }
+        self.master_cycle_phase += 1;
+        if self.master_cycle_phase >= cpu_cycle_length {
+            self.master_cycle_phase = 0;
+            self.end_cpu_cycle();
+        }
+    }
+
+    fn begin_cpu_cycle(&mut self) {
         cycle_cpu::run_one_clock(self);
-        self.master_clock = self.master_clock + 12;
-        // Three PPU clocks per every 1 CPU clock
-        self.ppu.clock(&mut *self.mapper);
-        self.ppu.clock(&mut *self.mapper);
-        self.ppu.clock(&mut *self.mapper);
+        self.sprite_zero_hit_before = (self.ppu.status & 0x40) != 0;
+    }
+
+    fn end_cpu_cycle(&mut self) {
+        if self.ppu.current_frame != self.event_tracker.current_frame {
+            // Swap on the exact cycle the frame ends, rather than after the instruction that
+            // straddles it, so events early in scanline 0 can't land at the top of the old frame
+            self.event_tracker.current_frame = self.ppu.current_frame;
+            self.event_tracker.swap_buffers();
+        }
         self.event_tracker.current_scanline = self.ppu.current_scanline;
         self.event_tracker.current_cycle = self.ppu.current_scanline_cycle;
+        if !self.sprite_zero_hit_before && (self.ppu.status & 0x40) != 0 {
+            self.event_tracker.snoop_sprite_zero_hit();
+        }
         self.apu.clock_apu(&mut *self.mapper);
-        self.mapper.clock_cpu();
+        self.mapper.on_cpu_cycle();
     }
 
     pub fn step(&mut self) {
+        // Resuming acknowledges any breakpoint we stopped on. Otherwise a hit nobody took would
+        // make every run loop after it return straight away.
+        self.breakpoints.hit = None;
         // Always run at least one cycle
         self.cycle();
         let mut i = 0;
@@ -123,24 +341,259 @@ impl NesState {
             i += 1;
         }
         if self.ppu.current_frame != self.last_frame {
-            self.event_tracker.swap_buffers();
+            self.p1_zapper.new_frame();
+            self.p2_zapper.new_frame();
+            self.advance_movie();
             self.last_frame = self.ppu.current_frame;
         }
+        // Checked once the previous instruction is done, so a hit leaves PC pointing at the
+        // instruction that hasn't run yet
+        if !self.breakpoints.is_empty() {
+            self.breakpoints.check(BreakpointKind::Execute, self.registers.pc);
+        }
+    }
+
+    pub fn cpu_registers(&self) -> CpuRegisters {
+        return CpuRegisters {
+            a: self.registers.a,
+            x: self.registers.x,
+            y: self.registers.y,
+            p: self.registers.status_as_byte(false),
+            s: self.registers.s,
+            pc: self.registers.pc,
+        };
+    }
+
+    /// Overwrites the programmer-visible registers. Meant for use between instructions, as
+    /// after step(); a new PC then acts as "set next instruction."
+    pub fn set_cpu_registers(&mut self, registers: CpuRegisters) {
+        self.registers.a = registers.a;
+        self.registers.x = registers.x;
+        self.registers.y = registers.y;
+        self.registers.set_status_from_byte(registers.p);
+        self.registers.s = registers.s;
+        self.registers.pc = registers.pc;
+    }
+
+    pub fn cpu_flag(&self, flag: CpuFlag) -> bool {
+        return self.registers.flags.get(flag);
     }
 
+    pub fn set_cpu_flag(&mut self, flag: CpuFlag, value: bool) {
+        self.registers.flags.set(flag, value);
+    }
+
+    /// Steps one instruction, except that a JSR runs the whole subroutine and stops on the
+    /// instruction after it returns. Stops early if a breakpoint is hit, or after
+    /// STEP_INSTRUCTION_LIMIT instructions if the subroutine never comes back.
+    pub fn step_over(&mut self) {
+        let opcode = memory::debug_read_byte(self, self.registers.pc);
+        if opcode != 0x20 {
+            self.step();
+            return;
+        }
+        let return_address = self.registers.pc.wrapping_add(3);
+        let starting_depth = self.registers.s;
+        self.step();
+        for _i in 0 .. STEP_INSTRUCTION_LIMIT {
+            // Checking the stack pointer too means a recursive call that happens to pass through
+            // the return address doesn't count as coming back
+            if self.registers.pc == return_address && self.registers.s == starting_depth {
+                return;
+            }
+            if self.breakpoints.hit.is_some() {
+                return;
+            }
+            self.step();
+        }
+    }
+
+    /// Runs until the current subroutine returns, stopping on the instruction after the JSR
+    /// that called it. Interrupts taken along the way are run to completion. Stops early if a
+    /// breakpoint is hit, or after STEP_INSTRUCTION_LIMIT instructions.
+    pub fn step_out(&mut self) {
+        let starting_depth = self.registers.s;
+        for _i in 0 .. STEP_INSTRUCTION_LIMIT {
+            let opcode = memory::debug_read_byte(self, self.registers.pc);
+            self.step();
+            // An RTS that leaves the stack above where we started has popped our caller's
+            // return address. RTS from deeper calls (including any made by an interrupt
+            // handler) only unwinds back down to our depth or below.
+            if opcode == 0x60 && self.registers.s > starting_depth {
+                return;
+            }
+            if self.breakpoints.hit.is_some() {
+                return;
+            }
+        }
+    }
+
+    // Both of these return early if a breakpoint is hit, leaving it in breakpoints.hit until
+    // the next step.
     pub fn run_until_hblank(&mut self) {
         let old_scanline = self.ppu.current_scanline;
         while old_scanline == self.ppu.current_scanline {
             self.step();
+            if self.breakpoints.hit.is_some() {
+                return;
+            }
         }
     }
 
     pub fn run_until_vblank(&mut self) {
         while self.ppu.current_scanline == 242 {
             self.step();
+            if self.breakpoints.hit.is_some() {
+                return;
+            }
         }
         while self.ppu.current_scanline != 242 {
             self.step();
+            if self.breakpoints.hit.is_some() {
+                return;
+            }
+        }
+    }
+
+    /// Runs until the PPU finishes drawing the next frame, leaving the result in framebuffer().
+    /// Meant for headless use; interactive shells should keep using run_until_vblank and read
+    /// ppu.screen directly.
+    pub fn step_frame(&mut self) {
+        self.run_until_vblank();
+        for i in 0 .. (256 * 240) {
+            let rgb = self.palette.rgb(self.ppu.screen[i]);
+            self.rgba_framebuffer[i * 4 + 0] = rgb[0];
+            self.rgba_framebuffer[i * 4 + 1] = rgb[1];
+            self.rgba_framebuffer[i * 4 + 2] = rgb[2];
+            self.rgba_framebuffer[i * 4 + 3] = 255;
+        }
+    }
+
+    /// Runs up to `frames` frames, giving up once `deadline` of wall-clock time has passed. The
+    /// clock is only checked between frames, so a single frame can overshoot it slightly; that
+    /// is enough to keep a ROM stuck in a loop from hanging a batch run. Not available on wasm,
+    /// where Instant has no clock to read.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_frames_bounded(&mut self, frames: u32, deadline: Duration) -> RunOutcome {
+        let start = Instant::now();
+        for frame in 0 .. frames {
+            if start.elapsed() >= deadline {
+                return RunOutcome::TimedOut(frame);
+            }
+            self.run_until_vblank();
+            if self.breakpoints.hit.is_some() {
+                return RunOutcome::BreakpointHit(frame);
+            }
+        }
+        return RunOutcome::Completed;
+    }
+
+    /// RGBA pixels, 256x240, as of the most recent call to step_frame.
+    pub fn framebuffer(&self) -> &[u8] {
+        return &self.rgba_framebuffer;
+    }
+
+    /// Logs every instruction to `sink` just before it executes, in roughly the format of
+    /// Nintendulator's trace logger (and so nestest.log).
+    pub fn set_trace_sink(&mut self, sink: Box<dyn Write + Send>) {
+        self.trace_sink = Some(sink);
+    }
+
+    pub fn clear_trace_sink(&mut self) {
+        self.trace_sink = None;
+    }
+
+    /// The instruction at PC and the current register state, formatted as one trace line.
+    pub fn trace_line(&self) -> String {
+        let pc = self.registers.pc;
+        let bytes: Vec<u8> = (0 .. 3).map(|offset| memory::debug_read_byte(self, pc.wrapping_add(offset))).collect();
+        let instruction = &asm::disassemble(&bytes, pc)[0];
+        let hex_bytes = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+        return format!("{:04X}  {:<8}  {:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+            pc, hex_bytes, instruction.text(),
+            self.registers.a, self.registers.x, self.registers.y,
+            self.registers.status_as_byte(false), self.registers.s,
+            self.master_clock / self.region.master_clocks_per_cpu_cycle() as u64);
+    }
+
+    pub fn trace_instruction(&mut self) {
+        let line = self.trace_line();
+        match self.trace_sink.as_mut() {
+            Some(sink) => {let _ = writeln!(sink, "{}", line);},
+            None => {}
+        }
+    }
+
+    pub fn add_cheat(&mut self, code: &str) -> Result<(), CheatError> {
+        let cheat = GameGenieCode::decode(code)?;
+        if !self.cheats.contains(&cheat) {
+            self.cheats.push(cheat);
+        }
+        return Ok(());
+    }
+
+    pub fn remove_cheat(&mut self, code: &str) -> Result<(), CheatError> {
+        let cheat = GameGenieCode::decode(code)?;
+        let count_before = self.cheats.len();
+        self.cheats.retain(|active| active.code != cheat.code);
+        if self.cheats.len() == count_before {
+            return Err(CheatError::NotFound(cheat.code));
+        }
+        return Ok(());
+    }
+
+    /// The controller state games actually see. Movies latch input once per frame, so that
+    /// playback matches recording exactly.
+    pub fn controller_input(&self, player_index: usize) -> u8 {
+        if self.movie_mode != MovieMode::Idle {
+            return self.movie_input[player_index];
+        }
+        return if player_index == 0 {self.p1_input} else {self.p2_input};
+    }
+
+    /// Begins recording a movie from the current state. Call reset or power_on first to
+    /// record from a clean boot.
+    pub fn start_recording(&mut self) {
+        self.movie = Some(Movie::new(self.save_state()));
+        self.movie_mode = MovieMode::Recording;
+        self.movie_input = [self.p1_input, self.p2_input];
+    }
+
+    /// Ends recording or playback, returning the movie. Only complete frames are kept.
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.movie_mode = MovieMode::Idle;
+        return self.movie.take();
+    }
+
+    pub fn play(&mut self, movie: Movie) -> Result<(), StateError> {
+        self.load_state(&movie.initial_state)?;
+        self.movie_input = movie.frames.first().cloned().unwrap_or([0, 0]);
+        self.movie_mode = MovieMode::Playing(1);
+        self.movie = Some(movie);
+        return Ok(());
+    }
+
+    fn advance_movie(&mut self) {
+        match self.movie_mode {
+            MovieMode::Idle => {},
+            MovieMode::Recording => {
+                // Store the input the finished frame ran with, then latch input for the next one
+                match self.movie.as_mut() {
+                    Some(movie) => {movie.frames.push(self.movie_input);},
+                    None => {}
+                }
+                self.movie_input = [self.p1_input, self.p2_input];
+            },
+            MovieMode::Playing(next_frame) => {
+                let frame_count = self.movie.as_ref().map(|movie| movie.frames.len()).unwrap_or(0);
+                if next_frame < frame_count {
+                    self.movie_input = self.movie.as_ref().unwrap().frames[next_frame];
+                    self.movie_mode = MovieMode::Playing(next_frame + 1);
+                } else {
+                    // The movie is over; hand control back to the live controllers
+                    self.movie_mode = MovieMode::Idle;
+                }
+            }
         }
     }
 
@@ -157,9 +610,69 @@ impl NesState {
 
     pub fn set_sram(&mut self, sram_data: Vec<u8>) {
         if sram_data.len() != self.mapper.get_sram().len() {
-            println!("SRAM size mismatch, expected {} bytes but file is {} bytes!", self.mapper.get_sram().len(), sram_data.len());
+            error!("SRAM size mismatch, expected {} bytes but file is {} bytes!", self.mapper.get_sram().len(), sram_data.len());
         } else {
             self.mapper.load_sram(sram_data);
         }
     }
+
+    /// Serializes the entire emulator state, including mapper registers and cartridge RAM.
+    /// The result can only be loaded back into an NesState running the same cartridge.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut buff = Vec::new();
+        save_header(&mut buff);
+        self.registers.save_state(&mut buff);
+        self.cpu.save_state(&mut buff);
+        self.memory.save_state(&mut buff);
+        self.ppu.save_state(&mut buff);
+        self.apu.save_state(&mut buff);
+        self.mapper.save_state(&mut buff);
+        save_u64(&mut buff, self.master_clock);
+        save_u8(&mut buff, self.ppu_clock_debt);
+        save_u8(&mut buff, self.master_cycle_phase);
+        save_bool(&mut buff, self.sprite_zero_hit_before);
+        save_u8(&mut buff, self.p1_input);
+        save_u8(&mut buff, self.p1_data);
+        save_u8(&mut buff, self.p2_input);
+        save_u8(&mut buff, self.p2_data);
+        save_bool(&mut buff, self.input_latch);
+        save_u32(&mut buff, self.last_frame);
+        return buff;
+    }
+
+    /// Restores state produced by save_state. If the data is rejected partway through,
+    /// the emulator is returned to the state it was in before the call.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let backup = self.save_state();
+        let result = self._load_state(data);
+        if result.is_err() {
+            self._load_state(&backup).expect("Failed to restore state after a rejected load!");
+        }
+        return result;
+    }
+
+    fn _load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut buff = StateReader::new(data);
+        load_header(&mut buff)?;
+        self.registers.load_state(&mut buff)?;
+        self.cpu.load_state(&mut buff)?;
+        self.memory.load_state(&mut buff)?;
+        self.ppu.load_state(&mut buff)?;
+        self.apu.load_state(&mut buff)?;
+        self.mapper.load_state(&mut buff)?;
+        self.master_clock = buff.load_u64()?;
+        self.ppu_clock_debt = buff.load_u8()?;
+        self.master_cycle_phase = buff.load_u8()?;
+        self.sprite_zero_hit_before = buff.load_bool()?;
+        self.p1_input = buff.load_u8()?;
+        self.p1_data = buff.load_u8()?;
+        self.p2_input = buff.load_u8()?;
+        self.p2_data = buff.load_u8()?;
+        self.input_latch = buff.load_bool()?;
+        self.last_frame = buff.load_u32()?;
+        if buff.remaining() > 0 {
+            return Err(StateError::TrailingData(buff.remaining()));
+        }
+        return Ok(());
+    }
 }
@@ -46,6 +46,7 @@ pub fn rmw_block(opcode: u8, addressing_mode_index: u8, opcode_index: u8) -> (&'
     0x96 => ("STX", "d, y"),
     0xB6 => ("LDX", "d, y"),
     0xBE => ("LDX", "a, y"),
+    0x9E => ("SHX", "a, y"),
     _ => {
       let addressing_mode = match addressing_mode_index {
         // Zero Page Mode
@@ -79,18 +80,25 @@ pub fn rmw_block(opcode: u8, addressing_mode_index: u8, opcode_index: u8) -> (&'
 pub fn control_block(opcode: u8) -> (&'static str, &'static str) {
   // Everything is pretty irregular, so we'll just match the whole opcode
   return match opcode {
-  	0x10 => ("BPL", ""),
-  	0x30 => ("BMI", ""),
-  	0x50 => ("BVC", ""),
-  	0x70 => ("BVS", ""),
-  	0x90 => ("BCC", ""),
-  	0xB0 => ("BCS", ""),
-  	0xD0 => ("BNE", ""),
-  	0xF0 => ("BEQ", ""),
+  	0x10 => ("BPL", "r"),
+  	0x30 => ("BMI", "r"),
+  	0x50 => ("BVC", "r"),
+  	0x70 => ("BVS", "r"),
+  	0x90 => ("BCC", "r"),
+  	0xB0 => ("BCS", "r"),
+  	0xD0 => ("BNE", "r"),
+  	0xF0 => ("BEQ", "r"),
 
     0x00 => ("BRK", ""),
     0x80 => ("NOP", "#i"),
 
+    // Unofficial NOPs and SHY
+    0x04 | 0x44 | 0x64 => ("NOP", "d"),
+    0x0C => ("NOP", "a"),
+    0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 => ("NOP", "d, x"),
+    0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC => ("NOP", "a, x"),
+    0x9C => ("SHY", "a, x"),
+
     // Opcodes with similar addressing modes
     0xA0 => ("LDY", "#i"),
     0xC0 => ("CPY", "#i"),
@@ -117,7 +125,7 @@ pub fn control_block(opcode: u8) -> (&'static str, &'static str) {
     0x48 => ("PHA", ""),
     0x68 => ("PLA", ""),
 
-    0x20 => ("JSR", ""),
+    0x20 => ("JSR", "a"),
     0x40 => ("RTI", ""),
     0x60 => ("RTS", ""),
 
@@ -139,26 +147,92 @@ pub fn control_block(opcode: u8) -> (&'static str, &'static str) {
   };
 }
 
-pub fn addressing_bytes(addressing_mode: &str) -> u8 {
-	return match addressing_mode {
-		"#i" | "d" | "(d, x)" | "(d), y" | "d, x"  => 1,
-		"a" | "a, x" | "a, y" | "(a)" => 2,
-		_ => 0
-	}
+pub fn unofficial_block(opcode: u8, addressing_mode_index: u8, opcode_index: u8) -> (&'static str, &'static str) {
+  // Mirrors cycle_cpu::unofficial_block: a handful of irregular opcodes, then a regular grid
+  return match opcode {
+    0x0B | 0x2B => ("ANC", "#i"),
+    0x4B => ("ALR", "#i"),
+    0x6B => ("ARR", "#i"),
+    0x8B => ("XAA", "#i"),
+    0x93 => ("AHX", "(d), y"),
+    0x9B => ("TAS", "a, y"),
+    0x97 => ("SAX", "d, y"),
+    0x9F => ("AHX", "a, y"),
+    0xB7 => ("LAX", "d, y"),
+    0xBB => ("LAS", "a, y"),
+    0xBF => ("LAX", "a, y"),
+    0xCB => ("AXS", "#i"),
+    0xEB => ("SBC", "#i"),
+    _ => {
+      let addressing_mode = match addressing_mode_index {
+        0b000 => "(d, x)",
+        0b001 => "d",
+        0b010 => "#i",
+        0b011 => "a",
+        0b100 => "(d), y",
+        0b101 => "d, x",
+        0b110 => "a, y",
+        0b111 => "a, x",
+        _ => "???",
+      };
+
+      let opcode_name = match opcode_index {
+        0b000 => "SLO",
+        0b001 => "RLA",
+        0b010 => "SRE",
+        0b011 => "RRA",
+        0b100 => "SAX",
+        0b101 => "LAX",
+        0b110 => "DCP",
+        0b111 => "ISC",
+        _ => "???"
+      };
+
+      return (opcode_name, addressing_mode);
+    }
+  };
 }
 
-pub fn disassemble_instruction(opcode: u8, _: u8, _: u8) -> (String, u8) {
+pub fn is_unofficial(opcode: u8) -> bool {
+  if opcode & 0b0000_0011 == 0b11 {
+    return true;
+  }
+  return match opcode {
+    // NOPs other than $EA, STP, and the unstable stores
+    0x04 | 0x44 | 0x64 | 0x0C | 0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 |
+    0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC | 0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 |
+    0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA |
+    0x02 | 0x22 | 0x42 | 0x62 | 0x12 | 0x32 | 0x52 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 |
+    0x9C | 0x9E => true,
+    _ => false
+  };
+}
+
+pub fn opcode_name_and_mode(opcode: u8) -> (&'static str, &'static str) {
   let logic_block = opcode & 0b0000_0011;
   let addressing_mode_index = (opcode & 0b0001_1100) >> 2;
   let opcode_index = (opcode & 0b1110_0000) >> 5;
 
-  let (opcode_name, addressing_mode) = match logic_block {
+  return match logic_block {
     0b00 => control_block(opcode),
+    // STA #i doesn't exist; that slot is another NOP
+    0b01 if opcode == 0x89 => ("NOP", "#i"),
     0b01 => alu_block(addressing_mode_index, opcode_index),
     0b10 => rmw_block(opcode, addressing_mode_index, opcode_index),
-    _ => ("???", "")
+    _ => unofficial_block(opcode, addressing_mode_index, opcode_index),
   };
+}
 
+pub fn addressing_bytes(addressing_mode: &str) -> u8 {
+	return match addressing_mode {
+		"#i" | "d" | "(d, x)" | "(d), y" | "d, x" | "d, y" | "r"  => 1,
+		"a" | "a, x" | "a, y" | "(a)" => 2,
+		_ => 0
+	}
+}
+
+pub fn disassemble_instruction(opcode: u8, _: u8, _: u8) -> (String, u8) {
+  let (opcode_name, addressing_mode) = opcode_name_and_mode(opcode);
   let instruction = format!("{} {}", opcode_name, addressing_mode);
   let data_bytes = addressing_bytes(addressing_mode);
   return (instruction, data_bytes);
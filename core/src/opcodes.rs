@@ -2,6 +2,8 @@ use addressing;
 use cycle_cpu::Registers;
 use nes::NesState;
 use memory::read_byte;
+use memory::log_cdl;
+use memory::CDL_OPERAND;
 use memory::write_byte;
 
 // Memory Utilities
@@ -310,6 +312,7 @@ pub fn branch(nes: &mut NesState) {
     2 => {
       let pc = nes.registers.pc;
       nes.cpu.data1 = read_byte(nes, pc);
+      log_cdl(nes, pc, CDL_OPERAND);
       nes.registers.pc = nes.registers.pc.wrapping_add(1);
 
       // Determine if branch is to be taken
@@ -387,13 +390,15 @@ pub fn service_interrupt(nes: &mut NesState) {
     5 => {
       // At this point, NMI always takes priority, otherwise we run
       // an IRQ
-      if nes.cpu.nmi_requested {
+      let nmi = nes.cpu.nmi_requested;
+      if nmi {
         nes.cpu.nmi_requested = false;
         nes.cpu.old_nmi_requested = false;
         nes.cpu.temp_address = 0xFFFA;
       } else {
         nes.cpu.temp_address = 0xFFFE;
       }
+      nes.event_tracker.snoop_interrupt(nmi, nes.registers.pc);
       let status_byte = nes.registers.status_as_byte(false);
       push(nes, status_byte);
       nes.cpu.upcoming_write = false;
@@ -1,4 +1,32 @@
 // Palette generated by http://bisqwit.iki.fi/utils/nespalette.php
+// There are 8 copies of the 64 color palette, one per combination of the PPUMASK emphasis
+// bits, so the table is indexed directly by a PpuState::screen pixel: eee pppppp.
+
+use std::error::Error;
+use std::fmt;
+
+// .pal files come in two sizes: just the 64 base colors, or all 8 emphasis variants of them
+pub const BASE_PAL_SIZE: usize = 64 * 3;
+pub const FULL_PAL_SIZE: usize = 64 * 8 * 3;
+
+// How much each emphasis bit attenuates the two color channels it doesn't emphasize, used
+// to fill in the emphasis variants for files that only provide the base colors
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+
+#[derive(Debug, PartialEq)]
+pub enum PaletteError {
+    InvalidLength(usize),
+}
+
+impl Error for PaletteError {}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::InvalidLength(length) => {write!(f, "Palette files are {} or {} bytes long, got {}", BASE_PAL_SIZE, FULL_PAL_SIZE, length)},
+        }
+    }
+}
 
 pub const NTSC_PAL: [u8; 64 * 8 * 3] = [
 0x52, 0x52, 0x52, 
@@ -513,3 +541,48 @@ pub const NTSC_PAL: [u8; 64 * 8 * 3] = [
 0x5e, 0x5e, 0x5e,
 0x00, 0x00, 0x00,
 0x00, 0x00, 0x00];
+
+#[derive(Clone)]
+pub struct Palette {
+    // RGB triplets in the same layout as NTSC_PAL
+    pub colors: Vec<u8>,
+}
+
+impl Palette {
+    pub fn ntsc() -> Palette {
+        return Palette {
+            colors: NTSC_PAL.to_vec(),
+        };
+    }
+
+    /// Converts a PpuState::screen pixel to RGB. Grayscale has already been applied by the PPU
+    /// when it read the palette entry; emphasis is part of the pixel and selects the table copy.
+    pub fn rgb(&self, pixel: u16) -> [u8; 3] {
+        let index = ((pixel & 0x1FF) as usize) * 3;
+        return [self.colors[index + 0], self.colors[index + 1], self.colors[index + 2]];
+    }
+}
+
+pub fn load_pal(bytes: &[u8]) -> Result<Palette, PaletteError> {
+    if bytes.len() == FULL_PAL_SIZE {
+        return Ok(Palette {colors: bytes.to_vec()});
+    }
+    if bytes.len() != BASE_PAL_SIZE {
+        return Err(PaletteError::InvalidLength(bytes.len()));
+    }
+    let mut colors = vec!(0u8; FULL_PAL_SIZE);
+    for emphasis in 0 .. 8 {
+        for color in 0 .. 64 {
+            for channel in 0 .. 3 {
+                // Emphasis bits are red, green, blue from low to high, matching the channel order
+                let other_bits = emphasis & !(1 << channel);
+                let mut value = bytes[color * 3 + channel] as f32;
+                if other_bits != 0 {
+                    value *= EMPHASIS_ATTENUATION;
+                }
+                colors[((emphasis << 6) | color) * 3 + channel] = value as u8;
+            }
+        }
+    }
+    return Ok(Palette {colors: colors});
+}
@@ -3,6 +3,9 @@
 // and prototype stages.
 
 use mmc::mapper::*;
+use palettes::NTSC_PAL;
+use region::Region;
+use save_load::*;
 
 #[derive(Copy, Clone)]
 pub struct SpriteLatch {
@@ -73,6 +76,42 @@ impl SpriteLatch {
                 ((self.bitmap_low  & 0b1000_0000) >> 7);
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.tile_index);
+        save_u8(buff, self.bitmap_high);
+        save_u8(buff, self.bitmap_low);
+        save_u8(buff, self.attributes);
+        save_u8(buff, self.x_counter);
+        save_u8(buff, self.y_pos);
+        save_bool(buff, self.active);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        self.tile_index = buff.load_u8()?;
+        self.bitmap_high = buff.load_u8()?;
+        self.bitmap_low = buff.load_u8()?;
+        self.attributes = buff.load_u8()?;
+        self.x_counter = buff.load_u8()?;
+        self.y_pos = buff.load_u8()?;
+        self.active = buff.load_bool()?;
+        return Ok(());
+    }
+}
+
+/// A decoded OAM entry, for debugging views.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteInfo {
+    pub x: u8,
+    // As stored in OAM; the sprite is drawn starting one scanline below this
+    pub y: u8,
+    pub tile_index: u8,
+    pub pattern_address: u16,
+    pub height: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
 }
 
 pub struct PpuState {
@@ -106,7 +145,6 @@ pub struct PpuState {
 
     pub overall_cycle: usize,
     pub frame_starting_cycle: usize,
-    pub scanline_ntsc_samples: [f32; 256*8],
 
     // Framebuffer
     pub screen: Vec<u16>,
@@ -117,6 +155,9 @@ pub struct PpuState {
     pub sprite_zero: Vec<bool>,
 
     pub write_toggle: bool,
+    // Set by a PPUSTATUS read on the dot just before vblank begins; the flag is then never set
+    // for this frame. Only lives for the remainder of a single CPU cycle, so it isn't saved.
+    pub suppress_vblank: bool,
 
     // Internal State
     pub current_vram_address: u16,
@@ -134,6 +175,17 @@ pub struct PpuState {
 
     pub sprite_zero_on_scanline: bool,
 
+    // Cosmetic: draw every sprite on a scanline instead of the first 8. Overflow is unaffected.
+    pub disable_sprite_limit: bool,
+
+    // During forced blank, show the palette entry the VRAM address points at instead of the
+    // backdrop, as real hardware does. Some demos draw with this; turning it off shows a
+    // plain backdrop.
+    pub palette_backdrop_quirk: bool,
+
+    // Frame length and vblank position; set along with the rest of the console via NesState::set_region
+    pub region: Region,
+
     // Debug Viewer
     pub recent_reads: Vec<u16>,
     pub recent_writes: Vec<u16>,
@@ -163,7 +215,8 @@ impl PpuState {
         return PpuState {
             internal_vram: vec!(0u8; 0x1000),  // 4k for four-screen mirroring, most games only use upper 2k
             oam: vec!(0u8; 0x100),
-            secondary_oam: vec!(SpriteLatch::new(); 8),
+            // Only the first 8 are used unless the sprite limit is disabled
+            secondary_oam: vec!(SpriteLatch::new(); 64),
             secondary_oam_index: 0,
             palette: debug_default_palette(),
             current_frame: 0,
@@ -173,7 +226,6 @@ impl PpuState {
             frame_starting_cycle: 0,
             screen: vec!(0u16; 256 * 240),
             filtered_screen: vec!(0u32; 2048 * 240),
-            scanline_ntsc_samples: [0f32; 256 * 8],
             sprite_color: vec!(0u8; 256),
             sprite_index: vec!(0u8; 256),
             sprite_bg_priority: vec!(false; 256),
@@ -189,6 +241,7 @@ impl PpuState {
             read_buffer: 0,
     
             write_toggle: false,
+            suppress_vblank: false,
     
             // Internal State
             current_vram_address: 0,
@@ -204,6 +257,9 @@ impl PpuState {
             palette_latch: 0,
             attribute_byte: 0,
             sprite_zero_on_scanline: false,
+            disable_sprite_limit: false,
+            palette_backdrop_quirk: true,
+            region: Region::Ntsc,
 
             // Debug
             recent_reads: Vec::new(),
@@ -299,44 +355,107 @@ impl PpuState {
     }
 
     fn initialize_secondary_oam(&mut self) {
-        for i in 0 .. 8 {
+        for i in 0 .. self.secondary_oam.len() {
             self.secondary_oam[i].tile_index = 0xFF;
             self.secondary_oam[i].active = false;
         }
         self.secondary_oam_index = 0;
     }
 
+    fn sprite_in_range(&self, y: u8) -> bool {
+        let sprite_size: u16 = if (self.control & 0x20) != 0 {16} else {8};
+        let scanline = self.current_scanline & 0xFF;
+        return scanline >= (y as u16) && scanline < (y as u16) + sprite_size;
+    }
+
+    // Copy this sprite's data into temporary secondary OAM for this scanline
+    fn copy_to_secondary_oam(&mut self, sprite: usize) {
+        let slot = self.secondary_oam_index;
+        self.secondary_oam[slot].y_pos =      self.oam[sprite * 4 + 0];
+        self.secondary_oam[slot].tile_index = self.oam[sprite * 4 + 1];
+        self.secondary_oam[slot].attributes = self.oam[sprite * 4 + 2];
+        self.secondary_oam[slot].x_counter  = self.oam[sprite * 4 + 3];
+        self.secondary_oam[slot].active = false;
+        self.secondary_oam_index += 1;
+    }
+
     fn evaluate_sprites(&mut self) {
-        let scanline = self.current_scanline as u8;
-        let mut sprite_size = 8;
-        if (self.control & 0x20) != 0 {
-            sprite_size = 16;
-        }
         self.sprite_zero_on_scanline = false;
 
         self.initialize_secondary_oam();
 
-        // Gather first 8 visible sprites (and pay attention if there are more)
-        for i in 0 .. 64 {
-            let y = self.oam[i * 4 + 0];
-            if scanline >= y && scanline < y + sprite_size {
-                if self.secondary_oam_index < 8 {
-                    // Copy this sprite's data into temporary secondary OAM for this scanline
-                    self.secondary_oam[self.secondary_oam_index].y_pos =      self.oam[i * 4 + 0];
-                    self.secondary_oam[self.secondary_oam_index].tile_index = self.oam[i * 4 + 1];
-                    self.secondary_oam[self.secondary_oam_index].attributes = self.oam[i * 4 + 2];
-                    self.secondary_oam[self.secondary_oam_index].x_counter  = self.oam[i * 4 + 3];
-                    self.secondary_oam[self.secondary_oam_index].active = false;
-
-                    self.secondary_oam_index += 1;
-                    if i == 0 {
-                        self.sprite_zero_on_scanline = true;
-                    }
-                } else {
-                    self.status = self.status | 0x20; // bit 5 = sprite overflow this frame
+        // Gather first 8 visible sprites
+        let mut n = 0;
+        let mut sprites_found = 0;
+        while n < 64 {
+            let y = self.oam[n * 4 + 0];
+            if self.sprite_in_range(y) {
+                self.copy_to_secondary_oam(n);
+                if n == 0 {
+                    self.sprite_zero_on_scanline = true;
                 }
+                sprites_found += 1;
+            }
+            n += 1;
+            if sprites_found == 8 {
+                break;
+            }
+        }
+
+        // Pick up any stragglers for the no-limit option. Overflow below still only looks at OAM.
+        if self.disable_sprite_limit {
+            for i in n .. 64 {
+                let y = self.oam[i * 4 + 0];
+                if self.sprite_in_range(y) {
+                    self.copy_to_secondary_oam(i);
+                }
+            }
+        }
+
+        if sprites_found < 8 {
+            return;
+        }
+
+        // Once secondary OAM is full, the hardware keeps scanning for a 9th sprite, but it
+        // increments the byte offset (m) alongside the sprite index (n) whenever a check fails.
+        // This makes it treat tile, attribute and X bytes as Y coordinates, walking diagonally
+        // through OAM, so the flag can both miss real overflows and report false ones.
+        let mut m = 0;
+        while n < 64 {
+            let y = self.oam[n * 4 + m];
+            if self.sprite_in_range(y) {
+                self.status = self.status | 0x20; // bit 5 = sprite overflow this frame
+                return;
+            }
+            n += 1;
+            m = (m + 1) & 0x3;
+        }
+    }
+
+    // Side effects of a CPU read from PPUSTATUS: vblank is cleared, the $2005/$2006 write toggle
+    // resets, and the status bits land on the open bus latch for the caller to return.
+    // Reading right as vblank begins races the flag being set. One dot early, the read sees the
+    // flag clear and the flag then stays clear for the whole frame. On the dot it's set or the
+    // one after, the read sees it set, but clears it too soon for an NMI to go out. Returns true
+    // if any NMI this frame should be cancelled.
+    // Details here: https://www.nesdev.org/wiki/PPU_frame_timing#VBL_Flag_Timing
+    pub fn read_status(&mut self) -> bool {
+        let mut cancel_nmi = false;
+        if self.current_scanline == self.region.vblank_scanline() {
+            // current_scanline_cycle is the next dot to be clocked, not the one just finished
+            match self.current_scanline_cycle {
+                1 => {
+                    self.suppress_vblank = true;
+                    cancel_nmi = true;
+                },
+                2 | 3 => {cancel_nmi = true;},
+                _ => {}
             }
         }
+        self.write_toggle = false;
+        self.latch = (self.status & 0xE0) + (self.latch & 0x1F);
+        self.status = self.status & 0x7F; // Clear VBlank bit
+        return cancel_nmi;
     }
 
     pub fn rendering_enabled(&self) -> bool {
@@ -390,7 +509,9 @@ impl PpuState {
         let px = self.current_scanline_cycle - 1;
         let py = self.current_scanline;
 
-        // If backgrounds are disabled, ignore all that work above, and switch to color 0
+        // If backgrounds are disabled, ignore all that work above, and switch to color 0. The same
+        // goes for the leftmost 8 pixels when PPUMASK bit 1 clips them. Either way the pixel counts
+        // as transparent, which is also what keeps sprite zero from hitting there.
         if self.mask & 0b0000_1000 == 0 || ((self.mask & 0b0000_0010 == 0) && px < 8) {
             bg_palette_index = 0;
         }
@@ -402,12 +523,13 @@ impl PpuState {
 
         let mut pixel_color = self.read_byte(mapper, (((bg_palette_number as u16) << 2) + bg_palette_index) as u16 + 0x3F00);
 
-        // If sprites are enabled
+        // If sprites are enabled (and not clipped from the leftmost 8 pixels by PPUMASK bit 2)
         if self.mask & 0b0001_0000 != 0 && ((self.mask & 0b0000_0100 != 0) || px >= 8) {
             // Find the lowest active sprite with an opaque pixel
             for sprite_index in 0 .. self.secondary_oam_index {
                 if self.secondary_oam[sprite_index].active && self.secondary_oam[sprite_index].palette_index() != 0 {
-                    if self.sprite_zero_on_scanline && sprite_index == 0 && bg_palette_index != 0 {
+                    // The hit can never happen on the last pixel of the line, x=255
+                    if self.sprite_zero_on_scanline && sprite_index == 0 && bg_palette_index != 0 && px != 255 {
                         // Sprite zero hit!
                         self.status = self.status | 0x40;
                     }
@@ -535,45 +657,62 @@ impl PpuState {
         }
         if sub_cycle == 4 || sub_cycle == 6 {
             let sprite_index: usize = ((self.current_scanline_cycle - 257) / 8) as usize;
-            let mut tile_index = self.secondary_oam[sprite_index].tile_index;
-
-            let mut sprite_size: u16 = 8;
-            if (self.control & 0b0010_0000) != 0 {
-                sprite_size = 16;
+            let tile_address = self.sprite_tile_address(sprite_index);
+            match sub_cycle {
+                4 => self.secondary_oam[sprite_index].bitmap_low  = self.read_byte(mapper, tile_address),
+                6 => self.secondary_oam[sprite_index].bitmap_high = self.read_byte(mapper, tile_address + 8),
+                _ => ()
             }
-
-            let mut pattern_address: u16 = 0x0000;
-            // If we're using 8x16 sprites, set the pattern based on the sprite's tile index
-            if sprite_size == 16 {
-                if (tile_index & 0b1) != 0 {
-                    pattern_address = 0x1000;
-                }
-                tile_index &= 0b1111_1110;
-            } else {
-                // Otherwise, the pattern is selected by PPUCTL
-                if (self.control & 0b0000_1000) != 0 {
-                    pattern_address = 0x1000;
-                }
+            if sprite_index == 7 && sub_cycle == 6 {
+                self.fetch_extra_sprite_tiles(mapper);
             }
+        }
+    }
 
-            let mut y_offset = self.current_scanline.wrapping_sub(self.secondary_oam[sprite_index].y_pos as u16);
-            if self.secondary_oam[sprite_index].y_flip() {
-                y_offset = sprite_size.wrapping_sub(1).wrapping_sub(y_offset);
-            }
+    fn sprite_tile_address(&self, sprite_index: usize) -> u16 {
+        let mut tile_index = self.secondary_oam[sprite_index].tile_index;
 
-            if y_offset >= 8 {
-                y_offset = y_offset.wrapping_sub(8);
-                tile_index = tile_index.wrapping_add(1);
+        let mut sprite_size: u16 = 8;
+        if (self.control & 0b0010_0000) != 0 {
+            sprite_size = 16;
+        }
+
+        let mut pattern_address: u16 = 0x0000;
+        // If we're using 8x16 sprites, set the pattern based on the sprite's tile index
+        if sprite_size == 16 {
+            if (tile_index & 0b1) != 0 {
+                pattern_address = 0x1000;
+            }
+            tile_index &= 0b1111_1110;
+        } else {
+            // Otherwise, the pattern is selected by PPUCTL
+            if (self.control & 0b0000_1000) != 0 {
+                pattern_address = 0x1000;
             }
-            y_offset = y_offset % 8;
+        }
 
-            let tile_address = (((tile_index as u16 * 16) + y_offset) & 0xFFF) | pattern_address;
+        let mut y_offset = self.current_scanline.wrapping_sub(self.secondary_oam[sprite_index].y_pos as u16);
+        if self.secondary_oam[sprite_index].y_flip() {
+            y_offset = sprite_size.wrapping_sub(1).wrapping_sub(y_offset);
+        }
 
-            match sub_cycle {
-                4 => self.secondary_oam[sprite_index].bitmap_low  = self.read_byte(mapper, tile_address),
-                6 => self.secondary_oam[sprite_index].bitmap_high = self.read_byte(mapper, tile_address + 8),
-                _ => ()
-            }
+        if y_offset >= 8 {
+            y_offset = y_offset.wrapping_sub(8);
+            tile_index = tile_index.wrapping_add(1);
+        }
+        y_offset = y_offset % 8;
+
+        return (((tile_index as u16 * 16) + y_offset) & 0xFFF) | pattern_address;
+    }
+
+    // Sprites past the first 8 only exist when the sprite limit is disabled. Real hardware has no
+    // fetch slots for them, so read their patterns without side effects; mappers watching the PPU
+    // bus (MMC3's scanline counter, for one) must see exactly the same traffic either way.
+    fn fetch_extra_sprite_tiles(&mut self, mapper: &dyn Mapper) {
+        for sprite_index in 8 .. self.secondary_oam_index {
+            let tile_address = self.sprite_tile_address(sprite_index);
+            self.secondary_oam[sprite_index].bitmap_low  = self.debug_read_byte(mapper, tile_address);
+            self.secondary_oam[sprite_index].bitmap_high = self.debug_read_byte(mapper, tile_address + 8);
         }
     }
 
@@ -652,12 +791,15 @@ impl PpuState {
             },
             340 => {
                 if self.rendering_enabled() {
-                    if self.current_frame & 0x1 != 0 {
+                    if self.current_frame & 0x1 != 0 && self.region.skips_odd_frame_dot() {
                         // Skip ahead one cycle on odd frames. This jitter produces a cleaner image
                         // for NTSC signal generation.
 
                         // (note: the effect here is to skip to cycle 1 of scanline 0, since this
-                        // counter is immediately incremented)
+                        // counter is immediately incremented. Hardware instead jumps from dot 339
+                        // straight to 0,0; both dots are idle, so the frame comes out one dot shorter
+                        // with identical fetch timing either way. current_frame counts every frame,
+                        // rendered or not, so the odd/even phase keeps alternating while rendering is off.)
                         self.current_scanline_cycle = 0;
                         self.current_scanline = 0;
                         self.current_frame += 1;
@@ -720,7 +862,7 @@ impl PpuState {
                     let mut pixel_color = self.read_byte(mapper, 0x3F00);
                     // However, if the current VRAM address is within palette memory, instead
                     // show whatever that color is:
-                    if self.current_vram_address >= 0x3F00 && self.current_vram_address <= 0x3FFF {
+                    if self.palette_backdrop_quirk && self.current_vram_address >= 0x3F00 && self.current_vram_address <= 0x3FFF {
                         let vram_address = self.current_vram_address;
                         pixel_color = self.read_byte(mapper, vram_address);
                     }
@@ -736,12 +878,18 @@ impl PpuState {
 
     fn vblank_scanline(&mut self) {
         if self.current_scanline_cycle == 1 {
+            if self.suppress_vblank {
+                // PPUSTATUS was read on the previous dot, see read_status
+                self.suppress_vblank = false;
+                return;
+            }
             // VBlank! Set NMI flag here
             self.status = (self.status & 0x7F) + 0x80;
         }
     }
 
     pub fn clock(&mut self, mapper: &mut dyn Mapper) {
+        let starting_scanline = self.current_scanline;
         match self.current_scanline {
             0 => {
                 if self.current_scanline_cycle == 1 {
@@ -758,8 +906,8 @@ impl PpuState {
                     let _ = self.read_byte(mapper, vram_address);
                 }
             }
-            241 => self.vblank_scanline(),
-            261 => self.prerender_scanline(mapper),
+            scanline if scanline == self.region.vblank_scanline() => self.vblank_scanline(),
+            scanline if scanline == self.region.prerender_scanline() => self.prerender_scanline(mapper),
             _ => ()
         }
 
@@ -768,11 +916,15 @@ impl PpuState {
         if self.current_scanline_cycle > 340 {
             self.current_scanline_cycle = 0;
             self.current_scanline += 1;
-            if self.current_scanline > 261 {
+            if self.current_scanline >= self.region.scanlines_per_frame() {
                 self.current_scanline = 0;
                 self.current_frame += 1;
             }
         }
+        // Also catches the odd frame skip, which jumps from the prerender line to scanline 0 early
+        if self.current_scanline != starting_scanline {
+            mapper.on_ppu_scanline();
+        }
     }
 
     pub fn get_bg_tile(&self, mapper: &dyn Mapper, tx: u8, ty: u8) -> u8 {
@@ -803,36 +955,203 @@ impl PpuState {
         return (attr_byte & mask) >> shift;
     }
 
-    pub fn render_ntsc(&mut self, width: usize) {
-        // One scanline logic, needs wrapping for Y yet.
-        for scanline in 0 .. 240 {
-            // Compute ntsc signal from raw palette+emphasis values
-            for dot in 0 .. 256 {
-                let dot_phase = (self.frame_starting_cycle + (scanline*341) + dot) *8;
-                for sample_phase in  0 .. 8 {
-                    let pixel = self.screen[scanline*256+dot];
-                    self.scanline_ntsc_samples[dot*8+sample_phase] = render_ntsc_sample(pixel, dot_phase + sample_phase);
-                }
+    // Debug rendering for PPU viewers. Everything below reads through debug_read_byte, so the
+    // mapper never sees these accesses and drawing a viewer can't disturb emulation.
+
+    fn debug_palette_rgba(&self, mapper: &dyn Mapper, palette: usize, index: u8) -> [u8; 4] {
+        // Color 0 of every palette displays the universal background color
+        let palette_address = if index == 0 {0x3F00} else {0x3F00 + (palette as u16 & 0x7) * 4 + index as u16};
+        let color = (self.debug_read_byte(mapper, palette_address) & 0x3F) as usize * 3;
+        return [NTSC_PAL[color + 0], NTSC_PAL[color + 1], NTSC_PAL[color + 2], 255];
+    }
+
+    fn debug_draw_tile(&self, mapper: &dyn Mapper, pattern_address: u16, tile_index: u16, palette: usize,
+            buffer: &mut [u8], buffer_width: usize, dx: usize, dy: usize) {
+        for py in 0 .. 8 {
+            let tile_address = pattern_address + tile_index * 16 + py as u16;
+            let tile_low  = mapper.debug_read_ppu(tile_address).unwrap_or(0);
+            let tile_high = mapper.debug_read_ppu(tile_address + 8).unwrap_or(0);
+            for px in 0 .. 8 {
+                let bit = 7 - px;
+                let palette_index = ((tile_low >> bit) & 0x1) | (((tile_high >> bit) & 0x1) << 1);
+                let offset = ((dy + py) * buffer_width + dx + px) * 4;
+                buffer[offset .. offset + 4].copy_from_slice(&self.debug_palette_rgba(mapper, palette, palette_index));
             }
+        }
+    }
 
-            // Decode scanline into framebuffer
-            let phase = (self.frame_starting_cycle + (scanline * 341)) * 8;
-            for x in 0 .. width {
-                let center = x * (256 * 8) / width + 0;
-                let begin = if center >= 6 {center - 6} else {0};
-                let end = if (center + 6) < (256 * 8) {center + 6} else {256*8};
-                let mut y = 0.0;
-                let mut i = 0.0;
-                let mut q = 0.0;
-                for p in begin .. end {
-                    let level = self.scanline_ntsc_samples[p] / 12.0;
-                    y = y + level;
-                    i = i + level * PHASED_COS[(phase + p) % 12];
-                    q = q + level * PHASED_SIN[(phase + p) % 12];
-                }
-                self.filtered_screen[scanline * width + x] = yiq_to_argb(y, i, q);
+    /// Renders one 128x128 pattern table (0 for $0000, 1 for $1000) as RGBA, using one of the
+    /// eight active palettes (0-3 background, 4-7 sprites) and the mapper's current CHR banks.
+    pub fn render_pattern_table(&self, mapper: &dyn Mapper, table: usize, palette: usize) -> Vec<u8> {
+        let mut buffer = vec![0u8; 128 * 128 * 4];
+        let pattern_address = ((table & 0x1) as u16) * 0x1000;
+        for tile in 0 .. 256 {
+            self.debug_draw_tile(mapper, pattern_address, tile as u16, palette, &mut buffer, 128, (tile % 16) * 8, (tile / 16) * 8);
+        }
+        return buffer;
+    }
+
+    /// Renders both pattern tables side by side into one 256x128 RGBA tilesheet, $0000 on the
+    /// left and $1000 on the right. Reads go through the mapper, so CHR RAM games export
+    /// whatever is currently in VRAM.
+    pub fn render_chr_sheet(&self, mapper: &dyn Mapper, palette: usize) -> Vec<u8> {
+        let mut buffer = vec![0u8; 256 * 128 * 4];
+        for table in 0 .. 2 {
+            let pattern_address = (table as u16) * 0x1000;
+            for tile in 0 .. 256 {
+                self.debug_draw_tile(mapper, pattern_address, tile as u16, palette, &mut buffer, 256, table * 128 + (tile % 16) * 8, (tile / 16) * 8);
+            }
+        }
+        return buffer;
+    }
+
+    /// Renders one 256x240 nametable as RGBA. Indices 0-3 correspond to $2000, $2400, $2800
+    /// and $2C00, so mirrored nametables come out as duplicates.
+    pub fn render_nametable(&self, mapper: &dyn Mapper, index: usize) -> Vec<u8> {
+        let mut buffer = vec![0u8; 256 * 240 * 4];
+        let pattern_address = if (self.control & 0x10) != 0 {0x1000} else {0x0000};
+        let tx_offset = ((index & 0x1) * 32) as u8;
+        let ty_offset = (((index >> 1) & 0x1) * 30) as u8;
+        for ty in 0 .. 30 {
+            for tx in 0 .. 32 {
+                let tile_index = self.get_bg_tile(mapper, tx + tx_offset, ty + ty_offset);
+                let palette = self.get_bg_palette(mapper, tx + tx_offset, ty + ty_offset);
+                self.debug_draw_tile(mapper, pattern_address, tile_index as u16, palette as usize, &mut buffer, 256, tx as usize * 8, ty as usize * 8);
             }
         }
+        return buffer;
+    }
+
+    pub fn sprite_info(&self, index: usize) -> SpriteInfo {
+        let base = (index & 0x3F) * 4;
+        let tile = self.oam[base + 1];
+        let attributes = self.oam[base + 2];
+        let tall_sprites = (self.control & 0x20) != 0;
+        // 8x16 sprites take their pattern table from bit 0 of the tile index and always start
+        // on an even tile; 8x8 sprites use the table selected by PPUCTRL
+        let (tile_index, pattern_address) = if tall_sprites {
+            (tile & 0xFE, ((tile & 0x01) as u16) * 0x1000)
+        } else {
+            (tile, if (self.control & 0x08) != 0 {0x1000} else {0x0000})
+        };
+        return SpriteInfo {
+            x: self.oam[base + 3],
+            y: self.oam[base + 0],
+            tile_index: tile_index,
+            pattern_address: pattern_address,
+            height: if tall_sprites {16} else {8},
+            palette: attributes & 0b0000_0011,
+            behind_background: (attributes & 0b0010_0000) != 0,
+            flip_horizontal: (attributes & 0b0100_0000) != 0,
+            flip_vertical: (attributes & 0b1000_0000) != 0,
+        };
+    }
+
+    pub fn all_sprites(&self) -> Vec<SpriteInfo> {
+        return (0 .. 64).map(|index| self.sprite_info(index)).collect();
+    }
+
+    /// All 32 palette entries as a 32x1 RGBA strip, background palettes first.
+    pub fn render_palette(&self, mapper: &dyn Mapper) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(32 * 4);
+        for entry in 0 .. 32 {
+            let color = (self.debug_read_byte(mapper, 0x3F00 + entry) & 0x3F) as usize * 3;
+            buffer.extend_from_slice(&[NTSC_PAL[color + 0], NTSC_PAL[color + 1], NTSC_PAL[color + 2], 255]);
+        }
+        return buffer;
+    }
+
+    pub fn render_ntsc(&mut self, width: usize) {
+        apply_ntsc_filter(&self.screen, self.frame_starting_cycle, width, &mut self.filtered_screen);
+    }
+
+    // The framebuffer itself is not saved; it is redrawn within a frame of loading.
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec(buff, &self.internal_vram);
+        save_vec(buff, &self.oam);
+        // Extra slots used by disable_sprite_limit are cosmetic and rebuilt on the next scanline
+        for sprite in self.secondary_oam.iter().take(8) {
+            sprite.save_state(buff);
+        }
+        save_usize(buff, self.secondary_oam_index);
+        save_vec(buff, &self.palette);
+        save_u8(buff, self.latch);
+        save_u8(buff, self.open_bus);
+        save_u8(buff, self.read_buffer);
+        save_u8(buff, self.control);
+        save_u8(buff, self.mask);
+        save_u8(buff, self.status);
+        save_u8(buff, self.oam_addr);
+        save_u8(buff, self.oam_dma_high);
+        save_u32(buff, self.current_frame);
+        save_u16(buff, self.current_scanline);
+        save_u16(buff, self.current_scanline_cycle);
+        save_usize(buff, self.overall_cycle);
+        save_usize(buff, self.frame_starting_cycle);
+        save_vec(buff, &self.sprite_color);
+        save_vec(buff, &self.sprite_index);
+        for i in 0 .. 256 {
+            save_bool(buff, self.sprite_bg_priority[i]);
+            save_bool(buff, self.sprite_zero[i]);
+        }
+        save_bool(buff, self.write_toggle);
+        save_u16(buff, self.current_vram_address);
+        save_u16(buff, self.temporary_vram_address);
+        save_u8(buff, self.fine_x);
+        save_u16(buff, self.tile_shift_low);
+        save_u16(buff, self.tile_shift_high);
+        save_u8(buff, self.tile_low);
+        save_u8(buff, self.tile_high);
+        save_u8(buff, self.tile_index);
+        save_u8(buff, self.palette_shift_low);
+        save_u8(buff, self.palette_shift_high);
+        save_u8(buff, self.palette_latch);
+        save_u8(buff, self.attribute_byte);
+        save_bool(buff, self.sprite_zero_on_scanline);
+    }
+
+    pub fn load_state(&mut self, buff: &mut StateReader) -> Result<(), StateError> {
+        buff.load_into(&mut self.internal_vram)?;
+        buff.load_into(&mut self.oam)?;
+        for sprite in self.secondary_oam.iter_mut().take(8) {
+            sprite.load_state(buff)?;
+        }
+        self.secondary_oam_index = buff.load_usize()?.min(8);
+        buff.load_into(&mut self.palette)?;
+        self.latch = buff.load_u8()?;
+        self.open_bus = buff.load_u8()?;
+        self.read_buffer = buff.load_u8()?;
+        self.control = buff.load_u8()?;
+        self.mask = buff.load_u8()?;
+        self.status = buff.load_u8()?;
+        self.oam_addr = buff.load_u8()?;
+        self.oam_dma_high = buff.load_u8()?;
+        self.current_frame = buff.load_u32()?;
+        self.current_scanline = buff.load_u16()?;
+        self.current_scanline_cycle = buff.load_u16()?;
+        self.overall_cycle = buff.load_usize()?;
+        self.frame_starting_cycle = buff.load_usize()?;
+        buff.load_into(&mut self.sprite_color)?;
+        buff.load_into(&mut self.sprite_index)?;
+        for i in 0 .. 256 {
+            self.sprite_bg_priority[i] = buff.load_bool()?;
+            self.sprite_zero[i] = buff.load_bool()?;
+        }
+        self.write_toggle = buff.load_bool()?;
+        self.current_vram_address = buff.load_u16()?;
+        self.temporary_vram_address = buff.load_u16()?;
+        self.fine_x = buff.load_u8()?;
+        self.tile_shift_low = buff.load_u16()?;
+        self.tile_shift_high = buff.load_u16()?;
+        self.tile_low = buff.load_u8()?;
+        self.tile_high = buff.load_u8()?;
+        self.tile_index = buff.load_u8()?;
+        self.palette_shift_low = buff.load_u8()?;
+        self.palette_shift_high = buff.load_u8()?;
+        self.palette_latch = buff.load_u8()?;
+        self.attribute_byte = buff.load_u8()?;
+        self.sprite_zero_on_scanline = buff.load_bool()?;
+        return Ok(());
     }
 }
 
@@ -917,6 +1236,48 @@ pub fn render_ntsc_sample(pixel: u16, phase: usize) -> f32 {
     return (ntsc_signal(pixel, phase) - NTSC_BLACK) / (NTSC_WHITE - NTSC_BLACK);
 }
 
+/// Runs a frame of raw PPU output (palette index plus emphasis bits, as in `PpuState::screen`)
+/// through a model of the composite signal and decodes it back to ARGB, `width` pixels per
+/// scanline. `frame_phase` is the color subcarrier phase at the start of the frame; it
+/// changes from one frame to the next, which is what makes dithered artifacts shimmer.
+///
+/// This takes palette indices rather than an RGB frame because the signal model needs the
+/// raw NES color and emphasis bits, which can't be recovered from RGB. It writes into a
+/// caller-owned buffer so `PpuState::render_ntsc` can reuse `filtered_screen` every frame
+/// instead of allocating a new one. Since colors come out of the signal model rather than a
+/// lookup table, a custom palette loaded with `load_pal` has no effect here.
+pub fn apply_ntsc_filter(screen: &[u16], frame_phase: usize, width: usize, output: &mut [u32]) {
+    let mut scanline_samples = [0f32; 256 * 8];
+    for scanline in 0 .. 240 {
+        // Compute ntsc signal from raw palette+emphasis values
+        for dot in 0 .. 256 {
+            let dot_phase = (frame_phase + (scanline*341) + dot) *8;
+            for sample_phase in  0 .. 8 {
+                let pixel = screen[scanline*256+dot];
+                scanline_samples[dot*8+sample_phase] = render_ntsc_sample(pixel, dot_phase + sample_phase);
+            }
+        }
+
+        // Decode scanline into framebuffer
+        let phase = (frame_phase + (scanline * 341)) * 8;
+        for x in 0 .. width {
+            let center = x * (256 * 8) / width + 0;
+            let begin = if center >= 6 {center - 6} else {0};
+            let end = if (center + 6) < (256 * 8) {center + 6} else {256*8};
+            let mut y = 0.0;
+            let mut i = 0.0;
+            let mut q = 0.0;
+            for p in begin .. end {
+                let level = scanline_samples[p] / 12.0;
+                y = y + level;
+                i = i + level * PHASED_COS[(phase + p) % 12];
+                q = q + level * PHASED_SIN[(phase + p) % 12];
+            }
+            output[scanline * width + x] = yiq_to_argb(y, i, q);
+        }
+    }
+}
+
 pub fn gammafix(f: f32) -> f32 {
     // This is excessively slow and seems to have a very minor impact on the output.
     // Skipping this for now.
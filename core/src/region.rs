@@ -0,0 +1,119 @@
+// Timing differences between the three console families. Dendy clones pair a PAL-like PPU
+// frame with an NTSC-speed APU, so each table is chosen individually rather than assuming
+// "not NTSC" means PAL everywhere.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+// Frame counter step timings, in CPU cycles. The 4-step list ends with the three cycles that
+// assert the frame IRQ; the last entry of each list is where the sequence wraps to 0.
+pub struct FrameSequencerSteps {
+    pub four_step: [u16; 6],
+    pub five_step: [u16; 5],
+}
+
+const NTSC_FRAME_SEQUENCER: FrameSequencerSteps = FrameSequencerSteps {
+    four_step: [7457, 14913, 22371, 29828, 29829, 29830],
+    five_step: [7457, 14913, 22371, 37281, 37282],
+};
+
+const PAL_FRAME_SEQUENCER: FrameSequencerSteps = FrameSequencerSteps {
+    four_step: [8313, 16627, 24939, 33252, 33253, 33254],
+    five_step: [8313, 16627, 24939, 41565, 41566],
+};
+
+const NTSC_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+const PAL_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778];
+
+const NTSC_DMC_PERIODS: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106,  84,  72,  54];
+const PAL_DMC_PERIODS: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118,  98,  78,  66,  50];
+
+impl Region {
+    pub fn cpu_clock_rate(&self) -> u64 {
+        return match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        };
+    }
+
+    // The master clock divides down to both the CPU and the PPU; the ratio between these two
+    // is 3 dots per CPU cycle, except on PAL where it's 3.2
+    pub fn master_clocks_per_cpu_cycle(&self) -> u8 {
+        return match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        };
+    }
+
+    pub fn master_clocks_per_ppu_dot(&self) -> u8 {
+        return match self {
+            Region::Ntsc => 4,
+            Region::Pal => 5,
+            Region::Dendy => 5,
+        };
+    }
+
+    // Nominal refresh rate, ignoring the dot NTSC skips on odd frames
+    pub fn frames_per_second(&self) -> f64 {
+        let master_clock_rate = (self.cpu_clock_rate() * self.master_clocks_per_cpu_cycle() as u64) as f64;
+        let master_clocks_per_frame = self.scanlines_per_frame() as f64 * 341.0 * self.master_clocks_per_ppu_dot() as f64;
+        return master_clock_rate / master_clocks_per_frame;
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        return match self {
+            Region::Ntsc => 262,
+            Region::Pal => 312,
+            Region::Dendy => 312,
+        };
+    }
+
+    pub fn prerender_scanline(&self) -> u16 {
+        return self.scanlines_per_frame() - 1;
+    }
+
+    // Dendy stretches the post-render period instead of vblank, so NMI arrives 50 lines later
+    pub fn vblank_scanline(&self) -> u16 {
+        return match self {
+            Region::Ntsc => 241,
+            Region::Pal => 241,
+            Region::Dendy => 291,
+        };
+    }
+
+    // Only NTSC drops a dot from the prerender line on odd frames
+    pub fn skips_odd_frame_dot(&self) -> bool {
+        return *self == Region::Ntsc;
+    }
+
+    pub fn frame_sequencer_steps(&self) -> &'static FrameSequencerSteps {
+        return match self {
+            Region::Pal => &PAL_FRAME_SEQUENCER,
+            _ => &NTSC_FRAME_SEQUENCER,
+        };
+    }
+
+    pub fn noise_periods(&self) -> &'static [u16; 16] {
+        return match self {
+            Region::Pal => &PAL_NOISE_PERIODS,
+            _ => &NTSC_NOISE_PERIODS,
+        };
+    }
+
+    pub fn dmc_periods(&self) -> &'static [u16; 16] {
+        return match self {
+            Region::Pal => &PAL_DMC_PERIODS,
+            _ => &NTSC_DMC_PERIODS,
+        };
+    }
+}
@@ -0,0 +1,131 @@
+// Bounded history of savestates, so a frontend can step backwards through
+// recent gameplay. Snapshots are run-length compressed on the way in; most of
+// a savestate is RAM and nametable data, which is dominated by runs of zeroes.
+
+use std::collections::VecDeque;
+
+use nes::NesState;
+use region::Region;
+
+pub struct RewindBuffer {
+    snapshots: VecDeque<Vec<u8>>,
+    frames_per_snapshot: usize,
+    frames_until_snapshot: usize,
+    max_snapshots: usize,
+    capacity_seconds: f32,
+    region: Region,
+}
+
+impl RewindBuffer {
+    /// Creates a buffer holding roughly capacity_seconds of history at the
+    /// given region's frame rate, taking one snapshot every frames_per_snapshot
+    /// calls to push.
+    pub fn new(capacity_seconds: f32, frames_per_snapshot: usize, region: Region) -> RewindBuffer {
+        let frames_per_snapshot = frames_per_snapshot.max(1);
+        let mut buffer = RewindBuffer {
+            snapshots: VecDeque::new(),
+            frames_per_snapshot: frames_per_snapshot,
+            frames_until_snapshot: 0,
+            max_snapshots: 0,
+            capacity_seconds: capacity_seconds,
+            region: region,
+        };
+        buffer.set_capacity_seconds(capacity_seconds, region);
+        return buffer;
+    }
+
+    pub fn set_capacity_seconds(&mut self, capacity_seconds: f32, region: Region) {
+        self.capacity_seconds = capacity_seconds;
+        self.region = region;
+        let capacity_frames = (capacity_seconds.max(0.0) as f64 * region.frames_per_second()).ceil() as usize;
+        self.max_snapshots = (capacity_frames + self.frames_per_snapshot - 1) / self.frames_per_snapshot;
+        while self.snapshots.len() > self.max_snapshots {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.max_snapshots;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.snapshots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.snapshots.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.frames_until_snapshot = 0;
+    }
+
+    /// Call once per emulated frame. Every frames_per_snapshot calls, the
+    /// current state is captured and the oldest snapshot is dropped if the
+    /// buffer is full.
+    pub fn push(&mut self, nes: &NesState) {
+        // The console may have switched regions since the capacity was last worked out
+        if nes.region != self.region {
+            let capacity_seconds = self.capacity_seconds;
+            self.set_capacity_seconds(capacity_seconds, nes.region);
+        }
+        if self.frames_until_snapshot > 0 {
+            self.frames_until_snapshot -= 1;
+            return;
+        }
+        self.frames_until_snapshot = self.frames_per_snapshot - 1;
+        if self.max_snapshots == 0 {
+            return;
+        }
+        while self.snapshots.len() >= self.max_snapshots {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(compress(&nes.save_state()));
+    }
+
+    /// Removes the most recent snapshot and returns it, ready to pass to
+    /// NesState::load_state.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        // Take the next snapshot promptly if the player resumes from here
+        self.frames_until_snapshot = 0;
+        return self.snapshots.pop_back().map(|snapshot| decompress(&snapshot));
+    }
+}
+
+// Zero runs are stored as a 0x00 marker followed by the run length (1-255);
+// every other byte is stored as-is.
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() / 2);
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == 0 {
+            let mut run_length = 0;
+            while i < data.len() && data[i] == 0 && run_length < 255 {
+                run_length += 1;
+                i += 1;
+            }
+            output.push(0);
+            output.push(run_length as u8);
+        } else {
+            output.push(data[i]);
+            i += 1;
+        }
+    }
+    return output;
+}
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() * 2);
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == 0 && i + 1 < data.len() {
+            output.resize(output.len() + data[i + 1] as usize, 0);
+            i += 2;
+        } else {
+            output.push(data[i]);
+            i += 1;
+        }
+    }
+    return output;
+}
@@ -0,0 +1,180 @@
+// Savestate serialization helpers. The format is deliberately simple: a short
+// header followed by every component's fields written in a fixed order, with
+// no tags or per-field metadata. Loading must mirror saving exactly, so any
+// change to what a component writes should bump STATE_VERSION.
+
+use std::error::Error;
+use std::fmt;
+
+use mmc::mapper::Mirroring;
+
+pub const STATE_MAGIC: [u8; 4] = *b"RNST";
+pub const STATE_VERSION: u8 = 7;
+
+#[derive(Debug)]
+pub enum StateError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    SizeMismatch{expected: usize, found: usize},
+    InvalidValue(&'static str),
+    TrailingData(usize),
+}
+
+impl Error for StateError {}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::InvalidMagic => {write!(f, "Not a savestate: invalid magic number")},
+            StateError::UnsupportedVersion(version) => {write!(f, "Unsupported savestate version {}, expected {}", version, STATE_VERSION)},
+            StateError::UnexpectedEnd => {write!(f, "Savestate is truncated")},
+            StateError::SizeMismatch{expected, found} => {write!(f, "Savestate memory block size mismatch: expected {} bytes, found {}", expected, found)},
+            StateError::InvalidValue(field) => {write!(f, "Savestate contains an invalid value for {}", field)},
+            StateError::TrailingData(length) => {write!(f, "Savestate has {} unexpected trailing bytes (was it saved with a different cartridge?)", length)},
+        }
+    }
+}
+
+pub fn save_u8(buff: &mut Vec<u8>, data: u8) {
+    buff.push(data);
+}
+
+pub fn save_bool(buff: &mut Vec<u8>, data: bool) {
+    buff.push(data as u8);
+}
+
+pub fn save_u16(buff: &mut Vec<u8>, data: u16) {
+    buff.extend_from_slice(&data.to_le_bytes());
+}
+
+pub fn save_u32(buff: &mut Vec<u8>, data: u32) {
+    buff.extend_from_slice(&data.to_le_bytes());
+}
+
+pub fn save_u64(buff: &mut Vec<u8>, data: u64) {
+    buff.extend_from_slice(&data.to_le_bytes());
+}
+
+pub fn save_usize(buff: &mut Vec<u8>, data: usize) {
+    save_u64(buff, data as u64);
+}
+
+pub fn save_vec(buff: &mut Vec<u8>, data: &[u8]) {
+    save_usize(buff, data.len());
+    buff.extend_from_slice(data);
+}
+
+pub fn save_mirroring(buff: &mut Vec<u8>, mirroring: Mirroring) {
+    let value = match mirroring {
+        Mirroring::Horizontal => 0,
+        Mirroring::Vertical => 1,
+        Mirroring::OneScreenLower => 2,
+        Mirroring::OneScreenUpper => 3,
+        Mirroring::FourScreen => 4,
+    };
+    save_u8(buff, value);
+}
+
+/// Reads fields back out of a savestate in the order they were written.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        return StateReader {
+            data: data,
+            position: 0,
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        return self.data.len() - self.position;
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        if length > self.remaining() {
+            return Err(StateError::UnexpectedEnd);
+        }
+        let slice = &self.data[self.position .. self.position + length];
+        self.position += length;
+        return Ok(slice);
+    }
+
+    pub fn load_u8(&mut self) -> Result<u8, StateError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    pub fn load_bool(&mut self) -> Result<bool, StateError> {
+        return Ok(self.load_u8()? != 0);
+    }
+
+    pub fn load_u16(&mut self) -> Result<u16, StateError> {
+        let mut bytes = [0u8; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        return Ok(u16::from_le_bytes(bytes));
+    }
+
+    pub fn load_u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        return Ok(u32::from_le_bytes(bytes));
+    }
+
+    pub fn load_u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        return Ok(u64::from_le_bytes(bytes));
+    }
+
+    pub fn load_usize(&mut self) -> Result<usize, StateError> {
+        return Ok(self.load_u64()? as usize);
+    }
+
+    /// Loads a length-prefixed byte vector of any size.
+    pub fn load_vec(&mut self) -> Result<Vec<u8>, StateError> {
+        let length = self.load_usize()?;
+        return Ok(self.take(length)?.to_vec());
+    }
+
+    /// Loads a length-prefixed byte vector into an existing buffer, which must
+    /// already be the same size. Used for RAM whose size is fixed by the cartridge.
+    pub fn load_into(&mut self, target: &mut [u8]) -> Result<(), StateError> {
+        let length = self.load_usize()?;
+        if length != target.len() {
+            return Err(StateError::SizeMismatch{expected: target.len(), found: length});
+        }
+        target.copy_from_slice(self.take(length)?);
+        return Ok(());
+    }
+
+    pub fn load_mirroring(&mut self) -> Result<Mirroring, StateError> {
+        return match self.load_u8()? {
+            0 => Ok(Mirroring::Horizontal),
+            1 => Ok(Mirroring::Vertical),
+            2 => Ok(Mirroring::OneScreenLower),
+            3 => Ok(Mirroring::OneScreenUpper),
+            4 => Ok(Mirroring::FourScreen),
+            _ => Err(StateError::InvalidValue("mirroring")),
+        };
+    }
+}
+
+pub fn save_header(buff: &mut Vec<u8>) {
+    buff.extend_from_slice(&STATE_MAGIC);
+    save_u8(buff, STATE_VERSION);
+}
+
+pub fn load_header(buff: &mut StateReader) -> Result<(), StateError> {
+    let magic = buff.take(4).map_err(|_| StateError::InvalidMagic)?;
+    if magic != STATE_MAGIC {
+        return Err(StateError::InvalidMagic);
+    }
+    let version = buff.load_u8()?;
+    if version != STATE_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    return Ok(());
+}
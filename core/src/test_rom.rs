@@ -0,0 +1,109 @@
+// Headless runner for test ROMs that follow blargg's reporting protocol: a status byte at
+// $6000, the signature $DE $B0 $61 at $6001-$6003, and a null terminated message from $6004.
+// Details here: https://github.com/christopherpow/nes-test-roms/blob/master/README.txt
+
+use cartridge::mapper_from_file;
+use memory;
+use nes::NesState;
+
+pub const STATUS_RUNNING: u8 = 0x80;
+pub const STATUS_NEEDS_RESET: u8 = 0x81;
+
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const MESSAGE_START: u16 = 0x6004;
+const MESSAGE_END: u16 = 0x8000;
+
+// The protocol asks for at least 100ms between the reset request and the reset itself
+const RESET_DELAY_FRAMES: u32 = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestRomResult {
+    // Final status byte (0 is a pass, anything else is a failure code) and the ROM's message
+    Completed(u8, String),
+    // Still running after the frame limit, with whatever message was written so far
+    TimedOut(String),
+    // The ROM couldn't be loaded at all
+    LoadFailed(String),
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        return match self {
+            TestRomResult::Completed(0, _) => true,
+            _ => false
+        };
+    }
+
+    pub fn message(&self) -> &str {
+        return match self {
+            TestRomResult::Completed(_, message) => message,
+            TestRomResult::TimedOut(message) => message,
+            TestRomResult::LoadFailed(why) => why,
+        };
+    }
+}
+
+/// The status byte and message, or None if the signature hasn't been written yet. Until it
+/// has, $6000 may hold anything at all (uninitialized SRAM, or a game's own save data).
+pub fn read_status(nes: &NesState) -> Option<(u8, String)> {
+    for (offset, expected) in SIGNATURE.iter().enumerate() {
+        if memory::debug_read_byte(nes, 0x6001 + offset as u16) != *expected {
+            return None;
+        }
+    }
+    let status = memory::debug_read_byte(nes, 0x6000);
+    let mut message_bytes: Vec<u8> = Vec::new();
+    let mut address = MESSAGE_START;
+    while address < MESSAGE_END {
+        let byte = memory::debug_read_byte(nes, address);
+        if byte == 0 {
+            break;
+        }
+        message_bytes.push(byte);
+        address += 1;
+    }
+    return Some((status, String::from_utf8_lossy(&message_bytes).into_owned()));
+}
+
+/// Runs a test ROM until it reports a result or `timeout_frames` frames have passed, pressing
+/// reset whenever the ROM asks for it.
+pub fn run_test_rom(rom: &[u8], timeout_frames: u32) -> TestRomResult {
+    let mapper = match mapper_from_file(rom) {
+        Ok(mapper) => mapper,
+        Err(why) => {return TestRomResult::LoadFailed(why.to_string());}
+    };
+    let mut nes = NesState::new(mapper);
+    nes.power_on();
+
+    let mut reset_requested_at: Option<u32> = None;
+    // Status stays at $81 for a little while after the reset, which shouldn't count as a second request
+    let mut reset_pressed = false;
+    for frame in 0 .. timeout_frames {
+        nes.run_until_vblank();
+        let status = read_status(&nes);
+        match status {
+            Some((STATUS_NEEDS_RESET, _)) => {},
+            _ => {reset_pressed = false;}
+        }
+        match status {
+            Some((STATUS_RUNNING, _)) => {},
+            Some((STATUS_NEEDS_RESET, _)) => {
+                if !reset_pressed {
+                    let requested_frame = *reset_requested_at.get_or_insert(frame);
+                    if frame - requested_frame >= RESET_DELAY_FRAMES {
+                        nes.reset();
+                        reset_requested_at = None;
+                        reset_pressed = true;
+                    }
+                }
+            },
+            Some((status, message)) => {
+                return TestRomResult::Completed(status, message);
+            },
+            None => {}
+        }
+    }
+
+    let message = read_status(&nes).map(|(_, message)| message).unwrap_or_default();
+    return TestRomResult::TimedOut(message);
+}
@@ -4,8 +4,37 @@ pub enum EventType {
     CpuRead{program_counter: u16, address: u16, data: u8},
     CpuWrite{program_counter: u16, address: u16, data: u8},
     CpuExecute{program_counter: u16, data: u8},
+    // program_counter is the address the interrupt will return to
+    Nmi{program_counter: u16},
+    Irq{program_counter: u16},
+    SpriteZeroHit,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventCategory {
+    RegisterRead,
+    RegisterWrite,
+    Execute,
+    Nmi,
+    Irq,
+    SpriteZeroHit,
+}
+
+impl EventType {
+    pub fn category(&self) -> Option<EventCategory> {
+        return match *self {
+            EventType::NullEvent => None,
+            EventType::CpuRead{..} => Some(EventCategory::RegisterRead),
+            EventType::CpuWrite{..} => Some(EventCategory::RegisterWrite),
+            EventType::CpuExecute{..} => Some(EventCategory::Execute),
+            EventType::Nmi{..} => Some(EventCategory::Nmi),
+            EventType::Irq{..} => Some(EventCategory::Irq),
+            EventType::SpriteZeroHit => Some(EventCategory::SpriteZeroHit),
+        };
+    }
+}
+
+// Where on the PPU's raster the event happened: scanline and dot within that scanline
 #[derive(Clone, Copy)]
 pub struct TrackedEvent {
     pub scanline: u16,
@@ -13,12 +42,22 @@ pub struct TrackedEvent {
     pub event_type: EventType,
 }
 
+impl TrackedEvent {
+    pub fn category(&self) -> Option<EventCategory> {
+        return self.event_type.category();
+    }
+}
+
 pub struct EventTracker {
     pub tracked_events_a: Vec<TrackedEvent>,
     pub size_a: usize,
     pub tracked_events_b: Vec<TrackedEvent>,
     pub size_b: usize,
     pub a_active: bool,
+    // The PPU frame each buffer was recording
+    pub frame_a: u32,
+    pub frame_b: u32,
+    pub current_frame: u32,
     pub current_scanline: u16,
     pub current_cycle: u16,
     pub cpu_snoop_list: Vec<u8>,
@@ -79,13 +118,16 @@ impl EventTracker {
 
 
         return EventTracker {
-            // Way, way more events than we could *possibly* need, just to be safe
+            // Way, way more events than we could *possibly* need (one per dot of a PAL frame), just to be safe
             // Manually indexed, and never resized, to avoid allocations at runtime
-            tracked_events_a: vec![TrackedEvent{scanline: 0xFFFF, cycle: 0xFFFF, event_type: EventType::NullEvent}; 262*341],
+            tracked_events_a: vec![TrackedEvent{scanline: 0xFFFF, cycle: 0xFFFF, event_type: EventType::NullEvent}; 312*341],
             size_a: 0,
-            tracked_events_b: vec![TrackedEvent{scanline: 0xFFFF, cycle: 0xFFFF, event_type: EventType::NullEvent}; 262*341],
+            tracked_events_b: vec![TrackedEvent{scanline: 0xFFFF, cycle: 0xFFFF, event_type: EventType::NullEvent}; 312*341],
             size_b: 0,
             a_active: true,
+            frame_a: 0,
+            frame_b: 0,
+            current_frame: 0,
             current_scanline: 0,
             current_cycle: 0,
             cpu_snoop_list: default_cpu_snoops,
@@ -109,10 +151,12 @@ impl EventTracker {
         match self.a_active {
             true => {
                 self.size_b = 0;
+                self.frame_b = self.current_frame;
                 self.a_active = false;
             },
            false => {
                 self.size_a = 0;
+                self.frame_a = self.current_frame;
                 self.a_active = true;
             }
         }
@@ -132,6 +176,18 @@ impl EventTracker {
         }
     }
 
+    /// Events recorded during a particular PPU frame. Only the frame in progress and the one
+    /// before it are kept, so anything older comes back empty.
+    pub fn events_for_frame(&self, frame: u32) -> &[TrackedEvent] {
+        if self.frame_a == frame {
+            return &self.tracked_events_a[..self.size_a];
+        }
+        if self.frame_b == frame {
+            return &self.tracked_events_b[..self.size_b];
+        }
+        return &[];
+    }
+
     pub fn snoop_cpu_read(&mut self, program_counter: u16, address: u16, data: u8) {
         if (self.cpu_snoop_list[address as usize] & CPU_READ) != 0 {
             self.track(TrackedEvent{
@@ -172,4 +228,25 @@ impl EventTracker {
             });
         }
     }
-}
\ No newline at end of file
+
+    pub fn snoop_interrupt(&mut self, nmi: bool, program_counter: u16) {
+        let event_type = if nmi {
+            EventType::Nmi{program_counter: program_counter}
+        } else {
+            EventType::Irq{program_counter: program_counter}
+        };
+        self.track(TrackedEvent{
+            scanline: self.current_scanline,
+            cycle: self.current_cycle,
+            event_type: event_type,
+        });
+    }
+
+    pub fn snoop_sprite_zero_hit(&mut self) {
+        self.track(TrackedEvent{
+            scanline: self.current_scanline,
+            cycle: self.current_cycle,
+            event_type: EventType::SpriteZeroHit,
+        });
+    }
+}
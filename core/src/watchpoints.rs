@@ -0,0 +1,73 @@
+// Value logging for the debugger. Every CPU write to a watched address is recorded along with
+// where the PPU was and which instruction did it, so a variable that keeps getting clobbered
+// can be traced back to the culprit. Unlike breakpoints, watches never stop emulation.
+
+use std::collections::VecDeque;
+
+// Enough for a few seconds of a variable written every frame
+const DEFAULT_LOG_LENGTH: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchEntry {
+    pub frame: u32,
+    pub scanline: u16,
+    pub dot: u16,
+    // Address of the instruction that performed the write, not the CPU's current PC
+    pub pc: u16,
+    pub address: u16,
+    pub old_value: u8,
+    pub new_value: u8,
+}
+
+pub struct Watchpoints {
+    pub addresses: Vec<u16>,
+    // Oldest entries are dropped first once this is full
+    pub log: VecDeque<WatchEntry>,
+    pub log_length: usize,
+    // Start of the instruction currently executing. Only kept up to date while any watch is set.
+    pub instruction_address: u16,
+}
+
+impl Watchpoints {
+    pub fn new() -> Watchpoints {
+        return Watchpoints {
+            addresses: Vec::new(),
+            log: VecDeque::new(),
+            log_length: DEFAULT_LOG_LENGTH,
+            instruction_address: 0,
+        };
+    }
+
+    pub fn add(&mut self, address: u16) {
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address);
+        }
+    }
+
+    pub fn remove(&mut self, address: u16) {
+        self.addresses.retain(|existing| *existing != address);
+    }
+
+    pub fn clear(&mut self) {
+        self.addresses.clear();
+        self.log.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.addresses.is_empty();
+    }
+
+    pub fn is_watched(&self, address: u16) -> bool {
+        return self.addresses.contains(&address);
+    }
+
+    pub fn record(&mut self, entry: WatchEntry) {
+        if self.log_length == 0 {
+            return;
+        }
+        while self.log.len() >= self.log_length {
+            self.log.pop_front();
+        }
+        self.log.push_back(entry);
+    }
+}
@@ -0,0 +1,108 @@
+// NES Zapper light gun. The Zapper has no shift register; reads from its port
+// report the trigger on bit 4 and the light sensor on bit 3, which reads 0 while
+// the photodiode is seeing light.
+// Reference: https://wiki.nesdev.com/w/index.php/Zapper
+
+use palettes::Palette;
+use ppu::PpuState;
+
+// The photodiode stays lit for a short while after the beam passes, roughly this many scanlines
+const LIGHT_PERSISTENCE_SCANLINES: u16 = 20;
+// Distance in pixels around the aim point that the sensor can see
+const SENSOR_RADIUS: i32 = 2;
+const BRIGHTNESS_THRESHOLD: u32 = 0x80 * 3;
+// The trigger switch stays closed for a few frames even after a quick tap
+const TRIGGER_HOLD_FRAMES: u8 = 3;
+
+#[derive(Clone)]
+pub struct ZapperState {
+    pub connected: bool,
+    pub x: i32,
+    pub y: i32,
+    pub trigger_pressed: bool,
+    pub trigger_frames_remaining: u8,
+}
+
+impl ZapperState {
+    pub fn new() -> ZapperState {
+        return ZapperState {
+            connected: false,
+            x: -1,
+            y: -1,
+            trigger_pressed: false,
+            trigger_frames_remaining: 0,
+        }
+    }
+
+    pub fn aim(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn set_trigger(&mut self, pressed: bool) {
+        if pressed && !self.trigger_pressed {
+            self.trigger_frames_remaining = TRIGGER_HOLD_FRAMES;
+        }
+        self.trigger_pressed = pressed;
+    }
+
+    pub fn trigger_active(&self) -> bool {
+        return self.trigger_pressed || self.trigger_frames_remaining > 0;
+    }
+
+    /// Should be called once per emulated frame.
+    pub fn new_frame(&mut self) {
+        if self.trigger_frames_remaining > 0 {
+            self.trigger_frames_remaining -= 1;
+        }
+    }
+
+    // Brightness comes from the palette being displayed, so a custom palette changes what
+    // the sensor sees just like it changes what the player sees
+    fn bright_pixel(ppu: &PpuState, palette: &Palette, x: i32, y: i32) -> bool {
+        if x < 0 || x >= 256 || y < 0 || y >= 240 {
+            return false;
+        }
+        let rgb = palette.rgb(ppu.screen[(y * 256 + x) as usize]);
+        let brightness = rgb[0] as u32 + rgb[1] as u32 + rgb[2] as u32;
+        return brightness >= BRIGHTNESS_THRESHOLD;
+    }
+
+    /// True if the sensor can see a bright pixel that the PPU has drawn recently. Only scanlines
+    /// the beam has passed within the persistence window count, so this depends on exactly when
+    /// during the frame the game polls the port.
+    pub fn light_detected(&self, ppu: &PpuState, palette: &Palette) -> bool {
+        if self.x < 0 || self.x >= 256 || self.y < 0 || self.y >= 240 {
+            return false;
+        }
+        let current_scanline = ppu.current_scanline;
+        for y in (self.y - SENSOR_RADIUS) ..= (self.y + SENSOR_RADIUS) {
+            if y < 0 || y >= 240 {
+                continue;
+            }
+            let row = y as u16;
+            let beam_has_passed = row < current_scanline || (row == current_scanline && (self.x as u16) < ppu.current_scanline_cycle);
+            if !beam_has_passed || current_scanline - row > LIGHT_PERSISTENCE_SCANLINES {
+                continue;
+            }
+            for x in (self.x - SENSOR_RADIUS) ..= (self.x + SENSOR_RADIUS) {
+                if ZapperState::bright_pixel(ppu, palette, x, y) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    pub fn read(&self, ppu: &PpuState, palette: &Palette) -> u8 {
+        let mut result = 0;
+        // Pulling the trigger blinds the sensor until the switch opens again
+        if self.trigger_frames_remaining > 0 || !self.light_detected(ppu, palette) {
+            result |= 0b0000_1000;
+        }
+        if self.trigger_active() {
+            result |= 0b0001_0000;
+        }
+        return result;
+    }
+}
@@ -0,0 +1,451 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::apu::ApuState;
+use rustico_core::apu::buffer_size_for_latency;
+use rustico_core::apu::AudioChannelState;
+use rustico_core::apu::ChannelId;
+use rustico_core::apu::FilterType;
+use rustico_core::apu::PlaybackRate;
+use rustico_core::apu::SampleQueue;
+use rustico_core::apu::filters::{DspFilter, HighPassIIR};
+use rustico_core::cartridge::mapper_from_file;
+use rustico_core::mmc::mapper::{Mapper, Mirroring};
+use rustico_core::region::Region;
+
+fn nrom() -> Box<dyn Mapper> {
+    return mapper_from_file(&common::nrom_image(&[])).unwrap();
+}
+
+fn vrc6() -> Box<dyn Mapper> {
+    let image = common::ines_image(24, 0, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+    return mapper_from_file(&image).unwrap();
+}
+
+// Register state as NesState::power_on leaves it
+fn new_apu() -> ApuState {
+    let mut apu = ApuState::new();
+    for address in 0x4000 .. 0x4010 {
+        apu.write_register(address, 0);
+    }
+    apu.write_register(0x4015, 0);
+    apu.write_register(0x4017, 0);
+    return apu;
+}
+
+// A constant volume square wave on pulse 1, with the length counter halted
+fn start_pulse_1(apu: &mut ApuState) {
+    apu.write_register(0x4015, 0b0000_0001);
+    apu.write_register(0x4000, 0b1011_1111);
+    apu.write_register(0x4002, 0xFD);
+    apu.write_register(0x4003, 0x00);
+}
+
+fn run(apu: &mut ApuState, mapper: &mut dyn Mapper, cycles: usize) -> Vec<i16> {
+    let mut samples = Vec::new();
+    for _ in 0 .. cycles {
+        apu.clock_apu(mapper);
+        samples.extend(apu.consume_samples());
+    }
+    return samples;
+}
+
+#[test]
+fn mappers_without_expansion_audio_pass_the_stock_mix_through() {
+    let mapper = nrom();
+    for sample in [-0.5f32, 0.0, 0.123, 0.5].iter() {
+        assert_eq!(mapper.mix_expansion_audio(*sample), *sample);
+    }
+}
+
+#[test]
+fn silent_expansion_chip_leaves_the_stock_mix_unchanged() {
+    let mut stock_apu = new_apu();
+    let mut stock_mapper = nrom();
+    start_pulse_1(&mut stock_apu);
+    let stock = run(&mut stock_apu, &mut *stock_mapper, 20000);
+
+    let mut expansion_apu = new_apu();
+    let mut expansion_mapper = vrc6();
+    start_pulse_1(&mut expansion_apu);
+    let with_expansion = run(&mut expansion_apu, &mut *expansion_mapper, 20000);
+
+    assert!(stock.iter().any(|sample| *sample != 0));
+    assert_eq!(stock, with_expansion);
+}
+
+#[test]
+fn active_expansion_channel_is_added_to_the_mix() {
+    let mut stock_apu = new_apu();
+    let mut stock_mapper = nrom();
+    let stock = run(&mut stock_apu, &mut *stock_mapper, 20000);
+
+    let mut expansion_apu = new_apu();
+    let mut expansion_mapper = vrc6();
+    // VRC6 pulse 1 in direct volume mode: a constant level
+    expansion_mapper.write_cpu(0x9003, 0x00);
+    expansion_mapper.write_cpu(0x9000, 0x8F);
+    expansion_mapper.write_cpu(0x9002, 0x80);
+    let with_expansion = run(&mut expansion_apu, &mut *expansion_mapper, 20000);
+
+    assert!(stock != with_expansion);
+}
+
+#[test]
+fn muted_pulse_is_silent_but_its_length_counter_keeps_running() {
+    let mut silent_apu = new_apu();
+    let mut silent_mapper = nrom();
+    let silence = run(&mut silent_apu, &mut *silent_mapper, 20000);
+
+    let mut apu = new_apu();
+    let mut mapper = nrom();
+    apu.write_register(0x4015, 0b0000_0001);
+    // Constant volume 15, length counter running
+    apu.write_register(0x4000, 0b1001_1111);
+    apu.write_register(0x4002, 0xFD);
+    // Length index 1: 254 half frames
+    apu.write_register(0x4003, 0b0000_1000);
+    apu.set_channel_enabled(&mut *mapper, ChannelId::Pulse1, false);
+    let starting_length = apu.pulse_1.length_counter.length;
+
+    let muted = run(&mut apu, &mut *mapper, 20000);
+    assert_eq!(muted, silence);
+    assert!(apu.pulse_1.length_counter.length < starting_length);
+
+    apu.set_channel_enabled(&mut *mapper, ChannelId::Pulse1, true);
+    let unmuted = run(&mut apu, &mut *mapper, 20000);
+    assert!(unmuted.iter().any(|sample| *sample != silence[0]));
+}
+
+#[test]
+fn channels_are_found_by_chip_and_name() {
+    let apu = new_apu();
+    let mapper = vrc6();
+    assert!(apu.channel_id(&*mapper, "2A03", "Pulse 1") == Some(ChannelId::Pulse1));
+    assert!(apu.channel_id(&*mapper, "2A03", "DMC") == Some(ChannelId::Dmc));
+    assert!(apu.channel_id(&*mapper, "VRC6", "Sawtooth") == Some(ChannelId::Expansion(2)));
+    assert!(apu.channel_id(&*mapper, "VRC6", "Triangle") == None);
+}
+
+#[test]
+fn muting_an_expansion_channel_silences_it_in_the_mapper_mix() {
+    let mut apu = new_apu();
+    let mut mapper = vrc6();
+    mapper.write_cpu(0x9003, 0x00);
+    mapper.write_cpu(0x9000, 0x8F);
+    mapper.write_cpu(0x9002, 0x80);
+    assert!(mapper.mix_expansion_audio(0.0) != 0.0);
+    apu.set_channel_enabled(&mut *mapper, ChannelId::Expansion(0), false);
+    assert_eq!(mapper.mix_expansion_audio(0.0), 0.0);
+}
+
+#[test]
+fn high_pass_filter_decays_dc_at_its_cutoff() {
+    let mut filter = HighPassIIR::new(44100.0, 90.0);
+    // One time constant, 1 / (2 pi 90 Hz), is about 78 samples at 44.1 kHz
+    let mut outputs = Vec::new();
+    for _ in 0 .. 44100 {
+        filter.consume(1.0);
+        outputs.push(filter.output());
+    }
+    assert!(outputs[0] > 0.98);
+    assert!((outputs[78] - (-1.0f32).exp()).abs() < 0.02, "after one time constant: {}", outputs[78]);
+    assert!(outputs[44099].abs() < 0.001);
+}
+
+#[test]
+fn dc_offset_decays_through_the_output_filters() {
+    let mut apu = new_apu();
+    let mut mapper = nrom();
+    apu.set_filter(FilterType::Nes, false);
+    apu.set_output_sample_rate(48000);
+    // $4011 sets the DMC output level directly, which just holds the DAC at a constant level
+    apu.write_register(0x4011, 0x7F);
+
+    // A tenth of a second is many time constants for the 90 Hz and 440 Hz high-pass filters
+    let samples = run(&mut apu, &mut *mapper, 178977);
+    assert!(samples.len() >= 4700, "only {} samples", samples.len());
+    let peak = samples.iter().map(|sample| (*sample as i32).abs()).max().unwrap();
+    assert!(peak > 5000, "the step never made it through: peak {}", peak);
+    assert!(samples[samples.len() - 100 ..].iter().all(|sample| sample.abs() < 10));
+}
+
+/// CPU cycles from a $4017 write selecting the 5-step sequence to the half frame clock it
+/// triggers once the sequencer restarts, for a write on an odd or even cycle.
+fn frame_counter_reset_delay(odd_cycle: bool) -> u32 {
+    let mut apu = new_apu();
+    let mut mapper = nrom();
+    run(&mut apu, &mut *mapper, 10);
+    if ((apu.current_cycle & 0b1) != 0) != odd_cycle {
+        run(&mut apu, &mut *mapper, 1);
+    }
+    let half_frames = apu.half_frame_counter;
+    apu.write_register(0x4017, 0x80);
+    let mut cycles = 0;
+    while apu.half_frame_counter == half_frames {
+        run(&mut apu, &mut *mapper, 1);
+        cycles += 1;
+    }
+    return cycles;
+}
+
+#[test]
+fn frame_counter_write_on_an_odd_cycle_takes_effect_after_3_cycles() {
+    assert_eq!(frame_counter_reset_delay(true), 3);
+}
+
+#[test]
+fn frame_counter_write_on_an_even_cycle_takes_effect_after_4_cycles() {
+    assert_eq!(frame_counter_reset_delay(false), 4);
+}
+
+#[test]
+fn reading_4015_acknowledges_only_the_frame_irq() {
+    let mut apu = new_apu();
+    let mut mapper = nrom();
+    // The 4-step sequence raises its IRQ at the end of every sequence
+    run(&mut apu, &mut *mapper, 30000);
+    assert!(apu.frame_interrupt);
+    apu.dmc.interrupt_flag = true;
+
+    assert_eq!(apu.read_register(0x4015) & 0xC0, 0xC0);
+    assert!(!apu.frame_interrupt);
+    assert!(apu.dmc.interrupt_flag);
+    assert_eq!(apu.read_register(0x4015) & 0xC0, 0x80);
+    assert!(apu.irq_signal());
+}
+
+#[test]
+fn status_reports_one_bit_per_active_channel() {
+    let mut apu = new_apu();
+    assert_eq!(apu.debug_read_register(0x4015), 0);
+    // A one byte sample
+    apu.write_register(0x4013, 0);
+    apu.write_register(0x4015, 0b0001_1111);
+    assert_eq!(apu.debug_read_register(0x4015), 0b0001_0000, "only the DMC starts on its own");
+
+    // Each length counter load lights up that channel's bit
+    for (bit, address) in [0x4003u16, 0x4007, 0x400B, 0x400F].iter().enumerate() {
+        apu.write_register(*address, 0b0000_1000);
+        assert_eq!(apu.debug_read_register(0x4015) & 0x0F, (1 << (bit + 1)) - 1);
+    }
+
+    // Disabling a channel zeroes its length counter, and so its bit
+    apu.write_register(0x4015, 0b0000_0101);
+    assert_eq!(apu.debug_read_register(0x4015), 0b0000_0101);
+    // The IRQ flags sit in the top two bits, leaving bit 5 (open bus) clear
+    apu.frame_interrupt = true;
+    apu.dmc.interrupt_flag = true;
+    assert_eq!(apu.debug_read_register(0x4015), 0b1100_0101);
+}
+
+#[test]
+fn dmc_sample_address_wraps_from_ffff_to_8000() {
+    let mut prg = vec![0u8; 32 * 1024];
+    prg[0x7FFF] = 0xAA;
+    prg[0x0000] = 0x55;
+    prg[0x0001] = 0x66;
+    let mut mapper = mapper_from_file(&common::ines_image(0, 0, &prg, &vec![0u8; 8 * 1024])).unwrap();
+    let mut apu = new_apu();
+    apu.dmc.current_address = 0xFFFF;
+    apu.dmc.bytes_remaining = 3;
+
+    let mut samples = Vec::new();
+    for _ in 0 .. 3 {
+        apu.dmc.read_next_sample(&mut *mapper);
+        samples.push(apu.dmc.sample_buffer);
+    }
+    assert_eq!(samples, vec![0xAA, 0x55, 0x66]);
+    assert_eq!(apu.dmc.current_address, 0x8002);
+}
+
+// A cartridge with nothing on it that keeps every 2A03 mix level the APU hands over, before
+// any filtering
+struct MixProbe {
+    levels: Vec<f32>,
+}
+
+impl Mapper for MixProbe {
+    fn write_cpu(&mut self, _address: u16, _data: u8) {}
+    fn write_ppu(&mut self, _address: u16, _data: u8) {}
+    fn debug_read_cpu(&self, _address: u16) -> Option<u8> {return None;}
+    fn debug_read_ppu(&self, _address: u16) -> Option<u8> {return None;}
+    fn mirroring(&self) -> Mirroring {return Mirroring::Horizontal;}
+    fn record_expansion_audio_output(&mut self, nes_sample: f32) {
+        self.levels.push(nes_sample);
+    }
+}
+
+/// How far the mix swings as pulse 1 toggles at constant `volume` with the given gain
+fn pulse_1_swing(volume: u8, gain: f32) -> f32 {
+    let mut apu = new_apu();
+    let mut probe = MixProbe {levels: Vec::new()};
+    start_pulse_1(&mut apu);
+    apu.write_register(0x4000, 0b1011_0000 | volume);
+    apu.set_channel_volume(ChannelId::Pulse1, gain);
+    run(&mut apu, &mut probe, 20000);
+    let highest = probe.levels.iter().cloned().fold(f32::MIN, f32::max);
+    let lowest = probe.levels.iter().cloned().fold(f32::MAX, f32::min);
+    return highest - lowest;
+}
+
+#[test]
+fn half_gain_halves_the_pulse_level_going_into_the_mixer() {
+    assert_eq!(pulse_1_swing(14, 0.5), pulse_1_swing(7, 1.0));
+    // The mixer itself isn't linear, so the output swing comes out a bit over half
+    let ratio = pulse_1_swing(14, 0.5) / pulse_1_swing(14, 1.0);
+    assert!(ratio > 0.5 && ratio < 0.6, "{}", ratio);
+}
+
+#[test]
+fn zero_gain_silences_pulse_1() {
+    assert_eq!(pulse_1_swing(15, 0.0), 0.0);
+    assert!(pulse_1_swing(15, 1.0) > 0.0);
+}
+
+#[test]
+fn sample_queue_drains_in_the_order_samples_were_pushed() {
+    let mut queue = SampleQueue::new(4);
+    queue.push(0.1);
+    queue.push(0.2);
+    queue.push(0.3);
+    assert_eq!(queue.len(), 3);
+
+    let mut out = [0f32; 2];
+    assert_eq!(queue.drain(&mut out), 2);
+    assert_eq!(out, [0.1, 0.2]);
+
+    // Wraps around the end of the buffer
+    queue.push(0.4);
+    queue.push(0.5);
+    queue.push(0.6);
+    let mut out = [9f32; 5];
+    assert_eq!(queue.drain(&mut out), 4);
+    assert_eq!(out, [0.3, 0.4, 0.5, 0.6, 9.0]);
+    assert_eq!(queue.len(), 0);
+    assert_eq!(queue.dropped(), 0);
+}
+
+#[test]
+fn full_sample_queue_drops_the_oldest_samples() {
+    let mut queue = SampleQueue::new(3);
+    for sample in [0.1f32, 0.2, 0.3, 0.4, 0.5].iter() {
+        queue.push(*sample);
+    }
+    assert_eq!(queue.len(), queue.capacity());
+    assert_eq!(queue.dropped(), 2);
+
+    let mut out = [0f32; 3];
+    assert_eq!(queue.drain(&mut out), 3);
+    assert_eq!(out, [0.3, 0.4, 0.5]);
+}
+
+fn start_triangle(apu: &mut ApuState, linear_control: u8) {
+    apu.write_register(0x4015, 0b0000_0100);
+    apu.write_register(0x4008, linear_control);
+    apu.write_register(0x400A, 0xFD);
+    apu.write_register(0x400B, 0x00);
+}
+
+#[test]
+fn retriggered_triangle_plays_before_the_linear_counter_reloads() {
+    let mut apu = new_apu();
+    start_triangle(&mut apu, 0x10);
+    assert_eq!(apu.triangle.linear_counter_current, 0);
+    assert!(apu.triangle.playing());
+
+    // A reload value of 0 stays silent no matter what
+    start_triangle(&mut apu, 0x00);
+    assert!(!apu.triangle.playing());
+}
+
+#[test]
+fn held_triangle_keeps_playing_and_released_triangle_stops() {
+    let mut mapper = nrom();
+    // Control set: the reload flag never clears, so the note is held indefinitely
+    let mut held = new_apu();
+    start_triangle(&mut held, 0x84);
+    run(&mut held, &mut *mapper, 40000);
+    assert_eq!(held.triangle.linear_counter_current, 4);
+    assert!(held.triangle.playing());
+
+    // Control clear: the first quarter frame reloads to 4, and 4 more run it out
+    let mut released = new_apu();
+    start_triangle(&mut released, 0x04);
+    run(&mut released, &mut *mapper, 40000);
+    assert_eq!(released.triangle.linear_counter_current, 0);
+    assert!(!released.triangle.playing());
+}
+
+#[test]
+fn snapshot_reports_each_channel() {
+    let mut apu = new_apu();
+    start_pulse_1(&mut apu);
+    apu.pulse_1.mute();
+    let snapshot = apu.snapshot();
+
+    assert!(snapshot.pulse_1.playing && snapshot.pulse_1.muted);
+    // 1.789773 MHz / (16 * ($0FD + 1))
+    assert_eq!(snapshot.pulse_1.frequency.map(|frequency| frequency.round()), Some(440.0));
+    assert!(snapshot.pulse_1.amplitude > 0.0);
+    assert!(!snapshot.pulse_2.playing && !snapshot.pulse_2.muted);
+    assert!(!snapshot.triangle.playing);
+    assert_eq!(snapshot.noise.frequency, None);
+
+    // A copy, not a view
+    apu.write_register(0x4015, 0);
+    assert!(snapshot.pulse_1.playing);
+    assert!(!apu.snapshot().pulse_1.playing);
+}
+
+fn noise_rate_index(apu: &mut ApuState, period_index: u8) -> usize {
+    apu.write_register(0x400E, period_index);
+    return match apu.noise.rate() {
+        PlaybackRate::LfsrRate {index, max: _} => index,
+        _ => panic!("noise should report an LFSR rate"),
+    };
+}
+
+#[test]
+fn noise_rate_recovers_the_period_index_in_every_region() {
+    for region in [Region::Ntsc, Region::Pal].iter() {
+        let mut apu = new_apu();
+        apu.set_region(*region);
+        for period_index in 0 .. 16 {
+            // Rate indices count up as the period shortens, the register counts down
+            assert_eq!(noise_rate_index(&mut apu, period_index), 0xF - period_index as usize);
+        }
+    }
+}
+
+#[test]
+fn buffer_latency_rounds_up_to_whole_samples() {
+    assert_eq!(buffer_size_for_latency(48000, 20), 960);
+    // 44.1 samples per millisecond
+    assert_eq!(buffer_size_for_latency(44100, 1), 45);
+    assert_eq!(buffer_size_for_latency(44100, 0), 1);
+}
+
+#[test]
+fn buffer_latency_sizes_the_output_buffer_and_follows_rate_changes() {
+    let mut apu = new_apu();
+    apu.set_output_sample_rate(48000);
+    apu.set_buffer_latency(20);
+    assert_eq!(apu.output_buffer.len(), 960);
+
+    apu.set_output_sample_rate(44100);
+    assert_eq!(apu.output_buffer.len(), 882);
+    apu.set_region(Region::Pal);
+    assert_eq!(apu.output_buffer.len(), 882);
+}
+
+#[test]
+fn buffer_latency_survives_a_power_cycle() {
+    let mut nes = common::nes_with_program(&[]);
+    nes.apu.set_output_sample_rate(48000);
+    nes.apu.set_buffer_latency(20);
+    nes.power_on();
+    assert_eq!(nes.apu.buffer_ms, Some(20));
+    assert_eq!(nes.apu.output_buffer.len(), 960);
+}
@@ -0,0 +1,146 @@
+extern crate rustico_core;
+
+use rustico_core::asm::{assemble_source, disassemble, disassemble_to_json};
+
+fn texts(bytes: &[u8], base_addr: u16) -> Vec<String> {
+    return disassemble(bytes, base_addr).iter().map(|instruction| instruction.text()).collect();
+}
+
+#[test]
+fn disassembles_each_addressing_mode() {
+    let bytes = [
+        0xEA,             // NOP
+        0xA9, 0x10,       // LDA #$10
+        0xA5, 0x20,       // LDA $20
+        0xB5, 0x21,       // LDA $21, X
+        0xB6, 0x22,       // LDX $22, Y
+        0xA1, 0x23,       // LDA ($23, X)
+        0xB1, 0x24,       // LDA ($24), Y
+        0xAD, 0x34, 0x12, // LDA $1234
+        0xBD, 0x35, 0x12, // LDA $1235, X
+        0xB9, 0x36, 0x12, // LDA $1236, Y
+        0x6C, 0xFC, 0xFF, // JMP ($FFFC)
+        0x0A,             // ASL A
+    ];
+    assert_eq!(texts(&bytes, 0x8000), vec![
+        "NOP", "LDA #$10", "LDA $20", "LDA $21, X", "LDX $22, Y", "LDA ($23, X)", "LDA ($24), Y",
+        "LDA $1234", "LDA $1235, X", "LDA $1236, Y", "JMP ($FFFC)", "ASL A"]);
+}
+
+#[test]
+fn records_addresses_and_raw_bytes() {
+    let instructions = disassemble(&[0xA9, 0x01, 0x8D, 0x00, 0x20, 0x60], 0xC000);
+    let addresses: Vec<u16> = instructions.iter().map(|i| i.address).collect();
+    assert_eq!(addresses, vec![0xC000, 0xC002, 0xC005]);
+    assert_eq!(instructions[1].bytes, vec![0x8D, 0x00, 0x20]);
+    assert_eq!(instructions[2].mnemonic, "RTS");
+    assert_eq!(instructions[2].operand, "");
+}
+
+#[test]
+fn branches_show_their_absolute_destination() {
+    // BNE -2 branches to itself, BEQ +4 skips two bytes past the next instruction
+    assert_eq!(texts(&[0xD0, 0xFE, 0xF0, 0x04], 0x8000), vec!["BNE $8000", "BEQ $8008"]);
+    let instructions = disassemble(&[0xD0, 0xFE, 0x20, 0x00, 0x90], 0x8000);
+    assert_eq!(instructions[0].target(), Some(0x8000));
+    assert_eq!(instructions[1].target(), Some(0x9000));
+}
+
+#[test]
+fn flags_unofficial_opcodes() {
+    let instructions = disassemble(&[0xA7, 0x10, 0xA5, 0x10], 0x8000);
+    assert!(instructions[0].unofficial);
+    assert_eq!(instructions[0].text(), "LAX $10");
+    assert!(!instructions[1].unofficial);
+}
+
+#[test]
+fn truncated_instruction_becomes_raw_bytes() {
+    let instructions = disassemble(&[0xEA, 0xAD, 0x00], 0x8000);
+    assert_eq!(instructions.len(), 2);
+    assert_eq!(instructions[1].text(), ".byte $AD, $00");
+    assert_eq!(instructions[1].target(), None);
+}
+
+#[test]
+fn assembles_branches_backwards_and_forwards() {
+    let source = "
+        .org $8000
+    loop:
+        DEX          ; $8000
+        BNE loop     ; $8001, back 3
+        BEQ done     ; $8003, ahead 1
+        NOP          ; $8005
+    done:
+        RTS          ; $8006
+    ";
+    assert_eq!(assemble_source(source), Ok(vec![0xCA, 0xD0, 0xFD, 0xF0, 0x01, 0xEA, 0x60]));
+}
+
+#[test]
+fn forward_labels_assume_a_full_address() {
+    // The label's value isn't known yet on the first pass, so even a zero page target gets
+    // absolute addressing, and the size stays consistent between passes
+    let source = "
+        .org $8000
+        LDA later
+        LDA $10
+        RTS
+        later = $0010
+    ";
+    assert_eq!(assemble_source(source), Ok(vec![0xAD, 0x10, 0x00, 0xA5, 0x10, 0x60]));
+}
+
+#[test]
+fn assembles_indirect_addressing_modes() {
+    let source = "
+        .org $C000
+        LDA ($20),Y
+        STA ( $21 , x )
+        JMP ($FFFC)
+    ";
+    assert_eq!(assemble_source(source), Ok(vec![0xB1, 0x20, 0x81, 0x21, 0x6C, 0xFC, 0xFF]));
+}
+
+#[test]
+fn assembled_source_disassembles_back_to_itself() {
+    let bytes = assemble_source(".org $8000\nLDA #$01\nSTA $2000\nLDX $30, Y\n").unwrap();
+    assert_eq!(texts(&bytes, 0x8000), vec!["LDA #$01", "STA $2000", "LDX $30, Y"]);
+}
+
+#[test]
+fn reports_out_of_range_values_with_their_line() {
+    let branch = assemble_source(".org $8000\nfar:\n.byte 0\n.org $8100\nBNE far\n").unwrap_err();
+    assert_eq!(branch.line, 5);
+    assert!(branch.message.contains("out of range"), "{}", branch.message);
+
+    let immediate = assemble_source("LDA #$100").unwrap_err();
+    assert_eq!(immediate.line, 1);
+    assert!(immediate.message.contains("doesn't fit"), "{}", immediate.message);
+
+    let word = assemble_source(".org $8000\n.word $10000").unwrap_err();
+    assert_eq!(word.line, 2);
+}
+
+#[test]
+fn reports_unknown_instructions_and_labels() {
+    assert_eq!(assemble_source("NOP\nFOO $10").unwrap_err().line, 2);
+    assert!(assemble_source("JMP nowhere").unwrap_err().message.contains("nowhere"));
+    assert!(assemble_source("a:\na:").unwrap_err().message.contains("twice"));
+}
+
+#[test]
+fn json_export_lists_every_field() {
+    // JSR $9000, BNE -2, LAX $10, and a lone LDA opcode with its operand cut off
+    let json = disassemble_to_json(&[0x20, 0x00, 0x90, 0xD0, 0xFE, 0xA7, 0x10, 0xA9], 0x8000);
+    assert_eq!(json, concat!(
+        "[{\"address\": 32768, \"bytes\": [32, 0, 144], \"mnemonic\": \"JSR\", \"operand\": \"$9000\", \"target\": 36864, \"unofficial\": false}, ",
+        "{\"address\": 32771, \"bytes\": [208, 254], \"mnemonic\": \"BNE\", \"operand\": \"$8003\", \"target\": 32771, \"unofficial\": false}, ",
+        "{\"address\": 32773, \"bytes\": [167, 16], \"mnemonic\": \"LAX\", \"operand\": \"$10\", \"target\": null, \"unofficial\": true}, ",
+        "{\"address\": 32775, \"bytes\": [169], \"mnemonic\": \".byte\", \"operand\": \"$A9\", \"target\": null, \"unofficial\": false}]"));
+}
+
+#[test]
+fn json_export_of_nothing_is_an_empty_array() {
+    assert_eq!(disassemble_to_json(&[], 0x8000), "[]");
+}
@@ -0,0 +1,44 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::axrom::AxRom;
+use rustico_core::mmc::mapper::{Mapper, Mirroring};
+
+// 8 numbered 32 KiB banks. Bank 7, mapped at power on, has a $01 at $8123 to conflict with.
+fn axrom(submapper: u8) -> AxRom {
+    let mut prg = common::numbered_banks(32 * 1024, 8);
+    prg[7 * 32 * 1024 + 0x0123] = 0x01;
+    let image = common::with_submapper(common::ines_image(7, 0, &prg, &[]), submapper);
+    return AxRom::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+#[test]
+fn submapper_2_ands_writes_with_the_rom_byte() {
+    let mut mapper = axrom(2);
+    assert!(mapper.bus_conflicts);
+    mapper.write_cpu(0x8123, 0x16);
+    // $16 & $01: bank 0, and the nametable bit was lost too
+    assert_eq!(mapper.prg_bank, 0);
+    assert!(mapper.mirroring() == Mirroring::OneScreenLower);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(0));
+}
+
+#[test]
+fn a_write_matching_the_rom_byte_survives_the_conflict() {
+    let mut mapper = axrom(2);
+    mapper.write_cpu(0x8000, 0x17);
+    // Bank 7 is full of $07, so bank 7 stays selected but bit 4 is masked off
+    assert_eq!(mapper.prg_bank, 7);
+    assert!(mapper.mirroring() == Mirroring::OneScreenLower);
+}
+
+#[test]
+fn other_submappers_take_the_written_value() {
+    let mut mapper = axrom(0);
+    assert!(!mapper.bus_conflicts);
+    mapper.write_cpu(0x8123, 0x16);
+    assert_eq!(mapper.prg_bank, 6);
+    assert!(mapper.mirroring() == Mirroring::OneScreenUpper);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(6));
+}
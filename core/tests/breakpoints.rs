@@ -0,0 +1,87 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::breakpoints::BreakpointKind;
+use rustico_core::nes::{NesState, RunOutcome};
+
+use std::time::Duration;
+
+const LOOP: [u8; 9] = [
+    0xAD, 0x00, 0x03, // $8000: LDA $0300
+    0x8D, 0x01, 0x03, // $8003: STA $0301
+    0x4C, 0x00, 0x80, // $8006: JMP $8000
+];
+
+fn looping_nes() -> NesState {
+    return common::nes_with_program(&LOOP);
+}
+
+#[test]
+fn execute_breakpoints_stop_before_the_instruction() {
+    let mut nes = looping_nes();
+    nes.breakpoints.add_execute(0x8003);
+    nes.run_until_vblank();
+    assert_eq!(nes.breakpoints.take_hit(), Some((0x8003, BreakpointKind::Execute)));
+    assert_eq!(nes.registers.pc, 0x8003);
+}
+
+#[test]
+fn read_breakpoints_stop_after_the_reading_instruction() {
+    let mut nes = looping_nes();
+    nes.breakpoints.add(BreakpointKind::Read, 0x02F0, 0x0300);
+    nes.run_until_vblank();
+    assert_eq!(nes.breakpoints.take_hit(), Some((0x0300, BreakpointKind::Read)));
+    assert_eq!(nes.registers.pc, 0x8003);
+}
+
+#[test]
+fn write_breakpoints_stop_after_the_writing_instruction() {
+    let mut nes = looping_nes();
+    // Reads of $0300 don't count
+    nes.breakpoints.add(BreakpointKind::Write, 0x0300, 0x0301);
+    nes.run_until_vblank();
+    assert_eq!(nes.breakpoints.take_hit(), Some((0x0301, BreakpointKind::Write)));
+    assert_eq!(nes.registers.pc, 0x8006);
+}
+
+#[test]
+fn running_again_clears_a_hit_nobody_took() {
+    let mut nes = looping_nes();
+    nes.breakpoints.add_execute(0x8003);
+    nes.run_until_vblank();
+    assert!(nes.breakpoints.hit.is_some());
+
+    nes.breakpoints.remove(BreakpointKind::Execute, 0x8003, 0x8003);
+    let frame = nes.ppu.current_frame;
+    nes.run_until_vblank();
+    assert!(nes.breakpoints.hit.is_none());
+    assert_eq!(nes.ppu.current_scanline, 242);
+    assert_eq!(nes.ppu.current_frame, frame);
+
+    assert_eq!(nes.run_frames_bounded(3, Duration::from_secs(60)), RunOutcome::Completed);
+}
+
+#[test]
+fn an_expired_deadline_stops_before_the_first_frame() {
+    let mut nes = looping_nes();
+    let frame = nes.ppu.current_frame;
+    assert_eq!(nes.run_frames_bounded(3, Duration::from_secs(0)), RunOutcome::TimedOut(0));
+    assert_eq!(nes.ppu.current_frame, frame);
+}
+
+#[test]
+fn resuming_from_an_execute_breakpoint_runs_past_it() {
+    let mut nes = looping_nes();
+    nes.breakpoints.add_execute(0x8003);
+    nes.run_until_vblank();
+    nes.breakpoints.take_hit();
+
+    // The next stop is the same breakpoint on the loop's next pass, not a repeat of this one
+    let clock_before = nes.master_clock;
+    nes.run_until_vblank();
+    assert_eq!(nes.breakpoints.take_hit(), Some((0x8003, BreakpointKind::Execute)));
+    // STA, JMP and LDA: a full trip around the loop
+    let cycles = (nes.master_clock - clock_before) / nes.region.master_clocks_per_cpu_cycle() as u64;
+    assert_eq!(cycles, 4 + 3 + 4);
+}
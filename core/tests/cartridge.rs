@@ -0,0 +1,155 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::cartridge::{crc32, mapper_from_file, mapper_from_reader, rom_hash, sha1, CartridgeError, RomHashes};
+use rustico_core::mmc::{is_mapper_supported, supported_mappers};
+
+use std::io::Read;
+
+#[test]
+fn crc32_matches_the_standard_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn sha1_matches_the_fips_test_vectors() {
+    assert_eq!(RomHashes::from_data(b"abc").sha1_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(&sha1(b"")[0 .. 4], &[0xda, 0x39, 0xa3, 0xee]);
+    // Long enough to need a second padding block
+    let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    assert_eq!(RomHashes::from_data(two_blocks).sha1_hex(), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+}
+
+#[test]
+fn ines_hashes_skip_the_header() {
+    let prg = common::nrom_prg(&[0xEA]);
+    let chr = vec![0x55u8; 8 * 1024];
+    let image = common::ines_image(0, 0, &prg, &chr);
+    let mut rom_data = prg.clone();
+    rom_data.extend_from_slice(&chr);
+    assert_eq!(rom_hash(&image), RomHashes::from_data(&rom_data));
+
+    // A header-only difference, here the mirroring bit, doesn't change the hash
+    let vertical = common::ines_image(0, 0x01, &prg, &chr);
+    assert_eq!(rom_hash(&vertical), rom_hash(&image));
+}
+
+fn load_error(image: &[u8]) -> Option<CartridgeError> {
+    return mapper_from_file(image).err();
+}
+
+struct BrokenReader;
+
+impl Read for BrokenReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, "disk on fire"));
+    }
+}
+
+#[test]
+fn unreadable_files_are_read_errors() {
+    match mapper_from_reader(&mut BrokenReader).err() {
+        Some(CartridgeError::ReadError(reason)) => assert!(reason.contains("disk on fire")),
+        other => panic!("expected ReadError, got {:?}", other),
+    }
+}
+
+#[test]
+fn files_too_short_for_a_header_are_bad_magic() {
+    assert_eq!(load_error(b""), Some(CartridgeError::BadMagic));
+    assert_eq!(load_error(b"NES\x1A\x01"), Some(CartridgeError::BadMagic));
+}
+
+#[test]
+fn unknown_signatures_are_bad_magic() {
+    let mut image = common::nrom_image(&[]);
+    image[0 .. 4].copy_from_slice(b"SNES");
+    assert_eq!(load_error(&image), Some(CartridgeError::BadMagic));
+}
+
+#[test]
+fn headers_without_prg_rom_are_bad_headers() {
+    let image = common::ines_image(0, 0, &[], &[]);
+    match load_error(&image) {
+        Some(CartridgeError::BadHeader(_)) => {},
+        other => panic!("expected BadHeader, got {:?}", other),
+    }
+}
+
+#[test]
+fn files_shorter_than_their_header_says_are_truncated() {
+    let mut image = common::nrom_image(&[]);
+    image.truncate(16 + 1024);
+    assert_eq!(load_error(&image), Some(CartridgeError::TruncatedRom));
+}
+
+#[test]
+fn unknown_mapper_numbers_are_unsupported() {
+    let image = common::ines_image(0xFF, 0, &common::nrom_prg(&[]), &[]);
+    assert_eq!(load_error(&image), Some(CartridgeError::UnsupportedMapper(0xFF)));
+}
+
+#[test]
+fn mappers_refusing_the_header_are_mapper_errors() {
+    // MMC3 with both volatile and battery backed PRG RAM, which it has no way to map
+    let image = common::ines_image(4, 0b0000_0010, &common::nrom_prg(&[]), &vec![0u8; 8 * 1024]);
+    let mut image = common::with_submapper(image, 0);
+    image[10] = 0x77;
+    match load_error(&image) {
+        Some(CartridgeError::MapperError(reason)) => assert!(reason.contains("PRG RAM")),
+        other => panic!("expected MapperError, got {:?}", other),
+    }
+}
+
+// A NES 2.0 image for any mapper number, with enough PRG and CHR ROM for all of them
+fn image_for_mapper(number: u16) -> Vec<u8> {
+    let image = common::ines_image((number & 0xFF) as u8, 0, &vec![0u8; 128 * 1024], &vec![0u8; 64 * 1024]);
+    let mut image = common::with_submapper(image, 0);
+    image[8] |= (number >> 8) as u8;
+    return image;
+}
+
+#[test]
+fn every_supported_mapper_loads() {
+    for &number in supported_mappers() {
+        assert!(mapper_from_file(&image_for_mapper(number)).is_ok(), "mapper {} should load", number);
+    }
+}
+
+#[test]
+fn every_other_mapper_is_unsupported() {
+    for number in 0 .. 1024 {
+        if !is_mapper_supported(number, 0) {
+            assert_eq!(mapper_from_file(&image_for_mapper(number)).err(), Some(CartridgeError::UnsupportedMapper(number)));
+        }
+    }
+}
+
+fn image_with_trainer(mapper_number: u8) -> Vec<u8> {
+    let trainer: Vec<u8> = (0 .. 512).map(|i| (i % 251) as u8).collect();
+    let mut image = common::ines_image(mapper_number, 0b0000_0100, &common::nrom_prg(&[0x12]), &vec![0u8; 8 * 1024]);
+    image.splice(16 .. 16, trainer);
+    return image;
+}
+
+#[test]
+fn trainers_are_skipped_before_prg_rom() {
+    let mapper = mapper_from_file(&image_with_trainer(0)).unwrap();
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(0x12));
+    assert_eq!(mapper.debug_read_cpu(0xFFFC), Some(0x00));
+    assert_eq!(mapper.debug_read_cpu(0xFFFD), Some(0x80));
+}
+
+#[test]
+fn trainers_are_mapped_at_7000() {
+    for mapper_number in [0u8, 1, 4].iter() {
+        let mapper = mapper_from_file(&image_with_trainer(*mapper_number)).unwrap();
+        assert_eq!(mapper.debug_read_cpu(0x6FFF), Some(0x00), "mapper {}", mapper_number);
+        for i in 0 .. 512u16 {
+            assert_eq!(mapper.debug_read_cpu(0x7000 + i), Some((i % 251) as u8), "mapper {}", mapper_number);
+        }
+        assert_eq!(mapper.debug_read_cpu(0x7200), Some(0x00), "mapper {}", mapper_number);
+    }
+}
@@ -0,0 +1,47 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::memory::{CDL_DATA, CDL_EXECUTED, CDL_OPERAND};
+
+#[test]
+fn cdl_flags_what_a_routine_touches() {
+    let mut program = vec![
+        0xAD, 0x00, 0x90, // $8000: LDA $9000
+        0x20, 0x10, 0x80, // $8003: JSR $8010
+        0x4C, 0x06, 0x80, // $8006: JMP $8006
+    ];
+    program.resize(0x10, 0xEA);
+    program.extend_from_slice(&[
+        0xE8,             // $8010: INX
+        0x60,             // $8011: RTS
+    ]);
+    let mut nes = common::nes_with_program(&program);
+    nes.enable_cdl(true);
+    assert_eq!(nes.cdl_buffer().len(), 32 * 1024);
+    common::step_instructions(&mut nes, 6);
+
+    let cdl = nes.cdl_buffer();
+    for &offset in [0x00, 0x03, 0x06, 0x10, 0x11].iter() {
+        assert_eq!(cdl[offset], CDL_EXECUTED, "opcode at ${:04X}", 0x8000 + offset);
+    }
+    for &offset in [0x01, 0x02, 0x04, 0x05, 0x07, 0x08].iter() {
+        assert_eq!(cdl[offset], CDL_OPERAND, "operand at ${:04X}", 0x8000 + offset);
+    }
+    assert_eq!(cdl[0x1000], CDL_DATA);
+    // The NOP padding between the loop and the subroutine never runs
+    for offset in 0x09 .. 0x10 {
+        assert_eq!(cdl[offset], 0, "padding at ${:04X}", 0x8000 + offset);
+    }
+    assert_eq!(cdl[0x1001], 0);
+}
+
+#[test]
+fn disabling_the_cdl_drops_the_log() {
+    let mut nes = common::nes_with_program(&[0x4C, 0x00, 0x80]); // JMP $8000
+    nes.enable_cdl(true);
+    common::step_instructions(&mut nes, 2);
+    assert_eq!(nes.cdl_buffer()[0], CDL_EXECUTED);
+    nes.enable_cdl(false);
+    assert!(nes.cdl_buffer().is_empty());
+}
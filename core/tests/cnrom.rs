@@ -0,0 +1,43 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::cnrom::CnRom;
+use rustico_core::mmc::mapper::Mapper;
+
+// 32 KiB of PRG, all $FF apart from a $01 at $8123 to conflict with, and 4 numbered CHR banks
+fn cnrom(submapper: u8) -> CnRom {
+    let mut prg = vec![0xFFu8; 32 * 1024];
+    prg[0x0123] = 0x01;
+    let chr = common::numbered_banks(8 * 1024, 4);
+    let image = common::with_submapper(common::ines_image(3, 0, &prg, &chr), submapper);
+    return CnRom::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+#[test]
+fn writes_select_the_chr_bank() {
+    let mut mapper = cnrom(0);
+    assert!(!mapper.bus_conflicts);
+    mapper.write_cpu(0x8000, 2);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(2));
+    assert_eq!(mapper.debug_read_ppu(0x1FFF), Some(2));
+    assert_eq!(mapper.chr_bank_at(0x1000), Some(2));
+
+    // Oversized bank numbers wrap, and the debugger reports the bank that actually answers
+    mapper.write_cpu(0x8000, 7);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(3));
+    assert_eq!(mapper.chr_bank_at(0x0000), Some(3));
+    assert_eq!(mapper.prg_bank_at(0xC000), Some(0));
+}
+
+#[test]
+fn submapper_2_ands_writes_with_the_rom_byte() {
+    let mut mapper = cnrom(2);
+    assert!(mapper.bus_conflicts);
+    mapper.write_cpu(0x8123, 0x03);
+    assert_eq!(mapper.chr_bank, 1);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(1));
+
+    mapper.write_cpu(0x8000, 0x03);
+    assert_eq!(mapper.chr_bank, 3);
+}
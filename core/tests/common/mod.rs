@@ -0,0 +1,83 @@
+// Shared helpers for the integration tests: tiny cartridge images built in memory, so tests
+// don't depend on ROM files that can't be committed.
+#![allow(dead_code)]
+
+use rustico_core::cartridge::mapper_from_file;
+use rustico_core::ines::INesCartridge;
+use rustico_core::nes::NesState;
+
+// Where nrom_image points the NMI and IRQ vectors; holds an RTI
+pub const INTERRUPT_HANDLER: u16 = 0xFFF0;
+
+/// An iNES 1.0 image. `prg` must be a multiple of 16 KiB and `chr` of 8 KiB (empty for CHR RAM).
+/// `flags_6` supplies the low nybble of header byte 6: mirroring, battery, trainer, four-screen.
+pub fn ines_image(mapper_number: u8, flags_6: u8, prg: &[u8], chr: &[u8]) -> Vec<u8> {
+    let mut image = vec![0x4E, 0x45, 0x53, 0x1A,
+        (prg.len() / (16 * 1024)) as u8,
+        (chr.len() / (8 * 1024)) as u8,
+        ((mapper_number & 0x0F) << 4) | (flags_6 & 0x0F),
+        mapper_number & 0xF0,
+        0, 0, 0, 0, 0, 0, 0, 0];
+    image.extend_from_slice(prg);
+    image.extend_from_slice(chr);
+    return image;
+}
+
+/// 32 KiB of NROM PRG with `program` at $8000 and the rest filled with NOP. Reset jumps to $8000,
+/// NMI and IRQ to an RTI at INTERRUPT_HANDLER.
+pub fn nrom_prg(program: &[u8]) -> Vec<u8> {
+    let mut prg = vec![0xEAu8; 32 * 1024];
+    prg[0 .. program.len()].copy_from_slice(program);
+    let handler = (INTERRUPT_HANDLER - 0x8000) as usize;
+    prg[handler] = 0x40;
+    prg[0x7FFA] = (INTERRUPT_HANDLER & 0xFF) as u8;
+    prg[0x7FFB] = (INTERRUPT_HANDLER >> 8) as u8;
+    prg[0x7FFC] = 0x00;
+    prg[0x7FFD] = 0x80;
+    prg[0x7FFE] = (INTERRUPT_HANDLER & 0xFF) as u8;
+    prg[0x7FFF] = (INTERRUPT_HANDLER >> 8) as u8;
+    return prg;
+}
+
+pub fn nrom_image(program: &[u8]) -> Vec<u8> {
+    return ines_image(0, 0, &nrom_prg(program), &vec![0u8; 8 * 1024]);
+}
+
+/// A powered-on console running `program` from $8000, with CHR ROM of all zeroes.
+pub fn nes_with_program(program: &[u8]) -> NesState {
+    return nes_from_image(&nrom_image(program));
+}
+
+pub fn nes_from_image(image: &[u8]) -> NesState {
+    let mapper = mapper_from_file(image).expect("test image should load");
+    let mut nes = NesState::new(mapper);
+    nes.power_on();
+    return nes;
+}
+
+pub fn step_instructions(nes: &mut NesState, count: usize) {
+    for _ in 0 .. count {
+        nes.step();
+    }
+}
+
+/// `bank_count` banks of `bank_size` bytes, each filled with its own bank number, so a read
+/// shows which bank is mapped.
+pub fn numbered_banks(bank_size: usize, bank_count: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(bank_size * bank_count);
+    for bank in 0 .. bank_count {
+        data.extend(std::iter::repeat(bank as u8).take(bank_size));
+    }
+    return data;
+}
+
+pub fn ines_cartridge(image: &[u8]) -> INesCartridge {
+    return INesCartridge::from_reader(&mut &image[..]).expect("test image should parse");
+}
+
+/// Converts an `ines_image` to an NES 2.0 header with the given submapper.
+pub fn with_submapper(mut image: Vec<u8>, submapper: u8) -> Vec<u8> {
+    image[7] = (image[7] & 0xF0) | 0x08;
+    image[8] = submapper << 4;
+    return image;
+}
@@ -0,0 +1,47 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::nes::NesState;
+
+fn cpu_cycles(nes: &NesState) -> u64 {
+    return nes.master_clock / nes.region.master_clocks_per_cpu_cycle() as u64;
+}
+
+// Cycles taken by 20 NOPs run right after $4015 is written with `dmc_enable`. `padding` is
+// run first to shift the DMA's alignment against the APU's get/put cycles. Runs to an address
+// rather than counting step() calls, as a cycle spent halted counts as a step of its own.
+fn cycles_after_4015_write(padding: &[u8], dmc_enable: u8) -> u64 {
+    // LDA #$00, STA $4013: a one byte sample
+    let mut program = vec![0xA9, 0x00, 0x8D, 0x13, 0x40];
+    program.extend_from_slice(padding);
+    // LDA #dmc_enable, STA $4015
+    program.extend_from_slice(&[0xA9, dmc_enable, 0x8D, 0x15, 0x40]);
+    let nops_start = 0x8000 + program.len() as u16;
+    let mut nes = common::nes_with_program(&program);
+    run_to(&mut nes, nops_start);
+    let start = cpu_cycles(&nes);
+    run_to(&mut nes, nops_start + 20);
+    return cpu_cycles(&nes) - start;
+}
+
+fn run_to(nes: &mut NesState, address: u16) {
+    while nes.registers.pc != address || nes.cpu.tick != 0 {
+        nes.step();
+    }
+}
+
+#[test]
+fn nops_without_dmc_take_two_cycles_each() {
+    assert_eq!(cycles_after_4015_write(&[], 0x00), 40);
+}
+
+#[test]
+fn dmc_fetch_stalls_the_cpu_for_3_or_4_cycles_depending_on_alignment() {
+    // NOP (2 cycles) and BIT zp (3 cycles) leave the STA on opposite APU cycle parities
+    let even = cycles_after_4015_write(&[0xEA], 0x10) - cycles_after_4015_write(&[0xEA], 0x00);
+    let odd = cycles_after_4015_write(&[0x24, 0x00], 0x10) - cycles_after_4015_write(&[0x24, 0x00], 0x00);
+    let mut stalls = [even, odd];
+    stalls.sort();
+    assert_eq!(stalls, [3, 4]);
+}
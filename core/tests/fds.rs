@@ -0,0 +1,82 @@
+extern crate rustico_core;
+
+use rustico_core::fds::FdsFile;
+use rustico_core::mmc::fds::FdsMapper;
+use rustico_core::mmc::mapper::Mapper;
+
+// How long a swapped-in side is held out of the drive, in CPU cycles
+const DISK_CHANGE_DELAY: usize = 1000000;
+
+// $4032 status bits; all three are set when the drive is empty
+const DISK_MISSING: u8 = 0b0000_0001;
+const NOT_READY: u8 = 0b0000_0010;
+const READ_ONLY: u8 = 0b0000_0100;
+
+fn two_sided_disk() -> FdsMapper {
+    let image = FdsFile::to_bytes(&[vec![0u8; 65500], vec![0u8; 65500]]);
+    let fds = FdsFile::from_reader(&mut &image[..]).unwrap();
+    return FdsMapper::from_fds(fds).unwrap();
+}
+
+fn drive_empty(mapper: &mut FdsMapper) -> bool {
+    let status = mapper.read_cpu(0x4032).unwrap();
+    return status & (DISK_MISSING | NOT_READY | READ_ONLY) == (DISK_MISSING | NOT_READY | READ_ONLY);
+}
+
+fn run_cycles(mapper: &mut FdsMapper, cycles: usize) {
+    for _ in 0 .. cycles {
+        mapper.on_cpu_cycle();
+    }
+}
+
+#[test]
+fn first_side_starts_inserted() {
+    let mut mapper = two_sided_disk();
+    assert_eq!(mapper.fds_side_count(), 2);
+    assert_eq!(mapper.fds_inserted_disk(), Some(0));
+    assert_eq!(mapper.read_cpu(0x4032).unwrap() & DISK_MISSING, 0);
+}
+
+#[test]
+fn eject_then_insert_is_immediate() {
+    let mut mapper = two_sided_disk();
+    mapper.fds_eject_disk();
+    assert_eq!(mapper.fds_inserted_disk(), None);
+    assert!(drive_empty(&mut mapper));
+
+    mapper.fds_insert_disk(1);
+    assert_eq!(mapper.fds_inserted_disk(), Some(1));
+    assert_eq!(mapper.read_cpu(0x4032).unwrap() & DISK_MISSING, 0);
+}
+
+#[test]
+fn swapping_sides_leaves_the_drive_empty_for_a_while() {
+    let mut mapper = two_sided_disk();
+    mapper.fds_insert_disk(1);
+    assert_eq!(mapper.fds_inserted_disk(), None);
+    assert!(drive_empty(&mut mapper));
+
+    run_cycles(&mut mapper, DISK_CHANGE_DELAY - 1);
+    assert_eq!(mapper.fds_inserted_disk(), None);
+    run_cycles(&mut mapper, 1);
+    assert_eq!(mapper.fds_inserted_disk(), Some(1));
+}
+
+#[test]
+fn ejecting_cancels_a_pending_swap() {
+    let mut mapper = two_sided_disk();
+    mapper.fds_insert_disk(1);
+    mapper.fds_eject_disk();
+    run_cycles(&mut mapper, DISK_CHANGE_DELAY);
+    assert_eq!(mapper.fds_inserted_disk(), None);
+}
+
+#[test]
+fn bad_or_redundant_inserts_are_ignored() {
+    let mut mapper = two_sided_disk();
+    mapper.fds_insert_disk(2);
+    assert_eq!(mapper.fds_inserted_disk(), Some(0));
+    // Re-inserting the side that's already in doesn't bounce it out of the drive
+    mapper.fds_insert_disk(0);
+    assert_eq!(mapper.fds_inserted_disk(), Some(0));
+}
@@ -0,0 +1,75 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::mmc::fme7::{Fme7, YM2149F};
+
+const BATTERY: u8 = 0b0000_0010;
+
+fn fme7() -> Fme7 {
+    let image = common::ines_image(69, BATTERY, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+    return Fme7::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+#[test]
+fn volume_table_steps_down_1_5_db_from_full_scale() {
+    let lut = YM2149F::generate_volume_lut();
+    assert_eq!(lut.len(), 32);
+    assert_eq!(lut[0], 0.0);
+    assert_eq!(lut[1], 0.0);
+    assert_eq!(lut[31], 1.0);
+    let step = 10f32.powf(-1.5 / 20.0);
+    for i in 2 .. 31 {
+        assert!((lut[i] / lut[i + 1] - step).abs() < 1e-5, "entry {} is {}", i, lut[i]);
+    }
+}
+
+#[test]
+fn tone_period_is_twelve_bits_across_two_registers() {
+    let mut chip = YM2149F::new();
+    chip.execute_command(0x0, 0x34);
+    chip.execute_command(0x1, 0xF2);
+    // Only the low nybble of the coarse register counts
+    assert_eq!(chip.channel_a.tone.period_compare, 0x234);
+    chip.execute_command(0x0, 0x0A);
+    assert_eq!(chip.channel_a.tone.period_compare, 0x20A);
+}
+
+#[test]
+fn tone_toggles_once_per_period_of_16_cpu_cycles() {
+    let mut chip = YM2149F::new();
+    chip.execute_command(0x0, 10);
+    chip.execute_command(0x1, 0);
+    let initial = chip.channel_a.tone.output();
+    for _ in 0 .. 159 {
+        chip.clock();
+    }
+    assert_eq!(chip.channel_a.tone.output(), initial);
+    chip.clock();
+    assert_eq!(chip.channel_a.tone.output(), initial ^ 1);
+    for _ in 0 .. 160 {
+        chip.clock();
+    }
+    assert_eq!(chip.channel_a.tone.output(), initial);
+}
+
+#[test]
+fn sram_round_trips_through_get_and_load() {
+    let mut mapper = fme7();
+    assert!(mapper.has_sram());
+    assert!(mapper.has_persistent_ram());
+
+    // Command 8: RAM enabled and selected at $6000, bank 0
+    mapper.write_cpu(0x8000, 0x8);
+    mapper.write_cpu(0xA000, 0xC0);
+    mapper.write_cpu(0x6000, 0x42);
+    let sram = mapper.get_sram();
+    assert_eq!(sram[0], 0x42);
+
+    let mut restored = fme7();
+    restored.write_cpu(0x8000, 0x8);
+    restored.write_cpu(0xA000, 0xC0);
+    restored.load_sram(sram);
+    assert_eq!(restored.debug_read_cpu(0x6000), Some(0x42));
+}
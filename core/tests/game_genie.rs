@@ -0,0 +1,57 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::game_genie::{apply_cheats, CheatError, GameGenieCode};
+use rustico_core::memory::debug_read_byte;
+
+#[test]
+fn decodes_six_letter_codes() {
+    // Super Mario Bros, infinite lives
+    let code = GameGenieCode::decode("SXIOPO").unwrap();
+    assert_eq!(code.address, 0x91D9);
+    assert_eq!(code.value, 0xAD);
+    assert_eq!(code.compare, None);
+}
+
+#[test]
+fn decodes_eight_letter_codes() {
+    let code = GameGenieCode::decode("zexpygla").unwrap();
+    assert_eq!(code.code, "ZEXPYGLA");
+    assert_eq!(code.address, 0x94A7);
+    assert_eq!(code.value, 0x02);
+    assert_eq!(code.compare, Some(0x03));
+}
+
+#[test]
+fn rejects_malformed_codes() {
+    assert_eq!(GameGenieCode::decode("SXIOP"), Err(CheatError::InvalidLength(5)));
+    assert_eq!(GameGenieCode::decode("SXIOPOA"), Err(CheatError::InvalidLength(7)));
+    assert_eq!(GameGenieCode::decode("SXIOPB"), Err(CheatError::InvalidCharacter('B')));
+}
+
+#[test]
+fn compare_codes_only_patch_a_matching_byte() {
+    let code = GameGenieCode::decode("ZEXPYGLA").unwrap();
+    assert_eq!(code.apply(0x94A7, 0x03), 0x02);
+    assert_eq!(code.apply(0x94A7, 0x04), 0x04);
+    assert_eq!(code.apply(0x94A8, 0x03), 0x03);
+
+    let cheats = vec![code, GameGenieCode::decode("SXIOPO").unwrap()];
+    assert_eq!(apply_cheats(&cheats, 0x91D9, 0x00), 0xAD);
+    assert_eq!(apply_cheats(&cheats, 0x94A7, 0x03), 0x02);
+}
+
+#[test]
+fn active_cheats_patch_cpu_reads() {
+    let mut nes = common::nes_with_program(&[]);
+    assert_eq!(debug_read_byte(&nes, 0x91D9), 0xEA);
+
+    nes.add_cheat("SXIOPO").unwrap();
+    assert_eq!(debug_read_byte(&nes, 0x91D9), 0xAD);
+    assert_eq!(debug_read_byte(&nes, 0x91DA), 0xEA);
+
+    nes.remove_cheat("sxiopo").unwrap();
+    assert_eq!(debug_read_byte(&nes, 0x91D9), 0xEA);
+    assert_eq!(nes.remove_cheat("SXIOPO"), Err(CheatError::NotFound("SXIOPO".to_string())));
+}
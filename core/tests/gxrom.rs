@@ -0,0 +1,49 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::gxrom::GxRom;
+use rustico_core::mmc::mapper::Mapper;
+
+// 4 numbered banks each of PRG and CHR. Bank 0, mapped at power on, is all $00 apart from an
+// $FF at $8100 and a $21 at $8123 to conflict with.
+fn gxrom() -> GxRom {
+    let mut prg = common::numbered_banks(32 * 1024, 4);
+    prg[0x0100] = 0xFF;
+    prg[0x0123] = 0x21;
+    let chr = common::numbered_banks(8 * 1024, 4);
+    let image = common::ines_image(66, 0, &prg, &chr);
+    return GxRom::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+#[test]
+fn writes_over_ff_select_both_banks() {
+    let mut mapper = gxrom();
+    mapper.write_cpu(0x8100, 0x32);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(3));
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(2));
+}
+
+#[test]
+fn writes_are_anded_with_the_rom_byte() {
+    let mut mapper = gxrom();
+    assert!(mapper.bus_conflicts);
+    mapper.write_cpu(0x8123, 0x33);
+    // $33 & $21: PRG bank 2, CHR bank 1
+    assert_eq!(mapper.prg_bank, 2);
+    assert_eq!(mapper.chr_bank, 1);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(2));
+    assert_eq!(mapper.debug_read_ppu(0x1FFF), Some(1));
+}
+
+#[test]
+fn nes2_header_without_chr_memory_gets_8k_of_chr_ram() {
+    let image = common::with_submapper(common::ines_image(66, 0, &common::numbered_banks(32 * 1024, 4), &[]), 0);
+    assert_eq!(image[11], 0, "no CHR RAM declared either");
+    let mut mapper = GxRom::from_ines(common::ines_cartridge(&image)).unwrap();
+    assert_eq!(mapper.chr.len(), 8 * 1024);
+    mapper.write_ppu(0x0000, 0x12);
+    mapper.write_ppu(0x1FFF, 0x34);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(0x12));
+    assert_eq!(mapper.debug_read_ppu(0x1FFF), Some(0x34));
+}
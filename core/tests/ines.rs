@@ -0,0 +1,105 @@
+extern crate rustico_core;
+
+use rustico_core::ines::INesCartridge;
+use rustico_core::ines::INesError;
+use rustico_core::ines::INesHeader;
+use rustico_core::mmc::mapper::Mirroring;
+use rustico_core::region::Region;
+
+fn header(bytes_4_to_15: [u8; 12]) -> [u8; 16] {
+    let mut raw = [0u8; 16];
+    raw[0..4].copy_from_slice(b"NES\x1A");
+    raw[4..16].copy_from_slice(&bytes_4_to_15);
+    return raw;
+}
+
+#[test]
+fn nes2_mmc3_header_with_submapper_and_battery() {
+    // 512 KiB PRG, 256 KiB CHR, mapper 4.1, 8 KiB PRG NVRAM, vertical mirroring, PAL
+    let raw = header([0x20, 0x20, 0x43, 0x08, 0x10, 0x00, 0x70, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    let header = INesHeader::from(&raw);
+    assert!(header.is_nes2());
+    assert_eq!(header.mapper_number(), 4);
+    assert_eq!(header.submapper_number(), 1);
+    assert_eq!(header.prg_rom_size(), 512 * 1024);
+    assert_eq!(header.chr_rom_size(), 256 * 1024);
+    assert_eq!(header.prg_ram_size(), 0);
+    assert_eq!(header.prg_sram_size(), 8 * 1024);
+    assert_eq!(header.prg_sram_shift(), 7);
+    assert!(header.has_battery());
+    assert!(header.mirroring() == Mirroring::Vertical);
+    assert!(header.region() == Region::Pal);
+}
+
+#[test]
+fn nes2_header_with_extended_mapper_and_simple_size_msb() {
+    // Mapper 0x155 (341), 4 MiB PRG via the size MSB nybble, 32 KiB CHR RAM
+    let raw = header([0x00, 0x00, 0x50, 0x58, 0x01, 0x01, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00]);
+    let header = INesHeader::from(&raw);
+    assert!(header.is_nes2());
+    assert_eq!(header.mapper_number(), 0x155);
+    assert_eq!(header.prg_rom_size(), 4 * 1024 * 1024);
+    assert_eq!(header.chr_rom_size(), 0);
+    assert_eq!(header.chr_ram_size(), 32 * 1024);
+    assert_eq!(header.chr_ram_shift(), 9);
+}
+
+#[test]
+fn nes2_header_with_exponent_multiplier_prg_size() {
+    // 2^14 * 3 = 48 KiB of PRG ROM
+    let raw = header([0x39, 0x01, 0x00, 0x08, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let header = INesHeader::from(&raw);
+    assert!(header.is_nes2());
+    assert_eq!(header.prg_rom_size(), 48 * 1024);
+    assert_eq!(header.chr_rom_size(), 8 * 1024);
+}
+
+#[test]
+fn overflowing_exponent_falls_back_to_ines1() {
+    // 2^63 * 7 can't be represented, so the NES 2.0 signature is treated as garbage
+    let raw = header([0xFF, 0x01, 0x00, 0x08, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let header = INesHeader::from(&raw);
+    assert!(!header.is_nes2());
+    assert_eq!(header.version(), 1);
+    assert_eq!(header.prg_rom_size(), 0xFF * 16 * 1024);
+}
+
+#[test]
+fn ines1_header_is_not_nes2() {
+    let raw = header([0x02, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let header = INesHeader::from(&raw);
+    assert!(!header.is_nes2());
+    assert_eq!(header.submapper_number(), 0);
+    assert_eq!(header.prg_rom_size(), 32 * 1024);
+    assert_eq!(header.chr_rom_size(), 8 * 1024);
+}
+
+#[test]
+fn cartridge_round_trips_prg_and_chr_data() {
+    let raw = header([0x39, 0x01, 0x00, 0x08, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let prg: Vec<u8> = (0 .. 48 * 1024).map(|i| (i * 7) as u8).collect();
+    let chr: Vec<u8> = (0 .. 8 * 1024).map(|i| (i * 13) as u8).collect();
+    let mut image = raw.to_vec();
+    image.extend_from_slice(&prg);
+    image.extend_from_slice(&chr);
+    image.extend_from_slice(&[0xAA, 0xBB]);
+
+    let cartridge = INesCartridge::from_reader(&mut image.as_slice()).unwrap();
+    assert!(cartridge.header.is_nes2());
+    assert_eq!(cartridge.prg, prg);
+    assert_eq!(cartridge.chr, chr);
+    assert_eq!(cartridge.misc_rom, vec![0xAA, 0xBB]);
+}
+
+#[test]
+fn oversized_declared_prg_is_truncated_not_allocated() {
+    // 2^40 bytes of PRG declared, followed by only a few bytes of data
+    let raw = header([0xA0, 0x00, 0x00, 0x08, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let mut image = raw.to_vec();
+    image.extend_from_slice(&[0u8; 64]);
+    match INesCartridge::from_reader(&mut image.as_slice()) {
+        Err(INesError::Truncated) => {},
+        Err(other) => panic!("expected Truncated, got {}", other),
+        Ok(_) => panic!("expected Truncated, got a cartridge"),
+    }
+}
@@ -0,0 +1,128 @@
+// The mapper IRQ hooks, exercised through a made up cartridge with a CPU cycle countdown,
+// rather than any one real mapper's quirks.
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::{Mapper, Mirroring};
+use rustico_core::nes::NesState;
+
+// Writing N to $8000 raises an IRQ N CPU cycles later; any write to $C000 acknowledges it.
+// $6000-$6001 reads back the number of scanlines the PPU has reported.
+struct CycleTimer {
+    prg: Vec<u8>,
+    countdown: u16,
+    pending: bool,
+    scanlines: u16,
+}
+
+impl Mapper for CycleTimer {
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 ..= 0xBFFF => {self.countdown = data as u16},
+            0xC000 ..= 0xFFFF => {self.pending = false},
+            _ => {}
+        }
+    }
+
+    fn write_ppu(&mut self, _address: u16, _data: u8) {}
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        return match address {
+            0x6000 => Some((self.scanlines & 0xFF) as u8),
+            0x6001 => Some((self.scanlines >> 8) as u8),
+            0x8000 ..= 0xFFFF => Some(self.prg[(address - 0x8000) as usize]),
+            _ => None
+        };
+    }
+
+    fn debug_read_ppu(&self, _address: u16) -> Option<u8> {
+        return Some(0);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return Mirroring::Horizontal;
+    }
+
+    fn irq_pending(&self) -> bool {
+        return self.pending;
+    }
+
+    fn on_cpu_cycle(&mut self) {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.pending = true;
+            }
+        }
+    }
+
+    fn on_ppu_scanline(&mut self) {
+        self.scanlines = self.scanlines.wrapping_add(1);
+    }
+}
+
+// Arms the timer for `delay` cycles with interrupts enabled, then spins. The IRQ handler
+// acknowledges the timer and counts itself in $00.
+fn timer_nes(delay: u8) -> NesState {
+    let mut prg = common::nrom_prg(&[
+        0x58,             // CLI
+        0xA9, delay,      // LDA #delay
+        0x8D, 0x00, 0x80, // STA $8000
+        0x4C, 0x06, 0x80, // JMP $8006
+    ]);
+    let handler = (common::INTERRUPT_HANDLER - 0x8000) as usize;
+    prg[handler .. handler + 6].copy_from_slice(&[
+        0x8D, 0x00, 0xC0, // STA $C000
+        0xE6, 0x00,       // INC $00
+        0x40,             // RTI
+    ]);
+    let mut nes = NesState::new(Box::new(CycleTimer {prg: prg, countdown: 0, pending: false, scanlines: 0}));
+    nes.power_on();
+    nes.memory.iram_raw[0] = 0;
+    return nes;
+}
+
+fn run_to(nes: &mut NesState, address: u16) {
+    while nes.registers.pc != address || nes.cpu.tick != 0 {
+        nes.step();
+    }
+}
+
+#[test]
+fn on_cpu_cycle_counts_every_cpu_cycle() {
+    let mut nes = timer_nes(100);
+    // The STA's write lands on its last cycle, which the countdown already counts
+    run_to(&mut nes, 0x8006);
+    for _ in 0 .. 98 {
+        nes.cycle();
+    }
+    assert!(!nes.mapper.irq_pending());
+    nes.cycle();
+    assert!(nes.mapper.irq_pending());
+}
+
+#[test]
+fn irq_pending_interrupts_the_cpu_until_acknowledged() {
+    let mut nes = timer_nes(100);
+    for _ in 0 .. 200 {
+        nes.cycle();
+    }
+    assert_eq!(nes.memory.iram_raw[0], 1);
+    assert!(!nes.mapper.irq_pending());
+    // Acknowledged, so the handler ran once and the CPU went back to spinning
+    for _ in 0 .. 1000 {
+        nes.cycle();
+    }
+    assert_eq!(nes.memory.iram_raw[0], 1);
+}
+
+#[test]
+fn on_ppu_scanline_runs_once_per_scanline() {
+    let mut nes = timer_nes(0);
+    nes.step_frame();
+    let scanlines = |nes: &NesState| nes.mapper.debug_read_cpu(0x6000).unwrap() as u16 | (nes.mapper.debug_read_cpu(0x6001).unwrap() as u16) << 8;
+    let before = scanlines(&nes);
+    nes.step_frame();
+    assert_eq!(scanlines(&nes) - before, 262);
+}
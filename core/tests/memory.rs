@@ -0,0 +1,93 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::cartridge::mapper_from_file;
+use rustico_core::memory::{read_byte, write_byte, RamInit};
+use rustico_core::nes::NesState;
+
+#[test]
+fn undriven_4015_bit_reads_the_open_bus() {
+    let mut nes = common::nes_with_program(&[]);
+    nes.memory.last_bus_value = 0xFF;
+    // Nothing is playing and no IRQs are pending, so only bit 5 comes back set
+    assert_eq!(read_byte(&mut nes, 0x4015), 0x20);
+    nes.memory.last_bus_value = 0x00;
+    assert_eq!(read_byte(&mut nes, 0x4015), 0x00);
+}
+
+#[test]
+fn undriven_controller_bits_read_the_open_bus() {
+    let mut nes = common::nes_with_program(&[]);
+    nes.memory.last_bus_value = 0xA5;
+    assert_eq!(read_byte(&mut nes, 0x4016) & 0xE0, 0xA0);
+    assert_eq!(read_byte(&mut nes, 0x4017) & 0xE0, 0xA0);
+}
+
+#[test]
+fn absolute_controller_reads_see_the_operand_high_byte() {
+    let mut nes = common::nes_with_program(&[
+        0xA9, 0x01,       // LDA #$01
+        0x8D, 0x16, 0x40, // STA $4016
+        0xA9, 0x00,       // LDA #$00
+        0x8D, 0x16, 0x40, // STA $4016
+        0xAD, 0x16, 0x40, // LDA $4016
+    ]);
+    nes.p1_input = 0x01; // A, the first button shifted out
+    common::step_instructions(&mut nes, 5);
+    assert_eq!(nes.registers.a, 0x41);
+}
+
+#[test]
+fn controller_reloads_when_strobe_falls() {
+    let mut nes = common::nes_with_program(&[]);
+    nes.p1_input = 0x00;
+    write_byte(&mut nes, 0x4016, 1);
+    // Pressed while strobe is still high: the shift register keeps reloading, so this counts
+    nes.p1_input = 0x01;
+    write_byte(&mut nes, 0x4016, 0);
+    // Changed after strobe fell: too late, the buttons have already been latched
+    nes.p1_input = 0x02;
+    assert_eq!(read_byte(&mut nes, 0x4016) & 0x01, 1, "A");
+    assert_eq!(read_byte(&mut nes, 0x4016) & 0x01, 0, "B");
+}
+
+fn seeded_nes(seed: u64) -> NesState {
+    let image = common::nrom_image(&[]);
+    let mapper = mapper_from_file(&image).unwrap();
+    return NesState::new_with_ram_init(mapper, RamInit::Seeded(seed));
+}
+
+#[test]
+fn seeded_ram_init_is_deterministic() {
+    let first = seeded_nes(0x1234);
+    let second = seeded_nes(0x1234);
+    assert_eq!(first.memory.iram_raw, second.memory.iram_raw);
+    // Not just a constant fill
+    assert!(first.memory.iram_raw.iter().any(|&byte| byte != first.memory.iram_raw[0]));
+
+    let other_seed = seeded_nes(0x1235);
+    assert!(first.memory.iram_raw != other_seed.memory.iram_raw);
+}
+
+#[test]
+fn ram_survives_a_reset() {
+    let mut nes = seeded_nes(0x1234);
+    write_byte(&mut nes, 0x0123, 0xA5);
+    let ram_before = nes.memory.iram_raw.clone();
+    nes.reset();
+    assert_eq!(nes.memory.iram_raw, ram_before);
+}
+
+#[test]
+fn power_cycle_refills_ram_from_ram_init() {
+    let mut nes = seeded_nes(0x1234);
+    let power_on_ram = nes.memory.iram_raw.clone();
+    write_byte(&mut nes, 0x0123, !power_on_ram[0x123]);
+    nes.power_cycle();
+    assert_eq!(nes.memory.iram_raw, power_on_ram);
+
+    nes.ram_init = RamInit::Pattern(0x5A);
+    nes.power_cycle();
+    assert!(nes.memory.iram_raw.iter().all(|&byte| byte == 0x5A));
+}
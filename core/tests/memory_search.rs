@@ -0,0 +1,45 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::memory;
+use rustico_core::memory_search::{MemorySearch, SearchFilter};
+use rustico_core::nes::NesState;
+
+fn nes() -> NesState {
+    let mut nes = common::nes_with_program(&[0x4C, 0x00, 0x80]); // JMP $8000
+    for byte in nes.memory.iram_raw.iter_mut() {
+        *byte = 0;
+    }
+    return nes;
+}
+
+#[test]
+fn candidates_narrow_across_three_snapshots() {
+    let mut nes = nes();
+    let mut search = MemorySearch::new(&nes, false);
+    assert_eq!(search.candidates().len(), 0x800);
+
+    // A "lives" counter at $0042 ticks down, with a couple of decoys along for the ride
+    memory::write_byte(&mut nes, 0x0042, 3);
+    memory::write_byte(&mut nes, 0x0043, 3);
+    memory::write_byte(&mut nes, 0x0300, 9);
+    assert_eq!(search.filter(&nes, SearchFilter::Changed), &[0x0042, 0x0043, 0x0300]);
+
+    memory::write_byte(&mut nes, 0x0042, 2);
+    memory::write_byte(&mut nes, 0x0300, 10);
+    assert_eq!(search.filter(&nes, SearchFilter::Decreased), &[0x0042]);
+    assert_eq!(search.last_value(0x0042), Some(2));
+
+    memory::write_byte(&mut nes, 0x0042, 1);
+    assert_eq!(search.filter(&nes, SearchFilter::Equals(1)), &[0x0042]);
+}
+
+#[test]
+fn prg_ram_is_only_searched_when_asked_for() {
+    let nes = nes();
+    assert!(!MemorySearch::new(&nes, false).candidates().contains(&0x6000));
+    let search = MemorySearch::new(&nes, true);
+    assert_eq!(search.candidates().len(), 0x800 + 0x2000);
+    assert!(search.candidates().contains(&0x7FFF));
+}
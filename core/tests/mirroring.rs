@@ -0,0 +1,32 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::cartridge::mapper_from_file;
+use rustico_core::mmc::mapper::Mirroring;
+
+const FOUR_SCREEN: u8 = 0b0000_1000;
+
+// UxROM, CNROM, mapper 31, BNROM and GxROM, which take their mirroring from the header
+const HEADER_MIRRORED_MAPPERS: [u8; 5] = [2, 3, 31, 34, 66];
+
+#[test]
+fn four_screen_nametables_are_distinct() {
+    for &mapper_number in HEADER_MIRRORED_MAPPERS.iter() {
+        let image = common::ines_image(mapper_number, FOUR_SCREEN, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+        let mut mapper = mapper_from_file(&image).expect("test image should load");
+        assert!(mapper.mirroring() == Mirroring::FourScreen, "mapper {} ignored the four-screen flag", mapper_number);
+
+        for nametable in 0 .. 4 {
+            mapper.write_ppu(0x2000 + nametable * 0x400 + 0x123, 0x10 + nametable as u8);
+        }
+        for nametable in 0 .. 4 {
+            let address = 0x2000 + nametable * 0x400 + 0x123;
+            assert_eq!(mapper.debug_read_ppu(address), Some(0x10 + nametable as u8),
+                "mapper {}, nametable {}", mapper_number, nametable);
+            // $3000-$3EFF mirrors the nametables
+            assert_eq!(mapper.debug_read_ppu(address + 0x1000), Some(0x10 + nametable as u8),
+                "mapper {}, nametable {} mirror", mapper_number, nametable);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::memory::debug_read_byte;
+use rustico_core::mmc::mmc1::Mmc1;
+
+fn mmc1() -> Mmc1 {
+    let image = common::ines_image(1, 0, &common::numbered_banks(16 * 1024, 8), &vec![0u8; 8 * 1024]);
+    return Mmc1::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+// One serial write, followed by the end of its CPU cycle
+fn serial_write(mapper: &mut Mmc1, data: u8) {
+    mapper.write_cpu(0x8000, data);
+    mapper.on_cpu_cycle();
+}
+
+#[test]
+fn writes_on_separate_cycles_each_shift_in_a_bit() {
+    let mut mapper = mmc1();
+    serial_write(&mut mapper, 1);
+    mapper.on_cpu_cycle();
+    serial_write(&mut mapper, 1);
+    assert_eq!(mapper.shift_counter, 2);
+}
+
+#[test]
+fn a_write_on_the_very_next_cycle_is_ignored() {
+    let mut mapper = mmc1();
+    serial_write(&mut mapper, 1);
+    serial_write(&mut mapper, 1);
+    assert_eq!(mapper.shift_counter, 1);
+    // ...but only that one
+    serial_write(&mut mapper, 1);
+    assert_eq!(mapper.shift_counter, 1);
+    mapper.on_cpu_cycle();
+    serial_write(&mut mapper, 1);
+    assert_eq!(mapper.shift_counter, 2);
+}
+
+#[test]
+fn read_modify_write_instructions_shift_in_only_the_first_write() {
+    // 128 KiB of numbered banks; the last one is fixed at $C000 and holds the program
+    let mut prg = common::numbered_banks(16 * 1024, 8);
+    let fixed_bank = 7 * 16 * 1024;
+    let program = [
+        0xEE, 0x10, 0xE0, // INC $E010: writes $00, then $01 on the very next cycle
+        0xA9, 0x01,       // LDA #$01
+        0x8D, 0x00, 0xE0, // STA $E000
+        0xA9, 0x00,       // LDA #$00
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x8D, 0x00, 0xE0, // STA $E000
+    ];
+    prg[fixed_bank .. fixed_bank + program.len()].copy_from_slice(&program);
+    prg[fixed_bank + 0x2010] = 0x00;
+    prg[fixed_bank + 0x3FFC] = 0x00;
+    prg[fixed_bank + 0x3FFD] = 0xC0;
+    let image = common::ines_image(1, 0, &prg, &vec![0u8; 8 * 1024]);
+    let mut nes = common::nes_from_image(&image);
+    common::step_instructions(&mut nes, 7);
+
+    // Bits 0, 1, 0, 0, 0 select bank 2. Had INC's second write counted, the register would
+    // have filled up with 0, 1, 1, 0, 0 instead and selected bank 6.
+    assert_eq!(debug_read_byte(&nes, 0x8000), 2);
+}
@@ -0,0 +1,244 @@
+// MMC3 scanline IRQs (MMC3B and the older MMC3A), driven by synthetic PPU A12 edges and M2 cycles
+// rather than a running PPU, the $8000 bank inversion bits, and the $A001 PRG RAM protect bits.
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::mmc::mmc3::Mmc3;
+
+fn mmc3() -> Mmc3 {
+    let image = common::ines_image(4, 0, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+    return Mmc3::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+fn nes2_mmc3(submapper: u8) -> Mmc3 {
+    let image = common::ines_image(4, 0, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+    let mut image = common::with_submapper(image, submapper);
+    image[10] = 0x07; // 8 KiB of PRG RAM
+    return Mmc3::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+// Eight numbered 8K PRG banks and sixteen numbered 1K CHR banks
+fn banked_mmc3() -> Mmc3 {
+    let image = common::ines_image(4, 0, &common::numbered_banks(0x2000, 8), &common::numbered_banks(0x400, 16));
+    return Mmc3::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+/// Writes `bank` to register R0-R7 through $8000/$8001, with `mode` supplying the inversion bits.
+fn select_bank(mapper: &mut Mmc3, mode: u8, register: u8, bank: u8) {
+    mapper.write_cpu(0x8000, mode | register);
+    mapper.write_cpu(0x8001, bank);
+}
+
+/// Holds A12 low for `m2_cycles` CPU cycles, then raises it, as a fetch from $1000 would.
+fn a12_rise_after(mapper: &mut Mmc3, m2_cycles: usize) {
+    mapper.read_ppu(0x0000);
+    for _ in 0 .. m2_cycles {
+        mapper.on_cpu_cycle();
+    }
+    mapper.read_ppu(0x1000);
+}
+
+/// A rising edge that gets past the filter, like the one sprite fetches make on each scanline.
+fn scanline(mapper: &mut Mmc3) {
+    a12_rise_after(mapper, 3);
+}
+
+fn enable_irq(mapper: &mut Mmc3, reload: u8) {
+    mapper.write_cpu(0xC000, reload);
+    mapper.write_cpu(0xC001, 0);
+    mapper.write_cpu(0xE001, 0);
+}
+
+#[test]
+fn irq_fires_once_the_counter_reaches_zero() {
+    let mut mapper = mmc3();
+    enable_irq(&mut mapper, 2);
+
+    // The first edge reloads the counter, the rest count it down
+    scanline(&mut mapper);
+    assert_eq!(mapper.irq_counter, 2);
+    scanline(&mut mapper);
+    assert!(!mapper.irq_pending());
+    scanline(&mut mapper);
+    assert_eq!(mapper.irq_counter, 0);
+    assert!(mapper.irq_pending());
+
+    // $E000 acknowledges and disables
+    mapper.write_cpu(0xE000, 0);
+    assert!(!mapper.irq_pending());
+    for _ in 0 .. 3 {
+        scanline(&mut mapper);
+    }
+    assert!(!mapper.irq_pending());
+}
+
+#[test]
+fn edges_too_close_together_are_filtered() {
+    let mut mapper = mmc3();
+    enable_irq(&mut mapper, 5);
+    scanline(&mut mapper);
+    assert_eq!(mapper.irq_counter, 5);
+
+    // Background and sprite fetches toggle A12 within a couple of M2 cycles; only the first
+    // rise after a long enough low period counts
+    for _ in 0 .. 4 {
+        a12_rise_after(&mut mapper, 2);
+    }
+    assert_eq!(mapper.irq_counter, 5);
+    a12_rise_after(&mut mapper, 0);
+    assert_eq!(mapper.irq_counter, 5);
+
+    scanline(&mut mapper);
+    assert_eq!(mapper.irq_counter, 4);
+}
+
+#[test]
+fn held_a12_clocks_only_once() {
+    let mut mapper = mmc3();
+    enable_irq(&mut mapper, 5);
+    scanline(&mut mapper);
+    for _ in 0 .. 8 {
+        mapper.read_ppu(0x1000);
+        mapper.on_cpu_cycle();
+    }
+    assert_eq!(mapper.irq_counter, 5);
+}
+
+#[test]
+fn ppu_writes_clock_the_counter_too() {
+    let mut mapper = mmc3();
+    enable_irq(&mut mapper, 5);
+    scanline(&mut mapper);
+
+    mapper.read_ppu(0x0000);
+    for _ in 0 .. 3 {
+        mapper.on_cpu_cycle();
+    }
+    mapper.write_ppu(0x1000, 0);
+    assert_eq!(mapper.irq_counter, 4);
+}
+
+#[test]
+fn prg_mode_swaps_r6_with_the_second_to_last_bank() {
+    let mut mapper = banked_mmc3();
+    select_bank(&mut mapper, 0, 6, 2);
+    select_bank(&mut mapper, 0, 7, 3);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(2));
+    assert_eq!(mapper.debug_read_cpu(0xA000), Some(3));
+    assert_eq!(mapper.debug_read_cpu(0xC000), Some(6));
+    assert_eq!(mapper.debug_read_cpu(0xE000), Some(7));
+
+    // Bit 6 moves R6 up to $C000; R7 and the last bank stay put
+    select_bank(&mut mapper, 0b0100_0000, 6, 4);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(6));
+    assert_eq!(mapper.debug_read_cpu(0xA000), Some(3));
+    assert_eq!(mapper.debug_read_cpu(0xC000), Some(4));
+    assert_eq!(mapper.debug_read_cpu(0xE000), Some(7));
+
+    select_bank(&mut mapper, 0, 6, 5);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(5));
+    assert_eq!(mapper.debug_read_cpu(0xC000), Some(6));
+}
+
+#[test]
+fn chr_mode_swaps_the_2k_and_1k_halves() {
+    let mut mapper = banked_mmc3();
+    // R0 is a 2K bank, so it ignores its low bit
+    select_bank(&mut mapper, 0, 0, 3);
+    select_bank(&mut mapper, 0, 2, 9);
+    assert_eq!(mapper.read_ppu(0x0000), Some(2));
+    assert_eq!(mapper.read_ppu(0x0400), Some(3));
+    assert_eq!(mapper.read_ppu(0x1000), Some(9));
+
+    select_bank(&mut mapper, 0b1000_0000, 2, 10);
+    assert_eq!(mapper.read_ppu(0x0000), Some(10));
+    assert_eq!(mapper.read_ppu(0x1000), Some(2));
+    assert_eq!(mapper.read_ppu(0x1400), Some(3));
+
+    select_bank(&mut mapper, 0, 0, 4);
+    assert_eq!(mapper.read_ppu(0x0000), Some(4));
+    assert_eq!(mapper.read_ppu(0x1000), Some(10));
+}
+
+#[test]
+fn writes_to_disabled_prg_ram_are_dropped() {
+    let mut mapper = nes2_mmc3(0);
+    mapper.write_cpu(0x6000, 0x11);
+
+    mapper.write_cpu(0xA001, 0x00);
+    assert!(!mapper.prg_ram_enabled());
+    assert_eq!(mapper.debug_read_cpu(0x6000), None);
+    mapper.write_cpu(0x6000, 0x22);
+
+    mapper.write_cpu(0xA001, 0x80);
+    assert!(mapper.prg_ram_enabled());
+    assert_eq!(mapper.debug_read_cpu(0x6000), Some(0x11));
+}
+
+#[test]
+fn write_protected_prg_ram_still_reads() {
+    let mut mapper = nes2_mmc3(0);
+    mapper.write_cpu(0x6000, 0x11);
+    mapper.write_cpu(0xA001, 0xC0);
+    mapper.write_cpu(0x6000, 0x22);
+    assert_eq!(mapper.debug_read_cpu(0x6000), Some(0x11));
+}
+
+#[test]
+fn ines_1_and_mmc6_ignore_prg_ram_protect() {
+    for mut mapper in vec![mmc3(), nes2_mmc3(1)] {
+        mapper.write_cpu(0xA001, 0x00);
+        assert!(mapper.prg_ram_enabled());
+        mapper.write_cpu(0x6000, 0x33);
+        assert_eq!(mapper.debug_read_cpu(0x6000), Some(0x33));
+    }
+}
+
+// With a reload value of 0, whether each of three scanlines raises an IRQ. Each one is
+// acknowledged (and IRQs re-enabled) before the next.
+fn irqs_with_reload_zero(mapper: &mut Mmc3) -> Vec<bool> {
+    enable_irq(mapper, 0);
+    let mut irqs = Vec::new();
+    for _ in 0 .. 3 {
+        scanline(mapper);
+        irqs.push(mapper.irq_pending());
+        mapper.write_cpu(0xE000, 0);
+        mapper.write_cpu(0xE001, 0);
+    }
+    return irqs;
+}
+
+#[test]
+fn only_nes2_submapper_4_selects_mmc3a_irqs() {
+    assert!(!mmc3().irq_alternate_behavior);
+    assert!(!nes2_mmc3(0).irq_alternate_behavior);
+    assert!(nes2_mmc3(4).irq_alternate_behavior);
+}
+
+#[test]
+fn mmc3b_fires_on_every_scanline_with_reload_zero() {
+    assert_eq!(irqs_with_reload_zero(&mut nes2_mmc3(0)), vec![true, true, true]);
+}
+
+#[test]
+fn mmc3a_fires_only_after_a_c001_reload_with_reload_zero() {
+    let mut mapper = nes2_mmc3(4);
+    assert_eq!(irqs_with_reload_zero(&mut mapper), vec![true, false, false]);
+    // Another $C001 write forces the next clock to count as a reload again
+    mapper.write_cpu(0xC001, 0);
+    scanline(&mut mapper);
+    assert!(mapper.irq_pending());
+}
+
+#[test]
+fn mmc3a_still_fires_when_the_counter_decrements_to_zero() {
+    let mut mapper = nes2_mmc3(4);
+    enable_irq(&mut mapper, 1);
+    scanline(&mut mapper);
+    assert!(!mapper.irq_pending());
+    scanline(&mut mapper);
+    assert_eq!(mapper.irq_counter, 0);
+    assert!(mapper.irq_pending());
+}
@@ -0,0 +1,147 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::mmc::mmc5::Mmc5;
+
+fn mmc5() -> Mmc5 {
+    // 128 KiB PRG and 64 KiB CHR, numbered in 8 KiB and 1 KiB banks respectively
+    let prg = common::numbered_banks(8 * 1024, 16);
+    let chr = common::numbered_banks(1024, 64);
+    let image = common::ines_image(5, 0, &prg, &chr);
+    return Mmc5::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+// The scanline detector fires on the third consecutive fetch of the same nametable address,
+// which the PPU only does at the end of each rendered line
+fn signal_scanline(mapper: &mut Mmc5) {
+    mapper.read_ppu(0x1000);
+    for _ in 0 .. 4 {
+        mapper.read_ppu(0x2000);
+    }
+}
+
+#[test]
+fn boots_with_the_last_bank_at_e000() {
+    let mapper = mmc5();
+    assert_eq!(mapper.debug_read_cpu(0xE000), Some(15));
+    assert_eq!(mapper.debug_read_cpu(0xFFFF), Some(15));
+}
+
+#[test]
+fn prg_mode_3_switches_8k_banks() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5100, 3);
+    mapper.write_cpu(0x5114, 0x80 | 2);
+    mapper.write_cpu(0x5115, 0x80 | 5);
+    mapper.write_cpu(0x5116, 0x80 | 9);
+    mapper.write_cpu(0x5117, 0x80 | 12);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(2));
+    assert_eq!(mapper.debug_read_cpu(0xA000), Some(5));
+    assert_eq!(mapper.debug_read_cpu(0xC000), Some(9));
+    assert_eq!(mapper.debug_read_cpu(0xE000), Some(12));
+}
+
+#[test]
+fn prg_mode_0_switches_a_single_32k_bank() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5100, 0);
+    // 32 KiB bank 1 is 8 KiB banks 4-7
+    mapper.write_cpu(0x5117, 0x80 | 0x04);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(4));
+    assert_eq!(mapper.debug_read_cpu(0xA000), Some(5));
+    assert_eq!(mapper.debug_read_cpu(0xE000), Some(7));
+}
+
+#[test]
+fn chr_mode_3_switches_1k_banks() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5101, 3);
+    mapper.write_cpu(0x5120, 10);
+    mapper.write_cpu(0x5123, 33);
+    mapper.write_cpu(0x5127, 63);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(10));
+    assert_eq!(mapper.debug_read_ppu(0x0C00), Some(33));
+    assert_eq!(mapper.debug_read_ppu(0x1C00), Some(63));
+}
+
+#[test]
+fn chr_mode_0_switches_a_single_8k_bank() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5101, 0);
+    // 8 KiB bank 2 is 1 KiB banks 16-23
+    mapper.write_cpu(0x5127, 2);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(16));
+    assert_eq!(mapper.debug_read_ppu(0x1C00), Some(23));
+}
+
+#[test]
+fn multiplier_returns_the_16_bit_product() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5205, 200);
+    mapper.write_cpu(0x5206, 150);
+    // 200 * 150 = 30000 = $7530
+    assert_eq!(mapper.debug_read_cpu(0x5205), Some(0x30));
+    assert_eq!(mapper.debug_read_cpu(0x5206), Some(0x75));
+}
+
+#[test]
+fn scanline_irq_fires_on_the_compare_line_and_is_acknowledged_by_reading_5204() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5203, 2);
+    mapper.write_cpu(0x5204, 0x80);
+
+    signal_scanline(&mut mapper);
+    assert_eq!(mapper.debug_read_cpu(0x5204), Some(0x40), "in frame, no IRQ yet");
+    signal_scanline(&mut mapper);
+    assert!(!mapper.irq_pending());
+    signal_scanline(&mut mapper);
+    assert!(mapper.irq_pending());
+
+    assert_eq!(mapper.read_cpu(0x5204), Some(0xC0));
+    assert!(!mapper.irq_pending());
+}
+
+#[test]
+fn scanline_irq_is_masked_when_disabled() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5203, 1);
+    signal_scanline(&mut mapper);
+    signal_scanline(&mut mapper);
+    assert!(!mapper.irq_pending());
+    // The pending flag is still visible in the status register
+    assert_eq!(mapper.debug_read_cpu(0x5204), Some(0xC0));
+}
+
+#[test]
+fn fill_mode_nametable_returns_the_fill_tile_and_attribute() {
+    let mut mapper = mmc5();
+    mapper.write_cpu(0x5105, 0xFF);
+    mapper.write_cpu(0x5106, 0x42);
+    mapper.write_cpu(0x5107, 0x02);
+    assert_eq!(mapper.debug_read_ppu(0x2000), Some(0x42));
+    assert_eq!(mapper.debug_read_ppu(0x2FC0), Some(0xAA));
+}
+
+#[test]
+fn vertical_split_reads_its_nametable_from_exram() {
+    let mut mapper = mmc5();
+    // ExRAM mode 2 allows plain CPU writes
+    mapper.write_cpu(0x5104, 2);
+    mapper.write_cpu(0x5C00 + 2 * 32 + 3, 0x77);
+    mapper.write_cpu(0x5C00 + 0x3C0, 0b11_10_01_00);
+
+    mapper.write_cpu(0x5200, 0x80 | 0x40 | 8);
+    assert!(!mapper.vertical_split_active(), "split needs ExRAM mode 0 or 1");
+    mapper.write_cpu(0x5104, 1);
+    assert!(mapper.vertical_split_active());
+
+    // Tile 3, line 17 is row 2 of the split nametable
+    assert_eq!(mapper.read_split_nametable(0x2000, 3, 17), 0x77);
+    // Attribute byte 0 covers tiles 0-3 of rows 0-3; the palette is copied to every quadrant
+    assert_eq!(mapper.read_split_nametable(0x23C0, 1, 0), 0b00_00_00_00);
+    assert_eq!(mapper.read_split_nametable(0x23C0, 3, 0), 0b01_01_01_01);
+    assert_eq!(mapper.read_split_nametable(0x23C0, 1, 17), 0b10_10_10_10);
+    assert_eq!(mapper.read_split_nametable(0x23C0, 3, 17), 0b11_11_11_11);
+}
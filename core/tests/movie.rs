@@ -0,0 +1,85 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::movie::{Movie, MOVIE_MAGIC, MOVIE_VERSION};
+use rustico_core::nes::NesState;
+use rustico_core::save_load::{save_usize, save_vec, StateError};
+
+// Strobes controller 1 in a loop, shifting the 8 buttons into $00
+const READ_CONTROLLER: [u8; 24] = [
+    0xA9, 0x01,       // LDA #$01
+    0x8D, 0x16, 0x40, // STA $4016
+    0xA9, 0x00,       // LDA #$00
+    0x8D, 0x16, 0x40, // STA $4016
+    0xA2, 0x08,       // LDX #$08
+    0xAD, 0x16, 0x40, // LDA $4016
+    0x4A,             // LSR A
+    0x26, 0x00,       // ROL $00
+    0xCA,             // DEX
+    0xD0, 0xF7,       // BNE -9
+    0x4C, 0x00, 0x80, // JMP $8000
+];
+
+const INPUTS: [u8; 6] = [0x00, 0x01, 0x80, 0x81, 0x3C, 0x00];
+
+fn controller_byte_per_frame(nes: &mut NesState, inputs: &[u8]) -> Vec<u8> {
+    let mut seen = Vec::new();
+    for input in inputs.iter() {
+        nes.p1_input = *input;
+        nes.step_frame();
+        seen.push(nes.memory.iram_raw[0]);
+    }
+    return seen;
+}
+
+#[test]
+fn playback_reproduces_the_recorded_input() {
+    let mut nes = common::nes_with_program(&READ_CONTROLLER);
+    nes.start_recording();
+    let recorded = controller_byte_per_frame(&mut nes, &INPUTS);
+    let movie = nes.stop_recording().expect("a movie was being recorded");
+    // step_frame stops at vblank, so the last frame is still in progress and isn't kept
+    assert_eq!(movie.frames.len(), INPUTS.len() - 1);
+    assert!(recorded.iter().any(|&byte| byte != 0), "the program never saw any input");
+
+    let mut nes = common::nes_with_program(&READ_CONTROLLER);
+    nes.play(Movie::from_bytes(&movie.to_bytes()).unwrap()).unwrap();
+    // Live input is ignored until the movie runs out
+    let played = controller_byte_per_frame(&mut nes, &[0xFF; 5]);
+    assert_eq!(played[..], recorded[.. 5]);
+}
+
+#[test]
+fn movies_round_trip_through_bytes() {
+    let mut movie = Movie::new(vec![1, 2, 3, 4]);
+    movie.frames.push([0x01, 0x80]);
+    movie.frames.push([0xFF, 0x00]);
+
+    let loaded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(loaded.initial_state, movie.initial_state);
+    assert_eq!(loaded.frames, movie.frames);
+}
+
+#[test]
+fn truncated_movies_are_rejected() {
+    let mut movie = Movie::new(Vec::new());
+    movie.frames.push([0x01, 0x02]);
+    let bytes = movie.to_bytes();
+    match Movie::from_bytes(&bytes[.. bytes.len() - 1]) {
+        Err(StateError::SizeMismatch{expected: 2, found: 1}) => {},
+        other => panic!("expected a size mismatch, got {:?}", other.err())
+    }
+}
+
+#[test]
+fn overflowing_frame_counts_are_rejected() {
+    let mut bytes = MOVIE_MAGIC.to_vec();
+    bytes.push(MOVIE_VERSION);
+    save_vec(&mut bytes, &[]);
+    save_usize(&mut bytes, usize::max_value());
+    match Movie::from_bytes(&bytes) {
+        Err(StateError::InvalidValue(_)) => {},
+        other => panic!("expected an invalid frame count, got {:?}", other.err())
+    }
+}
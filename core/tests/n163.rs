@@ -0,0 +1,85 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::mmc::n163::Namco163;
+
+fn n163() -> Namco163 {
+    let image = common::ines_image(19, 0, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+    return Namco163::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+// Channel 1 (the registers at $78) plays a 4 sample wave from $00, advancing one sample per
+// update at full volume
+fn load_ramp(mapper: &mut Namco163) {
+    // $F800: internal RAM address 0 with auto-increment
+    mapper.write_cpu(0xF800, 0x80);
+    // Samples 0, 4, 8, 15, low nybble first
+    mapper.write_cpu(0x4800, 0x40);
+    mapper.write_cpu(0x4800, 0xF8);
+
+    mapper.write_cpu(0xF800, 0x80 | 0x78);
+    for &byte in [
+        0x00, // frequency low
+        0x00, // phase low
+        0x00, // frequency mid
+        0x00, // phase mid
+        0xFD, // length 256 - 252 = 4, frequency high = 1
+        0x00, // phase high
+        0x00, // wave address
+        0x0F, // volume 15, one channel enabled
+    ].iter() {
+        mapper.write_cpu(0x4800, byte);
+    }
+}
+
+/// Each channel updates once every 15 CPU cycles; returns the output after each update.
+fn outputs(mapper: &mut Namco163, updates: usize) -> Vec<f32> {
+    let mut outputs = Vec::new();
+    for _ in 0 .. updates {
+        for _ in 0 .. 15 {
+            mapper.on_cpu_cycle();
+        }
+        outputs.push(mapper.expansion_audio_chip.current_output);
+    }
+    return outputs;
+}
+
+#[test]
+fn sound_starts_disabled() {
+    let mut mapper = n163();
+    assert!(!mapper.sound_enabled);
+    load_ramp(&mut mapper);
+
+    assert_eq!(outputs(&mut mapper, 4), vec![0.0; 4]);
+    assert_eq!(mapper.mix_expansion_audio(0.25), 0.25);
+}
+
+#[test]
+fn wavetable_channel_plays_its_samples() {
+    let mut mapper = n163();
+    load_ramp(&mut mapper);
+    // $E000 bit 6 clear enables sound
+    mapper.write_cpu(0xE000, 0x00);
+    assert!(mapper.sound_enabled);
+
+    // Samples are centered on 8 and scaled by volume, starting one step into the wave
+    assert_eq!(outputs(&mut mapper, 5), vec![-60.0, 0.0, 105.0, -120.0, -60.0]);
+    assert!(mapper.mix_expansion_audio(0.25) != 0.25);
+}
+
+#[test]
+fn disabling_sound_holds_the_phase() {
+    let mut mapper = n163();
+    load_ramp(&mut mapper);
+    mapper.write_cpu(0xE000, 0x00);
+    assert_eq!(outputs(&mut mapper, 1), vec![-60.0]);
+
+    mapper.write_cpu(0xE000, 0x40);
+    assert_eq!(outputs(&mut mapper, 2), vec![-60.0, -60.0]);
+    assert_eq!(mapper.mix_expansion_audio(0.25), 0.25);
+
+    mapper.write_cpu(0xE000, 0x00);
+    assert_eq!(outputs(&mut mapper, 1), vec![0.0]);
+}
@@ -0,0 +1,69 @@
+// Runs nestest in automated mode, comparing every instruction against Nintendulator's reference
+// log. Neither file can be distributed with rustico; drop nestest.nes and nestest.log into this
+// directory to run it, otherwise the test passes without doing anything.
+extern crate rustico_core;
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const ROM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/nestest.nes");
+const LOG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/nestest.log");
+
+// Collects trace output so it can be compared as it's produced
+struct TraceBuffer {
+    lines: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Write for TraceBuffer {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.lines.lock().unwrap().extend_from_slice(data);
+        return Ok(data.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+// The registers and cycle count of a trace line. The disassembly itself is skipped, since
+// Nintendulator annotates operands with the values they point to and we don't.
+fn trace_fields(line: &str) -> Option<(String, String, String)> {
+    let pc = line.get(0 .. 4)?.to_string();
+    let registers_start = line.find("A:")?;
+    let cycles_start = line.find("CYC:")?;
+    let registers_end = line.find("PPU:").unwrap_or(cycles_start);
+    let registers = line.get(registers_start .. registers_end)?.trim().to_string();
+    let cycles = line.get(cycles_start ..)?.trim().to_string();
+    return Some((pc, registers, cycles));
+}
+
+#[test]
+fn nestest_matches_the_reference_log() {
+    if !Path::new(ROM_PATH).exists() || !Path::new(LOG_PATH).exists() {
+        println!("nestest: {} or {} not found, skipping", ROM_PATH, LOG_PATH);
+        return;
+    }
+    let log = fs::read_to_string(LOG_PATH).unwrap();
+    let mut nes = common::nes_from_image(&fs::read(ROM_PATH).unwrap());
+
+    // Automated mode starts at $C000 instead of the reset vector. The reference log counts
+    // the 7 cycles of the reset sequence, so start from there too.
+    nes.registers.pc = 0xC000;
+    nes.master_clock = 7 * nes.region.master_clocks_per_cpu_cycle() as u64;
+    let trace_output = Arc::new(Mutex::new(Vec::new()));
+    nes.set_trace_sink(Box::new(TraceBuffer{lines: trace_output.clone()}));
+
+    for (index, expected) in log.lines().enumerate() {
+        nes.step();
+        let actual = String::from_utf8(trace_output.lock().unwrap().drain(..).collect()).unwrap();
+        let actual = actual.trim_end();
+        assert!(trace_fields(expected) == trace_fields(actual),
+            "mismatch on line {}\nexpected: {}\n  actual: {}", index + 1, expected, actual);
+    }
+    // nestest leaves its result codes here; both are zero when every test passed
+    assert_eq!((nes.memory.iram_raw[0x02], nes.memory.iram_raw[0x03]), (0, 0));
+}
@@ -0,0 +1,116 @@
+// NMI timing around the start of vblank, counted by an NMI handler that increments $00.
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::memory;
+use rustico_core::nes::NesState;
+
+const NMI_COUNT: usize = 0x00;
+
+fn nmi_counting_nes(program: &[u8]) -> NesState {
+    let mut prg = common::nrom_prg(program);
+    let handler = (common::INTERRUPT_HANDLER - 0x8000) as usize;
+    prg[handler .. handler + 3].copy_from_slice(&[0xE6, NMI_COUNT as u8, 0x40]); // INC $00, RTI
+    return common::nes_from_image(&common::ines_image(0, 0, &prg, &vec![0u8; 8 * 1024]));
+}
+
+// LDA #$80, STA $2000 (NMI on), JMP *
+const NMI_ON: [u8; 8] = [0xA9, 0x80, 0x8D, 0x00, 0x20, 0x4C, 0x05, 0x80];
+
+/// Runs past the first frame's NMI to the scanline before the next vblank, and zeroes the count.
+fn run_to_next_vblank(nes: &mut NesState) {
+    nes.step_frame();
+    run_to_dot(nes, 240, 0);
+    nes.memory.iram_raw[NMI_COUNT] = 0;
+}
+
+/// Master clocks until the PPU is about to clock `dot` of `scanline`.
+fn run_to_dot(nes: &mut NesState, scanline: u16, dot: u16) {
+    while !(nes.ppu.current_scanline == scanline && nes.ppu.current_scanline_cycle == dot) {
+        nes.step_master_cycle();
+    }
+}
+
+/// Runs through the rest of vblank's first scanline, returning whether the vblank flag was
+/// ever seen set.
+fn finish_scanline_241(nes: &mut NesState) -> bool {
+    let mut vblank_seen = false;
+    while nes.ppu.current_scanline == 241 {
+        vblank_seen |= nes.ppu.status & 0x80 != 0;
+        nes.step_master_cycle();
+    }
+    return vblank_seen;
+}
+
+#[test]
+fn nmi_fires_once_per_frame() {
+    let mut nes = nmi_counting_nes(&NMI_ON);
+    run_to_next_vblank(&mut nes);
+    run_to_dot(&mut nes, 241, 0);
+    assert!(finish_scanline_241(&mut nes));
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 1);
+}
+
+#[test]
+fn reading_ppustatus_just_before_vblank_suppresses_the_flag_and_the_nmi() {
+    let mut nes = nmi_counting_nes(&NMI_ON);
+    run_to_next_vblank(&mut nes);
+    run_to_dot(&mut nes, 241, 1);
+    // Sees the flag still clear
+    assert_eq!(memory::read_byte(&mut nes, 0x2002) & 0x80, 0);
+    assert!(!finish_scanline_241(&mut nes));
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 0);
+}
+
+#[test]
+fn reading_ppustatus_as_vblank_begins_sees_the_flag_but_cancels_the_nmi() {
+    let mut nes = nmi_counting_nes(&NMI_ON);
+    run_to_next_vblank(&mut nes);
+    run_to_dot(&mut nes, 241, 2);
+    assert_eq!(memory::read_byte(&mut nes, 0x2002) & 0x80, 0x80);
+    finish_scanline_241(&mut nes);
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 0);
+}
+
+fn run_cpu_cycles(nes: &mut NesState, cycles: u64) {
+    let target = nes.master_clock + cycles * nes.region.master_clocks_per_cpu_cycle() as u64;
+    while nes.master_clock < target {
+        nes.step_master_cycle();
+    }
+}
+
+#[test]
+fn enabling_nmi_during_vblank_fires_one_immediately() {
+    let mut nes = nmi_counting_nes(&[0x4C, 0x00, 0x80]); // JMP *, NMI off
+    run_to_next_vblank(&mut nes);
+    run_to_dot(&mut nes, 245, 0);
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 0);
+
+    memory::write_byte(&mut nes, 0x2000, 0x80);
+    run_cpu_cycles(&mut nes, 20);
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 1);
+
+    // Still in vblank, so toggling bit 7 off and on again is another rising edge
+    memory::write_byte(&mut nes, 0x2000, 0x00);
+    run_cpu_cycles(&mut nes, 2);
+    memory::write_byte(&mut nes, 0x2000, 0x80);
+    run_cpu_cycles(&mut nes, 20);
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 2);
+
+    // Rewriting the same value is no edge at all
+    memory::write_byte(&mut nes, 0x2000, 0x80);
+    run_cpu_cycles(&mut nes, 20);
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 2);
+}
+
+#[test]
+fn enabling_nmi_after_ppustatus_clears_vblank_does_nothing() {
+    let mut nes = nmi_counting_nes(&[0x4C, 0x00, 0x80]);
+    run_to_next_vblank(&mut nes);
+    run_to_dot(&mut nes, 245, 0);
+    memory::read_byte(&mut nes, 0x2002);
+    memory::write_byte(&mut nes, 0x2000, 0x80);
+    run_cpu_cycles(&mut nes, 20);
+    assert_eq!(nes.memory.iram_raw[NMI_COUNT], 0);
+}
@@ -0,0 +1,78 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::nes::NesState;
+
+fn cpu_cycles(nes: &NesState) -> u64 {
+    return nes.master_clock / nes.region.master_clocks_per_cpu_cycle() as u64;
+}
+
+fn run_to(nes: &mut NesState, address: u16) {
+    while nes.registers.pc != address || nes.cpu.tick != 0 {
+        nes.step();
+    }
+}
+
+// Cycles the CPU spends halted by an OAM DMA from page $02. `setup` runs first, and can both
+// shift the write against the APU's get/put cycles and start the DMC. Measured as the time
+// taken by the NOP following the $4014 write, less the NOP's own 2 cycles.
+fn oam_dma_cycles(setup: &[u8]) -> u64 {
+    let mut program = setup.to_vec();
+    // LDA #$02, STA $4014
+    program.extend_from_slice(&[0xA9, 0x02, 0x8D, 0x14, 0x40]);
+    let nop = 0x8000 + program.len() as u16;
+    program.push(0xEA);
+    let mut nes = common::nes_with_program(&program);
+    run_to(&mut nes, nop);
+    let start = cpu_cycles(&nes);
+    run_to(&mut nes, nop + 1);
+    return cpu_cycles(&nes) - start - 2;
+}
+
+#[test]
+fn oam_dma_takes_513_or_514_cycles_depending_on_alignment() {
+    // NOP (2 cycles) and BIT zp (3 cycles) leave the STA on opposite APU cycle parities
+    let mut lengths = [oam_dma_cycles(&[0xEA]), oam_dma_cycles(&[0x24, 0x00])];
+    lengths.sort();
+    assert_eq!(lengths, [513, 514]);
+}
+
+#[test]
+fn oam_dma_copies_the_whole_page() {
+    let mut nes = common::nes_with_program(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0xEA]);
+    for i in 0 .. 256 {
+        nes.memory.iram_raw[0x200 + i] = i as u8;
+    }
+    run_to(&mut nes, 0x8006);
+    for i in 0 .. 256 {
+        assert_eq!(nes.ppu.oam[i], i as u8);
+    }
+}
+
+// Starts (or doesn't start) a long DMC sample at the fastest rate, then waits out a delay
+// loop long enough for the DMC to settle into a fetch every 450 cycles or so. Any OAM DMA
+// after this one overlaps exactly one DMC fetch.
+fn dmc_setup(dmc_enable: u8, alignment: &[u8]) -> Vec<u8> {
+    let mut setup = vec![
+        0xA9, 0x0F, 0x8D, 0x10, 0x40, // LDA #$0F, STA $4010
+        0xA9, 0xFF, 0x8D, 0x13, 0x40, // LDA #$FF, STA $4013
+        0xA9, dmc_enable, 0x8D, 0x15, 0x40, // LDA #dmc_enable, STA $4015
+        0xA2, 0x00, 0xCA, 0xD0, 0xFD, // LDX #$00, DEX, BNE -3
+    ];
+    setup.extend_from_slice(alignment);
+    return setup;
+}
+
+#[test]
+fn a_dmc_fetch_during_oam_dma_costs_two_cycles() {
+    // The DMC's own fetches during setup shift the alignment, so compare the pair of lengths
+    // rather than each alignment on its own
+    let alignments = [&[0xEAu8][..], &[0x24, 0x00][..]];
+    let mut without_dmc: Vec<u64> = alignments.iter().map(|a| oam_dma_cycles(&dmc_setup(0x00, a))).collect();
+    let mut with_dmc: Vec<u64> = alignments.iter().map(|a| oam_dma_cycles(&dmc_setup(0x10, a))).collect();
+    without_dmc.sort();
+    with_dmc.sort();
+    assert_eq!(without_dmc, vec![513, 514]);
+    assert_eq!(with_dmc, vec![515, 516]);
+}
@@ -0,0 +1,115 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::cartridge::mapper_from_file;
+use rustico_core::palettes::{load_pal, Palette, PaletteError, BASE_PAL_SIZE, FULL_PAL_SIZE, NTSC_PAL};
+use rustico_core::ppu::PpuState;
+use rustico_core::zapper::ZapperState;
+
+fn pixel_rgb(pixel: u16) -> [u8; 3] {
+    return Palette::ntsc().rgb(pixel);
+}
+
+fn table_entry(index: usize) -> [u8; 3] {
+    return [NTSC_PAL[index * 3 + 0], NTSC_PAL[index * 3 + 1], NTSC_PAL[index * 3 + 2]];
+}
+
+/// The first pixel of a frame drawn with rendering off, so it shows the backdrop color
+fn backdrop_pixel(backdrop: u8, mask: u8) -> u16 {
+    let image = common::ines_image(0, 0, &common::nrom_prg(&[]), &[]);
+    let mut mapper = mapper_from_file(&image).expect("test image should load");
+    let mut ppu = PpuState::new();
+    ppu.palette[0] = backdrop;
+    ppu.mask = mask;
+    while !(ppu.current_scanline == 0 && ppu.current_scanline_cycle == 2) {
+        ppu.clock(&mut *mapper);
+    }
+    return ppu.screen[0];
+}
+
+#[test]
+fn emphasis_bits_select_the_matching_table_copy() {
+    for emphasis in 0 .. 8 {
+        let pixel = 0x16 | (emphasis << 6);
+        assert_eq!(pixel_rgb(pixel), table_entry(emphasis as usize * 64 + 0x16));
+    }
+    // Red emphasis pulls the other components down
+    let plain = pixel_rgb(0x16);
+    let emphasized = pixel_rgb(0x16 | 0x40);
+    assert!(emphasized[1] < plain[1] && emphasized[2] < plain[2]);
+    // PPUMASK bit 7 (blue) lands in pixel bit 8 and pulls red and green down instead
+    let pixel = backdrop_pixel(0x16, 0b1000_0000);
+    assert_eq!(pixel, 0x100 | 0x16);
+    let emphasized = pixel_rgb(pixel);
+    assert!(emphasized[0] < plain[0] && emphasized[1] < plain[1]);
+}
+
+#[test]
+fn bits_above_the_emphasis_are_ignored() {
+    assert_eq!(pixel_rgb(0x16 | 0xFE00), pixel_rgb(0x16));
+}
+
+#[test]
+fn ppu_pixels_carry_emphasis_from_ppumask() {
+    // PPUMASK bit 5 (red) lands in pixel bit 6
+    let pixel = backdrop_pixel(0x16, 0b0010_0000);
+    assert_eq!(pixel, 0x40 | 0x16);
+    assert_eq!(pixel_rgb(pixel), table_entry(64 + 0x16));
+}
+
+#[test]
+fn grayscale_is_applied_before_the_palette_lookup() {
+    // $16 is a red; grayscale keeps only the brightness column, $10
+    let pixel = backdrop_pixel(0x16, 0b0000_0001);
+    assert_eq!(pixel, 0x10);
+    assert_eq!(pixel_rgb(pixel), table_entry(0x10));
+    // ...and still combines with emphasis
+    let pixel = backdrop_pixel(0x16, 0b1000_0001);
+    assert_eq!(pixel_rgb(pixel), table_entry(4 * 64 + 0x10));
+}
+
+#[test]
+fn full_pal_files_are_used_as_is() {
+    let bytes: Vec<u8> = (0 .. FULL_PAL_SIZE).map(|i| (i % 251) as u8).collect();
+    let palette = load_pal(&bytes).unwrap();
+    assert_eq!(palette.colors, bytes);
+}
+
+#[test]
+fn base_pal_files_fill_in_the_emphasis_variants() {
+    let bytes = vec![200u8; BASE_PAL_SIZE];
+    let palette = load_pal(&bytes).unwrap();
+    assert_eq!(palette.colors.len(), FULL_PAL_SIZE);
+    assert_eq!(palette.rgb(0x16), [200, 200, 200]);
+    // Red emphasis keeps red and darkens green and blue
+    let red = palette.rgb(0x40 | 0x16);
+    assert_eq!(red[0], 200);
+    assert!(red[1] < 200 && red[2] < 200);
+    assert_eq!(red[1], red[2]);
+    // With every bit set, each channel is darkened by the other two
+    let all = palette.rgb(0x1C0 | 0x16);
+    assert!(all[0] < 200 && all[0] == all[1] && all[1] == all[2]);
+}
+
+#[test]
+fn pal_files_of_any_other_size_are_rejected() {
+    assert_eq!(load_pal(&vec![0u8; 100]).err(), Some(PaletteError::InvalidLength(100)));
+    assert_eq!(load_pal(&[]).err(), Some(PaletteError::InvalidLength(0)));
+}
+
+#[test]
+fn zapper_sees_through_the_loaded_palette() {
+    let mut ppu = PpuState::new();
+    for pixel in ppu.screen.iter_mut() {
+        *pixel = 0x30;
+    }
+    // Just after the beam drew the aim point
+    ppu.current_scanline = 101;
+    let mut zapper = ZapperState::new();
+    zapper.aim(128, 100);
+
+    assert!(zapper.light_detected(&ppu, &Palette::ntsc()));
+    let black = Palette {colors: vec![0u8; FULL_PAL_SIZE]};
+    assert!(!zapper.light_detected(&ppu, &black));
+}
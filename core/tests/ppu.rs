@@ -0,0 +1,412 @@
+// PPU rendering and timing, driven dot by dot against a CHR RAM cartridge so each test can draw
+// its own tiles.
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::cartridge::mapper_from_file;
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::palettes::NTSC_PAL;
+use rustico_core::ppu::{apply_ntsc_filter, PpuState, SpriteInfo};
+
+// PPUMASK: show background and sprites, including the leftmost 8 pixels
+const SHOW_ALL: u8 = 0b0001_1110;
+
+fn chr_ram_mapper() -> Box<dyn Mapper> {
+    let image = common::ines_image(0, 0, &common::nrom_prg(&[]), &[]);
+    return mapper_from_file(&image).expect("test image should load");
+}
+
+/// A PPU with every sprite parked offscreen, and a mapper whose tile 0 is solid color 1, so
+/// the whole background (which defaults to tile 0) is opaque.
+fn opaque_background() -> (PpuState, Box<dyn Mapper>) {
+    let mut mapper = chr_ram_mapper();
+    for row in 0 .. 8 {
+        mapper.write_ppu(row, 0xFF);
+    }
+    let mut ppu = PpuState::new();
+    for byte in ppu.oam.iter_mut() {
+        *byte = 0xFF;
+    }
+    return (ppu, mapper);
+}
+
+fn set_sprite(ppu: &mut PpuState, index: usize, x: u8, y: u8, tile: u8, attributes: u8) {
+    ppu.oam[index * 4 + 0] = y;
+    ppu.oam[index * 4 + 1] = tile;
+    ppu.oam[index * 4 + 2] = attributes;
+    ppu.oam[index * 4 + 3] = x;
+}
+
+/// Clocks until `done` holds or a frame and a bit have gone by. Returns the scanline and dot
+/// of the last dot clocked.
+fn clock_until<F: Fn(&PpuState) -> bool>(ppu: &mut PpuState, mapper: &mut dyn Mapper, done: F) -> Option<(u16, u16)> {
+    for _ in 0 .. 341 * 263 {
+        let scanline = ppu.current_scanline;
+        let dot = ppu.current_scanline_cycle;
+        ppu.clock(mapper);
+        if done(ppu) {
+            return Some((scanline, dot));
+        }
+    }
+    return None;
+}
+
+#[test]
+fn sprite_zero_hits_on_its_first_opaque_pixel() {
+    let (mut ppu, mut mapper) = opaque_background();
+    set_sprite(&mut ppu, 0, 100, 30, 0, 0);
+    ppu.mask = SHOW_ALL;
+
+    // Sprites show up one line below their Y coordinate, and dot N draws pixel N - 1
+    let hit = clock_until(&mut ppu, &mut *mapper, |ppu| ppu.status & 0x40 != 0);
+    assert_eq!(hit, Some((31, 101)));
+}
+
+#[test]
+fn sprite_zero_hit_lasts_until_the_prerender_line() {
+    let (mut ppu, mut mapper) = opaque_background();
+    set_sprite(&mut ppu, 0, 100, 30, 0, 0);
+    ppu.mask = SHOW_ALL;
+
+    clock_until(&mut ppu, &mut *mapper, |ppu| ppu.status & 0x40 != 0).expect("sprite zero should hit");
+    let cleared = clock_until(&mut ppu, &mut *mapper, |ppu| ppu.status & 0x40 == 0);
+    assert_eq!(cleared, Some((261, 1)));
+}
+
+#[test]
+fn sprite_zero_never_hits_at_x_255() {
+    let (mut ppu, mut mapper) = opaque_background();
+    set_sprite(&mut ppu, 0, 255, 30, 0, 0);
+    ppu.mask = SHOW_ALL;
+
+    let hit = clock_until(&mut ppu, &mut *mapper, |ppu| ppu.status & 0x40 != 0 || ppu.current_scanline == 240);
+    assert_eq!(ppu.status & 0x40, 0, "hit reported at {:?}", hit);
+}
+
+#[test]
+fn sprite_info_decodes_8x8_oam_entries() {
+    let mut ppu = PpuState::new();
+    ppu.control = 0x08; // 8x8 sprites from $1000
+    set_sprite(&mut ppu, 5, 0x40, 0x30, 0x27, 0b1110_0010);
+
+    assert_eq!(ppu.sprite_info(5), SpriteInfo {
+        x: 0x40,
+        y: 0x30,
+        tile_index: 0x27,
+        pattern_address: 0x1000,
+        height: 8,
+        palette: 2,
+        behind_background: true,
+        flip_horizontal: true,
+        flip_vertical: true,
+    });
+}
+
+#[test]
+fn sprite_info_takes_8x16_pattern_table_from_the_tile_index() {
+    let mut ppu = PpuState::new();
+    ppu.control = 0x20 | 0x08; // 8x16 sprites; the PPUCTRL table bit no longer applies
+    set_sprite(&mut ppu, 0, 0x10, 0x20, 0x27, 0b0000_0001);
+    set_sprite(&mut ppu, 1, 0x10, 0x20, 0x26, 0b0000_0001);
+
+    let odd = ppu.sprite_info(0);
+    assert_eq!((odd.tile_index, odd.pattern_address, odd.height), (0x26, 0x1000, 16));
+    let even = ppu.sprite_info(1);
+    assert_eq!((even.tile_index, even.pattern_address, even.height), (0x26, 0x0000, 16));
+    assert_eq!(odd.palette, 1);
+    assert!(!odd.behind_background && !odd.flip_horizontal && !odd.flip_vertical);
+}
+
+#[test]
+fn all_sprites_lists_every_oam_entry_in_order() {
+    let mut ppu = PpuState::new();
+    for index in 0 .. 64 {
+        set_sprite(&mut ppu, index, index as u8, 0, 0, 0);
+    }
+    let sprites = ppu.all_sprites();
+    assert_eq!(sprites.len(), 64);
+    for (index, sprite) in sprites.iter().enumerate() {
+        assert_eq!(sprite.x, index as u8);
+    }
+}
+
+// Columns alternating between two colors, the classic dither that composite video blends
+fn dithered_screen() -> Vec<u16> {
+    let mut screen = vec![0u16; 256 * 240];
+    for (i, pixel) in screen.iter_mut().enumerate() {
+        *pixel = if i % 2 == 0 {0x16} else {0x2A};
+    }
+    return screen;
+}
+
+#[test]
+fn ntsc_filter_snapshot() {
+    let mut output = vec![0u32; 512 * 240];
+    apply_ntsc_filter(&dithered_screen(), 0, 512, &mut output);
+    assert_eq!(output[100 .. 108], [
+        0xFF7F8079, 0xFF9D859D, 0xFFA3CC93, 0xFFB3DD86, 0xFFA8D460, 0xFFA89917, 0xFF888F12, 0xFF6A8A00]);
+}
+
+#[test]
+fn ntsc_filter_shimmers_as_the_phase_advances() {
+    let mut even_frame = vec![0u32; 512 * 240];
+    let mut odd_frame = vec![0u32; 512 * 240];
+    apply_ntsc_filter(&dithered_screen(), 0, 512, &mut even_frame);
+    apply_ntsc_filter(&dithered_screen(), 1, 512, &mut odd_frame);
+    assert!(even_frame != odd_frame);
+}
+
+#[test]
+fn ntsc_filter_keeps_flat_white_white() {
+    let mut output = vec![0u32; 602 * 240];
+    apply_ntsc_filter(&vec![0x30u16; 256 * 240], 0, 602, &mut output);
+    // The very edges blend with the blanking around the picture
+    for scanline in 0 .. 240 {
+        for x in 4 .. 598 {
+            assert_eq!(output[scanline * 602 + x], 0xFFFFFFFF);
+        }
+    }
+}
+
+const SPRITE_COLOR: u8 = 0x16;
+const BACKDROP_COLOR: u8 = 0x0F;
+
+/// Transparent background, a solid tile 1 for sprites drawing in SPRITE_COLOR, and every
+/// sprite parked offscreen.
+fn sprite_scene() -> (PpuState, Box<dyn Mapper>) {
+    let mut mapper = chr_ram_mapper();
+    for row in 16 .. 24 {
+        mapper.write_ppu(row, 0xFF);
+    }
+    let mut ppu = PpuState::new();
+    for byte in ppu.oam.iter_mut() {
+        *byte = 0xFF;
+    }
+    ppu.palette[0x00] = BACKDROP_COLOR;
+    ppu.palette[0x11] = SPRITE_COLOR;
+    ppu.mask = SHOW_ALL;
+    return (ppu, mapper);
+}
+
+// Ten sprites side by side on scanlines 31-38, 16 pixels apart
+fn ten_sprites_on_one_line(ppu: &mut PpuState) {
+    for index in 0 .. 10 {
+        set_sprite(ppu, index, (8 + index * 16) as u8, 30, 1, 0);
+    }
+}
+
+fn drawn_sprites(ppu: &PpuState, scanline: usize) -> Vec<bool> {
+    return (0 .. 10).map(|index| (ppu.screen[scanline * 256 + 8 + index * 16] & 0x3F) as u8 == SPRITE_COLOR).collect();
+}
+
+#[test]
+fn only_eight_sprites_draw_per_scanline() {
+    let (mut ppu, mut mapper) = sprite_scene();
+    ten_sprites_on_one_line(&mut ppu);
+    clock_until(&mut ppu, &mut *mapper, |ppu| ppu.current_scanline == 40);
+    let mut expected = vec![true; 8];
+    expected.extend_from_slice(&[false, false]);
+    assert_eq!(drawn_sprites(&ppu, 31), expected);
+}
+
+#[test]
+fn disabling_the_sprite_limit_draws_every_sprite() {
+    let (mut ppu, mut mapper) = sprite_scene();
+    ppu.disable_sprite_limit = true;
+    ten_sprites_on_one_line(&mut ppu);
+    clock_until(&mut ppu, &mut *mapper, |ppu| ppu.current_scanline == 40);
+    assert_eq!(drawn_sprites(&ppu, 31), vec![true; 10]);
+    assert_eq!(drawn_sprites(&ppu, 38), vec![true; 10]);
+    // Purely cosmetic: games still see the overflow
+    assert!(ppu.status & 0x20 != 0);
+}
+
+fn overflow_after_scanline_40(ppu: &mut PpuState, mapper: &mut dyn Mapper) -> bool {
+    clock_until(ppu, mapper, |ppu| ppu.current_scanline == 40);
+    return ppu.status & 0x20 != 0;
+}
+
+// Fills secondary OAM: sprites 0-7 on scanlines 31-38
+fn eight_sprites_on_one_line(ppu: &mut PpuState) {
+    for index in 0 .. 8 {
+        set_sprite(ppu, index, (8 + index * 16) as u8, 30, 1, 0);
+    }
+}
+
+#[test]
+fn eight_sprites_on_a_line_do_not_overflow() {
+    let (mut ppu, mut mapper) = sprite_scene();
+    eight_sprites_on_one_line(&mut ppu);
+    assert!(!overflow_after_scanline_40(&mut ppu, &mut *mapper));
+}
+
+#[test]
+fn a_ninth_sprite_right_after_the_first_eight_overflows() {
+    let (mut ppu, mut mapper) = sprite_scene();
+    eight_sprites_on_one_line(&mut ppu);
+    set_sprite(&mut ppu, 8, 200, 30, 1, 0);
+    assert!(overflow_after_scanline_40(&mut ppu, &mut *mapper));
+}
+
+#[test]
+fn overflow_bug_misses_a_ninth_sprite_after_a_miss() {
+    let (mut ppu, mut mapper) = sprite_scene();
+    eight_sprites_on_one_line(&mut ppu);
+    // Sprite 8 is out of range, so sprite 9 gets checked by its tile byte instead of its Y
+    set_sprite(&mut ppu, 9, 200, 30, 0xFF, 0);
+    assert!(!overflow_after_scanline_40(&mut ppu, &mut *mapper));
+}
+
+#[test]
+fn overflow_bug_reports_a_sprite_that_is_not_in_range() {
+    let (mut ppu, mut mapper) = sprite_scene();
+    eight_sprites_on_one_line(&mut ppu);
+    // Offscreen, but its tile byte reads as a Y coordinate on scanline 31
+    set_sprite(&mut ppu, 9, 200, 0xFF, 30, 0xFF);
+    assert!(overflow_after_scanline_40(&mut ppu, &mut *mapper));
+}
+
+/// Clocks through `count` whole frames, returning the number of each frame that ran and how
+/// many dots it took, counting from one frame increment to the next.
+fn frame_lengths(ppu: &mut PpuState, mapper: &mut dyn Mapper, count: usize) -> Vec<(u32, u32)> {
+    let starting_frame = ppu.current_frame;
+    while ppu.current_frame == starting_frame {
+        ppu.clock(mapper);
+    }
+    let mut lengths = Vec::new();
+    for _ in 0 .. count {
+        let frame = ppu.current_frame;
+        let mut dots = 0;
+        while ppu.current_frame == frame {
+            ppu.clock(mapper);
+            dots += 1;
+        }
+        lengths.push((frame, dots));
+    }
+    return lengths;
+}
+
+/// Clocks to the last dot of the prerender line on a frame with the given parity, then once
+/// more, returning where that lands.
+fn dot_after_prerender(ppu: &mut PpuState, mapper: &mut dyn Mapper, odd: bool) -> (u16, u16) {
+    clock_until(ppu, mapper, |ppu| (ppu.current_frame & 0x1 != 0) == odd
+        && ppu.current_scanline == 261 && ppu.current_scanline_cycle == 340).expect("should reach the prerender line");
+    ppu.clock(mapper);
+    return (ppu.current_scanline, ppu.current_scanline_cycle);
+}
+
+#[test]
+fn odd_frames_skip_a_dot_while_rendering() {
+    let (mut ppu, mut mapper) = opaque_background();
+    ppu.mask = SHOW_ALL;
+    assert_eq!(dot_after_prerender(&mut ppu, &mut *mapper, false), (0, 0));
+    assert_eq!(dot_after_prerender(&mut ppu, &mut *mapper, true), (0, 1));
+
+    // The skipped dot comes off the start of the frame after the odd one
+    for (frame, dots) in frame_lengths(&mut ppu, &mut *mapper, 4) {
+        let expected = if frame & 0x1 == 0 {262 * 341 - 1} else {262 * 341};
+        assert_eq!(dots, expected, "frame {}", frame);
+    }
+}
+
+#[test]
+fn odd_frames_keep_every_dot_with_rendering_off() {
+    let (mut ppu, mut mapper) = opaque_background();
+    ppu.mask = 0;
+    assert_eq!(dot_after_prerender(&mut ppu, &mut *mapper, false), (0, 0));
+    assert_eq!(dot_after_prerender(&mut ppu, &mut *mapper, true), (0, 0));
+    for (frame, dots) in frame_lengths(&mut ppu, &mut *mapper, 4) {
+        assert_eq!(dots, 262 * 341, "frame {}", frame);
+    }
+}
+
+// PPUMASK: show background and sprites, but clip both from the leftmost 8 pixels
+const CLIP_LEFT: u8 = 0b0001_1000;
+
+#[test]
+fn clipping_hides_the_background_in_the_leftmost_8_pixels() {
+    let (mut ppu, mut mapper) = opaque_background();
+    ppu.palette[0x00] = BACKDROP_COLOR;
+    ppu.palette[0x01] = SPRITE_COLOR;
+    ppu.mask = CLIP_LEFT;
+    clock_until(&mut ppu, &mut *mapper, |ppu| ppu.current_scanline == 2);
+    let colors: Vec<u8> = ppu.screen[256 .. 256 + 16].iter().map(|pixel| (*pixel & 0x3F) as u8).collect();
+    let mut expected = vec![BACKDROP_COLOR; 8];
+    expected.extend_from_slice(&[SPRITE_COLOR; 8]);
+    assert_eq!(colors, expected);
+}
+
+// Sprite zero at X=2, with tile 2 opaque only in its left half, so it never leaves the clipped column
+fn sprite_zero_in_the_left_column(mask: u8) -> (PpuState, Box<dyn Mapper>) {
+    let (mut ppu, mut mapper) = opaque_background();
+    for row in 0x20 .. 0x28 {
+        mapper.write_ppu(row, 0xF0);
+    }
+    set_sprite(&mut ppu, 0, 2, 30, 2, 0);
+    ppu.mask = mask;
+    return (ppu, mapper);
+}
+
+#[test]
+fn sprite_zero_cannot_hit_in_the_clipped_column() {
+    let (mut ppu, mut mapper) = sprite_zero_in_the_left_column(SHOW_ALL);
+    let hit = clock_until(&mut ppu, &mut *mapper, |ppu| ppu.status & 0x40 != 0);
+    assert_eq!(hit.map(|(scanline, _)| scanline), Some(31));
+
+    let (mut ppu, mut mapper) = sprite_zero_in_the_left_column(CLIP_LEFT);
+    let hit = clock_until(&mut ppu, &mut *mapper, |ppu| ppu.status & 0x40 != 0 || ppu.current_scanline == 240);
+    assert_eq!(ppu.status & 0x40, 0, "hit reported at {:?}", hit);
+}
+
+// Scanline 1 of a forced blank frame, with the VRAM address parked on palette entry 5
+fn forced_blank_line(palette_backdrop_quirk: bool) -> Vec<u8> {
+    let (mut ppu, mut mapper) = opaque_background();
+    ppu.palette[0x00] = BACKDROP_COLOR;
+    ppu.palette[0x05] = SPRITE_COLOR;
+    ppu.palette_backdrop_quirk = palette_backdrop_quirk;
+    ppu.mask = 0;
+    ppu.current_vram_address = 0x3F05;
+    clock_until(&mut ppu, &mut *mapper, |ppu| ppu.current_scanline == 2);
+    return ppu.screen[256 .. 512].iter().map(|pixel| (*pixel & 0x3F) as u8).collect();
+}
+
+#[test]
+fn forced_blank_shows_the_palette_entry_at_the_vram_address() {
+    assert_eq!(forced_blank_line(true), vec![SPRITE_COLOR; 256]);
+}
+
+#[test]
+fn forced_blank_shows_the_backdrop_with_the_quirk_off() {
+    assert_eq!(forced_blank_line(false), vec![BACKDROP_COLOR; 256]);
+}
+
+fn rgba(color: u8) -> [u8; 4] {
+    let index = color as usize * 3;
+    return [NTSC_PAL[index + 0], NTSC_PAL[index + 1], NTSC_PAL[index + 2], 255];
+}
+
+fn sheet_pixel(sheet: &[u8], x: usize, y: usize) -> [u8; 4] {
+    let offset = (y * 256 + x) * 4;
+    return [sheet[offset], sheet[offset + 1], sheet[offset + 2], sheet[offset + 3]];
+}
+
+#[test]
+fn chr_sheet_draws_the_upper_table_on_the_right() {
+    let mut mapper = chr_ram_mapper();
+    // Tile $21 at $1000, row 3 only: one pixel each of colors 1, 2 and 3, then backdrop
+    mapper.write_ppu(0x1213, 0b1010_0000);
+    mapper.write_ppu(0x121B, 0b0110_0000);
+    let mut ppu = PpuState::new();
+    ppu.palette[0x00] = 0x0F;
+    ppu.palette[0x05 .. 0x08].copy_from_slice(&[0x16, 0x2A, 0x12]);
+
+    let sheet = ppu.render_chr_sheet(&*mapper, 1);
+    assert_eq!(sheet.len(), 256 * 128 * 4);
+    // Tile $21 is column 1, row 2 of the right hand table
+    let (x, y) = (128 + 8, 16 + 3);
+    let row: Vec<[u8; 4]> = (0 .. 4).map(|px| sheet_pixel(&sheet, x + px, y)).collect();
+    assert_eq!(row, vec![rgba(0x16), rgba(0x2A), rgba(0x12), rgba(0x0F)]);
+    // The same tile number in the left hand table is empty
+    assert_eq!(sheet_pixel(&sheet, 8, y), rgba(0x0F));
+}
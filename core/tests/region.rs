@@ -0,0 +1,96 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::nes::NesState;
+use rustico_core::region::Region;
+
+fn nes_for(region: Region) -> NesState {
+    let mut nes = common::nes_with_program(&[0x4C, 0x00, 0x80]); // JMP $8000
+    nes.set_region(region);
+    return nes;
+}
+
+/// Runs from the start of the next frame to the start of the one after, returning the master
+/// clocks it took, the last scanline seen, and the scanline vblank started on.
+fn measure_frame(nes: &mut NesState) -> (u64, u16, u16) {
+    let starting_frame = nes.ppu.current_frame;
+    while nes.ppu.current_frame == starting_frame {
+        nes.step_master_cycle();
+    }
+    let frame = nes.ppu.current_frame;
+    let clock_before = nes.master_clock;
+    let mut last_scanline = 0;
+    let mut vblank_scanline = None;
+    while nes.ppu.current_frame == frame {
+        last_scanline = std::cmp::max(last_scanline, nes.ppu.current_scanline);
+        if vblank_scanline.is_none() && nes.ppu.status & 0x80 != 0 {
+            vblank_scanline = Some(nes.ppu.current_scanline);
+        }
+        nes.step_master_cycle();
+    }
+    return (nes.master_clock - clock_before, last_scanline, vblank_scanline.expect("vblank should start"));
+}
+
+#[test]
+fn ntsc_frames_are_262_scanlines() {
+    let mut nes = nes_for(Region::Ntsc);
+    let (master_clocks, last_scanline, vblank) = measure_frame(&mut nes);
+    assert_eq!(last_scanline, 261);
+    assert_eq!(master_clocks, 262 * 341 * 4);
+    assert_eq!(vblank, 241);
+}
+
+#[test]
+fn pal_frames_are_312_scanlines() {
+    let mut nes = nes_for(Region::Pal);
+    let (master_clocks, last_scanline, vblank) = measure_frame(&mut nes);
+    assert_eq!(last_scanline, 311);
+    assert_eq!(master_clocks, 312 * 341 * 5);
+    assert_eq!(vblank, 241);
+}
+
+#[test]
+fn dendy_frames_are_312_scanlines_with_a_late_vblank() {
+    let mut nes = nes_for(Region::Dendy);
+    let (master_clocks, last_scanline, vblank) = measure_frame(&mut nes);
+    assert_eq!(last_scanline, 311);
+    assert_eq!(master_clocks, 312 * 341 * 5);
+    assert_eq!(vblank, 291);
+}
+
+#[test]
+fn ntsc_runs_three_ppu_dots_per_cpu_cycle() {
+    let mut nes = nes_for(Region::Ntsc);
+    for _ in 0 .. 1000 {
+        let dots_before = nes.ppu.overall_cycle;
+        let clock_before = nes.master_clock;
+        nes.cycle();
+        assert_eq!(nes.ppu.overall_cycle - dots_before, 3);
+        assert_eq!(nes.master_clock - clock_before, 12);
+    }
+}
+
+#[test]
+fn pal_runs_sixteen_ppu_dots_per_five_cpu_cycles() {
+    let mut nes = nes_for(Region::Pal);
+    let dots_before = nes.ppu.overall_cycle;
+    for _ in 0 .. 500 {
+        nes.cycle();
+    }
+    assert_eq!(nes.ppu.overall_cycle - dots_before, 1600);
+}
+
+#[test]
+fn master_clock_stepping_matches_whole_cpu_cycles() {
+    let mut by_cycle = nes_for(Region::Ntsc);
+    let mut by_master_clock = nes_for(Region::Ntsc);
+    for _ in 0 .. 3000 {
+        by_cycle.cycle();
+        for _ in 0 .. 12 {
+            by_master_clock.step_master_cycle();
+        }
+    }
+    assert_eq!(by_cycle.ppu.overall_cycle, by_master_clock.ppu.overall_cycle);
+    assert_eq!(by_cycle.save_state(), by_master_clock.save_state());
+}
@@ -0,0 +1,90 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::region::Region;
+use rustico_core::rewind::RewindBuffer;
+
+#[test]
+fn push_drops_the_oldest_snapshot_once_full() {
+    let mut nes = common::nes_with_program(&[]);
+    // NTSC runs slightly faster than 60 Hz, so this rounds up to 3 frames of history, one
+    // snapshot per frame
+    let mut rewind = RewindBuffer::new(0.04, 1, Region::Ntsc);
+    assert_eq!(rewind.capacity(), 3);
+
+    let mut states = Vec::new();
+    for i in 0 .. 5 {
+        nes.memory.iram_raw[0x10] = i;
+        states.push(nes.save_state());
+        rewind.push(&nes);
+    }
+    assert_eq!(rewind.len(), 3);
+
+    // Most recent first, and only the last three survive
+    assert_eq!(rewind.pop(), Some(states[4].clone()));
+    assert_eq!(rewind.pop(), Some(states[3].clone()));
+    assert_eq!(rewind.pop(), Some(states[2].clone()));
+    assert_eq!(rewind.pop(), None);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn popped_snapshot_restores_the_console() {
+    let mut nes = common::nes_with_program(&[]);
+    let mut rewind = RewindBuffer::new(1.0, 1, Region::Ntsc);
+    nes.memory.iram_raw[0x20] = 0x42;
+    rewind.push(&nes);
+    nes.memory.iram_raw[0x20] = 0x99;
+
+    let snapshot = rewind.pop().unwrap();
+    nes.load_state(&snapshot).unwrap();
+    assert_eq!(nes.memory.iram_raw[0x20], 0x42);
+}
+
+#[test]
+fn snapshots_are_taken_every_frames_per_snapshot_pushes() {
+    let nes = common::nes_with_program(&[]);
+    let mut rewind = RewindBuffer::new(1.0, 4, Region::Ntsc);
+    for _ in 0 .. 9 {
+        rewind.push(&nes);
+    }
+    // Pushes 0, 4 and 8 take snapshots
+    assert_eq!(rewind.len(), 3);
+}
+
+// Stamps a frame number into RAM so a snapshot shows which push it came from
+fn stamp(nes: &mut rustico_core::nes::NesState, frame: usize) {
+    nes.memory.iram_raw[0x10] = (frame & 0xFF) as u8;
+    nes.memory.iram_raw[0x11] = (frame >> 8) as u8;
+}
+
+fn stamped_frame(snapshot: &[u8]) -> usize {
+    let mut nes = common::nes_with_program(&[]);
+    nes.load_state(snapshot).unwrap();
+    return nes.memory.iram_raw[0x10] as usize | (nes.memory.iram_raw[0x11] as usize) << 8;
+}
+
+#[test]
+fn capacity_follows_the_region_frame_rate() {
+    let mut nes = common::nes_with_program(&[]);
+    let mut rewind = RewindBuffer::new(5.0, 1, Region::Ntsc);
+    // 5 seconds at ~60.1 Hz
+    assert_eq!(rewind.capacity(), 301);
+
+    for frame in 0 .. 600 {
+        stamp(&mut nes, frame);
+        rewind.push(&nes);
+    }
+    assert_eq!(rewind.len(), 301);
+    let mut oldest = 0;
+    while let Some(snapshot) = rewind.pop() {
+        oldest = stamped_frame(&snapshot);
+    }
+    assert_eq!(oldest, 600 - 301);
+
+    // 5 seconds at ~50 Hz, picked up from the console on the next push
+    nes.set_region(Region::Pal);
+    rewind.push(&nes);
+    assert_eq!(rewind.capacity(), 251);
+}
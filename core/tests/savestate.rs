@@ -0,0 +1,91 @@
+// Whole-console savestates: mapper state that has to survive a round trip, and states the
+// loader must turn away without disturbing the running game.
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::fds::FdsFile;
+use rustico_core::mmc::fds::FdsMapper;
+use rustico_core::nes::NesState;
+use rustico_core::save_load::{StateError, STATE_VERSION};
+
+fn fds_nes() -> NesState {
+    let image = FdsFile::to_bytes(&[vec![0u8; 65500], vec![0u8; 65500]]);
+    let fds = FdsFile::from_reader(&mut &image[..]).unwrap();
+    return NesState::new(Box::new(FdsMapper::from_fds(fds).unwrap()));
+}
+
+// Mapper 682 needs an NES 2.0 header for its high bits. Four numbered 8K PRG banks.
+fn rainbow_nes() -> NesState {
+    let image = common::ines_image((682 & 0xFF) as u8, 0, &common::numbered_banks(0x2000, 4), &common::numbered_banks(0x2000, 1));
+    let mut image = common::with_submapper(image, 0);
+    image[8] |= (682 >> 8) as u8;
+    return common::nes_from_image(&image);
+}
+
+#[test]
+fn fds_state_survives_a_round_trip() {
+    let mut nes = fds_nes();
+    nes.mapper.write_cpu(0x6000, 0x42);
+    nes.mapper.write_cpu(0x4020, 0x34);
+    let state = nes.save_state();
+
+    nes.mapper.write_cpu(0x6000, 0x99);
+    nes.mapper.write_cpu(0x4020, 0x12);
+    nes.mapper.write_cpu(0x4023, 0x01);
+    nes.mapper.fds_eject_disk();
+    assert!(nes.save_state() != state);
+
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.mapper.debug_read_cpu(0x6000), Some(0x42));
+    assert_eq!(nes.mapper.fds_inserted_disk(), Some(0));
+    assert_eq!(nes.save_state(), state);
+}
+
+#[test]
+fn rainbow_banks_survive_a_round_trip() {
+    let mut nes = rainbow_nes();
+    // 4x8K PRG mode, bank 2 at $8000
+    nes.mapper.write_cpu(0x4100, 0b011);
+    nes.mapper.write_cpu(0x4118, 2);
+    assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(2));
+    let state = nes.save_state();
+
+    nes.mapper.write_cpu(0x4118, 1);
+    nes.mapper.write_cpu(0x4100, 0b000);
+    assert!(nes.save_state() != state);
+
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(2));
+    assert_eq!(nes.save_state(), state);
+}
+
+#[test]
+fn bad_headers_leave_the_console_untouched() {
+    let mut nes = common::nes_with_program(&[]);
+    let mut other = common::nes_with_program(&[]);
+    common::step_instructions(&mut other, 10);
+    other.memory.iram_raw[0] = 0x42;
+    let valid = other.save_state();
+    let before = nes.save_state();
+
+    let mut bad_magic = valid.clone();
+    bad_magic[0] ^= 0xFF;
+    match nes.load_state(&bad_magic) {
+        Err(StateError::InvalidMagic) => {},
+        other => panic!("expected invalid magic, got {:?}", other.err())
+    }
+    assert_eq!(nes.save_state(), before);
+
+    let mut bad_version = valid.clone();
+    bad_version[4] = STATE_VERSION.wrapping_add(1);
+    match nes.load_state(&bad_version) {
+        Err(StateError::UnsupportedVersion(version)) => {assert_eq!(version, STATE_VERSION.wrapping_add(1))},
+        other => panic!("expected an unsupported version, got {:?}", other.err())
+    }
+    assert_eq!(nes.save_state(), before);
+
+    // The same bytes with an intact header load fine
+    nes.load_state(&valid).unwrap();
+    assert_eq!(nes.memory.iram_raw[0], 0x42);
+}
@@ -0,0 +1,30 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::cartridge::mapper_from_file;
+
+const BATTERY: u8 = 0b0000_0010;
+
+// Mappers with PRG RAM that's only worth saving when the header says it's battery backed
+const SRAM_MAPPERS: [u8; 5] = [1, 4, 19, 69, 85];
+
+fn persistent_ram(mapper_number: u8, flags_6: u8) -> bool {
+    let image = common::ines_image(mapper_number, flags_6, &vec![0u8; 32 * 1024], &vec![0u8; 8 * 1024]);
+    let mapper = mapper_from_file(&image).expect("test image should load");
+    return mapper.has_persistent_ram();
+}
+
+#[test]
+fn battery_backed_carts_have_persistent_ram() {
+    for &mapper_number in SRAM_MAPPERS.iter() {
+        assert!(persistent_ram(mapper_number, BATTERY), "mapper {} should keep its SRAM", mapper_number);
+    }
+}
+
+#[test]
+fn carts_without_a_battery_have_volatile_ram() {
+    for &mapper_number in SRAM_MAPPERS.iter() {
+        assert!(!persistent_ram(mapper_number, 0), "mapper {} shouldn't keep its SRAM", mapper_number);
+    }
+}
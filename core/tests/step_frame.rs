@@ -0,0 +1,40 @@
+extern crate rustico_core;
+
+mod common;
+
+#[test]
+fn step_frame_advances_one_frame_and_fills_the_framebuffer() {
+    let program = [
+        0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+        0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+        0xA9, 0x21, 0x8D, 0x07, 0x20, // LDA #$21, STA $2007 (backdrop color)
+        0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+        0x8D, 0x06, 0x20,             // STA $2006
+        0x4C, 0x17, 0x80,             // JMP *
+    ];
+    let mut nes = common::nes_with_program(&program);
+    nes.step_frame();
+    let first_frame = nes.ppu.current_frame;
+    nes.step_frame();
+    assert_eq!(nes.ppu.current_frame, first_frame + 1);
+
+    let framebuffer = nes.framebuffer();
+    assert_eq!(framebuffer.len(), 256 * 240 * 4);
+    let backdrop = nes.palette.rgb(0x21);
+    for pixel in framebuffer.chunks(4) {
+        assert_eq!(pixel, &[backdrop[0], backdrop[1], backdrop[2], 255]);
+    }
+}
+
+#[test]
+fn step_frame_is_deterministic() {
+    let mut a = common::nes_with_program(&[0xE6, 0x00, 0x4C, 0x00, 0x80]); // INC $00, JMP $8000
+    let mut b = common::nes_with_program(&[0xE6, 0x00, 0x4C, 0x00, 0x80]);
+    for _ in 0 .. 3 {
+        a.step_frame();
+        b.step_frame();
+    }
+    assert_eq!(a.framebuffer(), b.framebuffer());
+    assert_eq!(a.save_state(), b.save_state());
+    assert!(a.memory.iram_raw[0] != 0);
+}
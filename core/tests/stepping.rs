@@ -0,0 +1,60 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::nes::NesState;
+
+fn nested_calls() -> NesState {
+    let mut program = vec![0xEAu8; 0x30];
+    program[0x00 .. 0x04].copy_from_slice(&[0x20, 0x10, 0x80, 0xEA]); // $8000: JSR $8010, NOP
+    program[0x10 .. 0x15].copy_from_slice(&[0xE8, 0x20, 0x20, 0x80, 0x60]); // $8010: INX, JSR $8020, RTS
+    program[0x20 .. 0x22].copy_from_slice(&[0xC8, 0x60]); // $8020: INY, RTS
+    let mut nes = common::nes_with_program(&program);
+    nes.registers.x = 0;
+    nes.registers.y = 0;
+    return nes;
+}
+
+#[test]
+fn step_over_runs_a_jsr_to_the_instruction_after_it() {
+    let mut nes = nested_calls();
+    assert_eq!(nes.registers.pc, 0x8000);
+    let stack = nes.registers.s;
+    nes.step_over();
+    assert_eq!(nes.registers.pc, 0x8003);
+    assert_eq!(nes.registers.s, stack);
+    // Both subroutines ran
+    assert_eq!((nes.registers.x, nes.registers.y), (1, 1));
+}
+
+#[test]
+fn step_over_anything_else_is_a_single_step() {
+    let mut nes = nested_calls();
+    nes.step();
+    assert_eq!(nes.registers.pc, 0x8010);
+    nes.step_over();
+    assert_eq!(nes.registers.pc, 0x8011);
+}
+
+#[test]
+fn step_out_returns_to_the_caller() {
+    let mut nes = nested_calls();
+    common::step_instructions(&mut nes, 2);
+    assert_eq!(nes.registers.pc, 0x8011);
+    nes.step_out();
+    assert_eq!(nes.registers.pc, 0x8003);
+    assert_eq!((nes.registers.x, nes.registers.y), (1, 1));
+}
+
+#[test]
+fn setting_pc_moves_the_next_instruction() {
+    let mut nes = nested_calls();
+    let mut registers = nes.cpu_registers();
+    // Skip the outer JSR and land directly on the inner subroutine
+    registers.pc = 0x8020;
+    nes.set_cpu_registers(registers);
+    assert_eq!(nes.cpu_registers().pc, 0x8020);
+    nes.step();
+    assert_eq!(nes.registers.pc, 0x8021);
+    assert_eq!((nes.registers.x, nes.registers.y), (0, 1));
+}
@@ -0,0 +1,55 @@
+extern crate rustico_core;
+
+mod common;
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+const PROGRAM: [u8; 6] = [
+    0xA9, 0x42,       // LDA #$42
+    0x8D, 0x00, 0x02, // STA $0200
+    0xE8,             // INX
+];
+
+// The same columns nestest.log uses, minus its PPU dot counts
+const EXPECTED: [&str; 3] = [
+    "8000  A9 42     LDA #$42                        A:00 X:00 Y:00 P:24 SP:FD CYC:0",
+    "8002  8D 00 02  STA $0200                       A:42 X:00 Y:00 P:24 SP:FD CYC:2",
+    "8005  E8        INX                             A:42 X:00 Y:00 P:24 SP:FD CYC:6",
+];
+
+#[derive(Clone)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        return Ok(data.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+#[test]
+fn trace_lines_match_the_nestest_log_layout() {
+    let mut nes = common::nes_with_program(&PROGRAM);
+    for expected in EXPECTED.iter() {
+        assert_eq!(nes.trace_line(), *expected);
+        nes.step();
+    }
+}
+
+#[test]
+fn trace_sink_gets_a_line_per_instruction() {
+    let mut nes = common::nes_with_program(&PROGRAM);
+    let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+    nes.set_trace_sink(Box::new(buffer.clone()));
+    common::step_instructions(&mut nes, 3);
+    nes.clear_trace_sink();
+    common::step_instructions(&mut nes, 1);
+
+    let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(log.lines().collect::<Vec<&str>>(), EXPECTED);
+}
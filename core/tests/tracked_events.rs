@@ -0,0 +1,84 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::nes::NesState;
+use rustico_core::tracked_events::{EventCategory, TrackedEvent};
+
+fn in_category(events: &[TrackedEvent], category: EventCategory) -> Vec<(u16, u16)> {
+    return events.iter()
+        .filter(|event| event.category() == Some(category))
+        .map(|event| (event.scanline, event.cycle))
+        .collect();
+}
+
+/// Runs a few frames, stopping just after vblank begins, and returns the frame in progress.
+fn run_frames(nes: &mut NesState, count: usize) -> u32 {
+    for _ in 0 .. count {
+        nes.step_frame();
+    }
+    return nes.ppu.current_frame;
+}
+
+#[test]
+fn nmi_is_tracked_at_the_start_of_vblank() {
+    let mut nes = common::nes_with_program(&[
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000 (NMI on)
+        0x4C, 0x05, 0x80,             // JMP *
+    ]);
+    let frame = run_frames(&mut nes, 3);
+
+    let nmis = in_category(nes.event_tracker.events_for_frame(frame), EventCategory::Nmi);
+    assert_eq!(nmis.len(), 1, "one NMI per frame, got {:?}", nmis);
+    assert_eq!(nmis[0].0, 241);
+    // The previous frame had its own
+    assert_eq!(in_category(nes.event_tracker.events_for_frame(frame - 1), EventCategory::Nmi).len(), 1);
+}
+
+#[test]
+fn irq_is_tracked_when_the_frame_counter_fires() {
+    let mut nes = common::nes_with_program(&[
+        0xA9, 0x00, 0x8D, 0x17, 0x40, // LDA #$00, STA $4017 (4-step, IRQ on)
+        0x58,                         // CLI
+        0x4C, 0x06, 0x80,             // JMP *
+    ]);
+    let frame = run_frames(&mut nes, 3);
+
+    let events = nes.event_tracker.events_for_frame(frame - 1);
+    assert!(in_category(events, EventCategory::Irq).len() > 0);
+    assert_eq!(in_category(events, EventCategory::Nmi).len(), 0);
+}
+
+#[test]
+fn sprite_zero_hit_is_tracked_where_it_happens() {
+    let program = [
+        0xA9, 0x00, 0x8D, 0x03, 0x20, // LDA #$00, STA $2003
+        0xA9, 0x1E, 0x8D, 0x04, 0x20, // LDA #30, STA $2004 (Y)
+        0xA9, 0x00, 0x8D, 0x04, 0x20, // LDA #$00, STA $2004 (tile)
+        0x8D, 0x04, 0x20,             // STA $2004 (attributes)
+        0xA9, 0x64, 0x8D, 0x04, 0x20, // LDA #100, STA $2004 (X)
+        0xA9, 0x1E, 0x8D, 0x01, 0x20, // LDA #$1E, STA $2001 (show everything)
+        0x4C, 0x1C, 0x80,             // JMP *
+    ];
+    // Tile 0 is solid color 1, so the background (all tile 0) is opaque everywhere
+    let mut chr = vec![0u8; 8 * 1024];
+    for row in 0 .. 8 {
+        chr[row] = 0xFF;
+    }
+    let mut nes = common::nes_from_image(&common::ines_image(0, 0, &common::nrom_prg(&program), &chr));
+    let frame = run_frames(&mut nes, 3);
+
+    let hits = in_category(nes.event_tracker.events_for_frame(frame), EventCategory::SpriteZeroHit);
+    assert_eq!(hits.len(), 1, "one hit per frame, got {:?}", hits);
+    assert_eq!(hits[0].0, 31);
+}
+
+#[test]
+fn frames_older_than_the_last_are_gone() {
+    let mut nes = common::nes_with_program(&[
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000 (NMI on)
+        0x4C, 0x05, 0x80,             // JMP *
+    ]);
+    let frame = run_frames(&mut nes, 4);
+    assert!(nes.event_tracker.events_for_frame(frame - 2).is_empty());
+}
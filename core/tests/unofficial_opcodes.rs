@@ -0,0 +1,77 @@
+extern crate rustico_core;
+
+mod common;
+
+// SLO, RLA, SRE and RRA share one column layout; these are the SLO opcodes
+const SLO_INDIRECT_X: u8 = 0x03;
+const SLO_ZERO_PAGE: u8 = 0x07;
+const SLO_ABSOLUTE: u8 = 0x0F;
+const SLO_INDIRECT_Y: u8 = 0x13;
+const SLO_ZERO_PAGE_X: u8 = 0x17;
+const SLO_ABSOLUTE_Y: u8 = 0x1B;
+const SLO_ABSOLUTE_X: u8 = 0x1F;
+
+// Row offsets from SLO to the other three
+const GROUPS: [(&str, u8); 4] = [("SLO", 0x00), ("RLA", 0x20), ("SRE", 0x40), ("RRA", 0x60)];
+
+// Opcode, operand, cycles. Every mode points at $02F0 (directly or through the pointers at
+// $10 and $30), so an index of $20 crosses into the next page.
+const MODES: [(u8, &[u8], u64); 7] = [
+    (SLO_ZERO_PAGE,   &[0x10],       5),
+    (SLO_ZERO_PAGE_X, &[0x10],       6),
+    (SLO_ABSOLUTE,    &[0xF0, 0x02], 6),
+    (SLO_ABSOLUTE_X,  &[0xF0, 0x02], 7),
+    (SLO_ABSOLUTE_Y,  &[0xF0, 0x02], 7),
+    (SLO_INDIRECT_X,  &[0x10],       8),
+    (SLO_INDIRECT_Y,  &[0x10],       8),
+];
+
+/// CPU cycles taken by `opcode` with both index registers set to `index`
+fn cycles(opcode: u8, operand: &[u8], index: u8) -> u64 {
+    let mut program = vec![
+        0xA2, index, // LDX #index
+        0xA0, index, // LDY #index
+        opcode];
+    program.extend_from_slice(operand);
+    let mut nes = common::nes_with_program(&program);
+    for &pointer in [0x10, 0x30].iter() {
+        nes.memory.iram_raw[pointer] = 0xF0;
+        nes.memory.iram_raw[pointer + 1] = 0x02;
+    }
+    common::step_instructions(&mut nes, 2);
+
+    let clock_before = nes.master_clock;
+    nes.step();
+    assert_eq!(nes.registers.pc, 0x8005 + operand.len() as u16, "opcode {:02X} decoded the wrong length", opcode);
+    return (nes.master_clock - clock_before) / nes.region.master_clocks_per_cpu_cycle() as u64;
+}
+
+fn measure(index: u8) {
+    for &(name, offset) in GROUPS.iter() {
+        for &(slo_opcode, operand, expected) in MODES.iter() {
+            let opcode = slo_opcode + offset;
+            assert_eq!(cycles(opcode, operand, index), expected,
+                "{} (${:02X}) with index ${:02X}", name, opcode, index);
+        }
+    }
+}
+
+#[test]
+fn rmw_combos_take_official_rmw_timing() {
+    measure(0x00);
+}
+
+#[test]
+fn indexed_rmw_combos_take_no_extra_cycle_for_a_page_cross() {
+    measure(0x20);
+}
+
+#[test]
+fn slo_shifts_memory_and_ors_the_result_into_a() {
+    // SLO $10: shift $10 left, then OR it into A
+    let mut nes = common::nes_with_program(&[0xA9, 0x01, SLO_ZERO_PAGE, 0x10]);
+    nes.memory.iram_raw[0x10] = 0x21;
+    common::step_instructions(&mut nes, 2);
+    assert_eq!(nes.memory.iram_raw[0x10], 0x42);
+    assert_eq!(nes.registers.a, 0x43);
+}
@@ -0,0 +1,118 @@
+extern crate rustico_core;
+
+mod common;
+
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::mmc::vrc6::Vrc6;
+
+fn vrc6(mapper_number: u8) -> Vrc6 {
+    // 128 KiB PRG in numbered 8 KiB banks, 128 KiB CHR in numbered 1 KiB banks
+    let prg = common::numbered_banks(8 * 1024, 16);
+    let chr = common::numbered_banks(1024, 128);
+    let image = common::ines_image(mapper_number, 0, &prg, &chr);
+    return Vrc6::from_ines(common::ines_cartridge(&image)).unwrap();
+}
+
+#[test]
+fn switches_prg_banks() {
+    let mut mapper = vrc6(24);
+    // 16 KiB bank 3 is 8 KiB banks 6 and 7
+    mapper.write_cpu(0x8000, 3);
+    mapper.write_cpu(0xC000, 11);
+    assert_eq!(mapper.debug_read_cpu(0x8000), Some(6));
+    assert_eq!(mapper.debug_read_cpu(0xA000), Some(7));
+    assert_eq!(mapper.debug_read_cpu(0xC000), Some(11));
+    assert_eq!(mapper.debug_read_cpu(0xE000), Some(15), "last bank is fixed");
+}
+
+#[test]
+fn switches_1k_chr_banks_in_mode_0() {
+    let mut mapper = vrc6(24);
+    mapper.write_cpu(0xB003, 0x20);
+    mapper.write_cpu(0xD000, 17);
+    mapper.write_cpu(0xD003, 42);
+    mapper.write_cpu(0xE003, 127);
+    assert_eq!(mapper.debug_read_ppu(0x0000), Some(17));
+    assert_eq!(mapper.debug_read_ppu(0x0C00), Some(42));
+    assert_eq!(mapper.debug_read_ppu(0x1C00), Some(127));
+}
+
+#[test]
+fn mapper_26_swaps_a0_and_a1() {
+    let mut mapper = vrc6(26);
+    // On VRC6b, $D001 is the third register and $D002 the second
+    mapper.write_cpu(0xB003, 0x20);
+    mapper.write_cpu(0xD001, 5);
+    mapper.write_cpu(0xD002, 9);
+    assert_eq!(mapper.debug_read_ppu(0x0400), Some(9));
+    assert_eq!(mapper.debug_read_ppu(0x0800), Some(5));
+}
+
+#[test]
+fn cpu_cycle_irq_fires_when_the_counter_overflows() {
+    let mut mapper = vrc6(24);
+    mapper.write_cpu(0xF000, 0xFD);
+    // Cycle mode, enabled
+    mapper.write_cpu(0xF001, 0b0000_0110);
+    mapper.on_cpu_cycle();
+    mapper.on_cpu_cycle();
+    assert!(!mapper.irq_pending());
+    mapper.on_cpu_cycle();
+    assert!(mapper.irq_pending());
+    mapper.write_cpu(0xF002, 0);
+    assert!(!mapper.irq_pending());
+}
+
+#[test]
+fn pulse_duty_controls_the_high_portion_of_each_period() {
+    let mut mapper = vrc6(24);
+    mapper.write_cpu(0x9003, 0x00);
+    // Duty 7 (8/16), volume 15, period 4
+    mapper.write_cpu(0x9000, 0x7F);
+    mapper.write_cpu(0x9001, 0x04);
+    mapper.write_cpu(0x9002, 0x80);
+
+    let mut high_steps = 0;
+    for _ in 0 .. 16 {
+        for _ in 0 .. 5 {
+            mapper.on_cpu_cycle();
+        }
+        if mapper.pulse1.output() == 15 {
+            high_steps += 1;
+        } else {
+            assert_eq!(mapper.pulse1.output(), 0);
+        }
+    }
+    assert_eq!(high_steps, 8);
+}
+
+#[test]
+fn sawtooth_ramps_up_and_resets_every_14_steps() {
+    let mut mapper = vrc6(24);
+    mapper.write_cpu(0x9003, 0x00);
+    mapper.write_cpu(0xB000, 42);
+    mapper.write_cpu(0xB001, 0x00);
+    mapper.write_cpu(0xB002, 0x80);
+
+    let mut outputs = Vec::new();
+    for _ in 0 .. 14 {
+        mapper.on_cpu_cycle();
+        outputs.push(mapper.sawtooth.output());
+    }
+    // The accumulator grows on every other step, peaking at 6 * 42 = 252, then resets
+    assert_eq!(*outputs.iter().max().unwrap(), 252 >> 3);
+    assert_eq!(*outputs.last().unwrap(), 0);
+}
+
+#[test]
+fn expansion_audio_is_mixed_over_the_apu_sample() {
+    let mut mapper = vrc6(24);
+    assert_eq!(mapper.mix_expansion_audio(0.25), 0.25);
+    // Direct volume mode holds the pulse high regardless of the duty counter
+    mapper.write_cpu(0x9003, 0x00);
+    mapper.write_cpu(0x9000, 0x8F);
+    mapper.write_cpu(0x9002, 0x80);
+    assert!(mapper.mix_expansion_audio(0.25) > 0.25);
+    mapper.pulse1.debug_disable = true;
+    assert_eq!(mapper.mix_expansion_audio(0.25), 0.25);
+}
@@ -0,0 +1,31 @@
+extern crate rustico_core;
+
+mod common;
+
+#[test]
+fn writes_are_logged_with_the_writing_instruction() {
+    let mut nes = common::nes_with_program(&[
+        0xA9, 0x11,       // $8000: LDA #$11
+        0x85, 0x40,       // $8002: STA $40
+        0xA2, 0x22,       // $8004: LDX #$22
+        0x8E, 0x40, 0x00, // $8006: STX $0040
+        0x85, 0x41,       // $8009: STA $41 (not watched)
+    ]);
+    nes.memory.iram_raw[0x40] = 0x00;
+    nes.add_watch(0x0040);
+    common::step_instructions(&mut nes, 5);
+
+    let log: Vec<(u16, u8, u8)> = nes.watch_log().iter().map(|entry| (entry.pc, entry.old_value, entry.new_value)).collect();
+    assert_eq!(log, vec![(0x8002, 0x00, 0x11), (0x8006, 0x11, 0x22)]);
+    assert!(nes.watch_log().iter().all(|entry| entry.address == 0x0040));
+}
+
+#[test]
+fn removed_watches_stop_logging() {
+    let mut nes = common::nes_with_program(&[0x85, 0x40, 0x85, 0x40]); // STA $40, STA $40
+    nes.add_watch(0x0040);
+    nes.step();
+    nes.remove_watch(0x0040);
+    nes.step();
+    assert_eq!(nes.watch_log().len(), 1);
+}
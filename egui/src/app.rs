@@ -1,5 +1,9 @@
 use crate::worker;
+use crate::event_viewer;
 use crate::game_window;
+use crate::memory_viewer;
+use crate::ppu_viewer;
+use crate::preferences;
 
 use eframe::egui;
 use rustico_ui_common::events;
@@ -11,16 +15,30 @@ use std::sync::mpsc::{Sender, Receiver, TryRecvError};
 pub enum ShellEvent {
     ImageRendered(String, Arc<worker::RenderedImage>),
     HasSram(bool),
+    MemoryContents(u16, Arc<Vec<u8>>),
+    // PRG bank mapped at each 256 byte CPU page, None where nothing is banked
+    PrgBanks(Arc<Vec<Option<usize>>>),
+    // frame, region the frame was run in, events
+    FrameEvents(u32, rustico_core::region::Region, Arc<Vec<rustico_core::tracked_events::TrackedEvent>>),
+    SpriteInfo(Arc<Vec<rustico_core::ppu::SpriteInfo>>),
+    // current track, track count, paused
+    NsfStatus(u8, u8, bool),
+    // paused
+    RunStateChanged(bool),
+    BreakpointHit(u16, rustico_core::breakpoints::BreakpointKind),
+    // side count, inserted side
+    FdsStatus(usize, Option<usize>),
+    PerfStats(worker::PerfStats),
+    // recording
+    AudioRecordingChanged(bool),
     SettingsUpdated(Arc<rustico_ui_common::settings::SettingsState>)
 }
 
 pub struct RusticoApp {
-    pub old_p1_buttons_held: u8,
-
-    pub show_memory_viewer: bool,
-    pub show_event_viewer: bool,
-    pub show_ppu_viewer: bool,
-    pub show_piano_roll: bool,
+    pub old_buttons_held: [u8; preferences::PLAYER_COUNT],
+    // Buttons whose keys were already down when focus came back; ignored until released
+    pub window_focused: bool,
+    pub turbo_held: bool,
 
     pub runtime_tx: Sender<events::Event>,
     pub shell_rx: Receiver<ShellEvent>,
@@ -28,17 +46,18 @@ pub struct RusticoApp {
     pub settings_cache: rustico_ui_common::settings::SettingsState,
 
     pub game_window: game_window::GameWindow,
+    pub event_viewer: event_viewer::EventViewerWindow,
+    pub memory_viewer: memory_viewer::MemoryViewerWindow,
+    pub ppu_viewer: ppu_viewer::PpuViewerWindow,
+    pub preferences: preferences::PreferencesWindow,
 }
 
 impl RusticoApp {
     pub fn new(cc: &eframe::CreationContext, runtime_tx: Sender<events::Event>, shell_rx: Receiver<ShellEvent>) -> Self {
         Self {
-            old_p1_buttons_held: 0,
-
-            show_memory_viewer: false,
-            show_event_viewer: false,
-            show_ppu_viewer: false,
-            show_piano_roll: false,
+            old_buttons_held: [0; preferences::PLAYER_COUNT],
+            window_focused: true,
+            turbo_held: false,
 
             runtime_tx: runtime_tx,
             shell_rx: shell_rx,
@@ -46,6 +65,10 @@ impl RusticoApp {
             settings_cache: rustico_ui_common::settings::SettingsState::new(),
 
             game_window: game_window::GameWindow::new(cc),
+            event_viewer: event_viewer::EventViewerWindow::new(),
+            memory_viewer: memory_viewer::MemoryViewerWindow::new(),
+            ppu_viewer: ppu_viewer::PpuViewerWindow::new(),
+            preferences: preferences::PreferencesWindow::new(),
         }
     }
 
@@ -55,6 +78,9 @@ impl RusticoApp {
                 Ok(event) => {
                     self.handle_event(event.clone());
                     self.game_window.handle_event(event.clone());
+                    self.event_viewer.handle_event(event.clone());
+                    self.memory_viewer.handle_event(event.clone());
+                    self.ppu_viewer.handle_event(event.clone());
                 },
                 Err(error) => {
                     match error {
@@ -83,81 +109,69 @@ impl RusticoApp {
         }
     }
 
+    fn release_all_buttons(&mut self) {
+        for player in 0 .. preferences::PLAYER_COUNT {
+            for (index, (_, _, button)) in preferences::BUTTONS.iter().enumerate() {
+                if (self.old_buttons_held[player] & (1 << index)) != 0 {
+                    let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(player, button.clone()));
+                }
+            }
+            self.old_buttons_held[player] = 0;
+        }
+    }
+
     fn apply_player_input(&mut self, ctx: &egui::Context) {
-        // For now, use the same hard-coded input setup from the SDL build.
-        // We will eventually completely throw this out and replace it with the input mapping system
-        // TODO: how does this handle the application being unfocused on various platforms?
+        // Key releases that happen while another window has focus never reach us, so anything
+        // held when focus goes away would stay held forever. Let go of everything up front and
+        // ignore the keyboard until focus returns; anything still down then is pressed afresh.
+        let focused = ctx.input(|i| i.focused);
+        if !focused {
+            if self.window_focused {
+                self.release_all_buttons();
+                self.window_focused = false;
+            }
+            return;
+        }
+        self.window_focused = true;
 
         ctx.input(|i| {
-            let mut p1_buttons_held = 0;
-
-            if i.keys_down.contains(&egui::Key::X)          {p1_buttons_held |= 1 << 0;}
-            if i.keys_down.contains(&egui::Key::Z)          {p1_buttons_held |= 1 << 1;}
-            if i.keys_down.contains(&egui::Key::Backspace)  {p1_buttons_held |= 1 << 2;}
-            if i.keys_down.contains(&egui::Key::Enter)      {p1_buttons_held |= 1 << 3;}
-            if i.keys_down.contains(&egui::Key::ArrowUp)    {p1_buttons_held |= 1 << 4;}
-            if i.keys_down.contains(&egui::Key::ArrowDown)  {p1_buttons_held |= 1 << 5;}
-            if i.keys_down.contains(&egui::Key::ArrowLeft)  {p1_buttons_held |= 1 << 6;}
-            if i.keys_down.contains(&egui::Key::ArrowRight) {p1_buttons_held |= 1 << 7;}
+            for player in 0 .. preferences::PLAYER_COUNT {
+                let mut buttons_held = 0;
+                for (index, (button_name, _, _)) in preferences::BUTTONS.iter().enumerate() {
+                    match preferences::bound_key(&self.settings_cache, player, button_name) {
+                        Some(key) => {
+                            if i.keys_down.contains(&key) {buttons_held |= 1 << index;}
+                        },
+                        None => {}
+                    }
+                }
 
-            let p1_buttons_pressed = p1_buttons_held & !self.old_p1_buttons_held;
-            let p1_buttons_released = !p1_buttons_held & self.old_p1_buttons_held;
+                let buttons_pressed = buttons_held & !self.old_buttons_held[player];
+                let buttons_released = !buttons_held & self.old_buttons_held[player];
 
-            if (p1_buttons_pressed & (1 << 0)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::A));
-            }
-            if (p1_buttons_pressed & (1 << 1)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::B));
-            }
-            if (p1_buttons_pressed & (1 << 2)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::Select));
-            }
-            if (p1_buttons_pressed & (1 << 3)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::Start));
-            }
-            if (p1_buttons_pressed & (1 << 4)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::DPadUp));
-            }
-            if (p1_buttons_pressed & (1 << 5)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::DPadDown));
-            }
-            if (p1_buttons_pressed & (1 << 6)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::DPadLeft));
-            }
-            if (p1_buttons_pressed & (1 << 7)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerPress(0, events::StandardControllerButton::DPadRight));
-            }
+                for (index, (_, _, button)) in preferences::BUTTONS.iter().enumerate() {
+                    if (buttons_pressed & (1 << index)) != 0 {
+                        let _ = self.runtime_tx.send(events::Event::StandardControllerPress(player, button.clone()));
+                    }
+                    if (buttons_released & (1 << index)) != 0 {
+                        let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(player, button.clone()));
+                    }
+                }
 
-            if (p1_buttons_released & (1 << 0)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::A));
-            }
-            if (p1_buttons_released & (1 << 1)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::B));
-            }
-            if (p1_buttons_released & (1 << 2)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::Select));
-            }
-            if (p1_buttons_released & (1 << 3)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::Start));
+                self.old_buttons_held[player] = buttons_held;
             }
-            if (p1_buttons_released & (1 << 4)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::DPadUp));
-            }
-            if (p1_buttons_released & (1 << 5)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::DPadDown));
-            }
-            if (p1_buttons_released & (1 << 6)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::DPadLeft));
-            }
-            if (p1_buttons_released & (1 << 7)) != 0 {
-                let _ = self.runtime_tx.send(events::Event::StandardControllerRelease(0, events::StandardControllerButton::DPadRight));
-            }
-
-
-            self.old_p1_buttons_held = p1_buttons_held;
         });
     }
 
+    fn apply_turbo_key(&mut self, ctx: &egui::Context) {
+        // Fast-forward only lasts as long as the key is held
+        let turbo_held = ctx.input(|i| i.focused && i.keys_down.contains(&egui::Key::Tab));
+        if turbo_held != self.turbo_held {
+            let _ = self.runtime_tx.send(events::Event::SetTurbo(turbo_held));
+            self.turbo_held = turbo_held;
+        }
+    }
+
     fn request_sram_save(&mut self) {
         self.game_window.request_sram_save(&mut self.runtime_tx);
     }
@@ -167,76 +181,95 @@ impl eframe::App for RusticoApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Presumably this is called at some FPS? I guess we can find out!
         self.apply_player_input(ctx);
+        self.apply_turbo_key(ctx);
         self.process_shell_events();
 
         // Always run the game window
         self.game_window.update(ctx, &self.settings_cache, &mut self.runtime_tx);
 
         // TODO: break these out into separate files, the UI definitions are going to get very tall
-        if self.show_memory_viewer {
+        if self.game_window.show_memory_viewer {
             ctx.show_viewport_immediate(
                 egui::ViewportId::from_hash_of("memory_viewer_viewport"),
                 egui::ViewportBuilder::default()
                     .with_title("Memory Viewer")
-                    .with_inner_size([300.0, 200.0]),
+                    .with_inner_size([480.0, 400.0]),
                 |ctx, class| {
                     assert!(
                         class == egui::ViewportClass::Immediate,
                         "This egui backend doesn't support multiple viewports!"
                     );
                     egui::CentralPanel::default().show(ctx, |ui| {
-                        ui.label("Hello Memory Viewer!");
+                        self.memory_viewer.update(ui, &mut self.runtime_tx);
                     });
                     if ctx.input(|i| i.viewport().close_requested()) {
-                        self.show_memory_viewer = false;
+                        self.game_window.show_memory_viewer = false;
                     }
                 }
             );
         }
 
-        if self.show_event_viewer {
+        if self.game_window.show_preferences {
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("preferences_viewport"),
+                egui::ViewportBuilder::default()
+                    .with_title("Preferences")
+                    .with_inner_size([360.0, 320.0]),
+                |ctx, class| {
+                    assert!(
+                        class == egui::ViewportClass::Immediate,
+                        "This egui backend doesn't support multiple viewports!"
+                    );
+                    self.preferences.update(ctx, &self.settings_cache, &mut self.runtime_tx);
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        self.game_window.show_preferences = false;
+                        self.preferences.rebinding = None;
+                    }
+                }
+            );
+        }
+
+        if self.game_window.show_event_viewer {
             ctx.show_viewport_immediate(
                 egui::ViewportId::from_hash_of("event_viewer_viewport"),
                 egui::ViewportBuilder::default()
                     .with_title("Event Viewer")
-                    .with_inner_size([300.0, 200.0]),
+                    .with_inner_size([720.0, 580.0]),
                 |ctx, class| {
                     assert!(
                         class == egui::ViewportClass::Immediate,
                         "This egui backend doesn't support multiple viewports!"
                     );
                     egui::CentralPanel::default().show(ctx, |ui| {
-                        ui.label("Hello Event Viewer!");
+                        self.event_viewer.update(ui, &mut self.runtime_tx);
                     });
                     if ctx.input(|i| i.viewport().close_requested()) {
-                        self.show_event_viewer = false;
+                        self.game_window.show_event_viewer = false;
                     }
                 }
             );
         }
 
-        if self.show_ppu_viewer {
+        if self.game_window.show_ppu_viewer {
             ctx.show_viewport_immediate(
                 egui::ViewportId::from_hash_of("ppu_viewer_viewport"),
                 egui::ViewportBuilder::default()
                     .with_title("PPU Viewer")
-                    .with_inner_size([300.0, 200.0]),
+                    .with_inner_size([1040.0, 760.0]),
                 |ctx, class| {
                     assert!(
                         class == egui::ViewportClass::Immediate,
                         "This egui backend doesn't support multiple viewports!"
                     );
-                    egui::CentralPanel::default().show(ctx, |ui| {
-                        ui.label("Hello PPU Viewer!");
-                    });
+                    self.ppu_viewer.update(ctx, &mut self.game_window.show_sprite_overlay, &mut self.runtime_tx);
                     if ctx.input(|i| i.viewport().close_requested()) {
-                        self.show_ppu_viewer = false;
+                        self.game_window.show_ppu_viewer = false;
                     }
                 }
             );
         }
 
-        if self.show_piano_roll {
+        if self.game_window.show_piano_roll {
             ctx.show_viewport_immediate(
                 egui::ViewportId::from_hash_of("piano_roll_viewport"),
                 egui::ViewportBuilder::default()
@@ -251,7 +284,7 @@ impl eframe::App for RusticoApp {
                         ui.label("Hello Piano Roll!");
                     });
                     if ctx.input(|i| i.viewport().close_requested()) {
-                        self.show_piano_roll = false;
+                        self.game_window.show_piano_roll = false;
                     }
                 }
             );
@@ -259,7 +292,7 @@ impl eframe::App for RusticoApp {
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        println!("Application closing! Attempting to save SRAM one last time...");
+        log::info!("Application closing! Attempting to save SRAM one last time...");
         self.request_sram_save();
         let _ = self.runtime_tx.send(events::Event::CloseApplication);
     }
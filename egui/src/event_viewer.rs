@@ -0,0 +1,118 @@
+use crate::app::ShellEvent;
+
+use eframe::egui;
+use rustico_core::region::Region;
+use rustico_core::tracked_events::{EventType, TrackedEvent};
+use rustico_ui_common::event_window::{cpu_register_color, cpu_register_label};
+use rustico_ui_common::events;
+
+use std::sync::Arc;
+use std::sync::mpsc::{Sender};
+
+const DOTS_PER_SCANLINE: usize = 341;
+const SCALE: f32 = 2.0;
+// How close, in screen pixels, the pointer needs to be for a mark's tooltip to show
+const HOVER_RADIUS: f32 = 3.0;
+
+pub struct EventViewerWindow {
+    pub frame: u32,
+    // PAL and Dendy frames are 50 scanlines taller than NTSC
+    pub region: Region,
+    pub events: Arc<Vec<TrackedEvent>>,
+}
+
+fn is_timeline_write(event: &TrackedEvent) -> bool {
+    match event.event_type {
+        EventType::CpuWrite{address, data: _, program_counter: _} => {
+            return (address >= 0x2000 && address <= 0x2007) || (address >= 0x4000 && address <= 0x4017);
+        },
+        _ => {return false;}
+    }
+}
+
+fn mark_color(address: u16) -> egui::Color32 {
+    let color = cpu_register_color(address);
+    return egui::Color32::from_rgb(color.r(), color.g(), color.b());
+}
+
+impl EventViewerWindow {
+    pub fn new() -> Self {
+        return EventViewerWindow {
+            frame: 0,
+            region: Region::Ntsc,
+            events: Arc::new(Vec::new()),
+        };
+    }
+
+    pub fn handle_event(&mut self, event: ShellEvent) {
+        match event {
+            ShellEvent::FrameEvents(frame, region, events) => {
+                self.frame = frame;
+                self.region = region;
+                self.events = events;
+            },
+            _ => {}
+        }
+    }
+
+    fn draw_timeline(&self, ui: &mut egui::Ui) {
+        let size = egui::vec2(DOTS_PER_SCANLINE as f32 * SCALE, self.region.scanlines_per_frame() as f32 * SCALE);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let origin = response.rect.min;
+        let raster_rect = |dot: f32, scanline: f32, width: f32, height: f32| {
+            egui::Rect::from_min_size(origin + egui::vec2(dot * SCALE, scanline * SCALE), egui::vec2(width * SCALE, height * SCALE))
+        };
+
+        painter.rect_filled(response.rect, 0.0, egui::Color32::from_gray(30));
+        // Visible picture: dots 1-256 of scanlines 0-239
+        painter.rect_filled(raster_rect(1.0, 0.0, 256.0, 240.0), 0.0, egui::Color32::from_gray(60));
+        // The pre-render line sets up the next frame's scroll, so set it apart from vblank
+        painter.rect_filled(raster_rect(0.0, self.region.prerender_scanline() as f32, DOTS_PER_SCANLINE as f32, 1.0), 0.0, egui::Color32::from_gray(45));
+
+        let mut hovered: Option<TrackedEvent> = None;
+        let pointer = response.hover_pos();
+        for event in self.events.iter().filter(|event| is_timeline_write(event)) {
+            let address = match event.event_type {
+                EventType::CpuWrite{address, data: _, program_counter: _} => address,
+                _ => continue
+            };
+            let center = origin + egui::vec2((event.cycle as f32 + 0.5) * SCALE, (event.scanline as f32 + 0.5) * SCALE);
+            painter.rect_filled(egui::Rect::from_center_size(center, egui::vec2(SCALE, SCALE)), 0.0, mark_color(address));
+            match pointer {
+                Some(position) if position.distance(center) <= HOVER_RADIUS => {hovered = Some(*event);},
+                _ => {}
+            }
+        }
+
+        match hovered {
+            Some(event) => {
+                match event.event_type {
+                    EventType::CpuWrite{address, data, program_counter} => {
+                        response.on_hover_ui_at_pointer(|ui| {
+                            let label = cpu_register_label(address);
+                            ui.strong(if label.is_empty() {format!("Write: ${:04X}", address)} else {format!("Write: {}", label)});
+                            ui.monospace(format!("Scanline: {}", event.scanline));
+                            ui.monospace(format!("Dot:      {}", event.cycle));
+                            ui.monospace(format!("Address:  ${:04X}", address));
+                            ui.monospace(format!("Data:     ${:02X} ({})", data, data));
+                            ui.monospace(format!("PC:       ${:04X}", program_counter));
+                        });
+                    },
+                    _ => {}
+                }
+            },
+            None => {}
+        }
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui, runtime_tx: &mut Sender<events::Event>) {
+        let write_count = self.events.iter().filter(|event| is_timeline_write(event)).count();
+        ui.label(format!("Frame {}: {} register writes", self.frame, write_count));
+        egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+            self.draw_timeline(ui);
+        });
+
+        // Ask for the most recently completed frame; while paused this stays the same one
+        let _ = runtime_tx.send(events::Event::RequestFrameEvents);
+    }
+}
@@ -1,25 +1,55 @@
 use crate::app;
+use crate::screenshot;
 use crate::worker;
 
 use app::ShellEvent;
 
 use eframe::egui;
 use rfd::FileDialog;
+use rustico_core::breakpoints::{Breakpoint, BreakpointKind};
+use rustico_core::ppu::SpriteInfo;
 use rustico_ui_common::events;
+use rustico_ui_common::game_window::MAX_OVERSCAN;
 
 use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::mpsc::{Sender};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rustico_ui_common::settings::SettingsState;
 
 pub struct GameWindow {
     pub texture_handle: egui::TextureHandle,
     pub last_rendered_frames: VecDeque<Arc<worker::RenderedImage>>,
+    // Whatever is currently on screen, kept around for copying to the clipboard
+    pub displayed_frame: Option<Arc<worker::RenderedImage>>,
+    // Created on first use; on X11 the clipboard contents only live as long as this does
+    pub clipboard: Option<arboard::Clipboard>,
     pub game_window_scale: usize,
     pub sram_path: PathBuf,
     pub has_sram: bool,
+    pub nsf_track: u8,
+    pub nsf_track_count: u8,
+    pub nsf_paused: bool,
+    pub fds_side_count: usize,
+    pub fds_inserted_disk: Option<usize>,
+    pub recording_audio: bool,
+    pub paused: bool,
+    pub emulation_speed: f32,
+    pub breakpoints: Vec<Breakpoint>,
+    pub breakpoint_kind: BreakpointKind,
+    pub breakpoint_range: String,
+    pub last_breakpoint_hit: Option<(u16, BreakpointKind)>,
+    pub sprites: Arc<Vec<SpriteInfo>>,
+    pub perf_stats: worker::PerfStats,
+
+    pub show_memory_viewer: bool,
+    pub show_event_viewer: bool,
+    pub show_ppu_viewer: bool,
+    pub show_sprite_overlay: bool,
+    pub show_piano_roll: bool,
+    pub show_preferences: bool,
 }
 
 impl GameWindow {
@@ -31,9 +61,32 @@ impl GameWindow {
         return GameWindow {
             texture_handle: texture_handle,
             last_rendered_frames: VecDeque::new(),
+            displayed_frame: None,
+            clipboard: None,
             game_window_scale: 2,
             sram_path: PathBuf::new(),
             has_sram: false,
+            nsf_track: 0,
+            nsf_track_count: 0,
+            nsf_paused: false,
+            fds_side_count: 0,
+            fds_inserted_disk: None,
+            recording_audio: false,
+            paused: false,
+            emulation_speed: 1.0,
+            breakpoints: Vec::new(),
+            breakpoint_kind: BreakpointKind::Execute,
+            breakpoint_range: String::new(),
+            last_breakpoint_hit: None,
+            sprites: Arc::new(Vec::new()),
+            perf_stats: worker::PerfStats::default(),
+
+            show_memory_viewer: false,
+            show_event_viewer: false,
+            show_ppu_viewer: false,
+            show_sprite_overlay: false,
+            show_piano_roll: false,
+            show_preferences: false,
         };
     }
 
@@ -42,6 +95,33 @@ impl GameWindow {
             ShellEvent::HasSram(has_sram) => {
                 self.has_sram = has_sram;
             },
+            ShellEvent::SpriteInfo(sprites) => {
+                self.sprites = sprites;
+            },
+            ShellEvent::NsfStatus(track, track_count, paused) => {
+                self.nsf_track = track;
+                self.nsf_track_count = track_count;
+                self.nsf_paused = paused;
+            },
+            ShellEvent::RunStateChanged(paused) => {
+                self.paused = paused;
+                if !paused {
+                    self.last_breakpoint_hit = None;
+                }
+            },
+            ShellEvent::BreakpointHit(address, kind) => {
+                self.last_breakpoint_hit = Some((address, kind));
+            },
+            ShellEvent::PerfStats(stats) => {
+                self.perf_stats = stats;
+            },
+            ShellEvent::AudioRecordingChanged(recording) => {
+                self.recording_audio = recording;
+            },
+            ShellEvent::FdsStatus(side_count, inserted_disk) => {
+                self.fds_side_count = side_count;
+                self.fds_inserted_disk = inserted_disk;
+            },
             ShellEvent::ImageRendered(id, canvas) => {
                 if id == "game_window" {
                     self.last_rendered_frames.push_back(canvas);
@@ -65,6 +145,75 @@ impl GameWindow {
                 };
                 self.texture_handle.set(image, texture_options);
                 self.game_window_scale = canvas.scale;
+                self.displayed_frame = Some(canvas);
+            },
+            None => {}
+        }
+    }
+
+    fn copy_frame_to_clipboard(&mut self) {
+        let frame = match &self.displayed_frame {
+            Some(frame) => frame.clone(),
+            None => {
+                log::warn!("No frame has been rendered yet, nothing to copy");
+                return;
+            }
+        };
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => {self.clipboard = Some(clipboard);},
+                Err(why) => {
+                    log::error!("Couldn't open the clipboard: {}", why);
+                    return;
+                }
+            }
+        }
+        let clipboard = self.clipboard.as_mut().unwrap();
+        match screenshot::copy_to_clipboard(clipboard, &frame) {
+            Ok(_) => {log::info!("Copied frame to clipboard");},
+            Err(why) => {log::error!("Couldn't copy frame to clipboard: {}", why);}
+        }
+    }
+
+    fn draw_breakpoint_menu(&mut self, ui: &mut egui::Ui, runtime_tx: &mut Sender<events::Event>) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.breakpoint_kind, BreakpointKind::Execute, "Exec");
+            ui.radio_value(&mut self.breakpoint_kind, BreakpointKind::Read, "Read");
+            ui.radio_value(&mut self.breakpoint_kind, BreakpointKind::Write, "Write");
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.breakpoint_range).hint_text("C000 or 2000-2007").desired_width(120.0));
+            if ui.button("Add").clicked() {
+                match parse_address_range(&self.breakpoint_range) {
+                    Some((start, end)) => {
+                        let breakpoint = Breakpoint{kind: self.breakpoint_kind, start: start, end: end};
+                        if !self.breakpoints.contains(&breakpoint) {
+                            self.breakpoints.push(breakpoint);
+                            let _ = runtime_tx.send(events::Event::AddBreakpoint(breakpoint.kind, start, end));
+                        }
+                        self.breakpoint_range.clear();
+                    },
+                    None => {log::warn!("Couldn't parse breakpoint address: {}", self.breakpoint_range);}
+                }
+            }
+        });
+        let mut removed: Option<usize> = None;
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if breakpoint.start == breakpoint.end {
+                    ui.monospace(format!("{:?} ${:04X}", breakpoint.kind, breakpoint.start));
+                } else {
+                    ui.monospace(format!("{:?} ${:04X}-${:04X}", breakpoint.kind, breakpoint.start, breakpoint.end));
+                }
+                if ui.small_button("x").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        match removed {
+            Some(index) => {
+                let breakpoint = self.breakpoints.remove(index);
+                let _ = runtime_tx.send(events::Event::RemoveBreakpoint(breakpoint.kind, breakpoint.start, breakpoint.end));
             },
             None => {}
         }
@@ -72,6 +221,8 @@ impl GameWindow {
 
     pub fn request_sram_save(&mut self, runtime_tx: &mut Sender<events::Event>) {
         let _ = runtime_tx.send(events::Event::RequestSramSave(self.sram_path.clone().to_string_lossy().into_owned()));
+        // Written next to the original, never over it
+        let _ = runtime_tx.send(events::Event::RequestFdsSave(self.sram_path.with_extension("save.fds").to_string_lossy().into_owned()));
     }
 
     fn open_cartridge_dialog(&mut self, runtime_tx: &mut Sender<events::Event>) {
@@ -83,7 +234,7 @@ impl GameWindow {
                 self.open_cartridge(file_path, runtime_tx);
             },
             None => {
-                println!("User canceled the dialog.");
+                log::debug!("User canceled the dialog.");
             }
         }
     }
@@ -101,24 +252,82 @@ impl GameWindow {
                         rustico_ui_common::Event::LoadCartridge(cartridge_path_as_str, Arc::new(cartridge_data), Arc::new(sram_data))
                     },
                     Err(reason) => {
-                        println!("Failed to load SRAM: {}", reason);
-                        println!("Continuing anyway.");
+                        log::warn!("Failed to load SRAM: {}", reason);
+                        log::warn!("Continuing anyway.");
                         let bucket_of_nothing: Vec<u8> = Vec::new();
                         rustico_ui_common::Event::LoadCartridge(cartridge_path_as_str, Arc::new(cartridge_data), Arc::new(bucket_of_nothing))
                     }
                 }
             },
             Err(reason) => {
-                println!("{}", reason);
+                log::error!("{}", reason);
                 rustico_ui_common::Event::LoadFailed(reason.to_string())
             }
         };
         let _ = runtime_tx.send(cartridge_load_event);
     }
 
+    fn draw_sprite_overlay(&self, ui: &mut egui::Ui, image_rect: egui::Rect) {
+        // The canvas may be wider than 256 pixels with the NTSC filter on, so scale from
+        // NES coordinates to whatever the image actually covers
+        let scale_x = image_rect.width() / 256.0;
+        let scale_y = image_rect.height() / 240.0;
+        let painter = ui.painter_at(image_rect);
+        for sprite in self.sprites.iter() {
+            if sprite.y >= 0xEF {
+                // Parked offscreen
+                continue;
+            }
+            let top_left = image_rect.min + egui::vec2(sprite.x as f32 * scale_x, (sprite.y as f32 + 1.0) * scale_y);
+            let size = egui::vec2(8.0 * scale_x, sprite.height as f32 * scale_y);
+            painter.rect_stroke(egui::Rect::from_min_size(top_left, size), 0.0, egui::Stroke::new(1.0, egui::Color32::from_rgb(64, 255, 64)));
+        }
+    }
+
+    fn draw_perf_overlay(&self, ui: &mut egui::Ui, image_rect: egui::Rect, position: &str) {
+        let text = format!("{:.1} FPS\n{:.0}% speed\nAudio {:.0}%",
+            self.perf_stats.fps, self.perf_stats.speed_percent, self.perf_stats.audio_buffer_percent);
+        let (anchor, offset) = match position {
+            "top_right" => (egui::Align2::RIGHT_TOP, egui::vec2(-4.0, 4.0)),
+            "bottom_left" => (egui::Align2::LEFT_BOTTOM, egui::vec2(4.0, -4.0)),
+            "bottom_right" => (egui::Align2::RIGHT_BOTTOM, egui::vec2(-4.0, -4.0)),
+            _ => (egui::Align2::LEFT_TOP, egui::vec2(4.0, 4.0)),
+        };
+        let painter = ui.painter_at(image_rect);
+        let font = egui::FontId::monospace(12.0);
+        let galley = painter.layout_no_wrap(text, font, egui::Color32::WHITE);
+        let text_rect = anchor.anchor_rect(egui::Rect::from_min_size(anchor.pos_in_rect(&image_rect) + offset, galley.size()));
+        painter.rect_filled(text_rect.expand(2.0), 2.0, egui::Color32::from_black_alpha(160));
+        painter.galley(text_rect.min, galley, egui::Color32::WHITE);
+    }
+
+    /// Where the screenshot hotkey saves: next to the cartridge, named for the current time
+    /// so repeated captures don't overwrite each other.
+    fn quick_screenshot_path(&self) -> PathBuf {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let stem = match self.sram_path.file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => "rustico".to_string()
+        };
+        return self.sram_path.with_file_name(format!("{}_{}.png", stem, timestamp));
+    }
+
     pub fn update(&mut self, ctx: &egui::Context, settings: &SettingsState, runtime_tx: &mut Sender<events::Event>) {
         self.process_rendered_frames();
 
+        // F12 saves a screenshot next to the ROM, Shift+F12 copies the frame to the clipboard
+        if ctx.input(|i| i.key_pressed(egui::Key::F12) && !i.modifiers.shift) {
+            let path = self.quick_screenshot_path();
+            let _ = runtime_tx.send(events::Event::SaveScreenshot(path.to_string_lossy().into_owned()));
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F12) && i.modifiers.shift) {
+            self.copy_frame_to_clipboard();
+        }
+        // Frame advance: pauses emulation if needed and steps exactly one frame per press
+        if ctx.input(|i| i.key_pressed(egui::Key::F6)) {
+            let _ = runtime_tx.send(events::Event::StepFrame);
+        }
+
         egui::TopBottomPanel::top("game_window_top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
@@ -131,6 +340,90 @@ impl GameWindow {
                         ui.close_menu();
                     }
                     ui.separator();
+                    if ui.button("Save Screenshot...").clicked() {
+                        let screenshot_path = FileDialog::new()
+                            .add_filter("png image", &["png"])
+                            .save_file();
+                        match screenshot_path {
+                            Some(path) => {
+                                let _ = runtime_tx.send(events::Event::SaveScreenshot(path.to_string_lossy().into_owned()));
+                            },
+                            None => {
+                                log::debug!("User canceled the dialog.");
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Copy Screenshot").clicked() {
+                        self.copy_frame_to_clipboard();
+                        ui.close_menu();
+                    }
+                    if ui.button("Export CHR Sheet...").clicked() {
+                        let sheet_path = FileDialog::new()
+                            .add_filter("png image", &["png"])
+                            .save_file();
+                        match sheet_path {
+                            Some(path) => {
+                                let _ = runtime_tx.send(events::Event::ExportChrSheet(path.to_string_lossy().into_owned()));
+                            },
+                            None => {
+                                log::debug!("User canceled the dialog.");
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if self.recording_audio {
+                        if ui.button("Stop Recording Audio").clicked() {
+                            let _ = runtime_tx.send(events::Event::StopAudioRecording);
+                            ui.close_menu();
+                        }
+                    } else if ui.button("Record Audio...").clicked() {
+                        let recording_path = FileDialog::new()
+                            .add_filter("wave audio", &["wav"])
+                            .save_file();
+                        match recording_path {
+                            Some(path) => {
+                                let _ = runtime_tx.send(events::Event::StartAudioRecording(path.to_string_lossy().into_owned()));
+                            },
+                            None => {
+                                log::debug!("User canceled the dialog.");
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Record Movie...").clicked() {
+                        let movie_path = FileDialog::new()
+                            .add_filter("rustico movie", &["rnm"])
+                            .save_file();
+                        match movie_path {
+                            Some(path) => {
+                                let _ = runtime_tx.send(events::Event::StartMovieRecord(path.to_string_lossy().into_owned()));
+                            },
+                            None => {
+                                log::debug!("User canceled the dialog.");
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Play Movie...").clicked() {
+                        let movie_path = FileDialog::new()
+                            .add_filter("rustico movie", &["rnm"])
+                            .pick_file();
+                        match movie_path {
+                            Some(path) => {
+                                let _ = runtime_tx.send(events::Event::PlayMovie(path.to_string_lossy().into_owned()));
+                            },
+                            None => {
+                                log::debug!("User canceled the dialog.");
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Stop Movie").clicked() {
+                        let _ = runtime_tx.send(events::Event::StopMovie);
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     if ui.button("Exit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         ui.close_menu();
@@ -138,59 +431,183 @@ impl GameWindow {
                 });
                 ui.menu_button("Settings", |ui| {
                     ui.menu_button("Video", |ui| {
-                        let mut overscan_checked = settings.get_boolean("video.simulate_overscan".into()).unwrap_or(false);
+                        let mut overscan_checked = settings.get_boolean_or_default("video.simulate_overscan");
                         if ui.checkbox(&mut overscan_checked, "Hide Overscan").clicked() {
                             let _ = runtime_tx.send(events::Event::ToggleBooleanSetting("video.simulate_overscan".into()));
                             ui.close_menu();
                         }
-                        let mut ntsc_checked = settings.get_boolean("video.ntsc_filter".into()).unwrap_or(false);
+                        ui.add_enabled_ui(overscan_checked, |ui| {
+                            for (edge, label) in [("top", "Top"), ("bottom", "Bottom"), ("left", "Left"), ("right", "Right")] {
+                                let path = format!("video.overscan.{}", edge);
+                                let mut crop = settings.get_integer_or_default(&path);
+                                ui.horizontal(|ui| {
+                                    if ui.add(egui::DragValue::new(&mut crop).clamp_range(0 ..= MAX_OVERSCAN).suffix(" px")).changed() {
+                                        let _ = runtime_tx.send(events::Event::StoreIntegerSetting(path, crop));
+                                    }
+                                    ui.label(label);
+                                });
+                            }
+                        });
+                        let mut ntsc_checked = settings.get_boolean_or_default("video.ntsc_filter");
                         if ui.checkbox(&mut ntsc_checked, "NTSC Filter").clicked() {
                             let _ = runtime_tx.send(events::Event::ToggleBooleanSetting("video.ntsc_filter".into()));
                             ui.close_menu();
                         }
+                        let mut sprite_limit_checked = settings.get_boolean_or_default("video.disable_sprite_limit");
+                        if ui.checkbox(&mut sprite_limit_checked, "Disable Sprite Limit").clicked() {
+                            let _ = runtime_tx.send(events::Event::ToggleBooleanSetting("video.disable_sprite_limit".into()));
+                            ui.close_menu();
+                        }
+                        let mut perf_overlay_checked = settings.get_boolean_or_default("video.perf_overlay");
+                        if ui.checkbox(&mut perf_overlay_checked, "Performance Overlay").clicked() {
+                            let _ = runtime_tx.send(events::Event::ToggleBooleanSetting("video.perf_overlay".into()));
+                            ui.close_menu();
+                        }
+                        let mut backdrop_quirk_checked = settings.get_boolean_or_default("video.palette_backdrop_quirk");
+                        if ui.checkbox(&mut backdrop_quirk_checked, "Palette Backdrop Quirk").clicked() {
+                            let _ = runtime_tx.send(events::Event::ToggleBooleanSetting("video.palette_backdrop_quirk".into()));
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("Load Palette...").clicked() {
+                            let palette_path = FileDialog::new()
+                                .add_filter("NES palette", &["pal"])
+                                .pick_file();
+                            match palette_path {
+                                Some(path) => {
+                                    let _ = runtime_tx.send(events::Event::LoadPalette(path.to_string_lossy().into_owned()));
+                                },
+                                None => {
+                                    log::debug!("User canceled the dialog.");
+                                }
+                            }
+                            ui.close_menu();
+                        }
+                        let custom_palette = !settings.get_string_or_default("video.palette").is_empty();
+                        if ui.add_enabled(custom_palette, egui::Button::new("Use Built-in Palette")).clicked() {
+                            let _ = runtime_tx.send(events::Event::LoadPalette(String::new()));
+                            ui.close_menu();
+                        }
                         ui.separator();
-                        if ui.radio(settings.get_integer("video.scale_factor".into()).unwrap_or(0) == 1, "1x scale").clicked() {
+                        if ui.radio(settings.get_integer_or_default("video.scale_factor") == 1, "1x scale").clicked() {
                             let _ = runtime_tx.send(events::Event::StoreIntegerSetting("video.scale_factor".into(), 1));
                             ui.close_menu();
                         }
-                        if ui.radio(settings.get_integer("video.scale_factor".into()).unwrap_or(0) == 2, "2x scale").clicked() {
+                        if ui.radio(settings.get_integer_or_default("video.scale_factor") == 2, "2x scale").clicked() {
                             let _ = runtime_tx.send(events::Event::StoreIntegerSetting("video.scale_factor".into(), 2));
                             ui.close_menu();
                         }
-                        if ui.radio(settings.get_integer("video.scale_factor".into()).unwrap_or(0) == 3, "3x scale").clicked() {
+                        if ui.radio(settings.get_integer_or_default("video.scale_factor") == 3, "3x scale").clicked() {
                             let _ = runtime_tx.send(events::Event::StoreIntegerSetting("video.scale_factor".into(), 3));
                             ui.close_menu();
                         }
-                        if ui.radio(settings.get_integer("video.scale_factor".into()).unwrap_or(0) == 4, "4x scale").clicked() {
+                        if ui.radio(settings.get_integer_or_default("video.scale_factor") == 4, "4x scale").clicked() {
                             let _ = runtime_tx.send(events::Event::StoreIntegerSetting("video.scale_factor".into(), 4));
                             ui.close_menu();
                         }
-                        if ui.radio(settings.get_integer("video.scale_factor".into()).unwrap_or(0) == 5, "5x scale").clicked() {
+                        if ui.radio(settings.get_integer_or_default("video.scale_factor") == 5, "5x scale").clicked() {
                             let _ = runtime_tx.send(events::Event::StoreIntegerSetting("video.scale_factor".into(), 5));
                             ui.close_menu();
                         }
                     });
                     ui.separator();
                     if ui.button("Preferences").clicked() {
+                        self.show_preferences = !self.show_preferences;
+                        ui.close_menu();
+                    }
+                });
+                if self.nsf_track_count > 0 {
+                    ui.menu_button("Playback", |ui| {
+                        ui.label(format!("Track {} / {}", self.nsf_track, self.nsf_track_count));
+                        ui.separator();
+                        if ui.add_enabled(self.nsf_track > 1, egui::Button::new("Previous Track")).clicked() {
+                            let _ = runtime_tx.send(events::Event::NsfPrevTrack);
+                        }
+                        if ui.add_enabled(self.nsf_track < self.nsf_track_count, egui::Button::new("Next Track")).clicked() {
+                            let _ = runtime_tx.send(events::Event::NsfNextTrack);
+                        }
+                        if ui.button(if self.nsf_paused {"Resume"} else {"Pause"}).clicked() {
+                            let _ = runtime_tx.send(events::Event::NsfTogglePause);
+                        }
+                    });
+                }
+                if self.fds_side_count > 0 {
+                    ui.menu_button("Disk", |ui| {
+                        for side in 0 .. self.fds_side_count {
+                            let label = format!("Disk {} Side {}", side / 2 + 1, if side % 2 == 0 {"A"} else {"B"});
+                            let mut inserted = self.fds_inserted_disk == Some(side);
+                            if ui.checkbox(&mut inserted, label).clicked() {
+                                let _ = runtime_tx.send(events::Event::FdsInsertDisk(side));
+                                ui.close_menu();
+                            }
+                        }
+                        ui.separator();
+                        if ui.add_enabled(self.fds_inserted_disk.is_some(), egui::Button::new("Eject")).clicked() {
+                            let _ = runtime_tx.send(events::Event::FdsEjectDisk);
+                            ui.close_menu();
+                        }
+                    });
+                }
+                ui.menu_button("Emulation", |ui| {
+                    if ui.button(if self.paused {"Resume"} else {"Pause"}).clicked() {
+                        let _ = runtime_tx.send(events::Event::NesToggleEmulation);
+                        ui.close_menu();
+                    }
+                    if ui.button("Reset").clicked() {
+                        let _ = runtime_tx.send(events::Event::NesReset);
                         ui.close_menu();
                     }
+                    if ui.button("Power Cycle").clicked() {
+                        let _ = runtime_tx.send(events::Event::NesPowerCycle);
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.add_enabled(self.paused, egui::Button::new("Step Instruction")).clicked() {
+                        let _ = runtime_tx.send(events::Event::NesRunOpcode);
+                    }
+                    if ui.add_enabled(self.paused, egui::Button::new("Step Over")).clicked() {
+                        let _ = runtime_tx.send(events::Event::StepOver);
+                    }
+                    if ui.add_enabled(self.paused, egui::Button::new("Step Out")).clicked() {
+                        let _ = runtime_tx.send(events::Event::StepOut);
+                    }
+                    if ui.add_enabled(self.paused, egui::Button::new("Step Frame")).clicked() {
+                        let _ = runtime_tx.send(events::Event::NesRunFrame);
+                    }
+                    ui.menu_button("Speed", |ui| {
+                        for (label, speed) in [("25%", 0.25), ("50%", 0.5), ("100%", 1.0), ("200%", 2.0), ("400%", 4.0)] {
+                            if ui.selectable_label(self.emulation_speed == speed, label).clicked() {
+                                let _ = runtime_tx.send(events::Event::SetEmulationSpeed(speed));
+                                self.emulation_speed = speed;
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.separator();
+                    ui.menu_button("Breakpoints", |ui| {
+                        self.draw_breakpoint_menu(ui, runtime_tx);
+                    });
+                    match self.last_breakpoint_hit {
+                        Some((address, kind)) => {ui.label(format!("Stopped: {:?} at ${:04X}", kind, address));},
+                        None => {}
+                    }
                 });
                 ui.menu_button("Tools", |ui| {
                     if ui.button("Memory").clicked() {
-                        //self.show_memory_viewer = !self.show_memory_viewer;
+                        self.show_memory_viewer = !self.show_memory_viewer;
                         ui.close_menu();
                     }
                     if ui.button("Events").clicked() {
-                        //self.show_event_viewer = !self.show_event_viewer;
+                        self.show_event_viewer = !self.show_event_viewer;
                         ui.close_menu();
                     }
                     if ui.button("PPU").clicked() {
-                        //self.show_ppu_viewer = !self.show_ppu_viewer;
+                        self.show_ppu_viewer = !self.show_ppu_viewer;
                         ui.close_menu();
                     }
                     ui.separator();
                     if ui.button("Piano Roll").clicked() {
-                        //self.show_piano_roll = !self.show_piano_roll;
+                        self.show_piano_roll = !self.show_piano_roll;
                         ui.close_menu();
                     }
                 });
@@ -200,13 +617,20 @@ impl GameWindow {
         let game_window_width = (self.texture_handle.size()[0] * self.game_window_scale) as f32;
         let game_window_height = (self.texture_handle.size()[1] * self.game_window_scale) as f32;
         egui::CentralPanel::default().frame(egui::Frame::none()).show(ctx, |ui| {
-            ui.add(
+            let response = ui.add(
                 egui::Image::new(egui::load::SizedTexture::from_handle(&self.texture_handle))
                     .fit_to_exact_size([
                         game_window_width,
                         game_window_height
                     ].into())
             );
+            // Sprite data only arrives while the PPU viewer is open, so tie the overlay to it
+            if self.show_ppu_viewer && self.show_sprite_overlay {
+                self.draw_sprite_overlay(ui, response.rect);
+            }
+            if settings.get_boolean_or_default("video.perf_overlay") {
+                self.draw_perf_overlay(ui, response.rect, &settings.get_string_or_default("video.perf_overlay_position"));
+            }
         });
 
         let menubar_height = ctx.style().spacing.interact_size[1];
@@ -217,3 +641,20 @@ impl GameWindow {
     }
 }
 
+// Hex addresses, optionally with a leading $, either alone or as an inclusive start-end range
+fn parse_address_range(text: &str) -> Option<(u16, u16)> {
+    let parse = |part: &str| u16::from_str_radix(part.trim().trim_start_matches('$'), 16).ok();
+    match text.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start > end {
+                return None;
+            }
+            return Some((start, end));
+        },
+        None => {
+            let address = parse(text)?;
+            return Some((address, address));
+        }
+    }
+}
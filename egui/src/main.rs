@@ -6,7 +6,13 @@ extern crate rustico_core;
 extern crate rustico_ui_common;
 
 mod app;
+mod event_viewer;
 mod game_window;
+mod memory_viewer;
+mod ppu_viewer;
+mod preferences;
+mod screenshot;
+mod wav_recorder;
 mod worker;
 
 use eframe::egui;
@@ -16,7 +22,9 @@ use std::sync::mpsc::{channel};
 use std::thread;
 
 fn main() -> Result<(), eframe::Error> {
-    env_logger::init();
+    // Log targets follow module paths (rustico_core::mmc, rustico_egui::worker, ...) and
+    // RUST_LOG overrides this; without it, show the same status messages println! used to
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     let (runtime_tx, runtime_rx) = channel::<events::Event>();
     let (shell_tx, shell_rx) = channel::<app::ShellEvent>();
@@ -0,0 +1,103 @@
+use crate::app::ShellEvent;
+
+use eframe::egui;
+use rustico_ui_common::events;
+
+use std::sync::mpsc::{Sender};
+
+const BYTES_PER_ROW: usize = 16;
+const TOTAL_ROWS: usize = 0x10000 / BYTES_PER_ROW;
+
+pub struct MemoryViewerWindow {
+    pub start_address: u16,
+    pub contents: Vec<u8>,
+    pub changed: Vec<bool>,
+    pub prg_banks: Vec<Option<usize>>,
+}
+
+impl MemoryViewerWindow {
+    pub fn new() -> Self {
+        return MemoryViewerWindow {
+            start_address: 0,
+            contents: Vec::new(),
+            changed: Vec::new(),
+            prg_banks: Vec::new(),
+        };
+    }
+
+    pub fn handle_event(&mut self, event: ShellEvent) {
+        match event {
+            ShellEvent::MemoryContents(start_address, contents) => {
+                // Highlight anything that differs from the last update, but only if we were
+                // looking at the same range. Scrolling shouldn't light up the whole window.
+                if start_address == self.start_address && contents.len() == self.contents.len() {
+                    self.changed = contents.iter().zip(self.contents.iter()).map(|(new, old)| new != old).collect();
+                } else {
+                    self.changed = vec![false; contents.len()];
+                }
+                self.start_address = start_address;
+                self.contents = contents.to_vec();
+            },
+            ShellEvent::PrgBanks(banks) => {
+                self.prg_banks = banks.to_vec();
+            },
+            _ => {}
+        }
+    }
+
+    fn byte_at(&self, address: usize) -> Option<(u8, bool)> {
+        let start = self.start_address as usize;
+        if address < start || address >= start + self.contents.len() {
+            return None;
+        }
+        let offset = address - start;
+        return Some((self.contents[offset], self.changed[offset]));
+    }
+
+    fn draw_row(&self, ui: &mut egui::Ui, row: usize) {
+        let row_address = row * BYTES_PER_ROW;
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            ui.monospace(format!("{:04X}: ", row_address));
+            let mut ascii = String::new();
+            for address in row_address .. row_address + BYTES_PER_ROW {
+                match self.byte_at(address) {
+                    Some((byte, changed)) => {
+                        let text = egui::RichText::new(format!("{:02X} ", byte)).monospace();
+                        if changed {
+                            ui.label(text.color(egui::Color32::from_rgb(255, 128, 64)));
+                        } else {
+                            ui.label(text);
+                        }
+                        ascii.push(if byte >= 0x20 && byte < 0x7F {byte as char} else {'.'});
+                    },
+                    None => {
+                        ui.monospace("-- ");
+                        ascii.push(' ');
+                    }
+                }
+            }
+            ui.monospace(format!(" {}", ascii));
+            match self.prg_banks.get(row_address >> 8) {
+                Some(Some(bank)) => {ui.monospace(format!("  bank {}", bank));},
+                _ => {}
+            }
+        });
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui, runtime_tx: &mut Sender<events::Event>) {
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let mut visible_rows = 0 .. 0;
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show_rows(ui, row_height, TOTAL_ROWS, |ui, row_range| {
+            for row in row_range.clone() {
+                self.draw_row(ui, row);
+            }
+            visible_rows = row_range;
+        });
+
+        // Ask the worker for whatever is on screen now; it will arrive in time for the next repaint
+        let start = (visible_rows.start * BYTES_PER_ROW) as u16;
+        let length = (visible_rows.end - visible_rows.start) * BYTES_PER_ROW;
+        let _ = runtime_tx.send(events::Event::ReadMemoryRange(start, length));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::app::ShellEvent;
+use crate::worker;
+
+use eframe::egui;
+use rustico_core::ppu::SpriteInfo;
+use rustico_ui_common::events;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{Sender};
+
+const PATTERN_TABLES: [&str; 2] = ["ppu_pattern_0", "ppu_pattern_1"];
+const NAMETABLES: [&str; 4] = ["ppu_nametable_0", "ppu_nametable_1", "ppu_nametable_2", "ppu_nametable_3"];
+const PALETTE: &str = "ppu_palette";
+
+pub struct PpuViewerWindow {
+    pub pattern_palette: usize,
+    pub sprites: Arc<Vec<SpriteInfo>>,
+    pub pending_images: HashMap<String, Arc<worker::RenderedImage>>,
+    pub textures: HashMap<String, egui::TextureHandle>,
+}
+
+impl PpuViewerWindow {
+    pub fn new() -> Self {
+        return PpuViewerWindow {
+            pattern_palette: 0,
+            sprites: Arc::new(Vec::new()),
+            pending_images: HashMap::new(),
+            textures: HashMap::new(),
+        };
+    }
+
+    pub fn handle_event(&mut self, event: ShellEvent) {
+        match event {
+            ShellEvent::ImageRendered(id, canvas) => {
+                if id.starts_with("ppu_") {
+                    self.pending_images.insert(id, canvas);
+                }
+            },
+            ShellEvent::SpriteInfo(sprites) => {
+                self.sprites = sprites;
+            },
+            _ => {}
+        }
+    }
+
+    fn upload_pending_images(&mut self, ctx: &egui::Context) {
+        let texture_options = egui::TextureOptions{
+            magnification: egui::TextureFilter::Nearest,
+            minification: egui::TextureFilter::Nearest,
+            ..egui::TextureOptions::default()
+        };
+        for (id, canvas) in self.pending_images.drain() {
+            let image = egui::ColorImage::from_rgba_unmultiplied([canvas.width, canvas.height], &canvas.rgba_buffer);
+            match self.textures.get_mut(&id) {
+                Some(texture_handle) => {
+                    texture_handle.set(image, texture_options);
+                },
+                None => {
+                    let texture_handle = ctx.load_texture(id.clone(), image, texture_options);
+                    self.textures.insert(id, texture_handle);
+                }
+            }
+        }
+    }
+
+    fn draw_texture(&self, ui: &mut egui::Ui, id: &str, width: f32, height: f32) {
+        match self.textures.get(id) {
+            Some(texture_handle) => {
+                ui.add(
+                    egui::Image::new(egui::load::SizedTexture::from_handle(texture_handle))
+                        .fit_to_exact_size([width, height].into())
+                );
+            },
+            None => {
+                ui.allocate_space([width, height].into());
+            }
+        }
+    }
+
+    fn draw_sprite_table(&self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("ppu_viewer_sprites").striped(true).show(ui, |ui| {
+                for heading in ["#", "X", "Y", "Tile", "Table", "Pal", "Pri", "Flip"].iter() {
+                    ui.strong(*heading);
+                }
+                ui.end_row();
+                for (index, sprite) in self.sprites.iter().enumerate() {
+                    ui.monospace(format!("{:02}", index));
+                    ui.monospace(format!("{:02X}", sprite.x));
+                    ui.monospace(format!("{:02X}", sprite.y));
+                    ui.monospace(format!("{:02X}", sprite.tile_index));
+                    ui.monospace(format!("${:04X}", sprite.pattern_address));
+                    ui.monospace(format!("{}", sprite.palette));
+                    ui.monospace(if sprite.behind_background {"BG"} else {"FG"});
+                    ui.monospace(format!("{}{}",
+                        if sprite.flip_horizontal {"H"} else {"-"},
+                        if sprite.flip_vertical {"V"} else {"-"}));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context, show_sprite_overlay: &mut bool, runtime_tx: &mut Sender<events::Event>) {
+        self.upload_pending_images(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.label("Pattern Tables");
+                    ui.horizontal(|ui| {
+                        for id in PATTERN_TABLES.iter() {
+                            self.draw_texture(ui, id, 256.0, 256.0);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Palette:");
+                        for palette in 0 .. 8 {
+                            ui.radio_value(&mut self.pattern_palette, palette, format!("{}", palette));
+                        }
+                    });
+                    ui.separator();
+                    ui.label("Palettes");
+                    self.draw_texture(ui, PALETTE, 512.0, 16.0);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Sprites");
+                        ui.checkbox(show_sprite_overlay, "Outline on game view");
+                    });
+                    self.draw_sprite_table(ui);
+                });
+                ui.vertical(|ui| {
+                    ui.label("Nametables");
+                    egui::Grid::new("ppu_viewer_nametables").spacing([0.0, 0.0]).show(ui, |ui| {
+                        for (index, id) in NAMETABLES.iter().enumerate() {
+                            self.draw_texture(ui, id, 256.0, 240.0);
+                            if index == 1 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+                });
+            });
+        });
+
+        // Ask the worker to redraw everything; the results land in time for the next repaint
+        let _ = runtime_tx.send(events::Event::RequestPpuImages(self.pattern_palette));
+    }
+}
@@ -0,0 +1,128 @@
+use eframe::egui;
+use rustico_ui_common::events;
+use rustico_ui_common::events::StandardControllerButton;
+use rustico_ui_common::settings::SettingsState;
+
+use std::sync::mpsc::{Sender};
+
+pub const PLAYER_COUNT: usize = 2;
+
+// Setting name, label, and the controller button it drives. The order here is also the
+// bit order used when tracking which buttons are held.
+pub const BUTTONS: [(&str, &str, StandardControllerButton); 8] = [
+    ("a", "A", StandardControllerButton::A),
+    ("b", "B", StandardControllerButton::B),
+    ("select", "Select", StandardControllerButton::Select),
+    ("start", "Start", StandardControllerButton::Start),
+    ("up", "Up", StandardControllerButton::DPadUp),
+    ("down", "Down", StandardControllerButton::DPadDown),
+    ("left", "Left", StandardControllerButton::DPadLeft),
+    ("right", "Right", StandardControllerButton::DPadRight),
+];
+
+pub fn binding_path(player: usize, button_name: &str) -> String {
+    return format!("input.p{}.{}", player + 1, button_name);
+}
+
+/// The key currently bound to a button. Settings files from older versions won't have an
+/// input section at all, so fall back to the built-in defaults when the path is missing.
+pub fn bound_key(settings: &SettingsState, player: usize, button_name: &str) -> Option<egui::Key> {
+    let path = binding_path(player, button_name);
+    let key_name = match settings.get_string(path.clone()) {
+        Some(key_name) => key_name,
+        None => SettingsState::new().get_string(path)?
+    };
+    return egui::Key::from_name(&key_name);
+}
+
+pub struct PreferencesWindow {
+    // The binding waiting for a key press, as (player, button name)
+    pub rebinding: Option<(usize, &'static str)>,
+}
+
+impl PreferencesWindow {
+    pub fn new() -> Self {
+        return PreferencesWindow {
+            rebinding: None,
+        };
+    }
+
+    fn capture_key(&mut self, ctx: &egui::Context, runtime_tx: &mut Sender<events::Event>) {
+        let (player, button_name) = match self.rebinding {
+            Some(binding) => binding,
+            None => {return}
+        };
+        let pressed_key = ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Key{key, pressed: true, ..} => Some(*key),
+                _ => None
+            })
+        });
+        match pressed_key {
+            Some(egui::Key::Escape) => {
+                // Escape cancels, so it can't itself be bound
+                self.rebinding = None;
+            },
+            Some(key) => {
+                let _ = runtime_tx.send(events::Event::StoreStringSetting(binding_path(player, button_name), key.name().to_string()));
+                self.rebinding = None;
+            },
+            None => {}
+        }
+    }
+
+    fn reset_to_defaults(&mut self, runtime_tx: &mut Sender<events::Event>) {
+        let defaults = SettingsState::new();
+        for player in 0 .. PLAYER_COUNT {
+            for (button_name, _, _) in BUTTONS.iter() {
+                let path = binding_path(player, button_name);
+                match defaults.get_string(path.clone()) {
+                    Some(key_name) => {
+                        let _ = runtime_tx.send(events::Event::StoreStringSetting(path, key_name));
+                    },
+                    None => {}
+                }
+            }
+        }
+        self.rebinding = None;
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context, settings: &SettingsState, runtime_tx: &mut Sender<events::Event>) {
+        self.capture_key(ctx, runtime_tx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Input");
+            ui.label("Click a binding, then press the new key. Escape cancels.");
+            ui.horizontal(|ui| {
+                for player in 0 .. PLAYER_COUNT {
+                    ui.group(|ui| {
+                        ui.vertical(|ui| {
+                            ui.label(format!("Player {}", player + 1));
+                            egui::Grid::new(format!("player_{}_bindings", player + 1)).show(ui, |ui| {
+                                for (button_name, label, _) in BUTTONS.iter() {
+                                    ui.label(*label);
+                                    let key_text = if self.rebinding == Some((player, *button_name)) {
+                                        "Press a key...".to_string()
+                                    } else {
+                                        match bound_key(settings, player, button_name) {
+                                            Some(key) => key.name().to_string(),
+                                            None => "(none)".to_string()
+                                        }
+                                    };
+                                    if ui.button(key_text).clicked() {
+                                        self.rebinding = Some((player, *button_name));
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                    });
+                }
+            });
+            ui.separator();
+            if ui.button("Reset to Defaults").clicked() {
+                self.reset_to_defaults(runtime_tx);
+            }
+        });
+    }
+}
@@ -0,0 +1,80 @@
+// PNG export of the game window. Frames arrive exactly as the worker drew them, with
+// overscan already cropped; the display scale is applied here so the file matches what
+// was on screen.
+
+use crate::worker::RenderedImage;
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+
+pub fn scaled_rgba(image: &RenderedImage) -> (usize, usize, Vec<u8>) {
+    let scale = std::cmp::max(image.scale, 1);
+    let width = image.width * scale;
+    let height = image.height * scale;
+    let mut buffer = vec![0u8; width * height * 4];
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let source = ((y / scale) * image.width + (x / scale)) * 4;
+            let dest = (y * width + x) * 4;
+            buffer[dest .. dest + 4].copy_from_slice(&image.rgba_buffer[source .. source + 4]);
+        }
+    }
+    return (width, height, buffer);
+}
+
+pub fn save_png(path: &str, image: &RenderedImage) -> Result<(), String> {
+    let (width, height, pixels) = scaled_rgba(image);
+    return save_rgba_png(path, width, height, &pixels);
+}
+
+pub fn save_rgba_png(path: &str, width: usize, height: usize, pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    return Ok(());
+}
+
+pub fn copy_to_clipboard(clipboard: &mut arboard::Clipboard, image: &RenderedImage) -> Result<(), String> {
+    let (width, height, pixels) = scaled_rgba(image);
+    let image_data = arboard::ImageData {
+        width: width,
+        height: height,
+        bytes: Cow::Owned(pixels),
+    };
+    return clipboard.set_image(image_data).map_err(|e| e.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_png_reads_back_at_display_scale() {
+        // 2x1: one red pixel, one translucent blue one
+        let image = RenderedImage {
+            width: 2,
+            height: 1,
+            scale: 2,
+            rgba_buffer: vec![255, 0, 0, 255, 0, 0, 255, 128],
+        };
+        let path = std::env::temp_dir().join(format!("rustico_screenshot_test_{}.png", std::process::id()));
+        save_png(path.to_str().unwrap(), &image).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((info.width, info.height), (4, 2));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 128];
+        let row: Vec<u8> = [red, red, blue, blue].concat();
+        assert_eq!(&pixels[.. info.buffer_size()], &[row.clone(), row].concat()[..]);
+    }
+}
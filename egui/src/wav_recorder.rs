@@ -0,0 +1,68 @@
+// Writes the emulator's output stream to a 16-bit mono PCM .wav file. The RIFF
+// header is written up front with placeholder sizes, which are patched in once
+// recording stops and the final length is known.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+const HEADER_SIZE: u32 = 44;
+
+pub struct WavRecorder {
+    pub path: String,
+    writer: BufWriter<File>,
+    data_bytes: u32,
+}
+
+fn write_header(writer: &mut impl Write, sample_rate: u32, data_bytes: u32) -> std::io::Result<()> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * (bits_per_sample / 8);
+    let byte_rate = sample_rate * block_align as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE - 8 + data_bytes).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_bytes.to_le_bytes())?;
+    return Ok(());
+}
+
+impl WavRecorder {
+    pub fn start(path: String, sample_rate: u32) -> std::io::Result<WavRecorder> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        write_header(&mut writer, sample_rate, 0)?;
+        return Ok(WavRecorder {
+            path: path,
+            writer: writer,
+            data_bytes: 0,
+        });
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> std::io::Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_bytes = self.data_bytes.saturating_add((samples.len() * 2) as u32);
+        return Ok(());
+    }
+
+    /// Flushes any buffered audio and fills in the RIFF and data chunk sizes.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        let file = self.writer.get_mut();
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&(HEADER_SIZE - 8 + self.data_bytes).to_le_bytes())?;
+        file.seek(SeekFrom::Start(40))?;
+        file.write_all(&self.data_bytes.to_le_bytes())?;
+        file.flush()?;
+        return Ok(());
+    }
+}
@@ -1,24 +1,42 @@
 use crate::app;
+use crate::screenshot;
+use crate::wav_recorder::WavRecorder;
 
 use rustico_ui_common::application::RuntimeState as RusticoRuntimeState;
 use rustico_ui_common::events;
 use rustico_ui_common::game_window::GameWindow;
 use rustico_ui_common::panel::Panel;
+use rustico_ui_common::settings::SettingsState;
+use rustico_core::apu::buffer_size_for_latency;
+use rustico_core::memory;
+use rustico_core::movie::{Movie, MovieMode};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 
+// How long a single turbo step may run before we go back to check for events, so releasing
+// the turbo key takes effect promptly
+const TURBO_BUDGET: Duration = Duration::from_millis(16);
+// How often the worker reports PerfStats; stats are averaged over this window
+const PERF_STATS_INTERVAL: Duration = Duration::from_millis(500);
+
 lazy_static! {
     pub static ref AUDIO_OUTPUT_BUFFER: Mutex<VecDeque<f32>> = Mutex::new(VecDeque::new());
 }
 
+// Audio callbacks that found too little queued and played silence instead
+static AUDIO_UNDERRUNS: AtomicUsize = AtomicUsize::new(0);
+
 pub struct RenderedImage {
     pub width: usize,
     pub height: usize,
@@ -26,6 +44,16 @@ pub struct RenderedImage {
     pub rgba_buffer: Vec<u8>,
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PerfStats {
+    // Frames actually handed to the shell for display
+    pub fps: f32,
+    // Emulated frames per second, relative to the console's own refresh rate
+    pub speed_percent: f32,
+    // Host audio queue length relative to the level the worker paces against
+    pub audio_buffer_percent: f32,
+}
+
 struct Worker {
     runtime_rx: Receiver<events::Event>,
     shell_tx: Sender<app::ShellEvent>,
@@ -36,23 +64,64 @@ struct Worker {
     runtime_state: RusticoRuntimeState,
     game_window: GameWindow,
 
+    config_path: OsString,
+    audio_recorder: Option<WavRecorder>,
+    movie_path: Option<String>,
+    last_frame: Option<Arc<RenderedImage>>,
+    emulation_speed: f32,
+    turbo: bool,
+    // Fractional progress through the output stream when resampling for non-realtime speeds
+    resample_phase: f32,
     exit_requested: bool,
+    // The worker keeps emulating until the host audio queue holds this many samples
+    audio_buffer_target: usize,
+
+    perf_window_start: Instant,
+    perf_frames_displayed: u32,
+    perf_frames_emulated: u32,
 }
 
 impl Worker {
     pub fn new(runtime_rx: Receiver<events::Event>, shell_tx: Sender<app::ShellEvent>) -> Worker {
-        let audio_stream = setup_audio_stream();
-        let runtime_state = RusticoRuntimeState::new();
+        let (audio_stream, sample_rate) = setup_audio_stream();
+        let mut runtime_state = RusticoRuntimeState::new();
+        runtime_state.nes.apu.set_output_sample_rate(sample_rate);
         let game_window = GameWindow::new();
 
-        return Worker{
+        let config_path = settings_path();
+        runtime_state.settings.load(&config_path);
+        // Settings saved before input remapping existed won't have any bindings yet
+        let defaults = SettingsState::new();
+        for (path, default_value) in [("input.p1", defaults.get("input.p1".into())), ("input.p2", defaults.get("input.p2".into()))] {
+            match default_value {
+                Some(value) => {runtime_state.settings.ensure_path_exists(path.into(), value.clone());},
+                None => {}
+            }
+        }
+
+        let mut worker = Worker{
             runtime_rx: runtime_rx,
             shell_tx: shell_tx,
             _audio_stream: audio_stream,
             runtime_state: runtime_state,
             game_window: game_window,
-            exit_requested: false
+            config_path: config_path,
+            audio_recorder: None,
+            movie_path: None,
+            last_frame: None,
+            emulation_speed: 1.0,
+            turbo: false,
+            resample_phase: 0.0,
+            exit_requested: false,
+            audio_buffer_target: buffer_size_for_latency(sample_rate as u64, 12),
+            perf_window_start: Instant::now(),
+            perf_frames_displayed: 0,
+            perf_frames_emulated: 0,
         };
+        for event in worker.runtime_state.settings.apply_settings() {
+            worker.dispatch_event(event);
+        }
+        return worker;
     }
 
     pub fn process_incoming_events(&mut self) {
@@ -94,14 +163,162 @@ impl Worker {
         let events: Vec<events::Event> = Vec::new();
         match event {
             rustico_ui_common::Event::CartridgeLoaded(_id) => {
-                let has_sram = self.runtime_state.nes.mapper.has_sram();
+                let has_sram = self.runtime_state.nes.mapper.has_persistent_ram();
                 let _ = self.shell_tx.send(app::ShellEvent::HasSram(has_sram));
+                // FDS games stay paused until the BIOS shows up
+                let _ = self.shell_tx.send(app::ShellEvent::RunStateChanged(!self.runtime_state.running));
+                self.send_nsf_status();
+                self.send_fds_status();
             }
+            rustico_ui_common::Event::ChangeDisk(_, _) |
+            rustico_ui_common::Event::FdsEjectDisk |
+            rustico_ui_common::Event::FdsInsertDisk(_) => {
+                self.send_fds_status();
+            },
+            rustico_ui_common::Event::SaveFdsDisk(fds_id, fds_data) => {
+                match fs::write(&fds_id, &*fds_data) {
+                    Ok(_) => {log::info!("Wrote modified disk to: {}", fds_id);},
+                    Err(why) => {log::error!("Couldn't write disk {}: {}", fds_id, why);}
+                }
+            },
+            rustico_ui_common::Event::NsfSelectTrack(_) |
+            rustico_ui_common::Event::NsfNextTrack |
+            rustico_ui_common::Event::NsfPrevTrack => {
+                self.send_nsf_status();
+            },
+            rustico_ui_common::Event::NsfTogglePause |
+            rustico_ui_common::Event::NesPauseEmulation |
+            rustico_ui_common::Event::NesResumeEmulation |
+            rustico_ui_common::Event::NesToggleEmulation => {
+                self.send_nsf_status();
+                let _ = self.shell_tx.send(app::ShellEvent::RunStateChanged(!self.runtime_state.running));
+            },
+            rustico_ui_common::Event::BreakpointHit(address, kind) => {
+                log::info!("Breakpoint hit: {:?} at ${:04X}", kind, address);
+                let _ = self.shell_tx.send(app::ShellEvent::BreakpointHit(address, kind));
+                let _ = self.shell_tx.send(app::ShellEvent::RunStateChanged(true));
+                self.dispatch_event(events::Event::RequestFrame);
+                self.send_game_frame();
+            },
+            rustico_ui_common::Event::NesRunOpcode |
+            rustico_ui_common::Event::StepOver |
+            rustico_ui_common::Event::StepOut |
+            rustico_ui_common::Event::NesRunFrame => {
+                // step_emulator won't repaint while paused, so show the result of the step here
+                if !self.runtime_state.running {
+                    self.dispatch_event(events::Event::RequestFrame);
+                    self.send_game_frame();
+                }
+            },
+            rustico_ui_common::Event::StepFrame => {
+                // Audio from a single stepped frame would just be a click once we resume, so drop it
+                let _ = self.runtime_state.nes.apu.consume_samples();
+                let _ = self.shell_tx.send(app::ShellEvent::RunStateChanged(true));
+                self.dispatch_event(events::Event::RequestFrame);
+                self.send_game_frame();
+            },
             rustico_ui_common::Event::SaveSram(sram_id, sram_data) => {
-                self.save_sram(sram_id, &sram_data);
+                // Carts without a battery lose their RAM at power off anyway, so don't leave a .sav behind
+                if self.runtime_state.nes.mapper.has_persistent_ram() {
+                    self.save_sram(sram_id, &sram_data);
+                }
+            },
+            rustico_ui_common::Event::ReadMemoryRange(start_address, length) => {
+                // Debug reads only, so peeking at registers doesn't disturb the running game
+                let nes = &self.runtime_state.nes;
+                let contents: Vec<u8> = (0 .. length)
+                    .map(|offset| memory::debug_read_byte(nes, start_address.wrapping_add(offset as u16)))
+                    .collect();
+                let _ = self.shell_tx.send(app::ShellEvent::MemoryContents(start_address, Arc::new(contents)));
+                let banks: Vec<Option<usize>> = (0 .. 0x100)
+                    .map(|page| nes.mapper.prg_bank_at((page as u16) << 8))
+                    .collect();
+                let _ = self.shell_tx.send(app::ShellEvent::PrgBanks(Arc::new(banks)));
+            },
+            rustico_ui_common::Event::RequestFrameEvents => {
+                let nes = &self.runtime_state.nes;
+                let frame = nes.ppu.current_frame.wrapping_sub(1);
+                let events = nes.event_tracker.events_for_frame(frame).to_vec();
+                let _ = self.shell_tx.send(app::ShellEvent::FrameEvents(frame, nes.region, Arc::new(events)));
+            },
+            rustico_ui_common::Event::RequestPpuImages(pattern_palette) => {
+                self.send_ppu_images(pattern_palette);
+            },
+            rustico_ui_common::Event::StartAudioRecording(path) => {
+                // Only one recording at a time; starting a new one closes out the old file
+                self.stop_audio_recording();
+                match WavRecorder::start(path.clone(), self.runtime_state.nes.apu.sample_rate as u32) {
+                    Ok(recorder) => {
+                        log::info!("Recording audio to {}", path);
+                        self.audio_recorder = Some(recorder);
+                        let _ = self.shell_tx.send(app::ShellEvent::AudioRecordingChanged(true));
+                    },
+                    Err(why) => {
+                        log::error!("Couldn't start audio recording to {}: {}", path, why);
+                    }
+                }
+            },
+            rustico_ui_common::Event::StopAudioRecording => {
+                self.stop_audio_recording();
+            },
+            rustico_ui_common::Event::StartMovieRecord(path) => {
+                self.stop_movie();
+                self.runtime_state.nes.start_recording();
+                log::info!("Recording movie to {}", path);
+                self.movie_path = Some(path);
+            },
+            rustico_ui_common::Event::PlayMovie(path) => {
+                self.stop_movie();
+                match fs::read(&path) {
+                    Ok(data) => {
+                        match Movie::from_bytes(&data) {
+                            Ok(movie) => {
+                                match self.runtime_state.nes.play(movie) {
+                                    Ok(_) => {log::info!("Playing movie {}", path);},
+                                    Err(why) => {log::error!("Couldn't start movie {}: {}", path, why);}
+                                }
+                            },
+                            Err(why) => {log::error!("Couldn't read movie {}: {}", path, why);}
+                        }
+                    },
+                    Err(why) => {log::error!("Couldn't open movie {}: {}", path, why);}
+                }
+            },
+            rustico_ui_common::Event::SaveScreenshot(path) => {
+                match &self.last_frame {
+                    Some(frame) => {
+                        match screenshot::save_png(&path, frame) {
+                            Ok(_) => {log::info!("Saved screenshot to {}", path);},
+                            Err(why) => {log::error!("Couldn't save screenshot {}: {}", path, why);}
+                        }
+                    },
+                    None => {log::warn!("No frame has been rendered yet, skipping screenshot");}
+                }
+            },
+            rustico_ui_common::Event::ExportChrSheet(path) => {
+                let nes = &self.runtime_state.nes;
+                let sheet = nes.ppu.render_chr_sheet(&*nes.mapper, 0);
+                match screenshot::save_rgba_png(&path, 256, 128, &sheet) {
+                    Ok(_) => {log::info!("Saved CHR sheet to {}", path);},
+                    Err(why) => {log::error!("Couldn't save CHR sheet {}: {}", path, why);}
+                }
+            },
+            rustico_ui_common::Event::SetEmulationSpeed(speed) => {
+                if speed.is_finite() && speed > 0.0 {
+                    self.emulation_speed = speed.clamp(0.1, 8.0);
+                }
+            },
+            rustico_ui_common::Event::SetTurbo(enabled) => {
+                self.turbo = enabled;
+            },
+            rustico_ui_common::Event::StopMovie => {
+                self.stop_movie();
             },
             rustico_ui_common::Event::CloseApplication => {
-                println!("WORKER: application close requested, will exit after processing remaining events...");
+                log::info!("WORKER: application close requested, will exit after processing remaining events...");
+                self.stop_audio_recording();
+                self.stop_movie();
+                self.runtime_state.settings.save(&self.config_path);
                 self.exit_requested = true;
             },
             rustico_ui_common::Event::ApplyBooleanSetting(_,_) => {
@@ -109,7 +326,14 @@ impl Worker {
                     Arc::new(self.runtime_state.settings.clone())
                 ));
             },
-            rustico_ui_common::Event::ApplyIntegerSetting(_,_) => {
+            rustico_ui_common::Event::ApplyIntegerSetting(path, value) => {
+                match path.as_str() {
+                    "audio.buffer_ms" => {
+                        let sample_rate = self.runtime_state.nes.apu.sample_rate;
+                        self.audio_buffer_target = buffer_size_for_latency(sample_rate, value as u32);
+                    },
+                    _ => {}
+                }
                 let _ = self.shell_tx.send(app::ShellEvent::SettingsUpdated(
                     Arc::new(self.runtime_state.settings.clone())
                 ));
@@ -129,20 +353,236 @@ impl Worker {
         return events;
     }
 
+    fn send_ppu_images(&self, pattern_palette: usize) {
+        let nes = &self.runtime_state.nes;
+        let mut images: Vec<(String, usize, usize, Vec<u8>)> = Vec::new();
+        for table in 0 .. 2 {
+            images.push((format!("ppu_pattern_{}", table), 128, 128, nes.ppu.render_pattern_table(&*nes.mapper, table, pattern_palette)));
+        }
+        for index in 0 .. 4 {
+            images.push((format!("ppu_nametable_{}", index), 256, 240, nes.ppu.render_nametable(&*nes.mapper, index)));
+        }
+        images.push(("ppu_palette".to_string(), 32, 1, nes.ppu.render_palette(&*nes.mapper)));
+        let _ = self.shell_tx.send(app::ShellEvent::SpriteInfo(Arc::new(nes.ppu.all_sprites())));
+        for (id, width, height, rgba_buffer) in images {
+            let _ = self.shell_tx.send(app::ShellEvent::ImageRendered(id, Arc::new(RenderedImage{
+                width: width,
+                height: height,
+                scale: 1,
+                rgba_buffer: rgba_buffer,
+            })));
+        }
+    }
+
+    fn stop_audio_recording(&mut self) {
+        match self.audio_recorder.take() {
+            Some(recorder) => {
+                let path = recorder.path.clone();
+                match recorder.finish() {
+                    Ok(_) => {log::info!("Finished audio recording: {}", path);},
+                    Err(why) => {log::error!("Failed to finalize audio recording {}: {}", path, why);}
+                }
+                let _ = self.shell_tx.send(app::ShellEvent::AudioRecordingChanged(false));
+            },
+            None => {}
+        }
+    }
+
+    /// Ends any movie in progress. Recordings are written out to the path they were started with.
+    fn stop_movie(&mut self) {
+        let was_recording = self.runtime_state.nes.movie_mode == MovieMode::Recording;
+        let movie = self.runtime_state.nes.stop_recording();
+        let path = self.movie_path.take();
+        if !was_recording {
+            return;
+        }
+        match (movie, path) {
+            (Some(movie), Some(path)) => {
+                match fs::write(&path, movie.to_bytes()) {
+                    Ok(_) => {log::info!("Saved {} frame movie to {}", movie.frames.len(), path);},
+                    Err(why) => {log::error!("Failed to save movie {}: {}", path, why);}
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn send_game_frame(&mut self) {
+        let frame = Arc::new(RenderedImage{
+            width: self.game_window.canvas.width as usize,
+            height: self.game_window.canvas.height as usize,
+            scale: if self.game_window.ntsc_filter == true {1} else {self.game_window.scale as usize},
+            rgba_buffer: Vec::from(self.game_window.canvas.buffer.clone())
+        });
+        // Kept around for screenshots, which should match what's on screen
+        self.last_frame = Some(frame.clone());
+        let repaint_event = app::ShellEvent::ImageRendered("game_window".to_string(), frame);
+        let _ = self.shell_tx.send(repaint_event);
+    }
+
+    fn send_nsf_status(&self) {
+        let mapper = &self.runtime_state.nes.mapper;
+        let _ = self.shell_tx.send(app::ShellEvent::NsfStatus(
+            mapper.nsf_current_track(), mapper.nsf_track_count(), !self.runtime_state.running));
+    }
+
+    fn send_fds_status(&self) {
+        let mapper = &self.runtime_state.nes.mapper;
+        let _ = self.shell_tx.send(app::ShellEvent::FdsStatus(
+            mapper.fds_side_count(), mapper.fds_inserted_disk()));
+    }
+
     pub fn save_sram(&self, filename: String, sram_data: &[u8]) {
         let file = File::create(filename.clone());
         match file {
             Err(why) => {
-                println!("Couldn't open {}: {}", filename, why.to_string());
+                log::error!("Couldn't open {}: {}", filename, why.to_string());
             },
             Ok(mut file) => {
                 let _ = file.write_all(sram_data);
-                println!("Wrote sram data to: {}", filename);
+                log::info!("Wrote sram data to: {}", filename);
+            },
+        };
+    }
+
+    /// The speed pacing should actually use. A recording in progress always runs at realtime,
+    /// since the .wav file would otherwise be pitched up or missing the samples turbo drops.
+    fn effective_speed(&self) -> f32 {
+        if self.audio_recorder.is_some() {
+            return 1.0;
+        }
+        return self.emulation_speed;
+    }
+
+    fn turbo_active(&self) -> bool {
+        return self.turbo && self.audio_recorder.is_none();
+    }
+
+    /// Runs a single scanline and hands back whatever audio it produced. Returns None if a
+    /// breakpoint paused us partway through.
+    fn run_scanline(&mut self) -> Option<Vec<i16>> {
+        self.dispatch_event(events::Event::NesRunScanline);
+        if !self.runtime_state.running {
+            return None;
+        }
+        if self.runtime_state.nes.ppu.current_scanline == 242 {
+            // we just finished a game frame, so have the game window repaint itself
+            self.dispatch_event(events::Event::RequestFrame);
+            self.perf_frames_emulated += 1;
+        }
+        let samples_i16 = self.runtime_state.nes.apu.consume_samples();
+        let mut recording_failed = false;
+        match self.audio_recorder.as_mut() {
+            Some(recorder) => {
+                match recorder.write_samples(&samples_i16) {
+                    Ok(_) => {},
+                    Err(why) => {
+                        log::error!("Audio recording failed: {}", why);
+                        recording_failed = true;
+                    }
+                }
             },
+            None => {}
+        }
+        if recording_failed {
+            self.stop_audio_recording();
+        }
+        return Some(samples_i16);
+    }
+
+    /// Resamples emulated audio so that it drains at the host rate while the emulator runs
+    /// at `speed`. Faster speeds drop samples, which also keeps the output buffer from overrunning.
+    /// Slower speeds hold each sample for several output samples, so the emulator only needs to
+    /// generate a fraction of the audio to keep the buffer fed and never underruns into clicks.
+    fn stretch_samples(&mut self, samples: Vec<i16>, speed: f32) -> Vec<f32> {
+        if speed == 1.0 {
+            self.resample_phase = 0.0;
+            return samples.into_iter().map(|x| <i16 as Into<f32>>::into(x) / 32767.0).collect();
+        }
+        let step = 1.0 / speed;
+        let mut output: Vec<f32> = Vec::new();
+        for sample in samples {
+            self.resample_phase += step;
+            while self.resample_phase >= 1.0 {
+                output.push(<i16 as Into<f32>>::into(sample) / 32767.0);
+                self.resample_phase -= 1.0;
+            }
+        }
+        return output;
+    }
+
+    fn after_repaint(&mut self) {
+        self.send_game_frame();
+        self.perf_frames_displayed += 1;
+        // The NSF player can advance tracks on its own, so keep the shell in sync
+        if self.runtime_state.nes.mapper.nsf_track_count() > 0 {
+            self.send_nsf_status();
+        }
+        // Swapping sides finishes a little while after the request, once the drive has seen the eject
+        if self.runtime_state.nes.mapper.fds_side_count() > 0 {
+            self.send_fds_status();
+        }
+    }
+
+    /// Sends a PerfStats to the shell once per PERF_STATS_INTERVAL. Cheap enough to call every
+    /// step; the work is a clock read unless the interval has passed.
+    fn update_perf_stats(&mut self) {
+        let elapsed = self.perf_window_start.elapsed();
+        if elapsed < PERF_STATS_INTERVAL {
+            return;
+        }
+        let seconds = elapsed.as_secs_f32();
+        let native_fps = self.runtime_state.nes.region.frames_per_second() as f32;
+        let audio_buffer_len = AUDIO_OUTPUT_BUFFER.lock().expect("wat").len();
+        let stats = PerfStats {
+            fps: self.perf_frames_displayed as f32 / seconds,
+            speed_percent: (self.perf_frames_emulated as f32 / seconds) / native_fps * 100.0,
+            audio_buffer_percent: audio_buffer_len as f32 / self.audio_buffer_target as f32 * 100.0,
         };
+        let _ = self.shell_tx.send(app::ShellEvent::PerfStats(stats));
+        self.perf_window_start = Instant::now();
+        self.perf_frames_displayed = 0;
+        self.perf_frames_emulated = 0;
+    }
+
+    /// Turbo runs uncapped for a short slice of wall time. Audio is muted rather than queued, and
+    /// only the last completed frame is sent to the shell.
+    fn step_turbo(&mut self) {
+        let start = Instant::now();
+        let mut repaint_needed = false;
+        while start.elapsed() < TURBO_BUDGET {
+            match self.run_scanline() {
+                Some(_) => {},
+                None => {break;}
+            }
+            if self.runtime_state.nes.ppu.current_scanline == 242 {
+                repaint_needed = true;
+            }
+        }
+        if repaint_needed {
+            self.after_repaint();
+        }
     }
 
     pub fn step_emulator(&mut self) {
+        self.update_perf_stats();
+        // The callback can't tell a paused emulator from a starved one, so only complain
+        // about underruns that happened while running
+        let underruns = AUDIO_UNDERRUNS.swap(0, Ordering::Relaxed);
+        if underruns > 0 && self.runtime_state.running && !self.turbo_active() {
+            log::warn!("Audio underrun: {} callback(s) ran dry, consider raising audio.buffer_ms", underruns);
+        }
+        if !self.runtime_state.running {
+            // Nothing will fill the audio buffer while paused, so don't wait on it
+            return;
+        }
+
+        if self.turbo_active() {
+            self.step_turbo();
+            return;
+        }
+        let speed = self.effective_speed();
+
         // Quickly poll the length of the audio buffer
         let audio_output_buffer = AUDIO_OUTPUT_BUFFER.lock().expect("wat");
         let mut output_buffer_len = audio_output_buffer.len();
@@ -150,18 +590,19 @@ impl Worker {
 
         // Now we do fun stuff: as long as we are under the audio threshold, run one scanline. If we happen
         // to complete a frame while doing this, update the game window texture (and later, call "draw" on all
-        // active subwindows so they know to repaint)
-        // (2048 is arbitrary, make this configurable later!)
+        // active subwindows so they know to repaint). Audio is stretched by the emulation speed before it is
+        // queued, so pacing against the buffer paces the emulator at that speed too.
         let mut repaint_needed = false;
-        while output_buffer_len < 512 {
-            self.dispatch_event(events::Event::NesRunScanline);
+        while output_buffer_len < self.audio_buffer_target {
+            let samples_i16 = match self.run_scanline() {
+                Some(samples) => samples,
+                // A breakpoint paused us partway through
+                None => {break;}
+            };
             if self.runtime_state.nes.ppu.current_scanline == 242 {
-                // we just finished a game frame, so have the game window repaint itself
-                self.dispatch_event(events::Event::RequestFrame);
                 repaint_needed = true;
             }
-            let samples_i16 = self.runtime_state.nes.apu.consume_samples();
-            let samples_float: Vec<f32> = samples_i16.into_iter().map(|x| <i16 as Into<f32>>::into(x) / 32767.0).collect();
+            let samples_float = self.stretch_samples(samples_i16, speed);
             // Apply those samples to the audio buffer AND recheck our count
             // (keep going until we rise above the threshold)
             let mut audio_output_buffer = AUDIO_OUTPUT_BUFFER.lock().expect("wat");
@@ -171,21 +612,28 @@ impl Worker {
         }
 
         if repaint_needed {
-            let repaint_event = app::ShellEvent::ImageRendered(
-                "game_window".to_string(),
-                Arc::new(RenderedImage{
-                    width: self.game_window.canvas.width as usize,
-                    height: self.game_window.canvas.height as usize,
-                    scale: if self.game_window.ntsc_filter == true {1} else {self.game_window.scale as usize},
-                    rgba_buffer: Vec::from(self.game_window.canvas.buffer.clone())
-                })
-            );
-            let _ = self.shell_tx.send(repaint_event);
+            self.after_repaint();
         }
     }
 }
 
-pub fn setup_audio_stream() -> Box<dyn StreamTrait> {
+fn settings_path() -> OsString {
+    // Same location the SDL shell uses, so both frontends share one settings file
+    match dirs::config_dir() {
+        Some(mut path) => {
+            path.push("rustico");
+            match fs::create_dir_all(&path) {
+                Ok(_) => {},
+                Err(e) => {log::error!("ERROR: {}\nFailed to create settings dir {}, settings will likely fail to save!", e, path.display())}
+            };
+            path.push("settings.toml");
+            return path.into_os_string();
+        },
+        None => {return "rustico_settings.toml".into()}
+    }
+}
+
+pub fn setup_audio_stream() -> (Box<dyn StreamTrait>, u32) {
     // Setup the audio callback, which will ultimately be in charge of trying to step emulation
     let host = cpal::default_host();
     let device = host.default_output_device().expect("no output device available");
@@ -193,12 +641,13 @@ pub fn setup_audio_stream() -> Box<dyn StreamTrait> {
     // TODO: eventually we want to present the supported configs to the end user, and let
     // them pick
     let default_output_config = device.default_output_config().unwrap();
-    println!("default config would be: {:?}", default_output_config);
+    log::debug!("default config would be: {:?}", default_output_config);
 
     let mut stream_config: cpal::StreamConfig = default_output_config.into();
     stream_config.buffer_size = cpal::BufferSize::Fixed(256);
     stream_config.channels = 1;
-    println!("stream config will be: {:?}", stream_config);
+    log::debug!("stream config will be: {:?}", stream_config);
+    let sample_rate = stream_config.sample_rate.0;
 
     let stream = device.build_output_stream(
         &stream_config.into(),
@@ -213,17 +662,18 @@ pub fn setup_audio_stream() -> Box<dyn StreamTrait> {
                 for sample in data.iter_mut() {
                     *sample = cpal::Sample::EQUILIBRIUM;
                 }
+                AUDIO_UNDERRUNS.fetch_add(1, Ordering::Relaxed);
             }
         },
         move |err| {
-            println!("Audio error occurred: {}", err)
+            log::error!("Audio error occurred: {}", err)
         },
         None // None=blocking, Some(Duration)=timeout
     ).unwrap();
 
     stream.play().unwrap();
 
-    return Box::new(stream);
+    return (Box::new(stream), sample_rate);
 }
 
 pub fn worker_main(runtime_rx: Receiver<events::Event>, shell_tx: Sender<app::ShellEvent>) {
@@ -240,5 +690,5 @@ pub fn worker_main(runtime_rx: Receiver<events::Event>, shell_tx: Sender<app::Sh
     // one more time, just in case things arrive out of order
     thread::sleep(Duration::from_millis(1));
     worker.process_incoming_events();
-    println!("WORKER: finished! proceeding to exit.")
+    log::info!("WORKER: finished! proceeding to exit.")
 }
\ No newline at end of file
@@ -2,6 +2,7 @@
 #![windows_subsystem = "windows"]
 
 extern crate dirs;
+extern crate env_logger;
 extern crate image;
 extern crate nfd2;
 extern crate sdl2;
@@ -59,7 +60,35 @@ pub fn dispatch_event(windows: &mut Vec<PlatformWindow>, runtime_state: &mut Rus
   return responses;
 }
 
+// Player 1 sits on the arrow keys, player 2 on the IJKL cluster with the same keys as the
+// [input.p2] defaults in ui-common's settings
+pub fn controller_binding(key: Keycode) -> Option<(usize, StandardControllerButton)> {
+  return match key {
+    Keycode::X =>      Some((0, StandardControllerButton::A)),
+    Keycode::Z =>      Some((0, StandardControllerButton::B)),
+    Keycode::RShift => Some((0, StandardControllerButton::Select)),
+    Keycode::Return => Some((0, StandardControllerButton::Start)),
+    Keycode::Up =>     Some((0, StandardControllerButton::DPadUp)),
+    Keycode::Down =>   Some((0, StandardControllerButton::DPadDown)),
+    Keycode::Left =>   Some((0, StandardControllerButton::DPadLeft)),
+    Keycode::Right =>  Some((0, StandardControllerButton::DPadRight)),
+
+    Keycode::Period => Some((1, StandardControllerButton::A)),
+    Keycode::Comma =>  Some((1, StandardControllerButton::B)),
+    Keycode::Minus =>  Some((1, StandardControllerButton::Select)),
+    Keycode::Equals => Some((1, StandardControllerButton::Start)),
+    Keycode::I =>      Some((1, StandardControllerButton::DPadUp)),
+    Keycode::K =>      Some((1, StandardControllerButton::DPadDown)),
+    Keycode::J =>      Some((1, StandardControllerButton::DPadLeft)),
+    Keycode::L =>      Some((1, StandardControllerButton::DPadRight)),
+    _ => None
+  };
+}
+
 pub fn main() {
+  // RUST_LOG overrides this, same as the egui shell
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
   let version = env!("CARGO_PKG_VERSION");
   println!("Welcome to Rustico {}", version);
 
@@ -123,6 +152,8 @@ pub fn main() {
   let device = audio_subsystem.open_queue::<i16, _>(None, &desired_spec).unwrap();
   device.clear();
   device.resume();
+  // SDL may not grant the rate we asked for
+  runtime_state.nes.apu.set_output_sample_rate(device.spec().freq as u32);
 
   let mut ctrl_mod = false;
   let mut dump_audio = false;
@@ -197,16 +228,9 @@ pub fn main() {
                     ctrl_mod = true;
                   }
 
-                  match key {
-                    Keycode::X =>      {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::A))},
-                    Keycode::Z =>      {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::B))},
-                    Keycode::RShift => {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::Select))},
-                    Keycode::Return => {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::Start))},
-                    Keycode::Up =>     {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadUp))},
-                    Keycode::Down =>   {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadDown))},
-                    Keycode::Left =>   {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadLeft))},
-                    Keycode::Right =>  {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadRight))},
-                    _ => {}
+                  match controller_binding(key) {
+                    Some((player, button)) => {application_events.push(events::Event::StandardControllerPress(player, button))},
+                    None => {}
                   }
                 },
                 Event::KeyUp { keycode: Some(key), .. } => {
@@ -239,6 +263,7 @@ pub fn main() {
                       Keycode::Kp6 => {application_events.push(events::Event::ChangeDisk(2, 1));},
                       Keycode::Kp7 => {application_events.push(events::Event::ChangeDisk(3, 0));},
                       Keycode::Kp8 => {application_events.push(events::Event::ChangeDisk(3, 1));},
+                      Keycode::Kp0 => {application_events.push(events::Event::FdsEjectDisk);},
                       _ => ()
                     }
                   } else {
@@ -278,19 +303,15 @@ pub fn main() {
                       Keycode::V => {application_events.push(events::Event::NesRunFrame);},
 
 
-                      Keycode::X =>      {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::A))},
-                      Keycode::Z =>      {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::B))},
-                      Keycode::RShift => {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::Select))},
-                      Keycode::Return => {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::Start))},
-                      Keycode::Up =>     {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::DPadUp))},
-                      Keycode::Down =>   {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::DPadDown))},
-                      Keycode::Left =>   {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::DPadLeft))},
-                      Keycode::Right =>  {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::DPadRight))},
-
                       Keycode::Equals | Keycode::KpPlus | Keycode::Plus => {application_events.push(events::Event::GameIncreaseScale);},
                       Keycode::KpMinus | Keycode::Minus => {application_events.push(events::Event::GameDecreaseScale);},
                       Keycode::KpMultiply => {application_events.push(events::Event::ToggleBooleanSetting("video.simulate_overscan".to_string()));},
-                      _ => ()
+                      _ => {
+                        match controller_binding(key) {
+                          Some((player, button)) => {application_events.push(events::Event::StandardControllerRelease(player, button))},
+                          None => ()
+                        }
+                      }
                     }
                   }
                 },
@@ -408,3 +429,51 @@ pub fn main() {
   runtime_state.settings.save(&config_path);
 }
 
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Runs a key press through the binding and into the console, without opening any windows
+  fn press(runtime_state: &mut RusticoRuntimeState, key: Keycode) {
+    let (player, button) = controller_binding(key).expect("key should be bound");
+    runtime_state.handle_event(events::Event::StandardControllerPress(player, button));
+  }
+
+  fn release(runtime_state: &mut RusticoRuntimeState, key: Keycode) {
+    let (player, button) = controller_binding(key).expect("key should be bound");
+    runtime_state.handle_event(events::Event::StandardControllerRelease(player, button));
+  }
+
+  #[test]
+  fn player_two_keys_drive_the_second_controller() {
+    let mut runtime_state = RusticoRuntimeState::new();
+    let keys = [Keycode::O, Keycode::U, Keycode::Y, Keycode::Backspace, Keycode::I, Keycode::J];
+    for key in keys.iter() {
+      press(&mut runtime_state, *key);
+    }
+    // A, B, Select, Start, Up, Left
+    assert_eq!(runtime_state.nes.p2_input, 0b0101_1111);
+    assert_eq!(runtime_state.nes.p1_input, 0);
+
+    for key in keys.iter() {
+      release(&mut runtime_state, *key);
+    }
+    assert_eq!(runtime_state.nes.p2_input, 0);
+  }
+
+  #[test]
+  fn player_one_keys_leave_the_second_controller_alone() {
+    let mut runtime_state = RusticoRuntimeState::new();
+    press(&mut runtime_state, Keycode::X);
+    press(&mut runtime_state, Keycode::Right);
+    assert_eq!(runtime_state.nes.p1_input, 0b1000_0001);
+    assert_eq!(runtime_state.nes.p2_input, 0);
+  }
+
+  #[test]
+  fn unbound_keys_have_no_controller() {
+    assert!(controller_binding(Keycode::Q).is_none());
+    assert!(controller_binding(Keycode::F1).is_none());
+  }
+}
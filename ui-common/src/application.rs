@@ -1,3 +1,4 @@
+use std::fs;
 use std::sync::Arc;
 
 use events::Event;
@@ -5,10 +6,14 @@ use events::StandardControllerButton;
 
 use settings::SettingsState;
 
+use rustico_core::memory_search::MemorySearch;
 use rustico_core::nes::NesState;
-use rustico_core::cartridge::mapper_from_file;
+use rustico_core::zapper::ZapperState;
+use rustico_core::cartridge::{mapper_from_file, region_from_file, rom_hash};
+use rustico_core::palettes::{load_pal, Palette};
 
 use rustico_core::apu::AudioChannelState;
+use rustico_core::apu::ChannelId;
 
 
 
@@ -21,6 +26,7 @@ pub struct RuntimeState {
     pub last_apu_quarter_frame_count: u32,
     pub last_apu_half_frame_count: u32,
     pub settings: SettingsState,
+    pub memory_search: Option<MemorySearch>,
 }
 
 impl RuntimeState {
@@ -35,6 +41,7 @@ impl RuntimeState {
             last_apu_quarter_frame_count: 0,
             last_apu_half_frame_count: 0,
             settings: SettingsState::new(),
+            memory_search: None,
         };
         state.nes.power_on();
         return state;
@@ -45,14 +52,26 @@ impl RuntimeState {
         let maybe_mapper = mapper_from_file(file_data);
         match maybe_mapper {
             Ok(mapper) => {
-
+                // The new console still plays through the same audio device. Everything else the
+                // user configured (palette, sprite limit, buffer size and so on) is a setting, and
+                // comes back through the apply events below.
+                let sample_rate = self.nes.apu.sample_rate;
+                let filter_type = self.nes.apu.filter_type;
+                let filter_hq = self.nes.apu.filter_hq;
                 self.nes = NesState::new(mapper);
+                self.nes.set_region(region_from_file(file_data));
+                self.nes.apu.set_filter(filter_type, filter_hq);
+                self.nes.apu.set_output_sample_rate(sample_rate as u32);
                 self.file_loaded = true;
+                self.memory_search = None;
+                // Reapply everything, so the last game's overrides don't linger into this one
+                self.settings.set_active_game(Some(rom_hash(file_data).sha1_hex()));
+                responses.extend(self.settings.apply_settings());
                 responses.push(Event::CartridgeLoaded(cart_id));
                 if self.nes.mapper.needs_bios() {
                     responses.push(Event::RequestBios);
                     self.running = false;
-                    println!("FDS game needs bios, pausing emulation to request it from the shell...");
+                    info!("FDS game needs bios, pausing emulation to request it from the shell...");
                 } else {
                     self.nes.power_on();
                     self.running = true;
@@ -67,13 +86,29 @@ impl RuntimeState {
     }
 
     pub fn load_sram(&mut self, file_data: &[u8]) {
-        if self.nes.mapper.has_sram() {
+        if self.nes.mapper.has_persistent_ram() {
             if file_data.len() > 0 {
                 self.nes.set_sram(file_data.to_vec());
             }
         }
     }
 
+    pub fn load_palette(&mut self, path: &str) {
+        if path.is_empty() {
+            self.nes.palette = Palette::ntsc();
+            return;
+        }
+        match fs::read(path) {
+            Ok(data) => {
+                match load_pal(&data) {
+                    Ok(palette) => {self.nes.palette = palette;},
+                    Err(why) => {warn!("Couldn't load palette {}: {}", path, why);}
+                }
+            },
+            Err(why) => {warn!("Couldn't open palette {}: {}", path, why);}
+        }
+    }
+
     pub fn load_bios(&mut self, file_data: &[u8]) {
         self.nes.mapper.load_bios(file_data.to_vec());
         // Set ourselves to running (but only if that succeeded)
@@ -89,7 +124,7 @@ impl RuntimeState {
             &mut self.nes.p2_input
         ];
 
-        if player_index > controllers.len() {
+        if player_index >= controllers.len() {
             return;
         }
 
@@ -106,7 +141,7 @@ impl RuntimeState {
             &mut self.nes.p2_input
         ];
 
-        if player_index > controllers.len() {
+        if player_index >= controllers.len() {
             return;
         }
 
@@ -117,6 +152,14 @@ impl RuntimeState {
         *controllers[player_index] = new_controller_byte;
     }
 
+    pub fn zapper(&mut self, port_index: usize) -> Option<&mut ZapperState> {
+        return match port_index {
+            0 => Some(&mut self.nes.p1_zapper),
+            1 => Some(&mut self.nes.p2_zapper),
+            _ => None
+        };
+    }
+
     pub fn collect_timing_events(&mut self) -> Vec<Event> {
         let mut responses: Vec<Event> = Vec::new();
         if self.nes.ppu.current_frame != self.last_frame {
@@ -135,6 +178,13 @@ impl RuntimeState {
             responses.push(Event::NesNewApuHalfFrame);
             self.last_apu_half_frame_count = self.nes.apu.half_frame_counter
         }
+        match self.nes.breakpoints.take_hit() {
+            Some((address, kind)) => {
+                self.running = false;
+                responses.push(Event::BreakpointHit(address, kind));
+            },
+            None => {}
+        }
         return responses;
     }
 
@@ -145,9 +195,27 @@ impl RuntimeState {
             Event::ApplyBooleanSetting(path, value) => {
                 match path.as_str() {
                     "audio.multiplexing" => {self.nes.mapper.audio_multiplexing(value)},
+                    "video.disable_sprite_limit" => {self.nes.ppu.disable_sprite_limit = value},
+                    "video.palette_backdrop_quirk" => {self.nes.ppu.palette_backdrop_quirk = value},
+                    _ => {}
+                }
+            },
+            Event::ApplyIntegerSetting(path, value) => {
+                match path.as_str() {
+                    "audio.buffer_ms" => {self.nes.apu.set_buffer_latency(value as u32)},
+                    _ => {}
+                }
+            },
+            Event::ApplyStringSetting(path, value) => {
+                match path.as_str() {
+                    "video.palette" => {self.load_palette(&value)},
                     _ => {}
                 }
             },
+            Event::LoadPalette(path) => {
+                // Storing the setting applies it, which does the actual loading
+                responses.push(Event::StoreStringSetting("video.palette".to_string(), path));
+            },
             Event::MuteChannel(chip_name, channel_name) => {
                 let mut channels: Vec<&mut dyn AudioChannelState> = Vec::new();
                 channels.extend(self.nes.apu.channels_mut());
@@ -168,18 +236,77 @@ impl RuntimeState {
                     }
                 }
             },
+            Event::MemorySearchStart(include_prg_ram) => {
+                let search = MemorySearch::new(&self.nes, include_prg_ram);
+                responses.push(Event::MemorySearchResults(Arc::new(search.candidates().to_vec())));
+                self.memory_search = Some(search);
+            },
+            Event::MemorySearchFilter(filter) => {
+                match self.memory_search {
+                    Some(ref mut search) => {
+                        let candidates = search.filter(&self.nes, filter).to_vec();
+                        responses.push(Event::MemorySearchResults(Arc::new(candidates)));
+                    },
+                    None => warn!("No memory search in progress, start one first")
+                }
+            },
+            Event::AddBreakpoint(kind, start, end) => {
+                self.nes.breakpoints.add(kind, start, end);
+            },
+            Event::RemoveBreakpoint(kind, start, end) => {
+                self.nes.breakpoints.remove(kind, start, end);
+            },
+            Event::ClearBreakpoints => {
+                self.nes.breakpoints.clear();
+            },
+            Event::AddCheat(code) => {
+                match self.nes.add_cheat(&code) {
+                    Ok(_) => {info!("Added cheat: {}", code);},
+                    Err(why) => {warn!("Couldn't add cheat {}: {}", code, why);}
+                }
+            },
+            Event::RemoveCheat(code) => {
+                match self.nes.remove_cheat(&code) {
+                    Ok(_) => {info!("Removed cheat: {}", code);},
+                    Err(why) => {warn!("Couldn't remove cheat {}: {}", code, why);}
+                }
+            },
+            Event::SetChannelMuted(chip_name, channel_name, muted) => {
+                match self.nes.apu.channel_id(&*self.nes.mapper, &chip_name, &channel_name) {
+                    Some(channel) => self.nes.apu.set_channel_enabled(&mut *self.nes.mapper, channel, !muted),
+                    None => warn!("No {} channel named {}", chip_name, channel_name)
+                }
+            },
+            Event::SetChannelVolume(channel_name, gain) => {
+                let channel = match channel_name.as_str() {
+                    "Pulse 1" => Some(ChannelId::Pulse1),
+                    "Pulse 2" => Some(ChannelId::Pulse2),
+                    "Triangle" => Some(ChannelId::Triangle),
+                    "Noise" => Some(ChannelId::Noise),
+                    "DMC" => Some(ChannelId::Dmc),
+                    _ => None
+                };
+                match channel {
+                    Some(channel) => self.nes.apu.set_channel_volume(channel, gain),
+                    None => warn!("No 2A03 channel named {}", channel_name)
+                }
+            },
             
             Event::ChangeDisk(disk_num, side_num) => {
                 let internal_side_num = disk_num * 2 + side_num;
                 self.nes.mapper.switch_disk(internal_side_num);
             },
+            Event::FdsEjectDisk => {
+                self.nes.mapper.fds_eject_disk();
+            },
+            Event::FdsInsertDisk(side) => {
+                self.nes.mapper.fds_insert_disk(side);
+            },
 
             Event::LoadCartridge(cart_id, file_data, sram_data) => {
+                // load_cartridge reapplies every setting to the fresh NesState
                 responses.extend(self.load_cartridge(cart_id, &file_data));
                 self.load_sram(&sram_data);
-                // Loading a new cartridge replaces the mapper and resets NesState, so we should
-                // reload all settings to make sure any emulation-specific things get re-appled.
-                responses.extend(self.settings.apply_settings());
             },
             Event::LoadBios(bios_data) => {
                 self.load_bios(&bios_data);
@@ -195,11 +322,36 @@ impl RuntimeState {
                 self.nes.run_until_vblank();
                 responses.extend(self.collect_timing_events());
             },
+            Event::StepFrame => {
+                self.running = false;
+                self.nes.run_until_vblank();
+                responses.extend(self.collect_timing_events());
+            },
             Event::NesRenderNTSC(width) => {
                 self.nes.ppu.render_ntsc(width);
             },
             Event::NesRunOpcode => {
                 self.nes.step();
+                responses.extend(self.collect_timing_events());
+            },
+            Event::RequestCpuRegisters => {
+                responses.push(Event::CpuRegisters(self.nes.cpu_registers()));
+            },
+            Event::SetCpuRegisters(registers) => {
+                self.nes.set_cpu_registers(registers);
+                responses.push(Event::CpuRegisters(self.nes.cpu_registers()));
+            },
+            Event::SetCpuFlag(flag, value) => {
+                self.nes.set_cpu_flag(flag, value);
+                responses.push(Event::CpuRegisters(self.nes.cpu_registers()));
+            },
+            Event::StepOver => {
+                self.nes.step_over();
+                responses.extend(self.collect_timing_events());
+            },
+            Event::StepOut => {
+                self.nes.step_out();
+                responses.extend(self.collect_timing_events());
             },
             Event::NesRunScanline => {
                 self.nes.run_until_hblank();
@@ -208,6 +360,9 @@ impl RuntimeState {
             Event::NesReset => {
                 self.nes.reset();
             },
+            Event::NesPowerCycle => {
+                self.nes.power_cycle();
+            },
             
             // These three events should ideally move to some sort of FrameTiming manager
             Event::NesPauseEmulation => {
@@ -220,15 +375,37 @@ impl RuntimeState {
                 self.running = !self.running;
             },
 
+            Event::NsfSelectTrack(track_index) => {
+                self.nes.mapper.nsf_set_track(track_index);
+            },
+            Event::NsfNextTrack => {
+                let next_track = self.nes.mapper.nsf_current_track().saturating_add(1);
+                self.nes.mapper.nsf_set_track(next_track);
+            },
+            Event::NsfPrevTrack => {
+                let previous_track = self.nes.mapper.nsf_current_track().saturating_sub(1);
+                self.nes.mapper.nsf_set_track(previous_track);
+            },
+            Event::NsfTogglePause => {
+                self.running = !self.running;
+            },
+
             Event::NesNudgeAlignment => {
                 self.nes.nudge_ppu_alignment();
             }
 
             Event::RequestSramSave(sram_id) => {
-                if self.nes.mapper.has_sram()  {
+                if self.nes.mapper.has_persistent_ram() {
                     responses.push(Event::SaveSram(sram_id, Arc::new(self.nes.sram())));
                 }
             },
+            // Only produces a file once the game has actually written to one of its disks
+            Event::RequestFdsSave(fds_id) => {
+                match self.nes.mapper.fds_write_back() {
+                    Some(fds_data) => {responses.push(Event::SaveFdsDisk(fds_id, Arc::new(fds_data)));},
+                    None => {}
+                }
+            },
 
             // Input is due for an overhaul. Ideally the IoBus should handle its own
             // events, rather than doing this here.
@@ -238,6 +415,19 @@ impl RuntimeState {
             Event::StandardControllerRelease(controller_index, button) => {
                 self.button_release(controller_index, button);
             },
+            // Any zapper event plugs a zapper into that port, replacing the standard controller
+            Event::ZapperAim(port_index, x, y) => {
+                if let Some(zapper) = self.zapper(port_index) {
+                    zapper.connected = true;
+                    zapper.aim(x, y);
+                }
+            },
+            Event::ZapperTrigger(port_index, pressed) => {
+                if let Some(zapper) = self.zapper(port_index) {
+                    zapper.connected = true;
+                    zapper.set_trigger(pressed);
+                }
+            },
             _ => {}
         }
         return responses;
@@ -255,4 +445,104 @@ pub fn fix_dpad(controller_byte: u8, last_button_pressed: StandardControllerButt
     }
 
     return fixed_byte;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustico_core::memory::debug_read_byte;
+    use rustico_core::palettes::FULL_PAL_SIZE;
+    use toml::Value;
+
+    const NO_CART: &[u8] = include_bytes!("assets/rustico_no_cart.nes");
+
+    // Runs events and everything they respond with, the way the shells' event loops do
+    fn dispatch(runtime_state: &mut RuntimeState, events: Vec<Event>) {
+        for event in events {
+            let responses = runtime_state.handle_event(event);
+            dispatch(runtime_state, responses);
+        }
+    }
+
+    fn load(runtime_state: &mut RuntimeState) {
+        let responses = runtime_state.load_cartridge("test".to_string(), NO_CART);
+        dispatch(runtime_state, responses);
+    }
+
+    #[test]
+    fn loading_a_cartridge_keeps_the_current_palette() {
+        let mut runtime_state = RuntimeState::new();
+        let custom = vec![0x42u8; FULL_PAL_SIZE];
+        let path = std::env::temp_dir().join("rustico_application_test.pal");
+        fs::write(&path, &custom).unwrap();
+        dispatch(&mut runtime_state, vec![Event::LoadPalette(path.to_string_lossy().into_owned())]);
+        assert_eq!(runtime_state.nes.palette.colors, custom);
+
+        load(&mut runtime_state);
+        let _ = fs::remove_file(&path);
+        assert_eq!(runtime_state.nes.palette.colors, custom);
+    }
+
+    #[test]
+    fn a_per_game_override_wins_over_the_global_value() {
+        let mut runtime_state = RuntimeState::new();
+        runtime_state.settings.set_active_game(Some(rom_hash(NO_CART).sha1_hex()));
+        runtime_state.settings.set_game_override("video.disable_sprite_limit".to_string(), Value::from(true));
+        runtime_state.settings.set_active_game(None);
+        assert_eq!(runtime_state.settings.get_boolean("video.disable_sprite_limit".to_string()), Some(false));
+
+        load(&mut runtime_state);
+        assert!(runtime_state.nes.ppu.disable_sprite_limit);
+        // Settings without an override still come from the global table
+        assert!(runtime_state.nes.ppu.palette_backdrop_quirk);
+    }
+
+    // Bits 3 (no light) and 4 (trigger) of the port a zapper is plugged into
+    fn zapper_bits(runtime_state: &RuntimeState, port_index: usize) -> u8 {
+        return debug_read_byte(&runtime_state.nes, 0x4016 + port_index as u16) & 0b0001_1000;
+    }
+
+    #[test]
+    fn zapper_events_drive_the_port_bits() {
+        let mut runtime_state = RuntimeState::new();
+        // White on the left half, black on the right, with the beam just past the aim points
+        for y in 0 .. 240 {
+            for x in 0 .. 256 {
+                runtime_state.nes.ppu.screen[y * 256 + x] = if x < 128 {0x30} else {0x0F};
+            }
+        }
+        runtime_state.nes.ppu.current_scanline = 100;
+        runtime_state.nes.ppu.current_scanline_cycle = 0;
+
+        for port_index in 0 .. 2 {
+            dispatch(&mut runtime_state, vec![Event::ZapperAim(port_index, 64, 90)]);
+            assert_eq!(zapper_bits(&runtime_state, port_index), 0b0000_0000);
+            dispatch(&mut runtime_state, vec![Event::ZapperAim(port_index, 192, 90)]);
+            assert_eq!(zapper_bits(&runtime_state, port_index), 0b0000_1000);
+
+            // The sensor sees nothing while the trigger window is open, even over white
+            dispatch(&mut runtime_state, vec![Event::ZapperAim(port_index, 64, 90), Event::ZapperTrigger(port_index, true)]);
+            assert_eq!(zapper_bits(&runtime_state, port_index), 0b0001_1000);
+            for _ in 0 .. 3 {
+                runtime_state.zapper(port_index).unwrap().new_frame();
+            }
+            assert_eq!(zapper_bits(&runtime_state, port_index), 0b0001_0000);
+            dispatch(&mut runtime_state, vec![Event::ZapperTrigger(port_index, false)]);
+            assert_eq!(zapper_bits(&runtime_state, port_index), 0b0000_0000);
+        }
+    }
+
+    #[test]
+    fn step_frame_advances_exactly_one_frame_and_pauses() {
+        let mut runtime_state = RuntimeState::new();
+        // The first step only gets from power on to the first vblank
+        runtime_state.handle_event(Event::StepFrame);
+        let first_frame = runtime_state.nes.ppu.current_frame;
+        assert!(!runtime_state.running);
+
+        runtime_state.handle_event(Event::StepFrame);
+        assert_eq!(runtime_state.nes.ppu.current_frame, first_frame + 1);
+        runtime_state.handle_event(Event::StepFrame);
+        assert_eq!(runtime_state.nes.ppu.current_frame, first_frame + 2);
+    }
+}
@@ -7,7 +7,6 @@ use events::Event;
 use panel::Panel;
 
 use rustico_core::nes::NesState;
-use rustico_core::palettes::NTSC_PAL;
 use rustico_core::tracked_events::TrackedEvent;
 use rustico_core::tracked_events::EventType;
 
@@ -20,7 +19,7 @@ pub struct EventWindow {
     pub my: i32,
 }
 
-fn cpu_register_label(address: u16) -> String {
+pub fn cpu_register_label(address: u16) -> String {
     let label = match address {
         0x2000 => "PPUCTRL",
         0x2001 => "PPUMASK",
@@ -38,7 +37,7 @@ fn cpu_register_label(address: u16) -> String {
     return label.to_string();
 }
 
-fn cpu_register_color(address: u16) -> Color {
+pub fn cpu_register_color(address: u16) -> Color {
     match address {
         0x2000 => Color::rgb(255, 94, 94),
         0x2001 => Color::rgb(142, 51, 255),
@@ -94,6 +93,10 @@ impl EventWindow {
         // Only draw the tooltip if our mouse coordinates are favorable!
         let x = event.cycle as u32;
         let y = event.scanline as u32;
+        if y > 261 {
+            // The extra vblank lines of a PAL or Dendy frame don't fit the NTSC-sized canvas
+            return;
+        }
 
         return x == (self.mx as u32) && y == (self.my as u32)
     }
@@ -117,6 +120,9 @@ impl EventWindow {
                 let label = cpu_register_label(program_counter);
                 format!("Execute: {}", label)
             },
+            EventType::Nmi{program_counter: _} => {format!("NMI")},
+            EventType::Irq{program_counter: _} => {format!("IRQ")},
+            EventType::SpriteZeroHit => {format!("Sprite Zero Hit")},
             _ => {format!("Huh!?")}
         };
 
@@ -142,6 +148,12 @@ impl EventWindow {
                     format!("Data:     ${:02X} ({})", data, data)
                 ]
             },
+            EventType::Nmi{program_counter} | EventType::Irq{program_counter} => {
+                vec![
+                    format!("Return:   ${:04X}", program_counter),
+                ]
+            },
+            EventType::SpriteZeroHit => {vec![]},
             _ => {vec![format!("I don't recognize this junk!")]}
         };
 
@@ -192,6 +204,10 @@ impl EventWindow {
 
         let x = event.cycle as u32;
         let y = event.scanline as u32;
+        if y > 261 {
+            // The extra vblank lines of a PAL or Dendy frame don't fit the NTSC-sized canvas
+            return;
+        }
         // Make the outline be very fancy and gracefully handle canvas edges
         // (todo later: make this part of rect?)
         let mut rx = x;
@@ -230,6 +246,15 @@ impl EventWindow {
             EventType::CpuExecute{program_counter, data: _} => {
                 self.draw_event_dot(event, cpu_register_color(program_counter));
             },
+            EventType::Nmi{program_counter: _} => {
+                self.draw_event_dot(event, Color::rgb(0xFF, 0x40, 0x40));
+            },
+            EventType::Irq{program_counter: _} => {
+                self.draw_event_dot(event, Color::rgb(0xFF, 0xA0, 0x20));
+            },
+            EventType::SpriteZeroHit => {
+                self.draw_event_dot(event, Color::rgb(0xFF, 0xFF, 0xFF));
+            },
             _ => {}
         }
     }
@@ -243,12 +268,8 @@ impl EventWindow {
             for y in 0 .. 262 {
                 let pixel_freshness = freshness(y as u16, x as u16, nes.ppu.current_scanline, nes.ppu.current_scanline_cycle);
                 if x  > 0 && x <= 256 && y < 240 {
-                    let palette_index = ((nes.ppu.screen[(y * 256 + x - 1) as usize]) as usize) * 3;
-                    let color = Color::rgba(
-                            NTSC_PAL[palette_index + 0],
-                            NTSC_PAL[palette_index + 1],
-                            NTSC_PAL[palette_index + 2],
-                            192);
+                    let rgb = nes.palette.rgb(nes.ppu.screen[(y * 256 + x - 1) as usize]);
+                    let color = Color::rgba(rgb[0], rgb[1], rgb[2], 192);
                     let scanline_freshness = (pixel_freshness.powf(32.0) * 255.0) as u8;
                     //let freshness8 = (scanline_freshness + cycle_freshness).min(255.0) as u8;
                     self.canvas.put_pixel(x, y, Color::rgb(scanline_freshness, scanline_freshness, scanline_freshness));
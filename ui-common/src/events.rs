@@ -1,5 +1,10 @@
 use std::sync::Arc;
 
+use rustico_core::breakpoints::BreakpointKind;
+use rustico_core::cartridge::CartridgeError;
+use rustico_core::cycle_cpu::{CpuFlag, CpuRegisters};
+use rustico_core::memory_search::SearchFilter;
+
 #[derive(Clone, Debug)]
 pub enum StandardControllerButton {
     A,
@@ -14,15 +19,25 @@ pub enum StandardControllerButton {
 
 #[derive(Clone, Debug)]
 pub enum Event {
+    AddBreakpoint(BreakpointKind, u16, u16),
+    AddCheat(String),
     ApplyBooleanSetting(String, bool),
     ApplyFloatSetting(String, f64),
     ApplyIntegerSetting(String, i64),
     ApplyStringSetting(String, String),
+    BreakpointHit(u16, BreakpointKind),
+    ClearBreakpoints,
+    // Response to RequestCpuRegisters
+    CpuRegisters(CpuRegisters),
     CloseApplication,
     CloseWindow,
     CartridgeLoaded(String),
-    CartridgeRejected(String, String),
+    CartridgeRejected(String, CartridgeError),
     ChangeDisk(usize, usize),
+    // Both pattern tables side by side as a 256x128 PNG, using background palette 0
+    ExportChrSheet(String),
+    FdsEjectDisk,
+    FdsInsertDisk(usize),
     GameToggleOverscan,
     GameIncreaseScale,
     GameDecreaseScale,
@@ -30,12 +45,17 @@ pub enum Event {
     LoadSram(Arc<Vec<u8>>),
     LoadBios(Arc<Vec<u8>>),
     LoadFailed(String),
+    LoadPalette(String),
     MouseMove(i32, i32),
     MouseClick(i32, i32),
     MouseRelease,
     MemoryViewerNextPage,
     MemoryViewerPreviousPage,
     MemoryViewerNextBus,
+    // Begin a RAM search (optionally including PRG RAM), narrow it, and the surviving addresses
+    MemorySearchStart(bool),
+    MemorySearchFilter(SearchFilter),
+    MemorySearchResults(Arc<Vec<u16>>),
     MuteChannel(String, String),
     UnmuteChannel(String, String),
     NesNudgeAlignment,
@@ -47,16 +67,45 @@ pub enum Event {
     NesRenderNTSC(usize),
     NesResumeEmulation,
     NesReset,
+    NesPowerCycle,
     NesRunCycle,
     NesRunFrame,
     NesRunOpcode,
     NesRunScanline,
     NesToggleEmulation,
+    NsfNextTrack,
+    NsfPrevTrack,
+    NsfSelectTrack(u8),
+    NsfTogglePause,
+    PlayMovie(String),
+    ReadMemoryRange(u16, usize),
     RequestFrame,
+    RequestFrameEvents,
+    RequestPpuImages(usize),
     RequestCartridgeDialog,
+    RequestCpuRegisters,
+    RemoveBreakpoint(BreakpointKind, u16, u16),
+    RemoveCheat(String),
     RequestSramSave(String),
+    RequestFdsSave(String),
     RequestBios,
+    SaveFdsDisk(String, Arc<Vec<u8>>),
+    SaveScreenshot(String),
     SaveSram(String, Arc<Vec<u8>>),
+    SetChannelMuted(String, String, bool),
+    // Best sent while paused; changing PC sets the next instruction to run
+    SetCpuRegisters(CpuRegisters),
+    SetCpuFlag(CpuFlag, bool),
+    // Mix level for a 2A03 channel by name, 1.0 being the console's own balance
+    SetChannelVolume(String, f32),
+    // 1.0 is realtime, fractional speeds run in slow motion; ignored (held at realtime) while audio is being recorded
+    SetEmulationSpeed(f32),
+    // Run as fast as the host allows with audio muted; also held off while recording audio
+    SetTurbo(bool),
+    StartAudioRecording(String),
+    StartMovieRecord(String),
+    StopAudioRecording,
+    StopMovie,
     ShowApuWindow,
     ShowCpuWindow,
     ShowGameWindow,
@@ -65,6 +114,12 @@ pub enum Event {
     ShowPianoRollWindow,
     ShowPpuWindow,
     ShowTestWindow,
+    // Pauses if needed, then advances exactly one frame
+    StepFrame,
+    // Runs a JSR to completion, otherwise the same as NesRunOpcode
+    StepOver,
+    // Runs until the current subroutine returns to its caller
+    StepOut,
     StandardControllerPress(usize, StandardControllerButton),
     StandardControllerRelease(usize, StandardControllerButton),
     StoreBooleanSetting(String, bool),
@@ -73,4 +128,6 @@ pub enum Event {
     StoreStringSetting(String, String),
     ToggleBooleanSetting(String),
     Update,
+    ZapperAim(usize, i32, i32),
+    ZapperTrigger(usize, bool),
 }
@@ -9,7 +9,8 @@ use panel::Panel;
 use std::time::Instant;
 
 use rustico_core::nes::NesState;
-use rustico_core::palettes::NTSC_PAL;
+
+pub const MAX_OVERSCAN: i64 = 64;
 
 pub struct GameWindow {
     pub canvas: SimpleBuffer,
@@ -17,6 +18,11 @@ pub struct GameWindow {
     pub shown: bool,
     pub scale: u32,
     pub simulate_overscan: bool,
+    // Pixels cropped from each edge while simulate_overscan is on
+    pub overscan_top: u32,
+    pub overscan_bottom: u32,
+    pub overscan_left: u32,
+    pub overscan_right: u32,
     pub ntsc_filter: bool,
     pub display_fps: bool,
 
@@ -36,6 +42,10 @@ impl GameWindow {
             shown: true,
             scale: 2,
             simulate_overscan: false,
+            overscan_top: 8,
+            overscan_bottom: 8,
+            overscan_left: 8,
+            overscan_right: 8,
             ntsc_filter: false,
             display_fps: false,
 
@@ -57,12 +67,20 @@ impl GameWindow {
         }
     }
 
+    // top, bottom, left, right
+    fn overscan(&self) -> (u32, u32, u32, u32) {
+        if self.simulate_overscan {
+            return (self.overscan_top, self.overscan_bottom, self.overscan_left, self.overscan_right);
+        }
+        return (0, 0, 0, 0);
+    }
+
     fn draw(&mut self, nes: &NesState) {
-        let overscan: u32 = if self.simulate_overscan {8} else {0};
+        let (top, bottom, left, right) = self.overscan();
 
         // Update the game screen
-        for x in overscan .. 256 - overscan {
-            for y in overscan .. 240 - overscan {
+        for x in left .. 256 - right {
+            for y in top .. 240 - bottom {
                 if self.ntsc_filter {
                     let scale = self.scale;
                     let base_x = x * scale;
@@ -71,18 +89,15 @@ impl GameWindow {
                     for sx in 0 .. self.scale {
                         let column_color = Color::from_raw(nes.ppu.filtered_screen[(base_y + base_x + sx) as usize]);
                         for sy in 0 .. self.scale {
-                            self.canvas.put_pixel((x - overscan) * scale + sx, (y - overscan) * scale + sy, column_color);        
+                            self.canvas.put_pixel((x - left) * scale + sx, (y - top) * scale + sy, column_color);
                         }
                     }
                 } else {
-                    let palette_index = ((nes.ppu.screen[(y * 256 + x) as usize]) as usize) * 3;
+                    let rgb = nes.palette.rgb(nes.ppu.screen[(y * 256 + x) as usize]);
                     self.canvas.put_pixel(
-                        x - overscan,
-                        y - overscan,
-                        Color::rgb(
-                            NTSC_PAL[palette_index + 0],
-                            NTSC_PAL[palette_index + 1],
-                            NTSC_PAL[palette_index + 2])
+                        x - left,
+                        y - top,
+                        Color::rgb(rgb[0], rgb[1], rgb[2])
                     );
                 }
             }
@@ -109,8 +124,9 @@ impl GameWindow {
     }
 
     fn update_canvas_size(&mut self) {
-        let base_width = if self.simulate_overscan {240} else {256};
-        let base_height = if self.simulate_overscan {224} else {240};
+        let (top, bottom, left, right) = self.overscan();
+        let base_width = 256 - left - right;
+        let base_height = 240 - top - bottom;
         let scaled_width = if self.ntsc_filter {base_width * self.scale} else {base_width};
         let scaled_height = if self.ntsc_filter {base_height * self.scale} else {base_height};
         self.canvas = SimpleBuffer::new(scaled_width, scaled_height);
@@ -161,11 +177,16 @@ impl Panel for GameWindow {
             Event::ApplyIntegerSetting(path, value) => {
                 match path.as_str() {
                     "video.scale_factor" => {
-                        if value > 0 && value < 8 {
+                        if value >= 1 && value <= 8 {
                             self.scale = value as u32;
                             self.update_canvas_size();
                         }
                     },
+                    // Capped so that opposite edges can never crop away the whole picture
+                    "video.overscan.top" => {self.overscan_top = value.clamp(0, MAX_OVERSCAN) as u32; self.update_canvas_size()},
+                    "video.overscan.bottom" => {self.overscan_bottom = value.clamp(0, MAX_OVERSCAN) as u32; self.update_canvas_size()},
+                    "video.overscan.left" => {self.overscan_left = value.clamp(0, MAX_OVERSCAN) as u32; self.update_canvas_size()},
+                    "video.overscan.right" => {self.overscan_right = value.clamp(0, MAX_OVERSCAN) as u32; self.update_canvas_size()},
                     _ => {}
                 }
             },
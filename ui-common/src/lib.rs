@@ -1,5 +1,7 @@
 extern crate csscolorparser;
 extern crate image;
+#[macro_use]
+extern crate log;
 extern crate regex;
 extern crate rustico_core;
 extern crate toml;
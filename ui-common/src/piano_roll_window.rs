@@ -207,6 +207,18 @@ fn midi_frequency(midi_index: u32) -> f32 {
     return 440.0 * (2.0_f32).powf(((midi_index as f32) - 69.0) / 12.0);
 }
 
+// Names the semitone nearest to this frequency in scientific pitch notation, so 440 Hz is "A4"
+// and 261.6 Hz is "C4". Note that this is one octave lower than the names midi_index() accepts.
+fn note_name(frequency: f32) -> String {
+    if !frequency.is_finite() || frequency <= 0.0 {
+        return String::new();
+    }
+    let note_names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    let midi_note = (69.0 + 12.0 * (frequency / 440.0).log2()).round() as i32;
+    let octave = midi_note.div_euclid(12) - 1;
+    return format!("{}{}", note_names[midi_note.rem_euclid(12) as usize], octave);
+}
+
 fn midi_index(note_name: &str) -> Result<u32, String> {
      let re = Regex::new(r"([A-Ga-g])([BbSs#]?)(\d+)").unwrap();
      if re.is_match(note_name) {
@@ -436,7 +448,10 @@ pub struct PianoRollWindow {
     pub surfboard_height: u32,
     pub scroll_direction: ScrollDirection,
     pub polling_type: PollingType,
-    pub speed_multiplier: u32,
+    // Time slices added per poll. Fractional speeds carry the remainder over to the next poll,
+    // so 1.5 alternates between one and two slices rather than rounding.
+    pub scroll_speed: f32,
+    pub scroll_remainder: f32,
     pub surfboard_line_thickness: f32,
     pub surfboard_glow_thickness: f32,
     pub draw_piano_strings: bool,
@@ -446,6 +461,9 @@ pub struct PianoRollWindow {
     pub draw_text_labels: bool,
     pub divider_color: Color,
     pub divider_width: u32,
+    pub octave_divider_color: Color,
+    // How far, in keys, sample channels may stray from the waveform string at full output
+    pub waveform_trace_width: f32,
 
     // Keyed on: chip name, then channel name within that chip
     pub channel_settings: HashMap<String, HashMap<String, ChannelSettings>>,
@@ -472,7 +490,8 @@ impl PianoRollWindow {
             polling_counter: 1,
             scroll_direction: ScrollDirection::TopToBottom,
             polling_type: PollingType::ApuQuarterFrame,
-            speed_multiplier: 6,
+            scroll_speed: 6.0,
+            scroll_remainder: 0.0,
             channel_settings: default_channel_settings(),
             surfboard_line_thickness: 0.5,
             surfboard_glow_thickness: 2.5,
@@ -483,6 +502,8 @@ impl PianoRollWindow {
             draw_text_labels: true,
             divider_color: Color::rgba(0, 0, 0, 255),
             divider_width: 5,
+            octave_divider_color: Color::rgb(0x14, 0x14, 0x14),
+            waveform_trace_width: 1.0,
         };
     }
 
@@ -531,6 +552,10 @@ impl PianoRollWindow {
         while key_counter < self.keys && y > safety_margin {
             let string_color = string_colors[(key_counter % 12) as usize];
             drawing::rect(&mut self.canvas, x, y, width, 1, string_color);
+            if key_counter % 12 == 0 && key_counter > 0 {
+                // Octave divider, halfway between this C and the B below it
+                drawing::rect(&mut self.canvas, x, y + self.key_thickness / 2, width, 1, self.octave_divider_color);
+            }
             y -= self.key_thickness;
             key_counter += 1;
         }
@@ -561,6 +586,10 @@ impl PianoRollWindow {
         while key_counter < self.keys && x < safety_margin {
             let string_color = string_colors[(key_counter % 12) as usize];
             drawing::rect(&mut self.canvas, x, y, 1, height, string_color);
+            if key_counter % 12 == 0 && key_counter > 0 {
+                // Octave divider, halfway between this C and the B below it
+                drawing::rect(&mut self.canvas, x - self.key_thickness / 2, y, 1, height, self.octave_divider_color);
+            }
             x += self.key_thickness; // TODO: it's not "height" anymore, more like key_size?
             key_counter += 1;
         }
@@ -689,7 +718,7 @@ impl PianoRollWindow {
                 let mut base_color = slice.color;
                 let volume_percent = slice.thickness / 6.0;
                 base_color.set_alpha((volume_percent * 255.0) as u8);
-                draw_speaker_key_horiz(canvas, base_color, x, ((starting_y as f32) - slice.y * (key_height as f32)) as u32);
+                draw_speaker_key_horiz(canvas, base_color, x, starting_y);
             },
             _ => {
                 let key_drawing_functions = [
@@ -795,6 +824,24 @@ impl PianoRollWindow {
         return coordinate;
     }
 
+    // The most recent output of this channel, scaled to the range -1.0 to 1.0
+    fn latest_sample_level(channel: &dyn AudioChannelState) -> f32 {
+        let sample_buffer = channel.sample_buffer();
+        let buffer = sample_buffer.buffer();
+        let latest_index = (sample_buffer.index() + buffer.len() - 1) % buffer.len();
+        let sample = buffer[latest_index] as f32;
+        let half_range = (channel.min_sample() as f32).abs().max(channel.max_sample() as f32);
+        if half_range <= 0.0 {
+            return 0.0;
+        }
+        return (sample / half_range).max(-1.0).min(1.0);
+    }
+
+    // Coordinates round to the nearest key, so allow half a key of slack on either end
+    fn coordinate_in_range(&self, coordinate: f32) -> bool {
+        return coordinate >= -0.5 && coordinate < (self.keys as f32) - 0.5;
+    }
+
     pub fn channel_is_hidden(&self, channel: &dyn AudioChannelState) -> bool {
         match self.channel_settings.get(&channel.chip()) {
             Some(chip_settings) => {
@@ -877,6 +924,11 @@ impl PianoRollWindow {
         match channel.rate() {
             PlaybackRate::FundamentalFrequency{frequency} => {
                 y = self.frequency_to_coordinate(frequency);
+                if !self.coordinate_in_range(y) {
+                    // Outside of the configured note range; drawing this would spill over the
+                    // waveform strip or off the edge of the canvas entirely
+                    return ChannelSlice::none();
+                }
                 note_type = NoteType::Frequency;
             },
             PlaybackRate::LfsrRate{index, max} => {
@@ -884,14 +936,18 @@ impl PianoRollWindow {
 
 
                 // Arbitrarily map all noise frequencies to 16 "strings" since this is what the
-                // base 2A03 uses. Accuracy is much less important here.
+                // base 2A03 uses. Accuracy is much less important here. The index comes from the
+                // region's own period table, so PAL noise lands on the same strings as NTSC.
                 let string_coord = (index as f32 / (max + 1) as f32) * 16.0;
                 let key_offset = string_coord as f32;
                 y = key_offset;
 
             },
             PlaybackRate::SampleRate{frequency: _} => {
-                y = 0.0;
+                // Sample channels have no pitch to plot, so offset them from the waveform string
+                // by their current output level instead. As the slices scroll, this traces out the
+                // shape of the sample.
+                y = PianoRollWindow::latest_sample_level(channel) * self.waveform_trace_width;
                 note_type = NoteType::Waveform;
             }
         }
@@ -1135,7 +1191,11 @@ impl PianoRollWindow {
     fn update(&mut self, apu: &ApuState, mapper: &dyn Mapper) {
         let channels = self.collect_channels(&apu, &*mapper);
 
-        for _i in 0 .. self.speed_multiplier {
+        self.scroll_remainder += self.scroll_speed;
+        let slice_count = self.scroll_remainder.floor();
+        self.scroll_remainder -= slice_count;
+
+        for _i in 0 .. slice_count as u32 {
             let mut frame_notes: Vec<ChannelSlice> = Vec::new();
             for channel in &channels {
                 frame_notes.push(self.slice_from_channel(*channel));
@@ -1285,25 +1345,54 @@ impl PianoRollWindow {
         drawing::text(&mut self.canvas, &self.font, channel_x + 0, channel_y, &channel_label, transparent_color);
         drawing::text(&mut self.canvas, &self.font, channel_x + 1, channel_y, &channel_label, transparent_color);
         drawing::text(&mut self.canvas, &self.font, channel_x, channel_y, &channel_label, channel_color);
+
+        if channel.playing() {
+            match channel.rate() {
+                PlaybackRate::FundamentalFrequency{frequency} => {
+                    let note_label = note_name(frequency);
+                    let note_width_px = (note_label.len() * 8) as u32;
+                    let note_x = x + width - 8 - note_width_px;
+                    let note_y = y + 4;
+                    drawing::text(&mut self.canvas, &self.font, note_x - 1, note_y, &note_label, transparent_color);
+                    drawing::text(&mut self.canvas, &self.font, note_x + 0, note_y, &note_label, transparent_color);
+                    drawing::text(&mut self.canvas, &self.font, note_x + 1, note_y, &note_label, transparent_color);
+                    drawing::text(&mut self.canvas, &self.font, note_x, note_y, &note_label, channel_color);
+                },
+                _ => {}
+            }
+        }
     }
 
-    fn draw_audio_surfboard_horiz(&mut self, runtime: &RuntimeState, x: u32, y: u32, width: u32, height: u32) {
-        let channels = self.collect_channels(&runtime.nes.apu, &*runtime.nes.mapper);
-        let channel_width = width / (channels.len() as u32);
-        let mut leftover_pixels = width - (channel_width * (channels.len() as u32));
-        let mut cx = 0;
-        for i in 0 .. channels.len() {
+    // Splits the surfboard evenly between however many channels are displayed, which varies with
+    // the cartridge's expansion audio. Leftover pixels go to the leftmost channels, one each.
+    // Returns the starting x coordinate and width of each channel's column.
+    fn surfboard_columns(channel_count: usize, x: u32, width: u32) -> Vec<(u32, u32)> {
+        let mut columns: Vec<(u32, u32)> = Vec::new();
+        if channel_count == 0 {
+            return columns;
+        }
+        let channel_width = width / (channel_count as u32);
+        let mut leftover_pixels = width - (channel_width * (channel_count as u32));
+        let mut cx = x;
+        for _i in 0 .. channel_count {
             let mut effective_width = channel_width;
             if leftover_pixels > 0 {
                 effective_width += 1;
                 leftover_pixels -= 1;
             }
-            let channel = channels[i];
-            let dx = x + cx;
-            self.draw_channel_surfboard(channel, dx, y, effective_width, height);
-            self.draw_channel_dividers(dx, y, effective_width, height);
+            columns.push((cx, effective_width));
             cx = cx + effective_width;
         }
+        return columns;
+    }
+
+    fn draw_audio_surfboard_horiz(&mut self, runtime: &RuntimeState, x: u32, y: u32, width: u32, height: u32) {
+        let channels = self.collect_channels(&runtime.nes.apu, &*runtime.nes.mapper);
+        let columns = PianoRollWindow::surfboard_columns(channels.len(), x, width);
+        for (channel, (dx, effective_width)) in channels.iter().zip(columns) {
+            self.draw_channel_surfboard(*channel, dx, y, effective_width, height);
+            self.draw_channel_dividers(dx, y, effective_width, height);
+        }
     }
 
     pub fn mouse_mutes_channel_horiz(&mut self, runtime: &RuntimeState, sx: u32, sy: u32, width: u32, height: u32, mouse_x: i32, mouse_y: i32) -> Vec<Event> {
@@ -1314,10 +1403,8 @@ impl PianoRollWindow {
         let mx = mouse_x as u32;
         let my = mouse_y as u32;
         let channels = self.collect_channels(&runtime.nes.apu, &*runtime.nes.mapper);
-        let channel_width = width / (channels.len() as u32);
-        for i in 0 .. channels.len() {
-            let channel = channels[i];
-            let cx = sx + (i as u32) * channel_width;
+        let columns = PianoRollWindow::surfboard_columns(channels.len(), sx, width);
+        for (channel, (cx, channel_width)) in channels.iter().zip(columns) {
             if mx >= cx && mx < cx + channel_width && my >= sy && my < sy + height {
                if channel.muted() {
                     events.push(Event::UnmuteChannel(channel.chip(), channel.name()))
@@ -1476,6 +1563,42 @@ impl PianoRollWindow {
         self.highest_frequency = highest_freq;
     }
 
+    // The key drawing routines assume the lowest key is a C, so the low end of the range is
+    // rounded down to the start of its octave. highest_index is one past the topmost key.
+    fn set_key_range(&mut self, lowest_index: u32, highest_index: u32) -> Result<(), String> {
+        let lowest_index = lowest_index - (lowest_index % 12);
+        if highest_index <= lowest_index {
+            return Err(format!("Invalid key range: {} to {}", lowest_index, highest_index));
+        }
+
+        self.keys = highest_index - lowest_index;
+        self.lowest_index = lowest_index;
+        self.lowest_frequency = midi_frequency(lowest_index);
+        self.highest_index = highest_index;
+        self.highest_frequency = midi_frequency(highest_index);
+        return Ok(());
+    }
+
+    // Widens or narrows the visible note range, using the same note names as the settings file.
+    // Both ends are inclusive. Notes outside of this range are not drawn.
+    pub fn set_note_range(&mut self, lowest_note: &str, highest_note: &str) -> Result<(), String> {
+        let lowest_index = midi_index(lowest_note)?;
+        let highest_index = midi_index(highest_note)? + 1;
+        return self.set_key_range(lowest_index, highest_index);
+    }
+
+    pub fn set_lowest_note(&mut self, note_name: &str) -> Result<(), String> {
+        let lowest_index = midi_index(note_name)?;
+        let highest_index = self.highest_index;
+        return self.set_key_range(lowest_index, highest_index);
+    }
+
+    pub fn set_highest_note(&mut self, note_name: &str) -> Result<(), String> {
+        let highest_index = midi_index(note_name)? + 1;
+        let lowest_index = self.lowest_index;
+        return self.set_key_range(lowest_index, highest_index);
+    }
+
     fn apply_channel_boolean_setting(&mut self, chip_name: &str, channel_name: &str, setting_name: &str, new_value: bool) {
         match self.channel_settings.get_mut(chip_name) {
             Some(chip_settings) => {
@@ -1486,17 +1609,17 @@ impl PianoRollWindow {
                                 channel_settings.hidden = new_value;
                             },
                             _ => {
-                                println!("Warning: Failed to apply unrecognized setting {} to channel {}", setting_name, channel_name);
+                                warn!("Failed to apply unrecognized setting {} to channel {}", setting_name, channel_name);
                             }
                         }
                     },
                     None => {
-                        println!("Warning: Failed to apply setting {} to unknown channel {}", setting_name, channel_name);
+                        warn!("Failed to apply setting {} to unknown channel {}", setting_name, channel_name);
                     }
                 }
             },
             None => {
-                println!("Warning: Failed to apply setting {} to unknown audio chip {}", setting_name, chip_name);
+                warn!("Failed to apply setting {} to unknown audio chip {}", setting_name, chip_name);
             }
         }
     }
@@ -1550,22 +1673,22 @@ impl PianoRollWindow {
                                         channel_settings.colors[*setting_index] = color;
                                     },
                                     Err(_) => {
-                                        println!("Warning: Invalid color string {}, ignoring.", color_string);
+                                        warn!("Invalid color string {}, ignoring.", color_string);
                                     }
                                 }
                             },
                             None => {
-                                println!("Warning: setting {} does not correspond to any color slot for channel {} on chip {}", setting_name, channel_name, chip_name);
+                                warn!("setting {} does not correspond to any color slot for channel {} on chip {}", setting_name, channel_name, chip_name);
                             }
                         }
                     },
                     None => {
-                        println!("Warning: Failed to apply color string {} to unknown channel {}", color_string, channel_name);
+                        warn!("Failed to apply color string {} to unknown channel {}", color_string, channel_name);
                     }
                 }
             },
             None => {
-                println!("Warning: Failed to apply color string {} to unknown audio chip {}", color_string, chip_name);
+                warn!("Failed to apply color string {} to unknown audio chip {}", color_string, chip_name);
             }
         }
     }
@@ -1633,7 +1756,7 @@ impl Panel for PianoRollWindow {
                     "piano_roll.key_length" => {self.key_length = value as u32},
                     "piano_roll.octave_count" => {self.set_octave_count(value as u32)},
                     "piano_roll.scale_factor" => {self.scale = value as u32},
-                    "piano_roll.speed_multiplier" => {self.speed_multiplier = value as u32},
+                    "piano_roll.speed_multiplier" => {self.scroll_speed = value as f32},
                     "piano_roll.starting_octave" => {self.set_starting_octave(value as u32)},
                     "piano_roll.waveform_height" => {self.surfboard_height = value as u32},
                     "piano_roll.oscilloscope_glow_thickness" => {self.surfboard_glow_thickness = value as f32},
@@ -1648,6 +1771,8 @@ impl Panel for PianoRollWindow {
                 match path.as_str() {
                     "piano_roll.oscilloscope_glow_thickness" => {self.surfboard_glow_thickness = value as f32},
                     "piano_roll.oscilloscope_line_thickness" => {self.surfboard_line_thickness = value as f32},
+                    "piano_roll.speed_multiplier" => {self.scroll_speed = (value as f32).max(0.0)},
+                    "piano_roll.waveform_trace_width" => {self.waveform_trace_width = (value as f32).max(0.0)},
                     _ => {}
                 }
             },
@@ -1662,7 +1787,7 @@ impl Panel for PianoRollWindow {
                             match Color::from_string(&value) {
                                 Ok(color) => {self.background_color = color},
                                 Err(_) => {
-                                    println!("Warning: Invalid color string {}, ignoring.", value);
+                                    warn!("Invalid color string {}, ignoring.", value);
                                 }
                             }
                         },
@@ -1670,7 +1795,31 @@ impl Panel for PianoRollWindow {
                             match Color::from_string(&value) {
                                 Ok(color) => {self.divider_color = color},
                                 Err(_) => {
-                                    println!("Warning: Invalid color string {}, ignoring.", value);
+                                    warn!("Invalid color string {}, ignoring.", value);
+                                }
+                            }
+                        },
+                        "piano_roll.lowest_note" => {
+                            match self.set_lowest_note(&value) {
+                                Ok(_) => {},
+                                Err(why) => {
+                                    warn!("{}, ignoring.", why);
+                                }
+                            }
+                        },
+                        "piano_roll.highest_note" => {
+                            match self.set_highest_note(&value) {
+                                Ok(_) => {},
+                                Err(why) => {
+                                    warn!("{}, ignoring.", why);
+                                }
+                            }
+                        },
+                        "piano_roll.octave_divider_color" => {
+                            match Color::from_string(&value) {
+                                Ok(color) => {self.octave_divider_color = color},
+                                Err(_) => {
+                                    warn!("Invalid color string {}, ignoring.", value);
                                 }
                             }
                         },
@@ -1678,7 +1827,7 @@ impl Panel for PianoRollWindow {
                             match Color::from_string(&value) {
                                 Ok(color) => {self.outline_color = color},
                                 Err(_) => {
-                                    println!("Warning: Invalid color string {}, ignoring.", value);
+                                    warn!("Invalid color string {}, ignoring.", value);
                                 }
                             }
                         },
@@ -1694,4 +1843,107 @@ impl Panel for PianoRollWindow {
     fn active_canvas(&self) -> &SimpleBuffer {
         return &self.canvas;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustico_core::cartridge::mapper_from_file;
+
+    const NO_CART: &[u8] = include_bytes!("assets/rustico_no_cart.nes");
+
+    #[test]
+    fn fractional_scroll_speeds_carry_the_remainder() {
+        let mut piano_roll = PianoRollWindow::new();
+        let apu = ApuState::new();
+        let mapper = mapper_from_file(NO_CART).unwrap();
+        piano_roll.scroll_speed = 1.5;
+
+        let mut slice_counts = Vec::new();
+        for _ in 0 .. 4 {
+            piano_roll.update(&apu, &*mapper);
+            slice_counts.push(piano_roll.time_slices.len());
+        }
+        assert_eq!(slice_counts, vec![1, 3, 4, 6]);
+        assert_eq!(piano_roll.scroll_remainder, 0.0);
+    }
+
+    #[test]
+    fn note_names_use_scientific_pitch() {
+        assert_eq!(note_name(440.0), "A4");
+        assert_eq!(note_name(261.63), "C4");
+        assert_eq!(note_name(466.16), "A#4");
+        // Rounds to the nearest semitone
+        assert_eq!(note_name(450.0), "A4");
+        assert_eq!(note_name(27.5), "A0");
+        assert_eq!(note_name(0.0), "");
+        assert_eq!(note_name(std::f32::NAN), "");
+    }
+
+    #[test]
+    fn note_names_are_one_octave_below_midi_index_names() {
+        assert_eq!(note_name(midi_frequency(midi_index("A3").unwrap())), "A2");
+        assert_eq!(note_name(midi_frequency(midi_index("Cs5").unwrap())), "C#4");
+    }
+
+    // Pulse 1 playing a constant volume A4 (440 Hz)
+    fn a440_on_pulse_1() -> ApuState {
+        let mut apu = ApuState::new();
+        apu.write_register(0x4015, 0b0000_0001);
+        apu.write_register(0x4000, 0b1011_1111);
+        apu.write_register(0x4002, 0xFD);
+        apu.write_register(0x4003, 0x00);
+        return apu;
+    }
+
+    #[test]
+    fn notes_outside_the_note_range_are_not_drawn() {
+        let apu = a440_on_pulse_1();
+        let mut piano_roll = PianoRollWindow::new();
+        assert!(piano_roll.slice_from_channel(&apu.pulse_1).visible);
+
+        // In midi_index() names, so this is 32.7 Hz to 61.7 Hz
+        piano_roll.set_note_range("C2", "B2").unwrap();
+        assert_eq!(piano_roll.keys, 12);
+        assert!(!piano_roll.slice_from_channel(&apu.pulse_1).visible);
+
+        // 440 Hz lands in the topmost key
+        piano_roll.set_note_range("C5", "A5").unwrap();
+        let slice = piano_roll.slice_from_channel(&apu.pulse_1);
+        assert!(slice.visible);
+        assert_eq!(slice.y.round(), 9.0);
+    }
+
+    #[test]
+    fn dmc_slices_trace_its_output_level() {
+        let mut apu = ApuState::new();
+        let mut piano_roll = PianoRollWindow::new();
+        piano_roll.waveform_trace_width = 2.0;
+        for _ in 0 .. 300 {
+            apu.dmc.record_current_output();
+        }
+        assert!(!piano_roll.slice_from_channel(&apu.dmc).visible);
+
+        // The debug filter turns a jump in the output level into a spike, inverted on the way out
+        apu.dmc.output_level = 100;
+        apu.dmc.record_current_output();
+        let slice = piano_roll.slice_from_channel(&apu.dmc);
+        assert!(slice.visible);
+        assert!(slice.note_type == NoteType::Waveform);
+        assert!(slice.y < -1.0 && slice.y >= -2.0, "y was {}", slice.y);
+
+        // Held at one level, the spike decays back onto the waveform string and the channel goes quiet
+        for _ in 0 .. 2000 {
+            apu.dmc.record_current_output();
+        }
+        assert_eq!(PianoRollWindow::latest_sample_level(&apu.dmc), 0.0);
+        assert!(!piano_roll.slice_from_channel(&apu.dmc).visible);
+
+        // Dropping back down swings the other way
+        apu.dmc.output_level = 0;
+        apu.dmc.record_current_output();
+        let slice = piano_roll.slice_from_channel(&apu.dmc);
+        assert!(slice.visible);
+        assert!(slice.y > 1.0 && slice.y <= 2.0, "y was {}", slice.y);
+    }
+}
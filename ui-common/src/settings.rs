@@ -8,6 +8,7 @@ use toml::map::Map;
 
 // First-party
 use events::Event;
+use game_window::MAX_OVERSCAN;
 
 
 const DEFAULT_CONFIG: &str = r###"
@@ -15,7 +16,48 @@ const DEFAULT_CONFIG: &str = r###"
 ntsc_filter = false
 simulate_overscan = false
 display_fps = false
+# FPS, emulation speed and audio buffer level, drawn over the game
+perf_overlay = false
+# One of top_left, top_right, bottom_left or bottom_right
+perf_overlay_position = "top_left"
 scale_factor = 2
+# Draw every sprite on a scanline instead of flickering past 8
+disable_sprite_limit = false
+# During forced blank, show the palette entry the PPU address points to instead of the backdrop
+palette_backdrop_quirk = true
+# Path to a .pal file; empty uses the built-in palette
+palette = ""
+
+[video.overscan]
+top = 8
+bottom = 8
+left = 8
+right = 8
+
+[audio]
+# Audio queued ahead of playback, in milliseconds. Lower is more responsive, but too low will
+# crackle as the output runs dry.
+buffer_ms = 12
+
+[input.p1]
+a = "X"
+b = "Z"
+select = "Backspace"
+start = "Enter"
+up = "Up"
+down = "Down"
+left = "Left"
+right = "Right"
+
+[input.p2]
+a = "Period"
+b = "Comma"
+select = "Minus"
+start = "Equals"
+up = "I"
+down = "K"
+left = "J"
+right = "L"
 
 [piano_roll]
 canvas_width = 1280
@@ -32,6 +74,7 @@ draw_text_labels = true
 
 divider_width = 5
 divider_color = "rgb(0, 0, 0)"
+octave_divider_color = "rgb(20, 20, 20)"
 background_color = "rgba(0, 0, 0, 255)"
 
 [piano_roll.settings.2A03.DMC]
@@ -240,19 +283,125 @@ hidden = true
 
 "###;
 
+// Typed defaults and limits for the settings the shells read directly. Anything listed here
+// is clamped into range on its way in, whether from the config file or a Store event, so a
+// hand edited scale_factor of 40 can't take down the window. Keys not listed (the piano roll
+// colors, input bindings and so on) are passed through as-is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettingKind {
+    Boolean(bool),
+    Integer{default: i64, min: i64, max: i64},
+    Float{default: f64, min: f64, max: f64},
+    String(&'static str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SettingSchema {
+    pub path: &'static str,
+    pub kind: SettingKind,
+}
+
+pub const SCHEMA: &[SettingSchema] = &[
+    SettingSchema{path: "video.ntsc_filter", kind: SettingKind::Boolean(false)},
+    SettingSchema{path: "video.simulate_overscan", kind: SettingKind::Boolean(false)},
+    SettingSchema{path: "video.display_fps", kind: SettingKind::Boolean(false)},
+    SettingSchema{path: "video.perf_overlay", kind: SettingKind::Boolean(false)},
+    SettingSchema{path: "video.perf_overlay_position", kind: SettingKind::String("top_left")},
+    SettingSchema{path: "video.scale_factor", kind: SettingKind::Integer{default: 2, min: 1, max: 8}},
+    SettingSchema{path: "video.disable_sprite_limit", kind: SettingKind::Boolean(false)},
+    SettingSchema{path: "video.palette_backdrop_quirk", kind: SettingKind::Boolean(true)},
+    SettingSchema{path: "video.palette", kind: SettingKind::String("")},
+    SettingSchema{path: "video.overscan.top", kind: SettingKind::Integer{default: 8, min: 0, max: MAX_OVERSCAN}},
+    SettingSchema{path: "video.overscan.bottom", kind: SettingKind::Integer{default: 8, min: 0, max: MAX_OVERSCAN}},
+    SettingSchema{path: "video.overscan.left", kind: SettingKind::Integer{default: 8, min: 0, max: MAX_OVERSCAN}},
+    SettingSchema{path: "video.overscan.right", kind: SettingKind::Integer{default: 8, min: 0, max: MAX_OVERSCAN}},
+    SettingSchema{path: "audio.buffer_ms", kind: SettingKind::Integer{default: 12, min: 5, max: 250}},
+    SettingSchema{path: "piano_roll.waveform_trace_width", kind: SettingKind::Float{default: 1.0, min: 0.0, max: 64.0}},
+];
+
+pub fn schema_for(path: &str) -> Option<&'static SettingSchema> {
+    return SCHEMA.iter().find(|entry| entry.path == path);
+}
+
+// Pulls numeric values into their schema range; everything else is returned unchanged
+pub fn clamp_to_schema(path: &str, value: Value) -> Value {
+    match (schema_for(path).map(|entry| entry.kind), value) {
+        (Some(SettingKind::Integer{default: _, min, max}), Value::Integer(integer_value)) => {
+            return Value::Integer(integer_value.clamp(min, max));
+        },
+        (Some(SettingKind::Float{default: _, min, max}), Value::Float(float_value)) => {
+            return Value::Float(float_value.clamp(min, max));
+        },
+        (_, value) => {return value;}
+    }
+}
+
+// Settings that can differ from game to game. Changing one of these while a game is loaded
+// records it under [games.<sha1 of the ROM>] rather than in the global settings, and from then
+// on that game's value wins over the global one whenever the game is loaded. Everything else
+// (input bindings, window scale and so on) is always global.
+pub const PER_GAME_SETTINGS: &[&str] = &[
+    "audio.multiplexing",
+    "video.disable_sprite_limit",
+    "video.ntsc_filter",
+    "video.overscan.bottom",
+    "video.overscan.left",
+    "video.overscan.right",
+    "video.overscan.top",
+    "video.palette",
+    "video.palette_backdrop_quirk",
+    "video.simulate_overscan",
+];
+
+const GAMES_TABLE: &str = "games";
+
 #[derive(Clone)]
 pub struct SettingsState {
-    pub root: Value
+    pub root: Value,
+    // SHA-1 (hex) of the loaded ROM, selecting which per-game overrides are live
+    pub active_game: Option<String>,
 }
 
 impl SettingsState {
     pub fn new() -> SettingsState {
         let default_config = DEFAULT_CONFIG.parse::<Value>().unwrap();
         return SettingsState {
-            root: default_config
+            root: default_config,
+            active_game: None,
         }
     }
 
+    pub fn set_active_game(&mut self, rom_hash: Option<String>) {
+        self.active_game = rom_hash;
+    }
+
+    // Override keys are stored whole ("video.palette"), not split into nested tables, so a
+    // game's table reads as a flat list of what it changes
+    fn game_overrides(&self) -> Option<&Map<String, Value>> {
+        let hash = self.active_game.as_ref()?;
+        let games = self.root.as_table()?.get(GAMES_TABLE)?.as_table()?;
+        return games.get(hash)?.as_table();
+    }
+
+    pub fn game_override(&self, path: &str) -> Option<&Value> {
+        return self.game_overrides()?.get(path);
+    }
+
+    pub fn set_game_override(&mut self, path: String, new_value: Value) {
+        let hash = match &self.active_game {
+            Some(hash) => hash.clone(),
+            None => {return;}
+        };
+        let root_table = self.root.as_table_mut().unwrap();
+        let games = root_table.entry(GAMES_TABLE.to_string()).or_insert(Value::Table(Map::new()));
+        let game = games.as_table_mut().unwrap().entry(hash).or_insert(Value::Table(Map::new()));
+        game.as_table_mut().unwrap().insert(path, new_value);
+    }
+
+    fn overrides_path(&self, path: &str) -> bool {
+        return self.active_game.is_some() && PER_GAME_SETTINGS.contains(&path);
+    }
+
     pub fn load(&mut self, filename: &OsString) {
         match fs::read_to_string(filename) {
             Ok(config_str) => {
@@ -260,7 +409,7 @@ impl SettingsState {
                 self.root = config_from_file;
             },
             Err(_) => {
-                println!("Failed to load settings from: {:?}", filename);
+                warn!("Failed to load settings from: {:?}", filename);
             }
         }
     }
@@ -273,7 +422,7 @@ impl SettingsState {
     pub fn save(&self, filename: &OsString) {
         let config_str = toml::to_string(&self.root).unwrap();
         fs::write(filename, config_str).expect("Unable to write settings!");
-        println!("Wrote settings to {:?}", filename);
+        info!("Wrote settings to {:?}", filename);
     }
 
     fn _emit_events(value: Value, prefix: String) -> Vec<Event> {
@@ -286,8 +435,14 @@ impl SettingsState {
                 }
             },
             Value::Boolean(boolean_value) => {events.push(Event::ApplyBooleanSetting(prefix, boolean_value));},
-            Value::Float(float_value) => {events.push(Event::ApplyFloatSetting(prefix, float_value));},
-            Value::Integer(integer_value) => {events.push(Event::ApplyIntegerSetting(prefix, integer_value));},
+            Value::Float(float_value) => {
+                let clamped_value = clamp_to_schema(&prefix, Value::Float(float_value)).as_float().unwrap();
+                events.push(Event::ApplyFloatSetting(prefix, clamped_value));
+            },
+            Value::Integer(integer_value) => {
+                let clamped_value = clamp_to_schema(&prefix, Value::Integer(integer_value)).as_integer().unwrap();
+                events.push(Event::ApplyIntegerSetting(prefix, clamped_value));
+            },
             Value::String(string_value) => {events.push(Event::ApplyStringSetting(prefix, string_value));},
             _ => {
                 /* Unimplemented! */
@@ -296,8 +451,24 @@ impl SettingsState {
         return events;
     }
 
+    /// Events for every global setting, followed by the active game's overrides so that
+    /// those are the values left applied.
     pub fn apply_settings(&self) -> Vec<Event> {
-        return SettingsState::_emit_events(self.root.clone(), "".to_string());
+        let mut events: Vec<Event> = Vec::new();
+        for (key, value) in self.root.as_table().unwrap() {
+            if key != GAMES_TABLE {
+                events.extend(SettingsState::_emit_events(value.clone(), key.to_string()));
+            }
+        }
+        match self.game_overrides() {
+            Some(overrides) => {
+                for (path, value) in overrides {
+                    events.extend(SettingsState::_emit_events(value.clone(), path.to_string()));
+                }
+            },
+            None => {}
+        }
+        return events;
     }
 
     fn _ensure_path_exists(path: String, current_table: &mut Map<String, Value>, default_value: Value) {
@@ -345,15 +516,19 @@ impl SettingsState {
         return None;
     }
 
+    /// The active game's override if there is one, and the global setting otherwise.
     pub fn get(&self, path: String) -> Option<&Value> {
+        match self.game_override(&path) {
+            Some(value) => {return Some(value);},
+            None => {}
+        }
         let root_table = self.root.as_table().unwrap();
         return SettingsState::_get(path, root_table);
     }
 
     // Convenience functions, mostly used by the settings UI
     pub fn get_boolean(&self, path: String) -> Option<bool> {
-        let root_table = self.root.as_table().unwrap();
-        match SettingsState::_get(path, root_table) {
+        match self.get(path) {
             Some(value) => {
                 match value {
                     Value::Boolean(boolean_value) => {return Some(*boolean_value)},
@@ -365,8 +540,7 @@ impl SettingsState {
     }
 
     pub fn get_integer(&self, path: String) -> Option<i64> {
-        let root_table = self.root.as_table().unwrap();
-        match SettingsState::_get(path, root_table) {
+        match self.get(path) {
             Some(value) => {
                 match value {
                     Value::Integer(integer_value) => {return Some(*integer_value)},
@@ -378,8 +552,7 @@ impl SettingsState {
     }
 
     pub fn get_float(&self, path: String) -> Option<f64> {
-        let root_table = self.root.as_table().unwrap();
-        match SettingsState::_get(path, root_table) {
+        match self.get(path) {
             Some(value) => {
                 match value {
                     Value::Float(float_value) => {return Some(*float_value)},
@@ -391,8 +564,7 @@ impl SettingsState {
     }
 
     pub fn get_string(&self, path: String) -> Option<String> {
-        let root_table = self.root.as_table().unwrap();
-        match SettingsState::_get(path, root_table) {
+        match self.get(path) {
             Some(value) => {
                 match value {
                     Value::String(string_value) => {return Some(string_value.into())},
@@ -403,6 +575,45 @@ impl SettingsState {
         }
     }
 
+    // Schema backed getters: the stored value clamped into range, or the schema default when
+    // it's missing or the wrong type. Panics if the key isn't in the schema with that type,
+    // since that's a typo in the caller rather than a bad config file.
+    pub fn get_boolean_or_default(&self, path: &str) -> bool {
+        match schema_for(path).map(|entry| entry.kind) {
+            Some(SettingKind::Boolean(default)) => {
+                return self.get_boolean(path.to_string()).unwrap_or(default);
+            },
+            _ => {panic!("{} is not a boolean setting in the schema", path);}
+        }
+    }
+
+    pub fn get_integer_or_default(&self, path: &str) -> i64 {
+        match schema_for(path).map(|entry| entry.kind) {
+            Some(SettingKind::Integer{default, min, max}) => {
+                return self.get_integer(path.to_string()).unwrap_or(default).clamp(min, max);
+            },
+            _ => {panic!("{} is not an integer setting in the schema", path);}
+        }
+    }
+
+    pub fn get_float_or_default(&self, path: &str) -> f64 {
+        match schema_for(path).map(|entry| entry.kind) {
+            Some(SettingKind::Float{default, min, max}) => {
+                return self.get_float(path.to_string()).unwrap_or(default).clamp(min, max);
+            },
+            _ => {panic!("{} is not a float setting in the schema", path);}
+        }
+    }
+
+    pub fn get_string_or_default(&self, path: &str) -> String {
+        match schema_for(path).map(|entry| entry.kind) {
+            Some(SettingKind::String(default)) => {
+                return self.get_string(path.to_string()).unwrap_or(default.to_string());
+            },
+            _ => {panic!("{} is not a string setting in the schema", path);}
+        }
+    }
+
     pub fn _set(path: String, current_table: &mut Map<String, Value>, new_value: Value) {
         let components = path.split(".").collect::<Vec<&str>>();
         if components.len() == 1 {
@@ -426,37 +637,84 @@ impl SettingsState {
         return SettingsState::_set(path, root_table, new_value);
     }
 
+    // Where user changes land: the active game's overrides for per-game settings, or the
+    // global table otherwise
+    fn store(&mut self, path: String, new_value: Value) {
+        if self.overrides_path(&path) {
+            self.set_game_override(path, new_value);
+        } else {
+            self.ensure_path_exists(path.clone(), Value::from(false));
+            self.set(path, new_value);
+        }
+    }
+
     pub fn handle_event(&mut self, event: Event) -> Vec<Event> {
         let mut events: Vec<Event> = Vec::new();
         match event {
             Event::StoreBooleanSetting(path, value) => {
-                self.ensure_path_exists(path.clone(), Value::from(false));
-                self.set(path.clone(), Value::from(value));
+                self.store(path.clone(), Value::from(value));
                 events.push(Event::ApplyBooleanSetting(path, value));
             },
             Event::StoreFloatSetting(path, value) => {
-                self.ensure_path_exists(path.clone(), Value::from(false));
-                self.set(path.clone(), Value::from(value));
+                let value = clamp_to_schema(&path, Value::from(value)).as_float().unwrap();
+                self.store(path.clone(), Value::from(value));
                 events.push(Event::ApplyFloatSetting(path, value));
             },
             Event::StoreIntegerSetting(path, value) => {
-                self.ensure_path_exists(path.clone(), Value::from(false));
-                self.set(path.clone(), Value::from(value));
+                let value = clamp_to_schema(&path, Value::from(value)).as_integer().unwrap();
+                self.store(path.clone(), Value::from(value));
                 events.push(Event::ApplyIntegerSetting(path, value));
             },
             Event::StoreStringSetting(path, value) => {
-                self.ensure_path_exists(path.clone(), Value::from(false));
-                self.set(path.clone(), Value::from(value.clone()));
+                self.store(path.clone(), Value::from(value.clone()));
                 events.push(Event::ApplyStringSetting(path, value.clone()));
             },
             Event::ToggleBooleanSetting(path) => {
                 self.ensure_path_exists(path.clone(), Value::from(false));
                 let current_value = self.get(path.clone()).unwrap().as_bool().unwrap();
-                self.set(path.clone(), Value::from(!current_value));
+                self.store(path.clone(), Value::from(!current_value));
                 events.push(Event::ApplyBooleanSetting(path, !current_value));
             },
             _ => {}
         }
         return events;
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_to_schema_pulls_numbers_into_range() {
+        assert_eq!(clamp_to_schema("video.scale_factor", Value::Integer(40)), Value::Integer(8));
+        assert_eq!(clamp_to_schema("video.scale_factor", Value::Integer(0)), Value::Integer(1));
+        assert_eq!(clamp_to_schema("video.scale_factor", Value::Integer(3)), Value::Integer(3));
+        assert_eq!(clamp_to_schema("piano_roll.waveform_trace_width", Value::Float(-2.0)), Value::Float(0.0));
+        // Keys outside the schema, and values of the wrong type, pass through untouched
+        assert_eq!(clamp_to_schema("piano_roll.speed_multiplier", Value::Integer(-5)), Value::Integer(-5));
+        assert_eq!(clamp_to_schema("video.scale_factor", Value::Float(40.0)), Value::Float(40.0));
+    }
+
+    #[test]
+    fn out_of_range_config_values_are_clamped_when_applied() {
+        let mut settings = SettingsState::new();
+        settings.load_str("[video]\nscale_factor = 40\n");
+        let applied: Vec<i64> = settings.apply_settings().into_iter().filter_map(|event| match event {
+            Event::ApplyIntegerSetting(path, value) if path == "video.scale_factor" => Some(value),
+            _ => None
+        }).collect();
+        assert_eq!(applied, vec![8]);
+        assert_eq!(settings.get_integer_or_default("video.scale_factor"), 8);
+    }
+
+    #[test]
+    fn out_of_range_stores_are_clamped_before_saving() {
+        let mut settings = SettingsState::new();
+        let events = settings.handle_event(Event::StoreIntegerSetting("video.overscan.top".into(), -3));
+        assert_eq!(settings.get_integer("video.overscan.top".into()), Some(0));
+        match events.as_slice() {
+            [Event::ApplyIntegerSetting(path, 0)] => assert_eq!(path, "video.overscan.top"),
+            _ => panic!("expected the clamped value to be applied, got {:?}", events),
+        }
+    }
+}
@@ -1,5 +1,7 @@
+extern crate console_log;
 #[macro_use]
 extern crate lazy_static;
+extern crate log;
 extern crate rustico_core;
 extern crate rustico_ui_common;
 extern crate wasm_bindgen;
@@ -78,6 +80,10 @@ pub fn resolve_events(mut events: Vec<Event>, runtime: &mut RuntimeState) {
 
 #[wasm_bindgen]
 pub fn wasm_init() {
+  // Core warnings (bad dumps, unsupported mappers) end up in the browser console. Only the
+  // first call installs the logger, so a second init is harmless.
+  let _ = console_log::init_with_level(log::Level::Info);
+
   let mut runtime = RUNTIME.lock().expect("wat");
 
   runtime.settings = SettingsState::new();
@@ -190,7 +196,7 @@ pub fn set_audio_samplerate(sample_rate: u32) {
   let mut runtime = RUNTIME.lock().expect("wat");
   let nes = &mut runtime.nes;
   
-  nes.apu.set_sample_rate(sample_rate as u64);
+  nes.apu.set_output_sample_rate(sample_rate as u32);
   // while we're here, set the filter to low quality
   nes.apu.set_filter(FilterType::FamiCom, false);
   // and if this happens to be an N163 ROM, tell it not
@@ -253,7 +259,7 @@ pub fn has_sram() -> bool {
   let runtime = RUNTIME.lock().expect("wat");
   let nes = &runtime.nes;
   
-  return nes.mapper.has_sram();
+  return nes.mapper.has_persistent_ram();
 }
 
 #[wasm_bindgen]